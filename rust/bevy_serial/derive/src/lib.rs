@@ -0,0 +1,17 @@
+//! Bevy 0.13 requires `#[derive(Event)]` and `#[derive(Resource)]` on the types used as events and resources, while
+//! older versions implement them for every `Send + Sync + 'static` type. With the `bevy_06` feature, `bevy_serial`
+//! uses these derives instead, which expand to nothing.
+
+use proc_macro::TokenStream;
+
+/// Nothing to implement: Bevy 0.6 uses any `Send + Sync + 'static` type as an event
+#[proc_macro_derive(Event)]
+pub fn derive_event(_input: TokenStream) -> TokenStream {
+    TokenStream::new()
+}
+
+/// Nothing to implement: Bevy 0.6 uses any `Send + Sync + 'static` type as a resource
+#[proc_macro_derive(Resource)]
+pub fn derive_resource(_input: TokenStream) -> TokenStream {
+    TokenStream::new()
+}
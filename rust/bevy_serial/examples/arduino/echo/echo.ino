@@ -0,0 +1,11 @@
+// Echo every byte back to the host (examples/echo.rs)
+
+void setup() {
+    Serial.begin(115200);
+}
+
+void loop() {
+    while (Serial.available() > 0) {
+        Serial.write(Serial.read());
+    }
+}
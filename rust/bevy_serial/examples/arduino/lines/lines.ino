@@ -0,0 +1,14 @@
+// Print a counter and the time as a line every 100 ms (examples/lines.rs, examples/multi_port.rs)
+
+unsigned long count = 0;
+
+void setup() {
+    Serial.begin(115200);
+}
+
+void loop() {
+    Serial.print(count++);
+    Serial.print(' ');
+    Serial.println(millis());
+    delay(100);
+}
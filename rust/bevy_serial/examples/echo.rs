@@ -0,0 +1,47 @@
+//! Write "ping N" every second and print what comes back.
+//! Flash `examples/arduino/echo/echo.ino` to the board, then run:
+//!
+//! ```sh
+//! cargo run --example echo -- COM5
+//! ```
+
+use bevy::prelude::*;
+use bevy_serial::{SerialPlugin, SerialReadEvent, SerialWriteEvent};
+
+#[derive(Resource)]
+struct Port(String);
+
+#[derive(Resource)]
+struct PingTimer(Timer);
+
+fn main() {
+    let port_name = std::env::args().nth(1).unwrap_or_else(|| "COM5".to_string());
+    App::new()
+        .add_plugins(MinimalPlugins)
+        .add_plugins(SerialPlugin::new(&port_name, 115200))
+        .insert_resource(Port(port_name))
+        .insert_resource(PingTimer(Timer::from_seconds(1.0, TimerMode::Repeating)))
+        .add_systems(Update, ping)
+        .add_systems(Update, print_echo)
+        .run();
+}
+
+fn ping(
+    mut ev_serial: EventWriter<SerialWriteEvent>,
+    mut timer: ResMut<PingTimer>,
+    mut count: Local<u32>,
+    port: Res<Port>,
+    time: Res<Time>,
+) {
+    if timer.0.tick(time.delta()).just_finished() {
+        *count += 1;
+        let buffer = format!("ping {}\n", *count).into_bytes();
+        ev_serial.send(SerialWriteEvent(port.0.clone(), buffer));
+    }
+}
+
+fn print_echo(mut ev_serial: EventReader<SerialReadEvent>) {
+    for SerialReadEvent(label, buffer) in ev_serial.read() {
+        print!("{}: {}", label, String::from_utf8_lossy(buffer));
+    }
+}
@@ -0,0 +1,25 @@
+//! Split the bytes read into lines with `LineDecoder` and print them.
+//! Flash `examples/arduino/lines/lines.ino` to the board, then run:
+//!
+//! ```sh
+//! cargo run --example lines --features codecs -- COM5
+//! ```
+
+use bevy::prelude::*;
+use bevy_serial::{DecoderPlugin, LineDecoder, SerialFrameEvent, SerialPlugin};
+
+fn main() {
+    let port_name = std::env::args().nth(1).unwrap_or_else(|| "COM5".to_string());
+    App::new()
+        .add_plugins(MinimalPlugins)
+        .add_plugins(SerialPlugin::new(&port_name, 115200))
+        .add_plugins(DecoderPlugin::new(&port_name, LineDecoder::default()))
+        .add_systems(Update, print_lines)
+        .run();
+}
+
+fn print_lines(mut ev_line: EventReader<SerialFrameEvent<Vec<u8>>>) {
+    for SerialFrameEvent(label, line) in ev_line.read() {
+        println!("{}: {}", label, String::from_utf8_lossy(line));
+    }
+}
@@ -0,0 +1,78 @@
+//! Decode a simulated accelerometer stream from a mock port with injected faults, without a device,
+//! and print how many lines were parsed and how many were malformed every second.
+//! The optional argument is the probability of corrupting each byte:
+//!
+//! ```sh
+//! cargo run --example mock --features testing,codecs -- 0.01
+//! ```
+
+use bevy::prelude::*;
+use bevy_serial::{
+    DecoderPlugin, FaultInjection, LineDecoder, MockPortPlugin, MockPorts, SerialFrameEvent,
+    SerialPlugin,
+};
+
+#[derive(Default, Resource)]
+struct LineCount {
+    parsed: usize,
+    malformed: usize,
+}
+
+#[derive(Resource)]
+struct ReportTimer(Timer);
+
+fn main() {
+    let corrupt = std::env::args()
+        .nth(1)
+        .and_then(|arg| arg.parse().ok())
+        .unwrap_or(0.01);
+    App::new()
+        .add_plugins(MinimalPlugins)
+        .add_plugins(SerialPlugin { settings: vec![] })
+        .add_plugins(MockPortPlugin::new("mock").with_faults(FaultInjection {
+            corrupt,
+            truncate: 0.05,
+            delay: 0.05,
+            ..Default::default()
+        }))
+        .add_plugins(DecoderPlugin::new("mock", LineDecoder::default()))
+        .init_resource::<LineCount>()
+        .insert_resource(ReportTimer(Timer::from_seconds(1.0, TimerMode::Repeating)))
+        .add_systems(Update, feed_samples)
+        .add_systems(Update, parse_lines)
+        .add_systems(Update, print_count)
+        .run();
+}
+
+/// Feed a line of `x y z` in g once the last one is read, in chunks of 5 bytes like a slow UART
+fn feed_samples(mut ports: ResMut<MockPorts>, time: Res<Time>) {
+    if ports.pending("mock") > 0 {
+        return;
+    }
+    let t = time.elapsed_seconds();
+    let line = format!("{:.3} {:.3} {:.3}\n", t.sin() * 0.1, t.cos() * 0.1, 0.98);
+    ports.feed_chunks("mock", line.as_bytes(), 5);
+}
+
+fn parse_lines(mut ev_line: EventReader<SerialFrameEvent<Vec<u8>>>, mut count: ResMut<LineCount>) {
+    for SerialFrameEvent(_, line) in ev_line.read() {
+        let values: Vec<f32> = String::from_utf8_lossy(line)
+            .split(' ')
+            .filter_map(|value| value.parse().ok())
+            .collect();
+        if values.len() == 3 {
+            count.parsed += 1;
+        } else {
+            count.malformed += 1;
+        }
+    }
+}
+
+fn print_count(mut timer: ResMut<ReportTimer>, time: Res<Time>, count: Res<LineCount>) {
+    if timer.0.tick(time.delta()).just_finished() {
+        println!(
+            "parsed {} lines, {} malformed",
+            count.parsed, count.malformed
+        );
+    }
+}
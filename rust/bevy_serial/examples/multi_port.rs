@@ -0,0 +1,63 @@
+//! Read two labeled ports, print the port state every second, and report ports which stop sending.
+//! Flash `examples/arduino/lines/lines.ino` to both boards, then run:
+//!
+//! ```sh
+//! cargo run --example multi_port -- COM5 COM6
+//! ```
+
+use bevy::prelude::*;
+use bevy_serial::{
+    SerialInfoPlugin, SerialPlugin, SerialPortInfo, SerialReadEvent, SerialSetting,
+    SerialStaleEvent,
+};
+use std::time::Duration;
+
+#[derive(Resource)]
+struct InfoTimer(Timer);
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let first = args.next().unwrap_or_else(|| "COM5".to_string());
+    let second = args.next().unwrap_or_else(|| "COM6".to_string());
+    let setting = |label: &str, port_name: String| SerialSetting {
+        label: Some(label.to_string()),
+        port_name,
+        stale_timeout: Some(Duration::from_secs(1)),
+        ..Default::default()
+    };
+
+    App::new()
+        .add_plugins(MinimalPlugins)
+        .add_plugins(SerialPlugin {
+            settings: vec![setting("left", first), setting("right", second)],
+        })
+        .add_plugins(SerialInfoPlugin)
+        .insert_resource(InfoTimer(Timer::from_seconds(1.0, TimerMode::Repeating)))
+        .add_systems(Update, count_bytes)
+        .add_systems(Update, print_info)
+        .add_systems(Update, print_stale)
+        .run();
+}
+
+fn count_bytes(mut ev_serial: EventReader<SerialReadEvent>, mut bytes: Local<usize>) {
+    for SerialReadEvent(_, buffer) in ev_serial.read() {
+        *bytes += buffer.len();
+    }
+}
+
+fn print_info(mut timer: ResMut<InfoTimer>, time: Res<Time>, info: Res<SerialPortInfo>) {
+    if timer.0.tick(time.delta()).just_finished() {
+        for (label, state) in info.iter() {
+            println!(
+                "{}: open = {}, read = {} bytes, written = {} bytes",
+                label, state.open, state.bytes_read, state.bytes_written
+            );
+        }
+    }
+}
+
+fn print_stale(mut ev_stale: EventReader<SerialStaleEvent>) {
+    for SerialStaleEvent(label, since) in ev_stale.read() {
+        println!("{} is not responding for {:?}", label, since.elapsed());
+    }
+}
@@ -0,0 +1,89 @@
+//! Open the first port of a USB device by VID/PID, and open it again wherever it is plugged back after being unplugged.
+//! A line is written every second, kept while the device is unplugged and echoed back once it is reconnected.
+//! Flash `examples/arduino/echo/echo.ino` to the board, then run with its VID and PID in hex (an Arduino Uno by default):
+//!
+//! ```sh
+//! cargo run --example reconnect --features enumeration -- 2341 0043
+//! ```
+
+use bevy::prelude::*;
+use bevy_serial::{
+    PortFilter, SerialHotplugPlugin, SerialInfoPlugin, SerialOpenedEvent, SerialPlugin,
+    SerialPortInfo, SerialReadEvent, SerialSetting, SerialWriteEvent,
+};
+use std::time::Duration;
+
+const LABEL: &str = "board";
+
+#[derive(Resource)]
+struct PingTimer(Timer);
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let mut id = |default: u16| {
+        args.next()
+            .and_then(|arg| u16::from_str_radix(&arg, 16).ok())
+            .unwrap_or(default)
+    };
+    let (vid, pid) = (id(0x2341), id(0x0043));
+
+    App::new()
+        .add_plugins(MinimalPlugins)
+        .add_plugins(SerialPlugin {
+            settings: vec![SerialSetting {
+                label: Some(LABEL.to_string()),
+                filter: Some(PortFilter::new().vid_pid(vid, pid)),
+                // keep the pings written while the device is unplugged
+                reconnect_buffer: Some(16),
+                // an Arduino resets when its port is opened
+                open_settle: Duration::from_secs(2),
+                ..Default::default()
+            }],
+        })
+        .add_plugins(SerialHotplugPlugin)
+        .add_plugins(SerialInfoPlugin)
+        .insert_resource(PingTimer(Timer::from_seconds(1.0, TimerMode::Repeating)))
+        .add_systems(Update, ping)
+        .add_systems(Update, print_echo)
+        .add_systems(Update, print_opened)
+        .add_systems(Update, print_unplugged)
+        .run();
+}
+
+fn ping(
+    mut timer: ResMut<PingTimer>,
+    time: Res<Time>,
+    mut ev_write: EventWriter<SerialWriteEvent>,
+    mut count: Local<usize>,
+) {
+    if timer.0.tick(time.delta()).just_finished() {
+        *count += 1;
+        let line = format!("ping {}\n", *count);
+        ev_write.send(SerialWriteEvent(LABEL.to_string(), line.into_bytes()));
+    }
+}
+
+fn print_echo(mut ev_serial: EventReader<SerialReadEvent>) {
+    for SerialReadEvent(label, buffer) in ev_serial.read() {
+        print!("{}: {}", label, String::from_utf8_lossy(buffer));
+    }
+}
+
+fn print_opened(mut ev_opened: EventReader<SerialOpenedEvent>) {
+    for SerialOpenedEvent {
+        label,
+        settings_actual,
+    } in ev_opened.read()
+    {
+        println!("{} opened at {:?} baud", label, settings_actual.baud_rate);
+    }
+}
+
+/// Print when the port is unplugged, until it is opened again
+fn print_unplugged(info: Res<SerialPortInfo>, mut open: Local<bool>) {
+    let now = info.get(LABEL).is_some_and(|state| state.open);
+    if *open && !now {
+        println!("{} unplugged, waiting for it to be plugged back", LABEL);
+    }
+    *open = now;
+}
@@ -0,0 +1,38 @@
+#!/usr/bin/env python3
+"""Send IMU and BATT messages as tagged JSON lines at 10 Hz, and print the LED commands received (examples/typed.rs)
+
+Create a virtual port pair, e.g. `socat -d -d pty,raw,echo=0 pty,raw,echo=0`,
+then run `python3 typed_sim.py /dev/pts/3` and `cargo run --example typed --features codecs -- /dev/pts/4`.
+Requires pyserial.
+"""
+
+import json
+import math
+import sys
+import time
+
+import serial
+
+
+def message(tag, body):
+    return (tag + " " + json.dumps(body, separators=(",", ":")) + "\n").encode()
+
+
+def main():
+    port = serial.Serial(sys.argv[1] if len(sys.argv) > 1 else "/dev/pts/3", 115200, timeout=0)
+    t = 0.0
+    while True:
+        accel = [round(0.1 * math.sin(t), 3), round(0.1 * math.cos(t), 3), 0.98]
+        port.write(message("IMU", {"accel": accel}))
+        if round(t * 10) % 10 == 0:
+            # drains from 4.2 V to 3.3 V over a minute, then starts again
+            volts = 4.2 - 0.9 * (t % 60.0) / 60.0
+            port.write(message("BATT", {"volts": round(volts, 2)}))
+        for line in port.read(1024).decode(errors="replace").splitlines():
+            print("received", line)
+        t += 0.1
+        time.sleep(0.1)
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,45 @@
+#!/usr/bin/env python3
+"""Send UBX NAV-PVT messages at 5 Hz to a serial port (examples/ubx.rs)
+
+Create a virtual port pair, e.g. `socat -d -d pty,raw,echo=0 pty,raw,echo=0`,
+then run `python3 ubx_sim.py /dev/pts/3` and `cargo run --example ubx --features codecs -- /dev/pts/4`.
+Requires pyserial.
+"""
+
+import struct
+import sys
+import time
+
+import serial
+
+
+def ubx(cls, id, payload):
+    body = struct.pack("<BBH", cls, id, len(payload)) + payload
+    ck_a = ck_b = 0
+    for b in body:
+        ck_a = (ck_a + b) & 0xFF
+        ck_b = (ck_b + ck_a) & 0xFF
+    return b"\xb5\x62" + body + bytes([ck_a, ck_b])
+
+
+def nav_pvt(itow, lat, lon, height):
+    payload = bytearray(92)
+    struct.pack_into("<IHBBBBBB", payload, 0, itow, 2024, 1, 1, 0, 0, itow // 1000 % 60, 0x07)
+    struct.pack_into("<BBB", payload, 20, 3, 0x01, 12)
+    struct.pack_into("<iiii", payload, 24, lon, lat, height, height)
+    return ubx(0x01, 0x07, bytes(payload))
+
+
+def main():
+    port = serial.Serial(sys.argv[1] if len(sys.argv) > 1 else "/dev/pts/3", 38400)
+    itow = 0
+    while True:
+        lat = 356_580_000 + itow // 200
+        lon = 1_397_016_000
+        port.write(nav_pvt(itow, lat, lon, 40_000))
+        itow += 200
+        time.sleep(0.2)
+
+
+if __name__ == "__main__":
+    main()
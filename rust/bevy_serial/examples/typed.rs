@@ -0,0 +1,85 @@
+//! Receive JSON messages tagged by their type, e.g. `IMU {"accel":[0.01,0.02,0.98]}`, as typed events with serde,
+//! and send a JSON command back when the battery is low.
+//! Run `examples/sim/typed_sim.py` on the other end of a virtual serial port pair, then run:
+//!
+//! ```sh
+//! cargo run --example typed --features codecs -- COM5
+//! ```
+
+use bevy::prelude::*;
+use bevy_serial::{
+    DecoderPlugin, SerialAppExt, SerialMessage, SerialMessageEvent, SerialPlugin, SerialWriteEvent,
+    TaggedLineDecoder,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize)]
+struct Imu {
+    accel: [f32; 3],
+}
+
+impl SerialMessage for Imu {
+    const TAG: &'static str = "IMU";
+
+    fn from_payload(payload: &[u8]) -> Option<Self> {
+        serde_json::from_slice(payload).ok()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Battery {
+    volts: f32,
+}
+
+impl SerialMessage for Battery {
+    const TAG: &'static str = "BATT";
+
+    fn from_payload(payload: &[u8]) -> Option<Self> {
+        serde_json::from_slice(payload).ok()
+    }
+}
+
+/// Command written to the device
+#[derive(Debug, Serialize)]
+struct Led {
+    on: bool,
+}
+
+fn main() {
+    let port_name = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "COM5".to_string());
+    App::new()
+        .add_plugins(MinimalPlugins)
+        .add_plugins(SerialPlugin::new(&port_name, 115200))
+        .add_plugins(DecoderPlugin::new(&port_name, TaggedLineDecoder::default()))
+        .add_serial_message::<TaggedLineDecoder, Imu>()
+        .add_serial_message::<TaggedLineDecoder, Battery>()
+        .add_systems(Update, print_imu)
+        .add_systems(Update, warn_low_battery)
+        .run();
+}
+
+fn print_imu(mut ev_imu: EventReader<SerialMessageEvent<Imu>>) {
+    for SerialMessageEvent(label, imu) in ev_imu.read() {
+        println!("{}: acceleration {:?} g", label, imu.accel);
+    }
+}
+
+/// Turn the LED of the device on while its battery is below 3.5 V
+fn warn_low_battery(
+    mut ev_battery: EventReader<SerialMessageEvent<Battery>>,
+    mut ev_write: EventWriter<SerialWriteEvent>,
+    mut low: Local<bool>,
+) {
+    for SerialMessageEvent(label, battery) in ev_battery.read() {
+        let now = battery.volts < 3.5;
+        if now != *low {
+            println!("{}: battery at {} V", label, battery.volts);
+            let led = serde_json::to_string(&Led { on: now }).unwrap();
+            let line = format!("LED {}\n", led);
+            ev_write.send(SerialWriteEvent(label.clone(), line.into_bytes()));
+            *low = now;
+        }
+    }
+}
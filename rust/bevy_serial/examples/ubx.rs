@@ -0,0 +1,29 @@
+//! Receive NAV-PVT messages of u-blox GNSS modules as typed events.
+//! Connect a u-blox module with UBX output enabled, or run `examples/sim/ubx_sim.py` on the other end
+//! of a virtual serial port pair, then run:
+//!
+//! ```sh
+//! cargo run --example ubx --features codecs -- COM5
+//! ```
+
+use bevy::prelude::*;
+use bevy_serial::{
+    DecoderPlugin, NavPvt, SerialAppExt, SerialMessageEvent, SerialPlugin, UbxDecoder,
+};
+
+fn main() {
+    let port_name = std::env::args().nth(1).unwrap_or_else(|| "COM5".to_string());
+    App::new()
+        .add_plugins(MinimalPlugins)
+        .add_plugins(SerialPlugin::new(&port_name, 38400))
+        .add_plugins(DecoderPlugin::new(&port_name, UbxDecoder::default()))
+        .add_serial_message::<UbxDecoder, NavPvt>()
+        .add_systems(Update, print_position)
+        .run();
+}
+
+fn print_position(mut ev_pvt: EventReader<SerialMessageEvent<NavPvt>>) {
+    for SerialMessageEvent(label, pvt) in ev_pvt.read() {
+        println!("{}: {:?}", label, pvt);
+    }
+}
@@ -0,0 +1,129 @@
+use crate::compat::*;
+use std::collections::vec_deque::Drain;
+use std::collections::{HashMap, VecDeque};
+
+/// What happens to a chunk read while the backlog of the port is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BacklogPolicy {
+    /// Drop the oldest chunk to make room
+    DropOldest,
+    /// Drop the chunk just read
+    DropNewest,
+    /// Stop reading the port until the backlog is drained, leaving the bytes in the OS driver buffer
+    Block,
+}
+
+/// Settings of the backlog of a port, counted in chunks (one chunk per read, like `SerialReadEvent`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BacklogSetting {
+    pub capacity: usize,
+    pub policy: BacklogPolicy,
+}
+
+impl BacklogSetting {
+    pub fn new(capacity: usize, policy: BacklogPolicy) -> Self {
+        Self { capacity, policy }
+    }
+}
+
+/// Chunks read from a port which are not drained yet, with the counts of the chunks drained and dropped
+#[derive(Debug)]
+pub struct PortBacklog {
+    chunks: VecDeque<Vec<u8>>,
+    setting: BacklogSetting,
+    drained: u64,
+    dropped: u64,
+}
+
+impl PortBacklog {
+    fn new(setting: BacklogSetting) -> Self {
+        Self {
+            chunks: VecDeque::with_capacity(setting.capacity),
+            setting,
+            drained: 0,
+            dropped: 0,
+        }
+    }
+
+    /// Number of chunks waiting to be drained
+    pub fn len(&self) -> usize {
+        self.chunks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    /// The backlog has no room for another chunk
+    pub fn is_full(&self) -> bool {
+        self.chunks.len() >= self.setting.capacity
+    }
+
+    /// Chunks drained since the port was added
+    pub fn drained(&self) -> u64 {
+        self.drained
+    }
+
+    /// Chunks dropped by `BacklogPolicy::DropOldest` or `BacklogPolicy::DropNewest` since the port was added
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Push a chunk based on the policy
+    fn push(&mut self, chunk: Vec<u8>) {
+        if self.is_full() {
+            match self.setting.policy {
+                BacklogPolicy::DropOldest => {
+                    self.chunks.pop_front();
+                    self.dropped += 1;
+                }
+                BacklogPolicy::DropNewest => {
+                    self.dropped += 1;
+                    return;
+                }
+                // the port is not read while full, so this is a chunk read before it became full
+                BacklogPolicy::Block => {}
+            }
+        }
+        self.chunks.push_back(chunk);
+    }
+}
+
+/// Resource holding the chunks read from ports with `SerialSetting::backlog`, instead of sending `SerialReadEvent`.
+/// Chunks stay until they are drained, so consumers can lag behind without missing data up to the capacity,
+/// and without the allocations of events kept for two frames.
+#[derive(Debug, Default, Resource)]
+pub struct SerialBacklog(HashMap<String, PortBacklog>);
+
+impl SerialBacklog {
+    /// Take all chunks read from the port with the label, the oldest first
+    pub fn drain(&mut self, label: &str) -> Option<Drain<'_, Vec<u8>>> {
+        let backlog = self.0.get_mut(label)?;
+        backlog.drained += backlog.chunks.len() as u64;
+        Some(backlog.chunks.drain(..))
+    }
+
+    /// Backlog of the port with the label
+    pub fn get(&self, label: &str) -> Option<&PortBacklog> {
+        self.0.get(label)
+    }
+
+    /// Backlogs of all ports with their labels
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &PortBacklog)> {
+        self.0.iter()
+    }
+
+    /// Whether the port with the label should not be read now
+    pub(crate) fn is_blocked(&self, label: &str) -> bool {
+        self.0.get(label).is_some_and(|backlog| {
+            backlog.setting.policy == BacklogPolicy::Block && backlog.is_full()
+        })
+    }
+
+    pub(crate) fn push(&mut self, label: &str, setting: BacklogSetting, chunk: Vec<u8>) {
+        self.0
+            .entry(label.to_string())
+            .or_insert_with(|| PortBacklog::new(setting))
+            .push(chunk);
+    }
+}
@@ -0,0 +1,33 @@
+use mio_serial::SerialPort;
+use std::io::{self, ErrorKind};
+use std::time::{Duration, Instant};
+
+/// Read exactly `buf.len()` bytes from a blocking port before the timeout
+pub(crate) fn read_exact_timeout(
+    port: &mut dyn SerialPort,
+    buf: &mut [u8],
+    timeout: Duration,
+) -> io::Result<()> {
+    let deadline = Instant::now() + timeout;
+    let mut bytes_read = 0;
+    while bytes_read < buf.len() {
+        match port.read(&mut buf[bytes_read..]) {
+            Ok(n) => bytes_read += n,
+            // keep reading until the deadline
+            Err(ref e) if matches!(e.kind(), ErrorKind::TimedOut | ErrorKind::Interrupted) => {}
+            Err(e) => return Err(e),
+        }
+        if bytes_read < buf.len() && Instant::now() > deadline {
+            return Err(io::Error::new(
+                ErrorKind::TimedOut,
+                "device did not respond",
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Error for unexpected responses from the device
+pub(crate) fn protocol_error(message: String) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message)
+}
@@ -0,0 +1,180 @@
+use crate::compat::*;
+use crate::{PendingErrors, SerialError, SerialPanicPolicy, SerialPorts, SerialSetting};
+use bevy::ecs::system::{Local, Res, ResMut};
+use bevy::log::{info, warn};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Length of the windows in which errors are counted
+const WINDOW: Duration = Duration::from_secs(1);
+
+/// Settings to close a port which keeps failing, e.g. a misbehaving adapter flooding read errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CircuitBreaker {
+    /// A second with more read and write errors than this counts as a storm
+    pub max_errors_per_sec: u32,
+    /// The port is closed after this many seconds of storm in a row
+    pub storm_secs: u32,
+    /// If set, open the port again with the same setting after this delay
+    pub reopen_after: Option<Duration>,
+}
+
+impl CircuitBreaker {
+    pub fn new(max_errors_per_sec: u32, storm_secs: u32) -> Self {
+        Self {
+            max_errors_per_sec,
+            storm_secs,
+            reopen_after: None,
+        }
+    }
+
+    /// Open the port again after the delay once it is closed
+    pub fn reopen_after(mut self, delay: Duration) -> Self {
+        self.reopen_after = Some(delay);
+        self
+    }
+}
+
+/// Bevy's event type to notify that a port with `SerialSetting::circuit_breaker` was closed or opened again
+#[derive(Debug, Clone, PartialEq, Eq, Event)]
+pub enum SerialBreakerEvent {
+    /// The port with the label was closed after `errors` errors in the storm
+    Tripped { label: String, errors: u64 },
+    /// The port with the label was opened again after `CircuitBreaker::reopen_after`
+    Reopened { label: String },
+}
+
+/// Errors of a port counted in the current window
+struct Storm {
+    window_start: Instant,
+    errors: u32,
+    /// Seconds of storm in a row, and the errors in them
+    secs: u32,
+    total: u64,
+}
+
+#[derive(Default)]
+pub(crate) struct Breakers {
+    storms: HashMap<String, Storm>,
+    /// Labels and settings of tripped ports to open again at the time
+    reopen: Vec<(Instant, String, SerialSetting)>,
+}
+
+/// Count the read and write errors of ports with `SerialSetting::circuit_breaker`, close the ports in a storm,
+/// and open them again after `CircuitBreaker::reopen_after`
+pub(crate) fn trip_breakers(
+    mut ev_error: EventReader<SerialError>,
+    mut ev_breaker: EventWriter<SerialBreakerEvent>,
+    mut breakers: Local<Breakers>,
+    mut ports: SerialPorts,
+    // errors are sent in the next update because this system reads them
+    mut pending: ResMut<PendingErrors>,
+    policy: Res<SerialPanicPolicy>,
+) {
+    let now = Instant::now();
+    let breakers = &mut *breakers;
+    for error in ev_error.read() {
+        let label = match error {
+            SerialError::Read { label, .. } | SerialError::Write { label, .. } => label,
+            _ => continue,
+        };
+        if breaker_of(&ports, label).is_none() {
+            continue;
+        }
+        let storm = breakers
+            .storms
+            .entry(label.clone())
+            .or_insert_with(|| Storm {
+                window_start: now,
+                errors: 0,
+                secs: 0,
+                total: 0,
+            });
+        storm.errors += 1;
+        storm.total += 1;
+    }
+
+    // close the ports whose storm has lasted long enough at the end of each window
+    let mut tripped = vec![];
+    for (label, storm) in breakers.storms.iter_mut() {
+        if now.duration_since(storm.window_start) < WINDOW {
+            continue;
+        }
+        let breaker = match breaker_of(&ports, label) {
+            Some(breaker) => breaker,
+            None => continue,
+        };
+        // a window without errors for longer than a second ends the storm too
+        let stormy = storm.errors > breaker.max_errors_per_sec
+            && now.duration_since(storm.window_start) < WINDOW * 2;
+        if stormy {
+            storm.secs += 1;
+        } else {
+            storm.secs = 0;
+            storm.total = 0;
+        }
+        storm.window_start = now;
+        storm.errors = 0;
+        if storm.secs >= breaker.storm_secs {
+            tripped.push((label.clone(), storm.total, breaker));
+        }
+    }
+    for (label, errors, breaker) in tripped {
+        breakers.storms.remove(&label);
+        let setting = match setting_of(&ports, &label) {
+            Some(setting) => setting,
+            None => continue,
+        };
+        warn!("{} is closed after {} errors", label, errors);
+        ports.remove(&label);
+        ev_breaker.send(SerialBreakerEvent::Tripped {
+            label: label.clone(),
+            errors,
+        });
+        if let Some(delay) = breaker.reopen_after {
+            breakers.reopen.push((now + delay, label, setting));
+        }
+    }
+
+    // open the tripped ports again, retrying after the same delay if they fail to open
+    let (due, waiting) = std::mem::take(&mut breakers.reopen)
+        .into_iter()
+        .partition(|(at, _, _)| *at <= now);
+    breakers.reopen = waiting;
+    for (_, label, setting) in due {
+        // the port was added again by the app
+        if ports.indices.0.contains_key(&label) {
+            continue;
+        }
+        match ports.add(setting.clone()) {
+            Ok(label) => {
+                info!(
+                    "{} is opened again after its circuit breaker tripped",
+                    label
+                );
+                ev_breaker.send(SerialBreakerEvent::Reopened { label });
+            }
+            Err(e) => {
+                policy.handle(&e, false);
+                pending.0.push(e);
+                let delay = setting
+                    .circuit_breaker
+                    .and_then(|breaker| breaker.reopen_after)
+                    .unwrap_or_default();
+                breakers.reopen.push((now + delay, label, setting));
+            }
+        }
+    }
+}
+
+fn breaker_of(ports: &SerialPorts, label: &str) -> Option<CircuitBreaker> {
+    let index = *ports.indices.0.get(label)?;
+    let serial = ports.serials.0.get(index)?.lock().ok()?;
+    serial.as_ref()?.setting.circuit_breaker
+}
+
+fn setting_of(ports: &SerialPorts, label: &str) -> Option<SerialSetting> {
+    let index = *ports.indices.0.get(label)?;
+    let serial = ports.serials.0.get(index)?.lock().ok()?;
+    Some(serial.as_ref()?.setting.clone())
+}
@@ -0,0 +1,99 @@
+use crate::compat::*;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// Default duration of arrivals to estimate clock offsets from
+const DEFAULT_WINDOW: Duration = Duration::from_secs(10);
+/// Offsets jumping more than this are treated as the device clock being reset, in seconds
+const RESET_THRESHOLD: f64 = 1.0;
+
+/// Offset of the clock of one device from the host clock
+#[derive(Debug, Clone, Default)]
+struct ClockEstimate {
+    /// Arrival time and `arrival - device time` of recent samples, in seconds since the epoch
+    samples: VecDeque<(f64, f64)>,
+    /// Minimum of the offsets in the window
+    offset: Option<f64>,
+}
+
+/// Resource to put timestamps of multiple devices on the host timeline, so that data of multiple sensors lines up.
+///
+/// Feed the timestamp of each sample from the device with its arrival time via `observe`.
+/// The offset of each device clock is the minimum of `arrival - device time` over the window,
+/// which is the sample with the least transport and scheduling delay, so it follows drift of the device clock
+/// as old samples leave the window. Add it with `app.init_resource::<SerialClock>()`.
+#[derive(Debug, Clone, Resource)]
+pub struct SerialClock {
+    epoch: Instant,
+    window: Duration,
+    ports: HashMap<String, ClockEstimate>,
+}
+
+impl Default for SerialClock {
+    fn default() -> Self {
+        Self::new(DEFAULT_WINDOW)
+    }
+}
+
+impl SerialClock {
+    /// Estimate offsets from arrivals within the last `window`
+    pub fn new(window: Duration) -> Self {
+        Self {
+            epoch: Instant::now(),
+            window,
+            ports: HashMap::new(),
+        }
+    }
+
+    /// Record a sample with the timestamp from the clock of the device on the port with the label,
+    /// e.g. `millis()` of an Arduino, and the time it arrived (e.g. when `SerialReadEvent` was read)
+    pub fn observe(&mut self, label: &str, device_time: Duration, arrival: Instant) {
+        let arrival = arrival.saturating_duration_since(self.epoch).as_secs_f64();
+        let offset = arrival - device_time.as_secs_f64();
+        let estimate = self.ports.entry(label.to_string()).or_default();
+
+        // the device has restarted or its clock wrapped around
+        if let Some(current) = estimate.offset {
+            if (offset - current).abs() > RESET_THRESHOLD {
+                estimate.samples.clear();
+            }
+        }
+        estimate.samples.push_back((arrival, offset));
+        let start = arrival - self.window.as_secs_f64();
+        while let Some((t, _)) = estimate.samples.front() {
+            if *t >= start {
+                break;
+            }
+            estimate.samples.pop_front();
+        }
+        estimate.offset = estimate
+            .samples
+            .iter()
+            .map(|(_, offset)| *offset)
+            .reduce(f64::min);
+    }
+
+    /// Host time of a timestamp from the clock of the device, or `None` if nothing has been observed for the port
+    pub fn to_host(&self, label: &str, device_time: Duration) -> Option<Instant> {
+        let offset = self.ports.get(label)?.offset?;
+        let secs = device_time.as_secs_f64() + offset;
+        Some(if secs >= 0.0 {
+            self.epoch + Duration::from_secs_f64(secs)
+        } else {
+            self.epoch - Duration::from_secs_f64(-secs)
+        })
+    }
+
+    /// Offset of the device clock from the clock of another device in seconds,
+    /// i.e. the time to add to timestamps of `label` to get timestamps of `other`
+    pub fn offset_between(&self, label: &str, other: &str) -> Option<f64> {
+        let offset = self.ports.get(label)?.offset?;
+        let other = self.ports.get(other)?.offset?;
+        Some(offset - other)
+    }
+
+    /// Forget the estimate of the port, e.g. after the device has been reset
+    pub fn reset(&mut self, label: &str) {
+        self.ports.remove(label);
+    }
+}
@@ -0,0 +1,280 @@
+use crate::compat::*;
+use crate::{SerialLatency, SerialReadEvent, SerialSystem};
+use bevy::app::{App, Plugin};
+use bevy::ecs::system::ResMut;
+use bevy::tasks::ComputeTaskPool;
+use std::collections::HashMap;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Mutex;
+
+/// Decoder to split bytes read from serial port into frames.
+/// A decoder keeps incomplete data internally until the rest of the frame arrives.
+pub trait Decoder: Send + Sync + 'static {
+    /// Type of decoded frames
+    type Frame: Send + Sync + 'static;
+
+    /// Feed bytes read from serial port and push complete frames to `frames`
+    fn decode(&mut self, bytes: &[u8], frames: &mut Vec<Self::Frame>);
+}
+
+/// Bevy's event type for frames decoded from serial port
+#[derive(Event)]
+pub struct SerialFrameEvent<F>(pub String, pub F);
+
+/// Plugin to decode bytes read from the serial port with the label into `SerialFrameEvent<D::Frame>`.
+/// `SerialPlugin` should be added before this plugin.
+pub struct DecoderPlugin<D: Decoder + Clone> {
+    /// Label (or port name) of the serial port to decode
+    pub label: String,
+    /// Decoder to use for this serial port
+    pub decoder: D,
+    /// Decode on `ComputeTaskPool` and send the frames in the next update, for CPU-heavy decoders
+    pub task_pool: bool,
+}
+
+impl<D: Decoder + Clone> DecoderPlugin<D> {
+    pub fn new(label: &str, decoder: D) -> Self {
+        Self {
+            label: label.to_string(),
+            decoder,
+            task_pool: false,
+        }
+    }
+
+    /// Decode on `ComputeTaskPool` instead of in `CoreStage::PreUpdate`.
+    /// The frames are sent one update later, and the bytes read while a chunk is decoded are decoded next.
+    pub fn on_task_pool(mut self) -> Self {
+        self.task_pool = true;
+        self
+    }
+}
+
+/// Decoders of each serial port by label
+#[derive(Resource)]
+struct Decoders<D>(HashMap<String, D>);
+
+/// Decoders of each serial port by label running on the task pool
+#[derive(Resource)]
+struct PooledDecoders<D: Decoder> {
+    ports: HashMap<String, PooledDecoder<D>>,
+    /// Decoders and frames sent back by the tasks
+    sender: Mutex<Sender<DecodedTask<D>>>,
+    receiver: Mutex<Receiver<DecodedTask<D>>>,
+}
+
+/// Label of the port, its decoder and the frames decoded by a task
+type DecodedTask<D> = (String, D, Vec<<D as Decoder>::Frame>);
+
+struct PooledDecoder<D> {
+    /// `None` while the decoder is on the task pool
+    decoder: Option<D>,
+    /// Bytes read while the decoder is on the task pool
+    queued: Vec<u8>,
+}
+
+impl<D: Decoder + Clone> Plugin for DecoderPlugin<D> {
+    fn build(&self, app: &mut App) {
+        if self.task_pool {
+            add_pooled_decoder(app, &self.label, self.decoder.clone());
+            return;
+        }
+
+        // the decoding system is shared by all ports using the same type of decoder
+        if !app.world.contains_resource::<Decoders<D>>() {
+            add_frame_event::<D::Frame>(app);
+            app.insert_resource(Decoders::<D>(HashMap::new()))
+                .add_systems(
+                    PreUpdate,
+                    decode_serial::<D>
+                        .in_set(SerialSystem::Decode)
+                        .after(SerialSystem::Read),
+                );
+        }
+
+        app.world
+            .get_resource_mut::<Decoders<D>>()
+            .expect("Decoders are not initialized")
+            .0
+            .insert(self.label.clone(), self.decoder.clone());
+    }
+}
+
+/// Add `SerialFrameEvent<F>` once, as decoders of different types (or the same decoder on and off the task pool)
+/// can decode the same type of frames, and adding the event twice would clear it twice each frame
+fn add_frame_event<F: Send + Sync + 'static>(app: &mut App) {
+    if !app.world.contains_resource::<Events<SerialFrameEvent<F>>>() {
+        app.add_event::<SerialFrameEvent<F>>();
+    }
+}
+
+/// Decode `SerialReadEvent` and send `SerialFrameEvent` for each complete frame
+fn decode_serial<D: Decoder>(
+    mut ev_read_serial: EventReader<SerialReadEvent>,
+    mut ev_frame: EventWriter<SerialFrameEvent<D::Frame>>,
+    mut decoders: ResMut<Decoders<D>>,
+    mut latency: Option<ResMut<SerialLatency>>,
+) {
+    let mut frames = vec![];
+    for SerialReadEvent(label, buffer) in ev_read_serial.read() {
+        if let Some(decoder) = decoders.0.get_mut(label) {
+            decoder.decode(buffer, &mut frames);
+            if let (Some(latency), false) = (&mut latency, frames.is_empty()) {
+                latency.decoded(label);
+            }
+            for frame in frames.drain(..) {
+                ev_frame.send(SerialFrameEvent(label.clone(), frame));
+            }
+        }
+    }
+}
+
+fn add_pooled_decoder<D: Decoder>(app: &mut App, label: &str, decoder: D) {
+    if !app.world.contains_resource::<PooledDecoders<D>>() {
+        add_frame_event::<D::Frame>(app);
+        let (sender, receiver) = mpsc::channel();
+        app.insert_resource(PooledDecoders::<D> {
+            ports: HashMap::new(),
+            sender: Mutex::new(sender),
+            receiver: Mutex::new(receiver),
+        })
+        .add_systems(
+            PreUpdate,
+            decode_on_task_pool::<D>
+                .in_set(SerialSystem::Decode)
+                .after(SerialSystem::Read),
+        );
+    }
+
+    app.world
+        .get_resource_mut::<PooledDecoders<D>>()
+        .expect("PooledDecoders are not initialized")
+        .ports
+        .insert(
+            label.to_string(),
+            PooledDecoder {
+                decoder: Some(decoder),
+                queued: vec![],
+            },
+        );
+}
+
+/// Send the frames decoded on the task pool since the last update,
+/// then spawn a task for each port with bytes read and its decoder back
+fn decode_on_task_pool<D: Decoder>(
+    mut ev_read_serial: EventReader<SerialReadEvent>,
+    mut ev_frame: EventWriter<SerialFrameEvent<D::Frame>>,
+    mut decoders: ResMut<PooledDecoders<D>>,
+    #[cfg(feature = "bevy_06")] pool: Option<bevy::ecs::system::Res<ComputeTaskPool>>,
+    mut latency: Option<ResMut<SerialLatency>>,
+) {
+    #[cfg(feature = "bevy_013")]
+    let pool = ComputeTaskPool::try_get();
+    #[cfg(feature = "bevy_06")]
+    let pool = pool.as_deref();
+    let decoders = &mut *decoders;
+    if let Ok(receiver) = decoders.receiver.lock() {
+        for (label, decoder, frames) in receiver.try_iter() {
+            if let (Some(latency), false) = (&mut latency, frames.is_empty()) {
+                latency.decoded(&label);
+            }
+            for frame in frames {
+                ev_frame.send(SerialFrameEvent(label.clone(), frame));
+            }
+            if let Some(port) = decoders.ports.get_mut(&label) {
+                port.decoder = Some(decoder);
+            }
+        }
+    }
+
+    for SerialReadEvent(label, buffer) in ev_read_serial.read() {
+        if let Some(port) = decoders.ports.get_mut(label) {
+            port.queued.extend_from_slice(buffer);
+        }
+    }
+
+    let sender = match decoders.sender.lock() {
+        Ok(sender) => sender,
+        Err(_) => return,
+    };
+    for (label, port) in decoders.ports.iter_mut() {
+        if port.queued.is_empty() {
+            continue;
+        }
+        let mut decoder = match port.decoder.take() {
+            Some(decoder) => decoder,
+            None => continue,
+        };
+        let bytes = std::mem::take(&mut port.queued);
+        match pool {
+            Some(pool) => {
+                let label = label.clone();
+                let sender = sender.clone();
+                pool.spawn(async move {
+                    let mut frames = vec![];
+                    decoder.decode(&bytes, &mut frames);
+                    // the app has stopped if the receiver is gone
+                    let _ = sender.send((label, decoder, frames));
+                })
+                .detach();
+            }
+            // without task pools (no `TaskPoolPlugin`, or `CorePlugin` in Bevy 0.6), decode here and send the frames in the next update
+            None => {
+                let mut frames = vec![];
+                decoder.decode(&bytes, &mut frames);
+                let _ = sender.send((label.clone(), decoder, frames));
+            }
+        }
+    }
+}
+
+/// Decoder which splits bytes into lines by the delimiter.
+/// A trailing `'\r'` is removed, so both `"\n"` and `"\r\n"` line endings work with the default delimiter.
+#[derive(Debug, Clone)]
+pub struct LineDecoder {
+    delimiter: u8,
+    max_len: usize,
+    buf: Vec<u8>,
+    overflowed: bool,
+}
+
+impl LineDecoder {
+    /// Create a decoder splitting lines by `delimiter`.
+    /// Lines longer than `max_len` are discarded not to grow the buffer infinitely.
+    pub fn new(delimiter: u8, max_len: usize) -> Self {
+        Self {
+            delimiter,
+            max_len,
+            buf: vec![],
+            overflowed: false,
+        }
+    }
+}
+
+impl Default for LineDecoder {
+    fn default() -> Self {
+        Self::new(b'\n', 1024)
+    }
+}
+
+impl Decoder for LineDecoder {
+    type Frame = Vec<u8>;
+
+    fn decode(&mut self, bytes: &[u8], frames: &mut Vec<Self::Frame>) {
+        for &b in bytes {
+            if b == self.delimiter {
+                let mut line = std::mem::take(&mut self.buf);
+                if line.last() == Some(&b'\r') {
+                    line.pop();
+                }
+                if !std::mem::take(&mut self.overflowed) {
+                    frames.push(line);
+                }
+            } else if self.buf.len() < self.max_len {
+                self.buf.push(b);
+            } else {
+                self.buf.clear();
+                self.overflowed = true;
+            }
+        }
+    }
+}
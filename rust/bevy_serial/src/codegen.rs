@@ -0,0 +1,346 @@
+use crate::codec::Decoder;
+use crate::rc::crc8_dvb_s2;
+use std::fmt::Write;
+
+/// Type of a field of a message, with the same size in Rust and in C on the device
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldType {
+    U8,
+    I8,
+    U16,
+    I16,
+    U32,
+    I32,
+    /// `float`, which is the only floating point type of the same size on AVR (where `double` is 4 bytes too)
+    F32,
+}
+
+impl FieldType {
+    /// Size in bytes on the wire
+    pub fn size(self) -> usize {
+        match self {
+            FieldType::U8 | FieldType::I8 => 1,
+            FieldType::U16 | FieldType::I16 => 2,
+            FieldType::U32 | FieldType::I32 | FieldType::F32 => 4,
+        }
+    }
+
+    fn c_type(self) -> &'static str {
+        match self {
+            FieldType::U8 => "uint8_t",
+            FieldType::I8 => "int8_t",
+            FieldType::U16 => "uint16_t",
+            FieldType::I16 => "int16_t",
+            FieldType::U32 => "uint32_t",
+            FieldType::I32 => "int32_t",
+            FieldType::F32 => "float",
+        }
+    }
+}
+
+/// Check byte at the end of each frame, computed over the payload
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Checksum {
+    /// XOR of the payload bytes
+    Xor,
+    /// CRC-8/DVB-S2 (polynomial 0xD5), as in CRSF frames
+    Crc8DvbS2,
+}
+
+impl Checksum {
+    pub fn compute(self, payload: &[u8]) -> u8 {
+        match self {
+            Checksum::Xor => payload.iter().fold(0, |acc, b| acc ^ b),
+            Checksum::Crc8DvbS2 => crc8_dvb_s2(payload),
+        }
+    }
+}
+
+/// Binary message sent by a device: the sync bytes, the fields in little endian without padding,
+/// and a checksum byte of the fields.
+///
+/// The same schema generates the C header of the firmware (`to_arduino`) and decodes the frames (`decoder`),
+/// so the firmware and the app can't disagree on the layout.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessageSchema {
+    /// Name of the C struct, e.g. `ImuSample`
+    pub name: String,
+    /// Bytes starting each frame, which should not be empty
+    pub sync: Vec<u8>,
+    /// Names (valid C identifiers) and types of the fields in order
+    pub fields: Vec<(String, FieldType)>,
+    pub checksum: Checksum,
+}
+
+impl MessageSchema {
+    /// Schema without fields, with an XOR checksum
+    pub fn new(name: &str, sync: &[u8]) -> Self {
+        Self {
+            name: name.to_string(),
+            sync: sync.to_vec(),
+            fields: vec![],
+            checksum: Checksum::Xor,
+        }
+    }
+
+    /// Add a field after the fields added before
+    pub fn field(mut self, name: &str, ty: FieldType) -> Self {
+        self.fields.push((name.to_string(), ty));
+        self
+    }
+
+    pub fn checksum(mut self, checksum: Checksum) -> Self {
+        self.checksum = checksum;
+        self
+    }
+
+    /// Length of the fields in bytes
+    pub fn payload_len(&self) -> usize {
+        self.fields.iter().map(|(_, ty)| ty.size()).sum()
+    }
+
+    /// Length of a frame with the sync bytes and the checksum
+    pub fn frame_len(&self) -> usize {
+        self.sync.len() + self.payload_len() + 1
+    }
+
+    /// Offset of the field in the payload
+    pub fn offset(&self, name: &str) -> Option<usize> {
+        let index = self.fields.iter().position(|(field, _)| field == name)?;
+        Some(self.fields[..index].iter().map(|(_, ty)| ty.size()).sum())
+    }
+
+    /// FNV-1a hash of the name, sync bytes, fields and checksum, which the device answers to the schema query
+    /// of `SchemaHandshakePlugin`. It is stable across builds, so it can be compared with older firmware.
+    pub fn fingerprint(&self) -> u32 {
+        let mut bytes = self.name.as_bytes().to_vec();
+        bytes.push(0);
+        bytes.extend_from_slice(&self.sync);
+        for (name, ty) in self.fields.iter() {
+            bytes.push(0);
+            bytes.extend_from_slice(name.as_bytes());
+            bytes.push(*ty as u8);
+        }
+        bytes.push(self.checksum as u8);
+        bytes.iter().fold(0x811c9dc5, |hash, &b| {
+            (hash ^ b as u32).wrapping_mul(0x01000193)
+        })
+    }
+
+    /// Frame of the payload, e.g. to send the message from the app or to test a decoder
+    pub fn encode(&self, payload: &[u8]) -> Vec<u8> {
+        let mut frame = self.sync.clone();
+        frame.extend_from_slice(payload);
+        frame.push(self.checksum.compute(payload));
+        frame
+    }
+
+    /// Decoder of the frames of this schema
+    pub fn decoder(&self) -> MessageDecoder {
+        MessageDecoder {
+            schema: self.clone(),
+            buf: vec![],
+        }
+    }
+
+    /// C header for Arduino with a packed struct of the fields and functions to frame and send it.
+    /// The struct is copied as is, so the device must be little endian (AVR, ESP32 and ARM Cortex-M are).
+    pub fn to_arduino(&self) -> String {
+        let ty = &self.name;
+        let prefix = snake_case(&self.name);
+        let upper = prefix.to_uppercase();
+        let sync: Vec<String> = self.sync.iter().map(|b| format!("0x{:02X}", b)).collect();
+        let checksum = match self.checksum {
+            Checksum::Xor => "XOR",
+            Checksum::Crc8DvbS2 => "CRC-8/DVB-S2",
+        };
+
+        // writing to a String never fails
+        let mut h = String::new();
+        let _ = writeln!(
+            h,
+            "// Generated by bevy_serial from the MessageSchema `{}`, do not edit.",
+            ty
+        );
+        let _ = writeln!(
+            h,
+            "// Frame: sync {}, {} bytes of fields in little endian, {} of the fields",
+            sync.join(" "),
+            self.payload_len(),
+            checksum
+        );
+        h.push_str(
+            "#pragma once\n#include <stddef.h>\n#include <stdint.h>\n#include <string.h>\n\n",
+        );
+        let _ = writeln!(h, "#define {}_PAYLOAD_LEN {}", upper, self.payload_len());
+        let _ = writeln!(h, "#define {}_FRAME_LEN {}", upper, self.frame_len());
+        let _ = writeln!(
+            h,
+            "#define {}_SCHEMA_FINGERPRINT 0x{:08X}UL\n",
+            upper,
+            self.fingerprint()
+        );
+        let _ = writeln!(
+            h,
+            "static const uint8_t {}_SYNC[{}] = {{{}}};\n",
+            upper,
+            self.sync.len(),
+            sync.join(", ")
+        );
+
+        h.push_str("typedef struct __attribute__((packed)) {\n");
+        for (name, field) in self.fields.iter() {
+            let _ = writeln!(h, "  {} {};", field.c_type(), name);
+        }
+        let _ = writeln!(h, "}} {};\n", ty);
+
+        let _ = writeln!(
+            h,
+            "static inline uint8_t {}_checksum(const uint8_t *payload, size_t len) {{",
+            prefix
+        );
+        h.push_str("  uint8_t check = 0;\n  for (size_t i = 0; i < len; i++) {\n");
+        match self.checksum {
+            Checksum::Xor => h.push_str("    check ^= payload[i];\n"),
+            Checksum::Crc8DvbS2 => h.push_str(
+                "    check ^= payload[i];\n    for (int bit = 0; bit < 8; bit++) {\n      \
+                 check = (check & 0x80) ? (uint8_t)((check << 1) ^ 0xD5) : (uint8_t)(check << 1);\n    }\n",
+            ),
+        }
+        h.push_str("  }\n  return check;\n}\n\n");
+
+        let _ = writeln!(
+            h,
+            "// Write the frame of the message to `frame`, which must have {}_FRAME_LEN bytes",
+            upper
+        );
+        let _ = writeln!(
+            h,
+            "static inline void {}_encode(const {} *message, uint8_t *frame) {{",
+            prefix, ty
+        );
+        let _ = writeln!(
+            h,
+            "  memcpy(frame, {}_SYNC, sizeof({}_SYNC));",
+            upper, upper
+        );
+        let _ = writeln!(
+            h,
+            "  memcpy(frame + sizeof({}_SYNC), message, {}_PAYLOAD_LEN);",
+            upper, upper
+        );
+        let _ = writeln!(
+            h,
+            "  frame[{u}_FRAME_LEN - 1] = {}_checksum(frame + sizeof({u}_SYNC), {u}_PAYLOAD_LEN);",
+            prefix,
+            u = upper
+        );
+        h.push_str("}\n\n#ifdef ARDUINO\n#include <Arduino.h>\n\n");
+        let _ = writeln!(
+            h,
+            "// Send the message, e.g. `{}_write(Serial, message)`",
+            prefix
+        );
+        let _ = writeln!(
+            h,
+            "static inline size_t {}_write(Print &out, const {} &message) {{",
+            prefix, ty
+        );
+        let _ = writeln!(h, "  uint8_t frame[{}_FRAME_LEN];", upper);
+        let _ = writeln!(h, "  {}_encode(&message, frame);", prefix);
+        h.push_str("  return out.write(frame, sizeof(frame));\n}\n\n");
+        let _ = writeln!(
+            h,
+            "// Answer the schema query of the app, when a line \"?schema\" is received"
+        );
+        let _ = writeln!(
+            h,
+            "static inline size_t {}_write_schema(Print &out) {{",
+            prefix
+        );
+        let _ = writeln!(
+            h,
+            "  return out.print(\"schema {} {:08x}\\n\");",
+            ty,
+            self.fingerprint()
+        );
+        h.push_str("}\n#endif\n");
+        h
+    }
+}
+
+/// `ImuSample` to `imu_sample`, for the names of the C functions and macros
+fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    let mut prev_lower = false;
+    for c in name.chars() {
+        if !c.is_ascii_alphanumeric() {
+            if !snake.ends_with('_') {
+                snake.push('_');
+            }
+            prev_lower = false;
+            continue;
+        }
+        if c.is_ascii_uppercase() && prev_lower {
+            snake.push('_');
+        }
+        prev_lower = c.is_ascii_lowercase() || c.is_ascii_digit();
+        snake.push(c.to_ascii_lowercase());
+    }
+    snake
+}
+
+/// Decoder for the frames of a `MessageSchema`. Frames are the payloads with a valid checksum,
+/// whose fields are at `MessageSchema::offset` in little endian.
+#[derive(Debug, Clone)]
+pub struct MessageDecoder {
+    schema: MessageSchema,
+    buf: Vec<u8>,
+}
+
+impl Decoder for MessageDecoder {
+    type Frame = Vec<u8>;
+
+    fn decode(&mut self, bytes: &[u8], frames: &mut Vec<Self::Frame>) {
+        self.buf.extend_from_slice(bytes);
+        let (sync_len, len) = (self.schema.sync.len(), self.schema.frame_len());
+        let sync = &self.schema.sync;
+
+        let mut pos = 0;
+        loop {
+            // skip to the sync bytes
+            let start = if sync.is_empty() {
+                Some(pos)
+            } else {
+                self.buf[pos..]
+                    .windows(sync_len)
+                    .position(|w| w == sync)
+                    .map(|i| pos + i)
+            };
+            match start {
+                Some(start) => pos = start,
+                None => {
+                    // keep the bytes which may be the start of the sync bytes
+                    pos = self
+                        .buf
+                        .len()
+                        .saturating_sub(sync_len.saturating_sub(1))
+                        .max(pos);
+                    break;
+                }
+            }
+            if self.buf.len() < pos + len {
+                break;
+            }
+            let payload = &self.buf[pos + sync_len..pos + len - 1];
+            if self.schema.checksum.compute(payload) == self.buf[pos + len - 1] {
+                frames.push(payload.to_vec());
+                pos += len;
+            } else {
+                // not a frame, look for the next sync bytes
+                pos += 1;
+            }
+        }
+        self.buf.drain(..pos);
+    }
+}
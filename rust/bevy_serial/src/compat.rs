@@ -0,0 +1,144 @@
+//! The crate is written against Bevy 0.13. The modules import the Bevy items which changed since 0.6 from here
+//! (`use crate::compat::*`), which re-exports them from Bevy 0.13 with the `bevy_013` feature, and shims them on top
+//! of Bevy 0.6 with the `bevy_06` feature:
+//!
+//! | Bevy 0.13                                  | Bevy 0.6                                            |
+//! | ------------------------------------------ | --------------------------------------------------- |
+//! | `#[derive(Event)]`, `#[derive(Resource)]`  | no-op derives of `bevy_serial_derive`               |
+//! | `#[derive(SystemSet)]`                     | `#[derive(SystemLabel)]`                            |
+//! | `App::add_systems(PreUpdate, system)`      | `App::add_system_to_stage(CoreStage::PreUpdate, ..)` |
+//! | `system.in_set(set)`                       | `system.label(label)`                               |
+//! | `system.run_if(condition)` (`bool`)        | `system.with_run_criteria(..)` (`ShouldRun`)        |
+//! | `EventReader::read`                        | `EventReader::iter`                                 |
+//!
+//! `PluginGroup`, `Timer`, `Time` and the task pools differ too much for a shim, their few users are `cfg`'d instead.
+
+// not every combination of features uses every item
+#![allow(unused_imports)]
+
+#[cfg(all(feature = "bevy_013", feature = "bevy_06"))]
+compile_error!("the `bevy_013` and `bevy_06` features are exclusive, disable the default features for `bevy_06`");
+#[cfg(not(any(feature = "bevy_013", feature = "bevy_06")))]
+compile_error!("one of the `bevy_013` and `bevy_06` features is required");
+
+#[cfg(feature = "bevy_013")]
+pub(crate) use bevy::app::{Last, PostUpdate, PreUpdate, Startup, Update};
+#[cfg(feature = "bevy_013")]
+pub(crate) use bevy::ecs::event::{Event, EventReader, EventWriter, Events};
+#[cfg(feature = "bevy_013")]
+pub(crate) use bevy::ecs::schedule::{IntoSystemConfigs, SystemSet};
+#[cfg(feature = "bevy_013")]
+pub(crate) use bevy::ecs::system::Resource;
+
+#[cfg(feature = "bevy_06")]
+pub(crate) use bevy::app::{EventReader, EventWriter, Events};
+#[cfg(feature = "bevy_06")]
+pub(crate) use bevy::ecs::schedule::SystemLabel as SystemSet;
+#[cfg(feature = "bevy_06")]
+pub(crate) use bevy_serial_derive::{Event, Resource};
+#[cfg(feature = "bevy_06")]
+pub(crate) use shims::*;
+
+#[cfg(feature = "bevy_06")]
+mod shims {
+    use bevy::app::{App, CoreStage, StartupStage};
+    use bevy::ecs::schedule::{
+        IntoSystemDescriptor, ParallelSystemDescriptor, ParallelSystemDescriptorCoercion,
+        ShouldRun, SystemLabel,
+    };
+    use bevy::ecs::system::{In, IntoChainSystem, IntoSystem, Resource};
+
+    use super::EventReader;
+
+    /// Schedules of Bevy 0.13, run in the stage of the same name
+    pub trait Schedule {
+        fn add_system<Params>(self, app: &mut App, system: impl IntoSystemDescriptor<Params>);
+    }
+
+    macro_rules! core_stages {
+        ($($stage:ident),*) => {
+            $(
+                pub struct $stage;
+
+                impl Schedule for $stage {
+                    fn add_system<Params>(self, app: &mut App, system: impl IntoSystemDescriptor<Params>) {
+                        app.add_system_to_stage(CoreStage::$stage, system);
+                    }
+                }
+            )*
+        };
+    }
+
+    core_stages!(PreUpdate, Update, PostUpdate, Last);
+
+    pub struct Startup;
+
+    impl Schedule for Startup {
+        fn add_system<Params>(self, app: &mut App, system: impl IntoSystemDescriptor<Params>) {
+            app.add_startup_system_to_stage(StartupStage::Startup, system);
+        }
+    }
+
+    /// `App::add_systems`, for one system
+    pub trait AddSystems {
+        fn add_systems<Params>(
+            &mut self,
+            schedule: impl Schedule,
+            system: impl IntoSystemDescriptor<Params>,
+        ) -> &mut Self;
+    }
+
+    impl AddSystems for App {
+        fn add_systems<Params>(
+            &mut self,
+            schedule: impl Schedule,
+            system: impl IntoSystemDescriptor<Params>,
+        ) -> &mut Self {
+            schedule.add_system(self, system);
+            self
+        }
+    }
+
+    /// Ordering and run conditions of Bevy 0.13, on system labels and run criteria
+    pub trait IntoSystemConfigs<Params>: ParallelSystemDescriptorCoercion<Params> + Sized {
+        fn in_set(self, set: impl SystemLabel) -> ParallelSystemDescriptor {
+            self.label(set)
+        }
+
+        fn before(self, set: impl SystemLabel) -> ParallelSystemDescriptor {
+            ParallelSystemDescriptorCoercion::before(self, set)
+        }
+
+        fn after(self, set: impl SystemLabel) -> ParallelSystemDescriptor {
+            ParallelSystemDescriptorCoercion::after(self, set)
+        }
+
+        fn run_if<Marker>(
+            self,
+            condition: impl IntoSystem<(), bool, Marker>,
+        ) -> ParallelSystemDescriptor {
+            self.with_run_criteria(condition.chain(should_run))
+        }
+    }
+
+    impl<Params, T: ParallelSystemDescriptorCoercion<Params>> IntoSystemConfigs<Params> for T {}
+
+    fn should_run(In(run): In<bool>) -> ShouldRun {
+        if run {
+            ShouldRun::Yes
+        } else {
+            ShouldRun::No
+        }
+    }
+
+    /// `EventReader::read`, renamed from `iter`
+    pub trait ReadEvents<T> {
+        fn read(&mut self) -> impl DoubleEndedIterator<Item = &T>;
+    }
+
+    impl<'w, 's, T: Resource> ReadEvents<T> for EventReader<'w, 's, T> {
+        fn read(&mut self) -> impl DoubleEndedIterator<Item = &T> {
+            self.iter()
+        }
+    }
+}
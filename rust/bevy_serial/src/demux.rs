@@ -0,0 +1,138 @@
+use crate::codec::{Decoder, LineDecoder, SerialFrameEvent};
+use crate::compat::*;
+use crate::SerialSystem;
+use bevy::app::App;
+use bevy::log::debug;
+use std::marker::PhantomData;
+
+/// Frame tagged with the type of message it carries
+pub trait TaggedFrame {
+    /// Message type of this frame
+    fn tag(&self) -> &str;
+    /// Body of the message without the tag
+    fn payload(&self) -> &[u8];
+}
+
+/// Message type which is demultiplexed from tagged frames
+pub trait SerialMessage: Sized + Send + Sync + 'static {
+    /// Tag of frames carrying this message
+    const TAG: &'static str;
+
+    /// Parse the payload of a frame. Returns `None` if the payload is malformed.
+    fn from_payload(payload: &[u8]) -> Option<Self>;
+}
+
+/// Bevy's event type for messages demultiplexed from serial port
+#[derive(Event)]
+pub struct SerialMessageEvent<M>(pub String, pub M);
+
+/// Marker resource for message types whose event is already added
+#[derive(Resource)]
+struct RegisteredMessage<M>(PhantomData<M>);
+
+/// Marker resource for message types already demultiplexed from frames of type `F`
+#[derive(Resource)]
+struct RegisteredDemux<F, M>(PhantomData<(F, M)>);
+
+/// Register a message type `M` demultiplexed from frames decoded by `D`.
+/// A message type can be demultiplexed from the frames of several decoders, which send the same event.
+pub(crate) fn add_serial_message<D, M>(app: &mut App)
+where
+    D: Decoder,
+    D::Frame: TaggedFrame,
+    M: SerialMessage,
+{
+    // adding the event twice would clear it twice each frame
+    if !app.world.contains_resource::<RegisteredMessage<M>>() {
+        app.insert_resource(RegisteredMessage::<M>(PhantomData))
+            .add_event::<SerialMessageEvent<M>>();
+    }
+    // decoders with the same type of frames send the same event, so demultiplexing it again
+    // (for the same decoder or another one) would send every message twice
+    if !app
+        .world
+        .contains_resource::<RegisteredDemux<D::Frame, M>>()
+    {
+        app.insert_resource(RegisteredDemux::<D::Frame, M>(PhantomData))
+            .add_systems(
+                PreUpdate,
+                demux_frames::<D::Frame, M>.after(SerialSystem::Decode),
+            );
+    }
+}
+
+/// Parse frames tagged with `M::TAG` and send `SerialMessageEvent<M>`
+fn demux_frames<F, M>(
+    mut ev_frame: EventReader<SerialFrameEvent<F>>,
+    mut ev_message: EventWriter<SerialMessageEvent<M>>,
+) where
+    F: TaggedFrame + Send + Sync + 'static,
+    M: SerialMessage,
+{
+    for SerialFrameEvent(label, frame) in ev_frame.read() {
+        if frame.tag() == M::TAG {
+            match M::from_payload(frame.payload()) {
+                Some(message) => {
+                    ev_message.send(SerialMessageEvent(label.clone(), message));
+                }
+                None => debug!("Failed to parse {} message from {}", M::TAG, label),
+            }
+        }
+    }
+}
+
+/// Line with its first token as the tag, e.g. `"IMU 0.01 0.02 0.98"`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaggedLine {
+    pub tag: String,
+    pub payload: Vec<u8>,
+}
+
+impl TaggedFrame for TaggedLine {
+    fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+}
+
+/// Decoder which splits bytes into lines and tags each line by its first token
+#[derive(Debug, Clone)]
+pub struct TaggedLineDecoder {
+    lines: LineDecoder,
+    separator: u8,
+}
+
+impl TaggedLineDecoder {
+    /// Create a decoder which tags lines decoded by `lines` with the token before `separator`
+    pub fn new(lines: LineDecoder, separator: u8) -> Self {
+        Self { lines, separator }
+    }
+}
+
+impl Default for TaggedLineDecoder {
+    fn default() -> Self {
+        Self::new(LineDecoder::default(), b' ')
+    }
+}
+
+impl Decoder for TaggedLineDecoder {
+    type Frame = TaggedLine;
+
+    fn decode(&mut self, bytes: &[u8], frames: &mut Vec<Self::Frame>) {
+        let mut lines = vec![];
+        self.lines.decode(bytes, &mut lines);
+        for line in lines {
+            let (tag, payload) = match line.iter().position(|&b| b == self.separator) {
+                Some(i) => (&line[..i], &line[i + 1..]),
+                None => (&line[..], &[][..]),
+            };
+            frames.push(TaggedLine {
+                tag: String::from_utf8_lossy(tag).into_owned(),
+                payload: payload.to_vec(),
+            });
+        }
+    }
+}
@@ -0,0 +1,124 @@
+/// Kermit-style escaping for devices which can't handle certain control bytes in-band.
+///
+/// Each byte in `escaped` (and the escape byte itself) is sent as the escape byte followed by the byte XORed with
+/// `xor`. Set as `SerialSetting::escape`, bytes written are escaped and bytes read are unescaped transparently.
+///
+/// ```rust
+/// use bevy_serial::EscapeCodec;
+///
+/// // escape XON/XOFF with `#` like Kermit does
+/// let codec = EscapeCodec::new(b'#', &[0x11, 0x13]);
+/// assert_eq!(codec.escape(&[0x01, 0x11, b'#']), vec![0x01, b'#', 0x51, b'#', b'#' ^ 0x40]);
+/// assert_eq!(codec.unescape(&codec.escape(&[0x01, 0x11, b'#'])), vec![0x01, 0x11, b'#']);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EscapeCodec {
+    /// Byte which prefixes an escaped byte
+    pub escape: u8,
+    /// Bytes which must not appear in-band
+    pub escaped: Vec<u8>,
+    /// Value XORed to an escaped byte (`0x40` by default, which maps control bytes to printable ones)
+    pub xor: u8,
+}
+
+impl EscapeCodec {
+    pub fn new(escape: u8, escaped: &[u8]) -> Self {
+        Self {
+            escape,
+            escaped: escaped.to_vec(),
+            xor: 0x40,
+        }
+    }
+
+    /// Set the value XORed to an escaped byte
+    pub fn xor(mut self, xor: u8) -> Self {
+        self.xor = xor;
+        self
+    }
+
+    /// Escape bytes to be written
+    pub fn escape(&self, bytes: &[u8]) -> Vec<u8> {
+        let mut escaped = Vec::with_capacity(bytes.len());
+        for &b in bytes {
+            if b == self.escape || self.escaped.contains(&b) {
+                escaped.push(self.escape);
+                escaped.push(b ^ self.xor);
+            } else {
+                escaped.push(b);
+            }
+        }
+        escaped
+    }
+
+    /// Unescape a complete buffer. A trailing escape byte is dropped.
+    pub fn unescape(&self, bytes: &[u8]) -> Vec<u8> {
+        self.unescape_chunk(bytes, &mut false)
+    }
+
+    /// Unescape one chunk of a stream.
+    /// `pending` is kept between chunks in case a chunk ends with the escape byte.
+    pub(crate) fn unescape_chunk(&self, bytes: &[u8], pending: &mut bool) -> Vec<u8> {
+        let mut unescaped = Vec::with_capacity(bytes.len());
+        for &b in bytes {
+            if *pending {
+                unescaped.push(b ^ self.xor);
+                *pending = false;
+            } else if b == self.escape {
+                *pending = true;
+            } else {
+                unescaped.push(b);
+            }
+        }
+        unescaped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every byte value, so that each escaped byte and the escape byte are in it
+    fn all_bytes() -> Vec<u8> {
+        (0..=255).collect()
+    }
+
+    #[test]
+    fn escapes_control_bytes_out_of_band() {
+        // SLIP-like escaping of END and ESC
+        let codec = EscapeCodec::new(0xDB, &[0xC0]).xor(0x20);
+        let escaped = codec.escape(&[0x01, 0xC0, 0xDB, 0x02]);
+        assert_eq!(escaped, [0x01, 0xDB, 0xE0, 0xDB, 0xFB, 0x02]);
+        assert!(!escaped.contains(&0xC0));
+    }
+
+    #[test]
+    fn round_trips_every_byte() {
+        let codec = EscapeCodec::new(b'#', &[0x00, 0x11, 0x13, 0x7F]);
+        let escaped = codec.escape(&all_bytes());
+        assert!(!escaped.iter().any(|b| codec.escaped.contains(b)));
+        // the escape byte and each escaped byte take two bytes
+        assert_eq!(escaped.len(), 256 + 5);
+        assert_eq!(codec.unescape(&escaped), all_bytes());
+    }
+
+    #[test]
+    fn round_trips_chunks_split_after_the_escape_byte() {
+        let codec = EscapeCodec::new(0x7D, &[0x7E, 0x11, 0x13]).xor(0x20);
+        let escaped = codec.escape(&all_bytes());
+        for chunk_len in 1..8 {
+            let mut pending = false;
+            let unescaped: Vec<u8> = escaped
+                .chunks(chunk_len)
+                .flat_map(|chunk| codec.unescape_chunk(chunk, &mut pending))
+                .collect();
+            assert_eq!(unescaped, all_bytes(), "chunks of {} bytes", chunk_len);
+            assert!(!pending);
+        }
+    }
+
+    #[test]
+    fn drops_a_trailing_escape_byte() {
+        let codec = EscapeCodec::new(b'#', &[0x11]);
+        assert_eq!(codec.unescape(&[0x01, b'#']), [0x01]);
+    }
+}
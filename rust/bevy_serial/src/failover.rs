@@ -0,0 +1,92 @@
+use crate::compat::*;
+use crate::{open_port, SerialError, SerialPanicPolicy, SerialPoll, SerialSetting, Serials};
+use bevy::ecs::system::{Local, Res};
+use bevy::log::{info, warn};
+use mio::Token;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Least time between attempts to open the other port of a pair
+const RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Bevy's event type to notify that the port with the label has switched to the other port of its failover pair
+#[derive(Debug, Clone, PartialEq, Eq, Event)]
+pub struct SerialFailoverEvent {
+    /// Label of the serial port, which is kept across the switch
+    pub label: String,
+    /// Name of the port which was disconnected
+    pub from: String,
+    /// Name of the port opened instead
+    pub to: String,
+}
+
+/// Open the backup port of disconnected ports with `SerialSetting::backup` under the same label.
+/// The disconnected port becomes the backup, so a pair keeps switching while either port works.
+pub(crate) fn fail_over(
+    mut ev_failover: EventWriter<SerialFailoverEvent>,
+    mut ev_error: EventWriter<SerialError>,
+    mut last_attempt: Local<HashMap<usize, Instant>>,
+    poll: Option<Res<SerialPoll>>,
+    policy: Res<SerialPanicPolicy>,
+    serials: Res<Serials>,
+) {
+    // no port has been opened without the poll
+    let poll = match poll {
+        Some(poll) => &poll.into_inner().0,
+        None => return,
+    };
+    for (index, serial_mtx) in serials.0.iter().enumerate() {
+        let mut serial = match serial_mtx.lock() {
+            Ok(serial) => serial,
+            Err(_) => continue,
+        };
+        let current = match serial.as_mut() {
+            Some(current) if !current.connected => current,
+            _ => continue,
+        };
+        let backup = match &current.setting.backup {
+            Some(backup) => backup.clone(),
+            None => continue,
+        };
+        if let Some(attempt) = last_attempt.get(&index) {
+            if attempt.elapsed() < RETRY_INTERVAL {
+                continue;
+            }
+        }
+        last_attempt.insert(index, Instant::now());
+
+        let from = current.setting.port_name.clone();
+        let setting = SerialSetting {
+            label: Some(current.label.clone()),
+            port_name: backup.clone(),
+            backup: Some(from.clone()),
+            // the backup is opened by its name, not by the filter which found the primary
+            #[cfg(feature = "enumeration")]
+            filter: None,
+            ..current.setting.clone()
+        };
+        match open_port(setting, Token(index), poll) {
+            Ok(opened) => {
+                // the token is now registered by the new stream
+                if let Err(e) = current.deregister(poll) {
+                    warn!("Failed to deregister {} from poll: {:?}", current.label, e);
+                }
+                info!(
+                    "{} has failed over from {} to {}",
+                    opened.label, from, backup
+                );
+                ev_failover.send(SerialFailoverEvent {
+                    label: opened.label.clone(),
+                    from,
+                    to: backup,
+                });
+                last_attempt.remove(&index);
+                *serial = Some(opened);
+            }
+            Err(e) => {
+                policy.handle(&e, false);
+                ev_error.send(e);
+            }
+        }
+    }
+}
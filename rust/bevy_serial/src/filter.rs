@@ -0,0 +1,265 @@
+use crate::compat::*;
+use crate::port::{enumerate_ports, SerialPortDescriptor};
+use crate::{
+    PendingErrors, SerialError, SerialPanicPolicy, SerialPorts, SerialSetting, SerialSystem,
+};
+use bevy::app::{App, Plugin};
+use bevy::ecs::system::{Local, Res, ResMut};
+use bevy::log::info;
+use mio_serial::SerialPortType;
+use regex::Regex;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// How often ports are enumerated while a port with a filter is not open
+const SCAN_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Rules to choose a serial port among enumerated ports, instead of a fixed port name.
+/// A port matches if it satisfies all rules which are set.
+///
+/// ```rust
+/// use bevy_serial::PortFilter;
+///
+/// // the second port of an FTDI FT2232 adapter whose serial number starts with "FT"
+/// let filter = PortFilter::new()
+///     .vid_pid(0x0403, 0x6010)
+///     .serial_number("^FT")
+///     .interface(1);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct PortFilter {
+    /// USB vendor ID
+    pub vid: Option<u16>,
+    /// USB product ID
+    pub pid: Option<u16>,
+    /// Regular expression for the serial number
+    pub serial_number: Option<Regex>,
+    /// Substring of the manufacturer name (case insensitive)
+    pub manufacturer: Option<String>,
+    /// Interface number of a multi-port adapter
+    pub interface: Option<u8>,
+}
+
+impl PortFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Match USB vendor ID and product ID
+    pub fn vid_pid(mut self, vid: u16, pid: u16) -> Self {
+        self.vid = Some(vid);
+        self.pid = Some(pid);
+        self
+    }
+
+    /// Match serial number by regular expression.
+    /// Panics if `pattern` is not a valid regular expression.
+    pub fn serial_number(mut self, pattern: &str) -> Self {
+        let regex = Regex::new(pattern).unwrap_or_else(|e| {
+            panic!("Invalid serial number pattern {}: {}", pattern, e);
+        });
+        self.serial_number = Some(regex);
+        self
+    }
+
+    /// Match a substring of the manufacturer name (case insensitive)
+    pub fn manufacturer(mut self, manufacturer: &str) -> Self {
+        self.manufacturer = Some(manufacturer.to_string());
+        self
+    }
+
+    /// Match the interface number of a multi-port adapter
+    pub fn interface(mut self, interface: u8) -> Self {
+        self.interface = Some(interface);
+        self
+    }
+
+    /// Check if the enumerated port satisfies all rules
+    pub fn matches(&self, port: &SerialPortDescriptor) -> bool {
+        let usb = match &port.port_type {
+            SerialPortType::UsbPort(usb) => Some(usb),
+            _ => None,
+        };
+        let usb_rules = self.vid.is_some()
+            || self.pid.is_some()
+            || self.serial_number.is_some()
+            || self.manufacturer.is_some();
+
+        let usb_matches = match usb {
+            Some(usb) => {
+                self.vid.map_or(true, |vid| vid == usb.vid)
+                    && self.pid.map_or(true, |pid| pid == usb.pid)
+                    && self.serial_number.as_ref().map_or(true, |regex| {
+                        usb.serial_number
+                            .as_deref()
+                            .is_some_and(|s| regex.is_match(s))
+                    })
+                    && self.manufacturer.as_ref().map_or(true, |manufacturer| {
+                        usb.manufacturer.as_deref().is_some_and(|m| {
+                            m.to_lowercase().contains(&manufacturer.to_lowercase())
+                        })
+                    })
+            }
+            // ports other than USB only match if no USB rules are set
+            None => !usb_rules,
+        };
+
+        usb_matches
+            && self
+                .interface
+                .map_or(true, |interface| port.interface_number == Some(interface))
+    }
+
+    /// Enumerate ports and return the ones matching this filter
+    pub fn find(&self) -> Vec<SerialPortDescriptor> {
+        enumerate_ports()
+            .into_iter()
+            .filter(|port| self.matches(port))
+            .collect()
+    }
+}
+
+// `Regex` does not implement `PartialEq`, so compare patterns instead
+impl PartialEq for PortFilter {
+    fn eq(&self, other: &Self) -> bool {
+        self.vid == other.vid
+            && self.pid == other.pid
+            && self.serial_number.as_ref().map(Regex::as_str)
+                == other.serial_number.as_ref().map(Regex::as_str)
+            && self.manufacturer == other.manufacturer
+            && self.interface == other.interface
+    }
+}
+
+impl Eq for PortFilter {}
+
+/// Replace the port name of the setting with the first port matching its filter, if the filter is set
+pub(crate) fn resolve_filter(mut setting: SerialSetting) -> Result<SerialSetting, SerialError> {
+    if let Some(filter) = &setting.filter {
+        let port = filter
+            .find()
+            .into_iter()
+            .next()
+            .ok_or_else(|| SerialError::Open {
+                port_name: setting.port_name.clone(),
+                error: format!("No serial port matches the filter {:?}", filter),
+            })?;
+        setting.port_name = port.port_name;
+    }
+    Ok(setting)
+}
+
+/// Plugin to open the ports with `SerialSetting::filter` when a matching port is plugged in, and to close them when
+/// they are unplugged to be opened again.
+/// This is included in `SerialPlugins`, or can be added after `SerialPlugin`. Without it, a port with a filter is only
+/// opened if a matching port is present when the port is added.
+pub struct SerialHotplugPlugin;
+
+impl Plugin for SerialHotplugPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(PreUpdate, connect_filtered.before(SerialSystem::Read));
+    }
+}
+
+/// Settings with a filter added by `SerialPlugin` or `add_serial_port`, by their label.
+/// Their ports are opened when a matching port is plugged in, and closed when they are unplugged,
+/// by `SerialHotplugPlugin`.
+#[derive(Default, Resource)]
+pub(crate) struct FilteredPorts(HashMap<String, SerialSetting>);
+
+impl FilteredPorts {
+    /// Open ports for the setting while the app runs, returning the setting with its label set:
+    /// without a label, the label would change with the port found by the filter
+    pub(crate) fn watch(&mut self, mut setting: SerialSetting) -> SerialSetting {
+        let label = setting
+            .label
+            .get_or_insert_with(|| setting.port_name.clone())
+            .clone();
+        self.0.insert(label, setting.clone());
+        setting
+    }
+}
+
+/// Close the unplugged ports with a filter, and open the ones which are not open on the first enumerated port
+/// matching their filter, which no other port has opened
+fn connect_filtered(
+    mut ports: SerialPorts,
+    filtered: Res<FilteredPorts>,
+    mut pending: ResMut<PendingErrors>,
+    policy: Res<SerialPanicPolicy>,
+    mut last_scan: Local<Option<Instant>>,
+) {
+    if filtered.0.is_empty() || last_scan.is_some_and(|at| at.elapsed() < SCAN_INTERVAL) {
+        return;
+    }
+    *last_scan = Some(Instant::now());
+
+    // a device plugged in again may get another port name, so it is found by the filter again
+    for label in filtered.0.keys() {
+        if is_disconnected(&ports, label) {
+            ports.remove(label);
+        }
+    }
+
+    let waiting: Vec<&SerialSetting> = filtered
+        .0
+        .iter()
+        .filter(|(label, _)| !ports.indices.0.contains_key(*label))
+        .map(|(_, setting)| setting)
+        .collect();
+    if waiting.is_empty() {
+        return;
+    }
+    let mut in_use: Vec<String> = ports
+        .serials
+        .0
+        .iter()
+        .filter_map(|serial_mtx| Some(serial_mtx.lock().ok()?.as_ref()?.setting.port_name.clone()))
+        .collect();
+    let enumerated = enumerate_ports();
+    for setting in waiting {
+        let filter = match &setting.filter {
+            Some(filter) => filter,
+            None => continue,
+        };
+        let port_name = match enumerated
+            .iter()
+            .find(|port| filter.matches(port) && !in_use.contains(&port.port_name))
+        {
+            Some(port) => port.port_name.clone(),
+            None => continue,
+        };
+        // the port found here is opened, rather than the first match of the filter
+        let setting = SerialSetting {
+            port_name: port_name.clone(),
+            filter: None,
+            ..setting.clone()
+        };
+        match ports.add(setting) {
+            Ok(label) => {
+                info!(
+                    "{} has been connected to {} matching its filter",
+                    label, port_name
+                );
+                in_use.push(port_name);
+            }
+            Err(e) => {
+                policy.handle(&e, false);
+                pending.0.push(e);
+            }
+        }
+    }
+}
+
+fn is_disconnected(ports: &SerialPorts, label: &str) -> bool {
+    let index = match ports.indices.0.get(label) {
+        Some(&index) => index,
+        None => return false,
+    };
+    ports
+        .serials
+        .0
+        .get(index)
+        .and_then(|serial_mtx| Some(!serial_mtx.lock().ok()?.as_ref()?.connected))
+        .unwrap_or(false)
+}
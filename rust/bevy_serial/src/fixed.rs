@@ -0,0 +1,96 @@
+use crate::compat::*;
+use crate::{SerialFrameEvent, SerialSystem};
+use bevy::app::{App, Plugin};
+use bevy::ecs::system::ResMut;
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+
+/// Frames decoded in one update, with the label of the serial port
+pub type FrameBatch<F> = Vec<(String, F)>;
+
+/// Resource to queue `SerialFrameEvent<F>`s and release them to fixed timestep systems.
+///
+/// Frames decoded in each update are queued as one batch, in the order they were decoded.
+/// Call `next_batch` once per tick of a fixed timestep system to get one batch per tick,
+/// so the frames are consumed at the same pace regardless of the frame rate.
+#[derive(Resource)]
+pub struct FixedFrameQueue<F> {
+    batches: VecDeque<FrameBatch<F>>,
+    max_batches: usize,
+}
+
+impl<F> FixedFrameQueue<F> {
+    /// Take the oldest batch. This is empty if no batch is queued.
+    pub fn next_batch(&mut self) -> FrameBatch<F> {
+        self.batches.pop_front().unwrap_or_default()
+    }
+
+    /// Number of batches not released yet
+    pub fn len(&self) -> usize {
+        self.batches.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.batches.is_empty()
+    }
+
+    fn push(&mut self, batch: FrameBatch<F>) {
+        self.batches.push_back(batch);
+        // if ticks can't keep up, merge the oldest batches instead of dropping frames
+        while self.batches.len() > self.max_batches.max(1) {
+            if let (Some(mut first), Some(second)) =
+                (self.batches.pop_front(), self.batches.pop_front())
+            {
+                first.extend(second);
+                self.batches.push_front(first);
+            }
+        }
+    }
+}
+
+/// Plugin to queue `SerialFrameEvent<F>`s into `FixedFrameQueue<F>`.
+/// `DecoderPlugin` decoding `F` should be added before this plugin.
+pub struct FixedFramePlugin<F> {
+    /// Maximum number of batches kept. Older batches are merged over this limit.
+    pub max_batches: usize,
+    _frame: PhantomData<fn() -> F>,
+}
+
+impl<F> FixedFramePlugin<F> {
+    pub fn new(max_batches: usize) -> Self {
+        Self {
+            max_batches,
+            _frame: PhantomData,
+        }
+    }
+}
+
+impl<F> Default for FixedFramePlugin<F> {
+    fn default() -> Self {
+        Self::new(8)
+    }
+}
+
+impl<F: Clone + Send + Sync + 'static> Plugin for FixedFramePlugin<F> {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(FixedFrameQueue::<F> {
+            batches: VecDeque::new(),
+            max_batches: self.max_batches,
+        })
+        .add_systems(PreUpdate, queue_frames::<F>.after(SerialSystem::Decode));
+    }
+}
+
+/// Queue frames decoded in this update as one batch
+fn queue_frames<F: Clone + Send + Sync + 'static>(
+    mut ev_frame: EventReader<SerialFrameEvent<F>>,
+    mut queue: ResMut<FixedFrameQueue<F>>,
+) {
+    let batch: FrameBatch<F> = ev_frame
+        .read()
+        .map(|SerialFrameEvent(label, frame)| (label.clone(), frame.clone()))
+        .collect();
+    if !batch.is_empty() {
+        queue.push(batch);
+    }
+}
@@ -0,0 +1,592 @@
+use crate::blocking::{protocol_error, read_exact_timeout};
+use crate::compat::*;
+use crate::IoThread;
+use bevy::app::{App, Plugin};
+use bevy::ecs::system::Res;
+use bevy::log::{error, info};
+use std::io;
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Bootloader protocol used to flash firmware
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlashProtocol {
+    /// STK500 version 1 used by Arduino bootloaders, with the flash page size in bytes (128 for ATmega328P)
+    Stk500v1 { page_size: usize },
+    /// Serial protocol of the ESP32 ROM bootloader used by `esptool`
+    Esptool,
+}
+
+/// Bevy's event type to start flashing firmware to the board attached to a serial port
+#[derive(Debug, Clone, Event)]
+pub struct FlashRequestEvent {
+    /// The port name, usually the device path
+    pub port_name: String,
+    /// The baud rate of the bootloader (115200 for optiboot and ESP32 ROM)
+    pub baud_rate: u32,
+    /// Bootloader protocol of the board
+    pub protocol: FlashProtocol,
+    /// Address in flash memory to write the image to
+    pub address: u32,
+    /// Firmware image to write
+    pub image: Vec<u8>,
+}
+
+/// Bevy's event type to notify the progress of flashing
+#[derive(Debug, Clone, PartialEq, Eq, Event)]
+pub struct FlashProgressEvent {
+    /// The port name given to `FlashRequestEvent`
+    pub port_name: String,
+    /// Number of bytes written so far
+    pub written: usize,
+    /// Total number of bytes to write
+    pub total: usize,
+}
+
+/// Bevy's event type to notify the completion or failure of flashing
+#[derive(Event)]
+pub struct FlashResultEvent {
+    /// The port name given to `FlashRequestEvent`
+    pub port_name: String,
+    /// Result of flashing
+    pub result: io::Result<()>,
+}
+
+/// Plugin to flash firmware to the board attached to a serial port via `FlashRequestEvent`.
+///
+/// - `FlashProtocol::Stk500v1`: Arduino bootloaders (optiboot) of AVR boards
+/// - `FlashProtocol::Esptool`: ROM bootloader of ESP32 boards
+///
+/// Flashing blocks the serial port for a while, so it runs on its own thread which opens the port by itself.
+/// The port must not be opened by `SerialPlugin` while flashing.
+pub struct FlashPlugin;
+
+impl Plugin for FlashPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FlashJobs>()
+            .add_event::<FlashRequestEvent>()
+            .add_event::<FlashProgressEvent>()
+            .add_event::<FlashResultEvent>()
+            .add_systems(Update, start_flash)
+            .add_systems(Update, poll_flash);
+    }
+}
+
+/// Message sent from a flashing thread
+enum FlashMessage {
+    Progress(usize, usize),
+    Done(io::Result<()>),
+}
+
+/// Flashing threads in progress, with their port names
+#[derive(Default, Resource)]
+struct FlashJobs(Mutex<Vec<(String, Receiver<FlashMessage>)>>);
+
+/// Spawn a flashing thread for each `FlashRequestEvent`, with the `IoThread` resource if it is inserted
+fn start_flash(
+    mut ev_request: EventReader<FlashRequestEvent>,
+    jobs: Res<FlashJobs>,
+    io_thread: Option<Res<IoThread>>,
+) {
+    let io_thread = io_thread.map(|io_thread| *io_thread).unwrap_or_default();
+    for request in ev_request.read() {
+        let (tx, rx) = channel();
+        let request = request.clone();
+        jobs.0
+            .lock()
+            .expect("Failed to lock FlashJobs")
+            .push((request.port_name.clone(), rx));
+
+        info!("Start flashing {}", request.port_name);
+        let name = format!("flash {}", request.port_name);
+        // the job is dropped with its channel if the thread fails to start
+        let spawned = io_thread.spawn(name, move || {
+            let result = flash(&request, &tx);
+            let _ = tx.send(FlashMessage::Done(result));
+        });
+        if let Err(e) = spawned {
+            error!("Failed to start the flashing thread: {}", e);
+        }
+    }
+}
+
+/// Send progress and result of flashing threads as events
+fn poll_flash(
+    mut ev_progress: EventWriter<FlashProgressEvent>,
+    mut ev_result: EventWriter<FlashResultEvent>,
+    jobs: Res<FlashJobs>,
+) {
+    let mut jobs = jobs.0.lock().expect("Failed to lock FlashJobs");
+    jobs.retain(|(port_name, rx)| loop {
+        match rx.try_recv() {
+            Ok(FlashMessage::Progress(written, total)) => {
+                ev_progress.send(FlashProgressEvent {
+                    port_name: port_name.clone(),
+                    written,
+                    total,
+                });
+            }
+            Ok(FlashMessage::Done(result)) => {
+                match &result {
+                    Ok(()) => info!("Finished flashing {}", port_name),
+                    Err(e) => error!("Failed to flash {}: {}", port_name, e),
+                }
+                ev_result.send(FlashResultEvent {
+                    port_name: port_name.clone(),
+                    result,
+                });
+                break false;
+            }
+            Err(TryRecvError::Empty) => break true,
+            Err(TryRecvError::Disconnected) => break false,
+        }
+    });
+}
+
+/// Open the port and flash the image with the protocol of the request
+fn flash(request: &FlashRequestEvent, tx: &Sender<FlashMessage>) -> io::Result<()> {
+    let mut port = mio_serial::new(&request.port_name, request.baud_rate)
+        .timeout(Duration::from_millis(100))
+        .open()?;
+    let progress = |written, total| {
+        let _ = tx.send(FlashMessage::Progress(written, total));
+    };
+
+    match request.protocol {
+        FlashProtocol::Stk500v1 { page_size } => stk500v1::flash(
+            &mut *port,
+            request.address,
+            &request.image,
+            page_size,
+            progress,
+        ),
+        FlashProtocol::Esptool => {
+            esptool::flash(&mut *port, request.address, &request.image, progress)
+        }
+    }
+}
+
+mod stk500v1 {
+    use super::{protocol_error, read_exact_timeout};
+    use mio_serial::{ClearBuffer, SerialPort};
+    use std::io;
+    use std::thread::sleep;
+    use std::time::Duration;
+
+    const STK_OK: u8 = 0x10;
+    const STK_INSYNC: u8 = 0x14;
+    const CRC_EOP: u8 = 0x20;
+    const STK_GET_SYNC: u8 = 0x30;
+    const STK_ENTER_PROGMODE: u8 = 0x50;
+    const STK_LEAVE_PROGMODE: u8 = 0x51;
+    const STK_LOAD_ADDRESS: u8 = 0x55;
+    const STK_PROG_PAGE: u8 = 0x64;
+
+    const SYNC_ATTEMPTS: usize = 10;
+    const TIMEOUT: Duration = Duration::from_millis(500);
+
+    pub(super) fn flash(
+        port: &mut dyn SerialPort,
+        address: u32,
+        image: &[u8],
+        page_size: usize,
+        progress: impl Fn(usize, usize),
+    ) -> io::Result<()> {
+        reset(port)?;
+        sync(port)?;
+        command(port, &[STK_ENTER_PROGMODE, CRC_EOP])?;
+
+        for (i, page) in image.chunks(page_size).enumerate() {
+            // the address is in words for flash memory
+            let word_address = (address as usize + i * page_size) / 2;
+            command(
+                port,
+                &[
+                    STK_LOAD_ADDRESS,
+                    (word_address & 0xFF) as u8,
+                    ((word_address >> 8) & 0xFF) as u8,
+                    CRC_EOP,
+                ],
+            )?;
+
+            let mut cmd = vec![
+                STK_PROG_PAGE,
+                (page.len() >> 8) as u8,
+                page.len() as u8,
+                b'F',
+            ];
+            cmd.extend_from_slice(page);
+            cmd.push(CRC_EOP);
+            command(port, &cmd)?;
+
+            progress(i * page_size + page.len(), image.len());
+        }
+
+        command(port, &[STK_LEAVE_PROGMODE, CRC_EOP])
+    }
+
+    /// Reset the board into the bootloader by toggling DTR/RTS, same as the Arduino IDE does
+    fn reset(port: &mut dyn SerialPort) -> io::Result<()> {
+        port.write_data_terminal_ready(false)?;
+        port.write_request_to_send(false)?;
+        sleep(Duration::from_millis(250));
+        port.write_data_terminal_ready(true)?;
+        port.write_request_to_send(true)?;
+        sleep(Duration::from_millis(50));
+        Ok(())
+    }
+
+    fn sync(port: &mut dyn SerialPort) -> io::Result<()> {
+        let mut last_error = None;
+        for _ in 0..SYNC_ATTEMPTS {
+            port.clear(ClearBuffer::Input)?;
+            match command(port, &[STK_GET_SYNC, CRC_EOP]) {
+                Ok(()) => return Ok(()),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| protocol_error("failed to sync".to_string())))
+    }
+
+    /// Send a command and check the `STK_INSYNC STK_OK` response
+    fn command(port: &mut dyn SerialPort, cmd: &[u8]) -> io::Result<()> {
+        port.write_all(cmd)?;
+        let mut response = [0_u8; 2];
+        read_exact_timeout(port, &mut response, TIMEOUT)?;
+        if response == [STK_INSYNC, STK_OK] {
+            Ok(())
+        } else {
+            Err(protocol_error(format!(
+                "unexpected response {:02X?} to command {:02X}",
+                response, cmd[0]
+            )))
+        }
+    }
+}
+
+mod esptool {
+    use super::{protocol_error, read_exact_timeout};
+    use mio_serial::{ClearBuffer, SerialPort};
+    use std::io;
+    use std::thread::sleep;
+    use std::time::Duration;
+
+    const FLASH_BEGIN: u8 = 0x02;
+    const FLASH_DATA: u8 = 0x03;
+    const FLASH_END: u8 = 0x04;
+    const SYNC: u8 = 0x08;
+    const SPI_ATTACH: u8 = 0x0D;
+
+    const SLIP_END: u8 = 0xC0;
+    const SLIP_ESC: u8 = 0xDB;
+    const SLIP_ESC_END: u8 = 0xDC;
+    const SLIP_ESC_ESC: u8 = 0xDD;
+
+    const CHECKSUM_SEED: u8 = 0xEF;
+    const BLOCK_SIZE: usize = 0x400;
+    const SYNC_ATTEMPTS: usize = 10;
+    const TIMEOUT: Duration = Duration::from_secs(3);
+    const ERASE_TIMEOUT_PER_MB: Duration = Duration::from_secs(30);
+
+    pub(super) fn flash(
+        port: &mut dyn SerialPort,
+        address: u32,
+        image: &[u8],
+        progress: impl Fn(usize, usize),
+    ) -> io::Result<()> {
+        reset_into_bootloader(port)?;
+        sync(port)?;
+        command(port, SPI_ATTACH, &[0; 8], 0, TIMEOUT)?;
+
+        // erasing is done by FLASH_BEGIN, so it takes longer for a larger image
+        let num_blocks = image.len().div_ceil(BLOCK_SIZE);
+        let erase_timeout = TIMEOUT.max(ERASE_TIMEOUT_PER_MB * (image.len() / 0x100000 + 1) as u32);
+        let mut begin = vec![];
+        for word in [
+            image.len() as u32,
+            num_blocks as u32,
+            BLOCK_SIZE as u32,
+            address,
+        ] {
+            begin.extend_from_slice(&word.to_le_bytes());
+        }
+        command(port, FLASH_BEGIN, &begin, 0, erase_timeout)?;
+
+        for (seq, block) in image.chunks(BLOCK_SIZE).enumerate() {
+            // the last block is padded with erased flash value
+            let mut block = block.to_vec();
+            block.resize(BLOCK_SIZE, 0xFF);
+            let mut data = vec![];
+            for word in [BLOCK_SIZE as u32, seq as u32, 0, 0] {
+                data.extend_from_slice(&word.to_le_bytes());
+            }
+            data.extend_from_slice(&block);
+            command(port, FLASH_DATA, &data, checksum(&block), TIMEOUT)?;
+
+            progress(
+                (seq * BLOCK_SIZE + BLOCK_SIZE).min(image.len()),
+                image.len(),
+            );
+        }
+
+        // stay in the bootloader, then reset to run the new firmware
+        command(port, FLASH_END, &1_u32.to_le_bytes(), 0, TIMEOUT)?;
+        hard_reset(port)
+    }
+
+    /// Pull IO0 low while resetting via EN, with the auto-reset circuit driven by DTR/RTS
+    fn reset_into_bootloader(port: &mut dyn SerialPort) -> io::Result<()> {
+        port.write_data_terminal_ready(false)?;
+        port.write_request_to_send(true)?;
+        sleep(Duration::from_millis(100));
+        port.write_data_terminal_ready(true)?;
+        port.write_request_to_send(false)?;
+        sleep(Duration::from_millis(50));
+        port.write_data_terminal_ready(false)?;
+        Ok(())
+    }
+
+    fn hard_reset(port: &mut dyn SerialPort) -> io::Result<()> {
+        port.write_request_to_send(true)?;
+        sleep(Duration::from_millis(100));
+        port.write_request_to_send(false)?;
+        Ok(())
+    }
+
+    fn sync(port: &mut dyn SerialPort) -> io::Result<()> {
+        let mut data = vec![0x07, 0x07, 0x12, 0x20];
+        data.extend_from_slice(&[0x55; 32]);
+
+        let mut last_error = None;
+        for _ in 0..SYNC_ATTEMPTS {
+            port.clear(ClearBuffer::Input)?;
+            match command(port, SYNC, &data, 0, Duration::from_millis(100)) {
+                Ok(()) => {
+                    // the bootloader responds to SYNC several times
+                    sleep(Duration::from_millis(50));
+                    port.clear(ClearBuffer::Input)?;
+                    return Ok(());
+                }
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| protocol_error("failed to sync".to_string())))
+    }
+
+    /// Send a command packet and check the status of its response
+    fn command(
+        port: &mut dyn SerialPort,
+        op: u8,
+        data: &[u8],
+        checksum: u8,
+        timeout: Duration,
+    ) -> io::Result<()> {
+        let mut packet = vec![0x00, op];
+        packet.extend_from_slice(&(data.len() as u16).to_le_bytes());
+        packet.extend_from_slice(&(checksum as u32).to_le_bytes());
+        packet.extend_from_slice(data);
+        port.write_all(&slip_encode(&packet))?;
+
+        // skip responses to other commands, e.g. repeated responses to SYNC
+        loop {
+            let response = read_slip_frame(port, timeout)?;
+            if response.len() < 10 || response[0] != 0x01 || response[1] != op {
+                continue;
+            }
+            // the body of responses is status and error codes
+            let (status, error) = (response[8], response[9]);
+            return if status == 0 {
+                Ok(())
+            } else {
+                Err(protocol_error(format!(
+                    "command {:02X} failed with error {:02X}",
+                    op, error
+                )))
+            };
+        }
+    }
+
+    fn checksum(data: &[u8]) -> u8 {
+        data.iter().fold(CHECKSUM_SEED, |acc, b| acc ^ b)
+    }
+
+    fn slip_encode(packet: &[u8]) -> Vec<u8> {
+        let mut encoded = vec![SLIP_END];
+        for &b in packet {
+            match b {
+                SLIP_END => encoded.extend_from_slice(&[SLIP_ESC, SLIP_ESC_END]),
+                SLIP_ESC => encoded.extend_from_slice(&[SLIP_ESC, SLIP_ESC_ESC]),
+                _ => encoded.push(b),
+            }
+        }
+        encoded.push(SLIP_END);
+        encoded
+    }
+
+    /// Read one SLIP frame and decode it
+    fn read_slip_frame(port: &mut dyn SerialPort, timeout: Duration) -> io::Result<Vec<u8>> {
+        let mut frame = vec![];
+        let mut started = false;
+        let mut escaped = false;
+        let mut b = [0_u8; 1];
+        loop {
+            read_exact_timeout(port, &mut b, timeout)?;
+            match (b[0], escaped) {
+                (SLIP_END, _) if started && !frame.is_empty() => return Ok(frame),
+                (SLIP_END, _) => started = true,
+                (_, _) if !started => {}
+                (SLIP_ESC, false) => escaped = true,
+                (SLIP_ESC_END, true) => {
+                    frame.push(SLIP_END);
+                    escaped = false;
+                }
+                (SLIP_ESC_ESC, true) => {
+                    frame.push(SLIP_ESC);
+                    escaped = false;
+                }
+                (b, _) => {
+                    frame.push(b);
+                    escaped = false;
+                }
+            }
+        }
+    }
+}
+
+/// Parse an Intel HEX file (the output of Arduino builds) into the start address and the binary image.
+/// Gaps between records are filled with `0xFF`, the value of erased flash.
+/// Images spanning more than `max_size` bytes, the size of the target's flash, are rejected.
+pub fn parse_intel_hex(hex: &str, max_size: usize) -> io::Result<(u32, Vec<u8>)> {
+    let mut base = 0_u32;
+    let mut records = vec![];
+
+    for (n, line) in hex.lines().map(str::trim).enumerate() {
+        if line.is_empty() {
+            continue;
+        }
+        let invalid = || protocol_error(format!("invalid Intel HEX record at line {}", n + 1));
+        let bytes = line
+            .strip_prefix(':')
+            .and_then(|record| crate::decode_hex(record).ok())
+            .ok_or_else(invalid)?;
+        if bytes.len() < 5 || bytes.len() != bytes[0] as usize + 5 {
+            return Err(invalid());
+        }
+        if bytes.iter().fold(0_u8, |acc, b| acc.wrapping_add(*b)) != 0 {
+            return Err(protocol_error(format!("checksum error at line {}", n + 1)));
+        }
+
+        let offset = u16::from_be_bytes([bytes[1], bytes[2]]) as u32;
+        let data = &bytes[4..bytes.len() - 1];
+        match bytes[3] {
+            // data
+            0x00 => records.push((base + offset, data.to_vec())),
+            // end of file
+            0x01 => break,
+            // extended segment address
+            0x02 if data.len() == 2 => base = (u16::from_be_bytes([data[0], data[1]]) as u32) << 4,
+            // extended linear address
+            0x04 if data.len() == 2 => base = (u16::from_be_bytes([data[0], data[1]]) as u32) << 16,
+            // start addresses are not needed for flashing
+            0x03 | 0x05 => {}
+            _ => return Err(invalid()),
+        }
+    }
+
+    let start = records
+        .iter()
+        .map(|(address, _)| *address)
+        .min()
+        .unwrap_or(0);
+    let end = records
+        .iter()
+        .map(|(address, data)| *address as u64 + data.len() as u64)
+        .max()
+        .unwrap_or(0);
+    if end - start as u64 > max_size as u64 {
+        return Err(protocol_error(format!(
+            "image spans {} bytes from {:#x}, more than the flash size of {} bytes",
+            end - start as u64,
+            start,
+            max_size
+        )));
+    }
+
+    let mut image = vec![];
+    for (address, data) in records {
+        let offset = (address - start) as usize;
+        if image.len() < offset + data.len() {
+            image.resize(offset + data.len(), 0xFF);
+        }
+        image[offset..offset + data.len()].copy_from_slice(&data);
+    }
+    Ok((start, image))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fills_gaps_between_data_records() {
+        let hex = ":0400000001020304F2\n:020008000708E7\n:00000001FF\n";
+        let (address, image) = parse_intel_hex(hex, 16).unwrap();
+        assert_eq!(address, 0);
+        assert_eq!(image, [1, 2, 3, 4, 0xFF, 0xFF, 0xFF, 0xFF, 7, 8]);
+    }
+
+    #[test]
+    fn applies_extended_linear_address() {
+        let hex = ":020000040800F2\n:0400000001020304F2\n:0400000508000139B5\n:00000001FF\n";
+        let (address, image) = parse_intel_hex(hex, 16).unwrap();
+        assert_eq!(address, 0x0800_0000);
+        assert_eq!(image, [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn applies_extended_segment_address() {
+        let hex = ":020000021000EC\n:020010000506E3\n:00000001FF\n";
+        let (address, image) = parse_intel_hex(hex, 16).unwrap();
+        assert_eq!(address, 0x1_0010);
+        assert_eq!(image, [5, 6]);
+    }
+
+    #[test]
+    fn stops_at_end_of_file() {
+        let hex = ":0400000001020304F2\n:00000001FF\n:020008000708E7\n";
+        assert_eq!(parse_intel_hex(hex, 16).unwrap().1, [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn rejects_image_larger_than_flash() {
+        // a far extended linear address would otherwise allocate a 128 MiB gap
+        let hex = ":0400000001020304F2\n:020000040800F2\n:0400000001020304F2\n";
+        let e = parse_intel_hex(hex, 32 * 1024).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        assert!(parse_intel_hex(":0400000001020304F2", 4).is_ok());
+        assert!(parse_intel_hex(":0400000001020304F2", 3).is_err());
+    }
+
+    #[test]
+    fn rejects_checksum_error() {
+        let hex = ":0400000001020304F2\n:020008000708E8\n";
+        let e = parse_intel_hex(hex, 16).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(e.to_string(), "checksum error at line 2");
+    }
+
+    #[test]
+    fn rejects_invalid_records() {
+        // missing colon, wrong length, odd digits and an unknown record type
+        for hex in [
+            "0400000001020304F2",
+            ":0500000001020304F1",
+            ":0400000001020304F",
+            ":00000006FA",
+        ] {
+            let e = parse_intel_hex(hex, 16).unwrap_err();
+            assert_eq!(e.to_string(), "invalid Intel HEX record at line 1");
+        }
+    }
+}
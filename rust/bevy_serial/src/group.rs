@@ -0,0 +1,71 @@
+use crate::compat::*;
+use crate::PendingWrite;
+use std::collections::HashMap;
+
+/// Resource defining groups of ports, e.g. `"all_leds"`.
+/// A write to the label of a group (with `SerialWriteEvent`, `SerialWriteHexEvent` or `SerialWriteRequestEvent`)
+/// is written to every member port, and each request gets its own `SerialWriteResultEvent` with the member label.
+/// Labels of ports take precedence over groups with the same name, and groups can't contain groups.
+#[derive(Debug, Default, Resource)]
+pub struct SerialGroups(HashMap<String, Vec<String>>);
+
+impl SerialGroups {
+    /// Define the group with the labels of its member ports, replacing the previous members
+    pub fn insert(&mut self, group: &str, members: &[&str]) {
+        self.0.insert(
+            group.to_string(),
+            members.iter().map(|member| member.to_string()).collect(),
+        );
+    }
+
+    /// Remove the group. Returns `false` if it is not defined.
+    pub fn remove(&mut self, group: &str) -> bool {
+        self.0.remove(group).is_some()
+    }
+
+    /// Add a port to the group, creating the group if needed
+    pub fn join(&mut self, group: &str, member: &str) {
+        let members = self.0.entry(group.to_string()).or_default();
+        if !members.iter().any(|m| m == member) {
+            members.push(member.to_string());
+        }
+    }
+
+    /// Remove a port from the group. Returns `false` if it is not a member.
+    pub fn leave(&mut self, group: &str, member: &str) -> bool {
+        match self.0.get_mut(group) {
+            Some(members) => {
+                let len = members.len();
+                members.retain(|m| m != member);
+                members.len() < len
+            }
+            None => false,
+        }
+    }
+
+    /// Labels of the member ports of the group
+    pub fn members(&self, group: &str) -> Option<&[String]> {
+        self.0.get(group).map(Vec::as_slice)
+    }
+
+    /// Replace writes to groups with a write to each member, keeping the order
+    pub(crate) fn expand(
+        &self,
+        writes: Vec<(String, PendingWrite)>,
+        is_port: impl Fn(&str) -> bool,
+    ) -> Vec<(String, PendingWrite)> {
+        if self.0.is_empty() {
+            return writes;
+        }
+        let mut expanded = Vec::with_capacity(writes.len());
+        for (label, write) in writes {
+            match self.0.get(&label) {
+                Some(members) if !is_port(&label) => {
+                    expanded.extend(members.iter().map(|member| (member.clone(), write.clone())));
+                }
+                _ => expanded.push((label, write)),
+            }
+        }
+        expanded
+    }
+}
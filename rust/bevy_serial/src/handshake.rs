@@ -0,0 +1,212 @@
+use crate::codegen::MessageSchema;
+use crate::compat::*;
+use crate::{SerialOpenedEvent, SerialPorts, SerialReadEvent, SerialSystem, SerialWriteEvent};
+use bevy::app::{App, Plugin};
+use bevy::ecs::system::ResMut;
+use bevy::log::{info, warn};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Line the app sends to ask the device for its schema, answered with `schema <name> <fingerprint in hex>`
+pub const SCHEMA_QUERY: &[u8] = b"?schema\n";
+
+/// Bytes kept while looking for the answer, which is much shorter
+const MAX_ANSWER_LEN: usize = 256;
+
+/// Bevy's event type to notify that the device on the port with the label sends messages of another schema,
+/// e.g. after a firmware update, so its frames would be misparsed
+#[derive(Debug, Clone, PartialEq, Eq, Event)]
+pub struct SchemaMismatchEvent {
+    pub label: String,
+    /// Fingerprint of the schema of the app
+    pub expected: u32,
+    /// Fingerprint answered by the device, or `None` if it didn't answer before the timeout
+    pub received: Option<u32>,
+}
+
+/// State of the handshake of a port since it was opened
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HandshakeState {
+    /// The query is sent and the answer is awaited
+    Pending,
+    /// The device answered the fingerprint of the schema
+    Matched,
+    /// The device answered another fingerprint or didn't answer
+    Mismatched,
+}
+
+struct Handshake {
+    name: String,
+    expected: u32,
+    timeout: Duration,
+    close_on_mismatch: bool,
+    /// `None` until the port is opened
+    state: Option<HandshakeState>,
+    deadline: Instant,
+    answer: Vec<u8>,
+}
+
+/// Resource with the handshakes of the ports added by `SchemaHandshakePlugin`
+#[derive(Default, Resource)]
+pub struct SchemaHandshakes(HashMap<String, Handshake>);
+
+impl SchemaHandshakes {
+    /// State of the handshake of the port with the label, or `None` if it has no handshake or is not opened yet
+    pub fn state(&self, label: &str) -> Option<HandshakeState> {
+        self.0.get(label)?.state
+    }
+
+    /// Whether frames of the port with the label can be trusted to have the layout of the schema
+    pub fn is_matched(&self, label: &str) -> bool {
+        self.state(label) == Some(HandshakeState::Matched)
+    }
+}
+
+/// Plugin to check that the device on the port with the label sends messages of the schema.
+/// Each time the port is opened, `SCHEMA_QUERY` is sent and the device must answer the line written by
+/// the function `<name>_write_schema` of the header generated by `MessageSchema::to_arduino`.
+/// `SerialPlugin` should be added before this plugin.
+pub struct SchemaHandshakePlugin {
+    pub label: String,
+    pub schema: MessageSchema,
+    /// Time to wait for the answer after the port is opened
+    pub timeout: Duration,
+    /// Close the port on a mismatch, so no frame of the wrong layout is decoded
+    pub close_on_mismatch: bool,
+}
+
+impl SchemaHandshakePlugin {
+    /// Handshake with a timeout of 2 seconds, closing the port on a mismatch
+    pub fn new(label: &str, schema: &MessageSchema) -> Self {
+        Self {
+            label: label.to_string(),
+            schema: schema.clone(),
+            timeout: Duration::from_secs(2),
+            close_on_mismatch: true,
+        }
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Only send `SchemaMismatchEvent` on a mismatch, leaving the port open
+    pub fn keep_open(mut self) -> Self {
+        self.close_on_mismatch = false;
+        self
+    }
+}
+
+impl Plugin for SchemaHandshakePlugin {
+    fn build(&self, app: &mut App) {
+        // the handshake system is shared by all ports with a schema
+        if !app.world.contains_resource::<SchemaHandshakes>() {
+            app.init_resource::<SchemaHandshakes>()
+                .add_event::<SchemaMismatchEvent>()
+                .add_systems(PreUpdate, shake_hands.after(SerialSystem::Read));
+        }
+        let mut handshakes = app.world.get_resource_mut::<SchemaHandshakes>().unwrap();
+        handshakes.0.insert(
+            self.label.clone(),
+            Handshake {
+                name: self.schema.name.clone(),
+                expected: self.schema.fingerprint(),
+                timeout: self.timeout,
+                close_on_mismatch: self.close_on_mismatch,
+                state: None,
+                deadline: Instant::now() + self.timeout,
+                answer: vec![],
+            },
+        );
+    }
+}
+
+/// Send the query when a port is opened, and check the answer or the timeout
+fn shake_hands(
+    mut ev_opened: EventReader<SerialOpenedEvent>,
+    mut ev_read: EventReader<SerialReadEvent>,
+    mut ev_write: EventWriter<SerialWriteEvent>,
+    mut ev_mismatch: EventWriter<SchemaMismatchEvent>,
+    mut handshakes: ResMut<SchemaHandshakes>,
+    mut ports: SerialPorts,
+) {
+    let now = Instant::now();
+    for SerialOpenedEvent { label, .. } in ev_opened.read() {
+        if let Some(handshake) = handshakes.0.get_mut(label) {
+            handshake.state = Some(HandshakeState::Pending);
+            handshake.deadline = now + handshake.timeout;
+            handshake.answer.clear();
+            ev_write.send(SerialWriteEvent(label.clone(), SCHEMA_QUERY.to_vec()));
+        }
+    }
+
+    for SerialReadEvent(label, buffer) in ev_read.read() {
+        let handshake = match handshakes.0.get_mut(label) {
+            Some(handshake) if handshake.state == Some(HandshakeState::Pending) => handshake,
+            _ => continue,
+        };
+        handshake.answer.extend_from_slice(buffer);
+        if handshake.answer.len() > MAX_ANSWER_LEN {
+            let excess = handshake.answer.len() - MAX_ANSWER_LEN;
+            handshake.answer.drain(..excess);
+        }
+    }
+
+    for (label, handshake) in handshakes.0.iter_mut() {
+        // not opened yet, or already checked
+        if handshake.state != Some(HandshakeState::Pending) {
+            continue;
+        }
+        let received = match parse_answer(&handshake.answer) {
+            Some((name, fingerprint)) => {
+                if name != handshake.name {
+                    warn!(
+                        "{} sends messages of {} instead of {}",
+                        label, name, handshake.name
+                    );
+                }
+                Some(fingerprint)
+            }
+            None if now >= handshake.deadline => None,
+            None => continue,
+        };
+        handshake.answer.clear();
+        if received == Some(handshake.expected) {
+            info!("{} sends messages of {}", label, handshake.name);
+            handshake.state = Some(HandshakeState::Matched);
+            continue;
+        }
+
+        handshake.state = Some(HandshakeState::Mismatched);
+        match received {
+            Some(fingerprint) => warn!(
+                "{} sends messages of schema {:08x} instead of {:08x}",
+                label, fingerprint, handshake.expected
+            ),
+            None => warn!("{} didn't answer the schema query", label),
+        }
+        if handshake.close_on_mismatch {
+            ports.remove(label);
+        }
+        ev_mismatch.send(SchemaMismatchEvent {
+            label: label.clone(),
+            expected: handshake.expected,
+            received,
+        });
+    }
+}
+
+/// Name and fingerprint of the first complete `schema <name> <fingerprint>` line
+fn parse_answer(bytes: &[u8]) -> Option<(String, u32)> {
+    let text = String::from_utf8_lossy(bytes);
+    // the last part may be an incomplete line
+    let mut lines: Vec<&str> = text.split('\n').collect();
+    lines.pop();
+    lines.into_iter().find_map(|line| {
+        let mut words = line.trim().strip_prefix("schema ")?.split_whitespace();
+        let name = words.next()?;
+        let fingerprint = u32::from_str_radix(words.next()?, 16).ok()?;
+        Some((name.to_string(), fingerprint))
+    })
+}
@@ -0,0 +1,49 @@
+use std::fmt;
+
+/// Error returned when a hex string cannot be converted to bytes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseHexError {
+    /// A character which is neither a hex digit nor whitespace was found
+    InvalidDigit(char),
+    /// The number of hex digits is odd, so the last byte is incomplete
+    OddLength,
+}
+
+impl fmt::Display for ParseHexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseHexError::InvalidDigit(c) => write!(f, "invalid hex digit {:?}", c),
+            ParseHexError::OddLength => write!(f, "odd number of hex digits"),
+        }
+    }
+}
+
+impl std::error::Error for ParseHexError {}
+
+/// Convert bytes to an upper case hex string separated by spaces (e.g. `"DE AD BE EF"`)
+pub fn encode_hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Convert a hex string to bytes. Whitespace between digits is ignored,
+/// so both `"DEADBEEF"` and `"DE AD BE EF"` are accepted.
+pub fn decode_hex(hex: &str) -> Result<Vec<u8>, ParseHexError> {
+    let digits = hex
+        .chars()
+        .filter(|c| !c.is_ascii_whitespace())
+        .map(|c| c.to_digit(16).ok_or(ParseHexError::InvalidDigit(c)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if digits.len() % 2 != 0 {
+        return Err(ParseHexError::OddLength);
+    }
+
+    Ok(digits
+        .chunks(2)
+        .map(|pair| (pair[0] * 16 + pair[1]) as u8)
+        .collect())
+}
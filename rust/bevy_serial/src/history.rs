@@ -0,0 +1,102 @@
+use crate::compat::*;
+use crate::{SerialFrameEvent, SerialSystem};
+use bevy::app::{App, Plugin};
+use bevy::ecs::system::ResMut;
+use std::collections::{HashMap, VecDeque};
+use std::marker::PhantomData;
+
+/// Resource retaining the last frames of type `F` decoded from each port.
+///
+/// Systems added at runtime, or UI panels opened later, can show recent frames
+/// without having read `SerialFrameEvent<F>` from the start.
+#[derive(Resource)]
+pub struct SerialHistory<F> {
+    ports: HashMap<String, VecDeque<F>>,
+    len: usize,
+}
+
+impl<F> SerialHistory<F> {
+    /// Frames of the port with the label, the oldest first
+    pub fn frames(&self, label: &str) -> impl Iterator<Item = &F> {
+        self.ports.get(label).into_iter().flatten()
+    }
+
+    /// The newest frame of the port with the label
+    pub fn latest(&self, label: &str) -> Option<&F> {
+        self.ports.get(label)?.back()
+    }
+
+    /// Labels of the ports which have frames
+    pub fn labels(&self) -> impl Iterator<Item = &String> {
+        self.ports.keys()
+    }
+
+    /// Maximum number of frames kept per port
+    pub fn capacity(&self) -> usize {
+        self.len
+    }
+
+    /// Forget the frames of the port with the label, e.g. after the device was reset
+    pub fn clear(&mut self, label: &str) {
+        self.ports.remove(label);
+    }
+
+    fn push(&mut self, label: &str, frame: F) {
+        // look up first not to allocate the label for every frame
+        if !self.ports.contains_key(label) {
+            self.ports
+                .insert(label.to_string(), VecDeque::with_capacity(self.len));
+        }
+        let frames = self
+            .ports
+            .get_mut(label)
+            .expect("frames were just inserted");
+        if frames.len() >= self.len {
+            frames.pop_front();
+        }
+        frames.push_back(frame);
+    }
+}
+
+/// Plugin to keep the last `len` frames of each port in `SerialHistory<F>`.
+/// `DecoderPlugin` decoding `F` should be added before this plugin.
+pub struct SerialHistoryPlugin<F> {
+    /// Number of frames kept per port
+    pub len: usize,
+    _frame: PhantomData<fn() -> F>,
+}
+
+impl<F> SerialHistoryPlugin<F> {
+    pub fn new(len: usize) -> Self {
+        Self {
+            len,
+            _frame: PhantomData,
+        }
+    }
+}
+
+impl<F> Default for SerialHistoryPlugin<F> {
+    fn default() -> Self {
+        Self::new(256)
+    }
+}
+
+impl<F: Clone + Send + Sync + 'static> Plugin for SerialHistoryPlugin<F> {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(SerialHistory::<F> {
+            ports: HashMap::new(),
+            len: self.len.max(1),
+        })
+        .add_systems(PreUpdate, keep_history::<F>.after(SerialSystem::Decode));
+    }
+}
+
+/// Keep the frames decoded in this update
+fn keep_history<F: Clone + Send + Sync + 'static>(
+    mut ev_frame: EventReader<SerialFrameEvent<F>>,
+    mut history: ResMut<SerialHistory<F>>,
+) {
+    for SerialFrameEvent(label, frame) in ev_frame.read() {
+        history.push(label, frame.clone());
+    }
+}
@@ -0,0 +1,49 @@
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+type Hook = Box<dyn FnMut(&mut Vec<u8>) + Send>;
+
+/// Closures which inspect, transform or drop each raw chunk read from a port before it is sent or decoded,
+/// e.g. to strip a bootloader banner or discard keepalive bytes.
+/// A chunk cleared by a hook is dropped. Clones share the same closures and their state.
+#[derive(Clone, Default)]
+pub struct ReadHook(Arc<Mutex<Vec<Hook>>>);
+
+impl ReadHook {
+    /// Run the closure after the hooks added before
+    pub fn then(self, hook: impl FnMut(&mut Vec<u8>) + Send + 'static) -> Self {
+        if let Ok(mut hooks) = self.0.lock() {
+            hooks.push(Box::new(hook));
+        }
+        self
+    }
+
+    /// Run the hooks on the chunk. Returns `false` if the chunk was dropped.
+    pub(crate) fn apply(&self, chunk: &mut Vec<u8>) -> bool {
+        if let Ok(mut hooks) = self.0.lock() {
+            for hook in hooks.iter_mut() {
+                hook(chunk);
+                if chunk.is_empty() {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+}
+
+impl fmt::Debug for ReadHook {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let len = self.0.lock().map_or(0, |hooks| hooks.len());
+        write!(f, "ReadHook({} hooks)", len)
+    }
+}
+
+// closures can't be compared, so hooks are equal only if they are clones of each other
+impl PartialEq for ReadHook {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for ReadHook {}
@@ -0,0 +1,254 @@
+use crate::codec::Decoder;
+use bevy::log::warn;
+use std::collections::HashMap;
+
+/// Sample of a virtual sensor streamed by a Bosch BHI260/BHI360 sensor hub
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BhiSample {
+    /// Virtual sensor ID, e.g. `BhiSample::ACCELEROMETER`
+    pub sensor_id: u8,
+    /// Timestamp in ticks of 1/64000 seconds
+    pub timestamp: u64,
+    /// Raw payload of the sample
+    pub data: Vec<u8>,
+}
+
+impl BhiSample {
+    /// Accelerometer passthrough
+    pub const ACCELEROMETER_PASSTHROUGH: u8 = 1;
+    /// Accelerometer uncalibrated
+    pub const ACCELEROMETER_RAW: u8 = 3;
+    /// Accelerometer corrected
+    pub const ACCELEROMETER: u8 = 4;
+    /// Gyroscope passthrough
+    pub const GYROSCOPE_PASSTHROUGH: u8 = 10;
+    /// Gyroscope uncalibrated
+    pub const GYROSCOPE_RAW: u8 = 12;
+    /// Gyroscope corrected
+    pub const GYROSCOPE: u8 = 13;
+    /// Magnetometer passthrough
+    pub const MAGNETOMETER_PASSTHROUGH: u8 = 19;
+    /// Magnetometer uncalibrated
+    pub const MAGNETOMETER_RAW: u8 = 21;
+    /// Magnetometer corrected
+    pub const MAGNETOMETER: u8 = 22;
+
+    /// Timestamp in seconds
+    pub fn seconds(&self) -> f64 {
+        self.timestamp as f64 / 64000.0
+    }
+
+    /// X, Y and Z of a 3-axis sample in LSB.
+    /// Returns `None` if the payload is not 3 axes of `i16`.
+    pub fn xyz(&self) -> Option<[i16; 3]> {
+        match self.data[..] {
+            [x0, x1, y0, y1, z0, z1] => Some([
+                i16::from_le_bytes([x0, x1]),
+                i16::from_le_bytes([y0, y1]),
+                i16::from_le_bytes([z0, z1]),
+            ]),
+            _ => None,
+        }
+    }
+}
+
+const PADDING: u8 = 0;
+const TIMESTAMP_SMALL_DELTA_WU: u8 = 245;
+const TIMESTAMP_LARGE_DELTA_WU: u8 = 246;
+const TIMESTAMP_FULL_WU: u8 = 247;
+const META_EVENT_WU: u8 = 248;
+const FILLER_WU: u8 = 249;
+const DEBUG_MESSAGE: u8 = 250;
+const TIMESTAMP_SMALL_DELTA: u8 = 251;
+const TIMESTAMP_LARGE_DELTA: u8 = 252;
+const TIMESTAMP_FULL: u8 = 253;
+const META_EVENT: u8 = 254;
+const FILLER: u8 = 255;
+
+/// Decoder for the FIFO stream of Bosch BHI260/BHI360 sensor hubs, as forwarded over UART by their
+/// evaluation boards. System events (timestamps, meta events and padding) are handled internally, and each
+/// sensor sample is decoded into a `BhiSample` with its timestamp.
+///
+/// The FIFO has no framing, so the payload size of every virtual sensor in the stream must be known.
+/// 3-axis accelerometer, gyroscope and magnetometer sensors are known by default, and others can be added with
+/// `BhiFifoDecoder::sensor`. The stream is dropped until the next read if an unknown sensor ID appears.
+///
+/// ST's MEMS Studio streaming protocol is not publicly documented, so it is not supported. ST eval kits running
+/// text-based firmware (e.g. DataLog) can be read with `LineDecoder`.
+#[derive(Debug, Clone)]
+pub struct BhiFifoDecoder {
+    payload_sizes: HashMap<u8, usize>,
+    timestamp: u64,
+    buf: Vec<u8>,
+}
+
+impl BhiFifoDecoder {
+    pub fn new() -> Self {
+        let payload_sizes = [
+            BhiSample::ACCELEROMETER_PASSTHROUGH,
+            BhiSample::ACCELEROMETER_RAW,
+            BhiSample::ACCELEROMETER,
+            BhiSample::GYROSCOPE_PASSTHROUGH,
+            BhiSample::GYROSCOPE_RAW,
+            BhiSample::GYROSCOPE,
+            BhiSample::MAGNETOMETER_PASSTHROUGH,
+            BhiSample::MAGNETOMETER_RAW,
+            BhiSample::MAGNETOMETER,
+        ]
+        .iter()
+        .map(|&id| (id, 6))
+        .collect();
+        Self {
+            payload_sizes,
+            timestamp: 0,
+            buf: vec![],
+        }
+    }
+
+    /// Add a virtual sensor with its payload size in bytes (without the sensor ID)
+    pub fn sensor(mut self, sensor_id: u8, payload_size: usize) -> Self {
+        self.payload_sizes.insert(sensor_id, payload_size);
+        self
+    }
+
+    /// Payload size of the event ID, or `None` if unknown
+    fn payload_size(&self, id: u8) -> Option<usize> {
+        match id {
+            PADDING | FILLER | FILLER_WU => Some(0),
+            TIMESTAMP_SMALL_DELTA | TIMESTAMP_SMALL_DELTA_WU => Some(1),
+            TIMESTAMP_LARGE_DELTA | TIMESTAMP_LARGE_DELTA_WU => Some(2),
+            TIMESTAMP_FULL | TIMESTAMP_FULL_WU => Some(5),
+            META_EVENT | META_EVENT_WU => Some(3),
+            DEBUG_MESSAGE => Some(17),
+            id => self.payload_sizes.get(&id).copied(),
+        }
+    }
+}
+
+impl Default for BhiFifoDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder for BhiFifoDecoder {
+    type Frame = BhiSample;
+
+    fn decode(&mut self, bytes: &[u8], frames: &mut Vec<Self::Frame>) {
+        self.buf.extend_from_slice(bytes);
+
+        let mut pos = 0;
+        while let Some(&id) = self.buf.get(pos) {
+            let size = match self.payload_size(id) {
+                Some(size) => size,
+                None => {
+                    warn!(
+                        "Unknown BHI sensor ID {}, dropping {} bytes",
+                        id,
+                        self.buf.len() - pos
+                    );
+                    pos = self.buf.len();
+                    break;
+                }
+            };
+            if self.buf.len() < pos + 1 + size {
+                break;
+            }
+
+            let payload = &self.buf[pos + 1..pos + 1 + size];
+            match id {
+                TIMESTAMP_SMALL_DELTA | TIMESTAMP_SMALL_DELTA_WU => {
+                    self.timestamp += payload[0] as u64;
+                }
+                TIMESTAMP_LARGE_DELTA | TIMESTAMP_LARGE_DELTA_WU => {
+                    self.timestamp += u16::from_le_bytes([payload[0], payload[1]]) as u64;
+                }
+                TIMESTAMP_FULL | TIMESTAMP_FULL_WU => {
+                    let mut full = [0_u8; 8];
+                    full[..5].copy_from_slice(payload);
+                    self.timestamp = u64::from_le_bytes(full);
+                }
+                PADDING | FILLER | FILLER_WU | META_EVENT | META_EVENT_WU | DEBUG_MESSAGE => {}
+                sensor_id => frames.push(BhiSample {
+                    sensor_id,
+                    timestamp: self.timestamp,
+                    data: payload.to_vec(),
+                }),
+            }
+            pos += 1 + size;
+        }
+        self.buf.drain(..pos);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// FIFO of a full timestamp of 64000 ticks (1 s), an accelerometer sample of (1, -2, 4096),
+    /// a meta event and padding, a small delta of 100 ticks, a gyroscope sample of (-1, 0, 256),
+    /// a large delta of 640 ticks and a magnetometer sample of (300, -300, 0)
+    #[rustfmt::skip]
+    const FIFO: [u8; 38] = [
+        TIMESTAMP_FULL, 0x00, 0xFA, 0x00, 0x00, 0x00,
+        BhiSample::ACCELEROMETER, 0x01, 0x00, 0xFE, 0xFF, 0x00, 0x10,
+        META_EVENT, 0x01, 0x02, 0x03, PADDING,
+        TIMESTAMP_SMALL_DELTA, 100,
+        BhiSample::GYROSCOPE, 0xFF, 0xFF, 0x00, 0x00, 0x00, 0x01,
+        TIMESTAMP_LARGE_DELTA, 0x80, 0x02,
+        BhiSample::MAGNETOMETER, 0x2C, 0x01, 0xD4, 0xFE, 0x00, 0x00,
+        FILLER,
+    ];
+
+    fn decode(decoder: &mut BhiFifoDecoder, bytes: &[u8], chunk_len: usize) -> Vec<BhiSample> {
+        let mut frames = vec![];
+        for chunk in bytes.chunks(chunk_len) {
+            decoder.decode(chunk, &mut frames);
+        }
+        frames
+    }
+
+    #[test]
+    fn decodes_samples_with_their_timestamps() {
+        // every split of the FIFO between reads
+        for chunk_len in 1..=FIFO.len() {
+            let samples = decode(&mut BhiFifoDecoder::new(), &FIFO, chunk_len);
+            let decoded: Vec<(u8, u64, Option<[i16; 3]>)> = samples
+                .iter()
+                .map(|sample| (sample.sensor_id, sample.timestamp, sample.xyz()))
+                .collect();
+            assert_eq!(
+                decoded,
+                [
+                    (BhiSample::ACCELEROMETER, 64000, Some([1, -2, 4096])),
+                    (BhiSample::GYROSCOPE, 64100, Some([-1, 0, 256])),
+                    (BhiSample::MAGNETOMETER, 64740, Some([300, -300, 0])),
+                ],
+                "chunks of {} bytes",
+                chunk_len
+            );
+            assert_eq!(samples[0].seconds(), 1.0);
+        }
+    }
+
+    #[test]
+    fn decodes_added_sensors() {
+        // a 4 byte quaternion-like payload of a sensor unknown by default
+        let mut bytes = vec![37, 0x01, 0x02, 0x03, 0x04, BhiSample::ACCELEROMETER];
+        bytes.extend_from_slice(&[0; 6]);
+        let samples = decode(&mut BhiFifoDecoder::new().sensor(37, 4), &bytes, 3);
+        assert_eq!(samples.len(), 2);
+        assert_eq!(samples[0].data, [0x01, 0x02, 0x03, 0x04]);
+        assert_eq!(samples[0].xyz(), None);
+    }
+
+    #[test]
+    fn drops_the_read_after_an_unknown_sensor() {
+        let mut decoder = BhiFifoDecoder::new();
+        let mut bytes = vec![200, 0x01];
+        bytes.extend_from_slice(&FIFO);
+        assert!(decode(&mut decoder, &bytes, bytes.len()).is_empty());
+        // the next read is decoded again
+        assert_eq!(decode(&mut decoder, &FIFO, FIFO.len()).len(), 3);
+    }
+}
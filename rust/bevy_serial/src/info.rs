@@ -0,0 +1,111 @@
+use crate::compat::*;
+use crate::{SerialSetting, Serials};
+use bevy::app::{App, Plugin};
+use bevy::ecs::system::{Res, ResMut};
+use mio_serial::{DataBits, FlowControl, Parity, SerialPort, StopBits};
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Settings which the OS actually applied to the serial port.
+/// Each value is `None` if the driver failed to report it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SerialSettingActual {
+    /// The baud rate in symbols-per-second
+    pub baud_rate: Option<u32>,
+    /// Number of bits used to represent a character sent on the line
+    pub data_bits: Option<DataBits>,
+    /// The type of signalling to use for controlling data transfer
+    pub flow_control: Option<FlowControl>,
+    /// The type of parity to use for error checking
+    pub parity: Option<Parity>,
+    /// Number of bits to use to signal the end of a character
+    pub stop_bits: Option<StopBits>,
+    /// Amount of time to wait to receive data before timing out
+    pub timeout: Duration,
+}
+
+impl SerialSettingActual {
+    pub(crate) fn from_port(port: &impl SerialPort) -> Self {
+        Self {
+            baud_rate: port.baud_rate().ok(),
+            data_bits: port.data_bits().ok(),
+            flow_control: port.flow_control().ok(),
+            parity: port.parity().ok(),
+            stop_bits: port.stop_bits().ok(),
+            timeout: port.timeout(),
+        }
+    }
+}
+
+/// Current state of one serial port
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SerialPortState {
+    /// Whether the port is open and connected
+    pub open: bool,
+    /// Settings given by the user
+    pub setting: SerialSetting,
+    /// Settings reported by the OS
+    pub actual: SerialSettingActual,
+    /// Number of bytes received but not read yet
+    pub bytes_to_read: Option<u32>,
+    /// Number of bytes written but not transmitted yet
+    pub bytes_to_write: Option<u32>,
+    /// The last error occurred on this port
+    pub last_error: Option<String>,
+    /// Number of overruns counted by the driver since the port was opened, or `None` if not supported
+    pub overruns: Option<u64>,
+    /// Number of bytes read since the port was opened
+    pub bytes_read: u64,
+    /// Number of bytes written since the port was opened
+    pub bytes_written: u64,
+}
+
+/// Resource to query the current state of each serial port by label.
+/// This is updated every frame in `CoreStage::Last`.
+#[derive(Debug, Clone, Default, Resource)]
+pub struct SerialPortInfo(pub HashMap<String, SerialPortState>);
+
+impl SerialPortInfo {
+    /// Get the state of the serial port with the label
+    pub fn get(&self, label: &str) -> Option<&SerialPortState> {
+        self.0.get(label)
+    }
+
+    /// Iterate over labels and states of all serial ports
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &SerialPortState)> {
+        self.0.iter()
+    }
+}
+
+/// Plugin to provide the `SerialPortInfo` resource.
+/// This is included in `SerialPlugins`, or can be added after `SerialPlugin`.
+pub struct SerialInfoPlugin;
+
+impl Plugin for SerialInfoPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SerialPortInfo>()
+            .add_systems(Last, update_port_info);
+    }
+}
+
+/// Update `SerialPortInfo` from the current state of serial ports
+fn update_port_info(mut info: ResMut<SerialPortInfo>, serials: Res<Serials>) {
+    // removed ports are not listed
+    info.0.clear();
+    for serial_mtx in serials.0.iter() {
+        if let Some(serial) = serial_mtx.lock().ok().as_deref().and_then(Option::as_ref) {
+            let state = SerialPortState {
+                open: serial.connected,
+                setting: serial.setting.clone(),
+                actual: SerialSettingActual::from_port(&serial.stream),
+                bytes_to_read: serial.stream.bytes_to_read().ok(),
+                bytes_to_write: serial.stream.bytes_to_write().ok(),
+                last_error: serial.last_error.clone(),
+                overruns: serial.overrun_base.map(|_| serial.overruns),
+                bytes_read: serial.bytes_read,
+                bytes_written: serial.bytes_written,
+            };
+            info.0.insert(serial.label.clone(), state);
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use crate::compat::*;
+use bevy::app::{App, Plugin};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Number of buckets of `LatencyHistogram`
+const BUCKETS: usize = 24;
+
+/// Histogram of latencies with power-of-two buckets in microseconds.
+/// Bucket `i` counts latencies below `2^i` us (the last bucket counts everything above).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LatencyHistogram {
+    buckets: [u64; BUCKETS],
+    count: u64,
+    sum: Duration,
+    max: Duration,
+}
+
+impl LatencyHistogram {
+    pub fn record(&mut self, latency: Duration) {
+        let us = latency.as_micros() as u64;
+        let index = (64 - us.leading_zeros() as usize).min(BUCKETS - 1);
+        self.buckets[index] += 1;
+        self.count += 1;
+        self.sum += latency;
+        self.max = self.max.max(latency);
+    }
+
+    /// Number of latencies recorded
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn mean(&self) -> Duration {
+        if self.count == 0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(self.sum.as_secs_f64() / self.count as f64)
+        }
+    }
+
+    pub fn max(&self) -> Duration {
+        self.max
+    }
+
+    /// Upper bound of the bucket containing the percentile `p` (0.0 to 1.0)
+    pub fn percentile(&self, p: f64) -> Duration {
+        let target = (self.count as f64 * p).ceil() as u64;
+        let mut seen = 0;
+        for (i, n) in self.buckets.iter().enumerate() {
+            seen += n;
+            if seen >= target && seen > 0 {
+                return Duration::from_micros(1 << i).min(self.max);
+            }
+        }
+        self.max
+    }
+
+    /// Counts of each bucket with its upper bound
+    pub fn buckets(&self) -> impl Iterator<Item = (Duration, u64)> + '_ {
+        self.buckets
+            .iter()
+            .enumerate()
+            .map(|(i, n)| (Duration::from_micros(1 << i), *n))
+    }
+}
+
+/// Latencies of each stage of one serial port
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PortLatency {
+    /// From the poll reporting the port readable to sending `SerialReadEvent`
+    pub poll_to_send: LatencyHistogram,
+    /// From sending `SerialReadEvent` to sending the decoded `SerialFrameEvent`s
+    pub send_to_decode: LatencyHistogram,
+    /// From sending `SerialReadEvent` to the first consumer calling `SerialLatency::consumed`
+    pub send_to_consume: LatencyHistogram,
+}
+
+/// Resource with latency histograms of each serial port by label.
+/// This is provided by `SerialLatencyPlugin`.
+#[derive(Debug, Clone, Default, Resource)]
+pub struct SerialLatency {
+    ports: HashMap<String, PortLatency>,
+    /// When `SerialReadEvent` was sent last for each port, until it is consumed
+    sent: HashMap<String, Instant>,
+}
+
+impl SerialLatency {
+    /// Get the latencies of the serial port with the label
+    pub fn get(&self, label: &str) -> Option<&PortLatency> {
+        self.ports.get(label)
+    }
+
+    /// Iterate over labels and latencies of all serial ports
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &PortLatency)> {
+        self.ports.iter()
+    }
+
+    /// Call this when reading `SerialReadEvent` (or the frames decoded from it) of the port with the label.
+    /// Only the first call after each read is recorded.
+    pub fn consumed(&mut self, label: &str) {
+        if let Some(sent) = self.sent.remove(label) {
+            self.port(label).send_to_consume.record(sent.elapsed());
+        }
+    }
+
+    pub(crate) fn sent(&mut self, label: &str, polled: Instant) {
+        let now = Instant::now();
+        self.port(label).poll_to_send.record(now - polled);
+        self.sent.insert(label.to_string(), now);
+    }
+
+    #[cfg(feature = "codecs")]
+    pub(crate) fn decoded(&mut self, label: &str) {
+        if let Some(&sent) = self.sent.get(label) {
+            self.port(label).send_to_decode.record(sent.elapsed());
+        }
+    }
+
+    fn port(&mut self, label: &str) -> &mut PortLatency {
+        self.ports.entry(label.to_string()).or_default()
+    }
+}
+
+/// Plugin to record latencies of serial ports in the `SerialLatency` resource.
+/// Latencies are not recorded without this plugin.
+pub struct SerialLatencyPlugin;
+
+impl Plugin for SerialLatencyPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SerialLatency>();
+    }
+}
@@ -0,0 +1,118 @@
+use crate::compat::*;
+use crate::{PendingWrite, SerialWriteId};
+use std::collections::{HashMap, VecDeque};
+use std::io;
+
+/// How writes from others than the holder of a lease are handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LeasePolicy {
+    /// Keep up to the given number of writes until the lease is released, then write them in order.
+    /// Writes over the limit are rejected.
+    Queue(usize),
+    /// Drop the writes and send `SerialLeaseRejectedEvent`
+    Reject,
+}
+
+/// Bevy's event type to notify that a write was dropped because another writer holds the lease of the port
+#[derive(Debug, Clone, PartialEq, Eq, Event)]
+pub struct SerialLeaseRejectedEvent {
+    /// Label of the serial port
+    pub label: String,
+    /// Holder of the lease
+    pub holder: SerialWriteId,
+    /// Identifier of the rejected write, if it was sent with `SerialWriteRequestEvent`
+    pub id: Option<SerialWriteId>,
+}
+
+struct Lease {
+    holder: SerialWriteId,
+    policy: LeasePolicy,
+    queue: VecDeque<PendingWrite>,
+}
+
+/// Resource to get exclusive write access to a port for a series of transactions, e.g. a firmware update.
+/// While a port is leased, only `SerialWriteRequestEvent`s with the id of the holder are written,
+/// and other writes (including keepalive bytes) are queued or rejected based on the `LeasePolicy`.
+#[derive(Default, Resource)]
+pub struct SerialLeases {
+    leases: HashMap<String, Lease>,
+    /// Writes queued by leases which have been released, written in the next update
+    released: Vec<(String, PendingWrite)>,
+}
+
+impl SerialLeases {
+    /// Lease the port with the label to `holder`.
+    /// Returns `false` if the port is already leased to another holder.
+    pub fn acquire(&mut self, label: &str, holder: SerialWriteId, policy: LeasePolicy) -> bool {
+        match self.leases.get_mut(label) {
+            Some(lease) if lease.holder != holder => false,
+            Some(lease) => {
+                lease.policy = policy;
+                true
+            }
+            None => {
+                self.leases.insert(
+                    label.to_string(),
+                    Lease {
+                        holder,
+                        policy,
+                        queue: VecDeque::new(),
+                    },
+                );
+                true
+            }
+        }
+    }
+
+    /// Release the lease of the port with the label, so that the writes queued are written.
+    /// Returns `false` if the port is not leased to `holder`.
+    pub fn release(&mut self, label: &str, holder: SerialWriteId) -> bool {
+        if self.holder(label) != Some(holder) {
+            return false;
+        }
+        if let Some(lease) = self.leases.remove(label) {
+            self.released.extend(
+                lease
+                    .queue
+                    .into_iter()
+                    .map(|write| (label.to_string(), write)),
+            );
+        }
+        true
+    }
+
+    /// Holder of the lease of the port with the label
+    pub fn holder(&self, label: &str) -> Option<SerialWriteId> {
+        self.leases.get(label).map(|lease| lease.holder)
+    }
+
+    /// Take the writes queued by released leases
+    pub(crate) fn take_released(&mut self) -> Vec<(String, PendingWrite)> {
+        std::mem::take(&mut self.released)
+    }
+
+    /// Check a write to the port with the label against its lease.
+    /// Returns the write back if it can be written now, or `None` if it was queued.
+    /// Rejected writes are returned as `Err` with the holder of the lease.
+    pub(crate) fn admit(
+        &mut self,
+        label: &str,
+        write: PendingWrite,
+    ) -> Result<Option<PendingWrite>, (SerialWriteId, PendingWrite)> {
+        let lease = match self.leases.get_mut(label) {
+            Some(lease) if write.id != Some(lease.holder) => lease,
+            _ => return Ok(Some(write)),
+        };
+        match lease.policy {
+            LeasePolicy::Queue(max_len) if lease.queue.len() < max_len => {
+                lease.queue.push_back(write);
+                Ok(None)
+            }
+            _ => Err((lease.holder, write)),
+        }
+    }
+}
+
+pub(crate) fn leased_error(label: &str, holder: SerialWriteId) -> io::Error {
+    io::Error::other(format!("{} is leased to {:?}", label, holder))
+}
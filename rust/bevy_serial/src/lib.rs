@@ -4,10 +4,28 @@
 //! that can realize non-blocking high-performance I/O.
 //!
 //! Reading and writing from/to serial port is realized via bevy's event system. Each serial port is handled via port
-//! name or a unique label you choose. These event handlers are added to the following stage to minimize the frame delay.
+//! name or a unique label you choose. These event handlers are added to the following schedules to minimize the frame delay.
 //!
-//! - Reading: `CoreStage::PreUpdate`
-//! - Writing: `CoreStage::PostUpdate`
+//! - Reading: `PreUpdate`
+//! - Writing: `PostUpdate`
+//!
+//! ## Features
+//!
+//! Only reading and writing raw bytes is enabled by default. Enable the features you need:
+//!
+//! - `bevy_013` (default) or `bevy_06`: the Bevy version to build against, see [Supported Versions](#supported-versions)
+//! - `codecs`: decoders and demultiplexing messages (`DecoderPlugin`, UBX, IMU eval boards, RC receivers, `FixedFramePlugin`)
+//! - `codegen`: Arduino headers and decoders generated from binary message schemas (`MessageSchema`, enables `codecs`)
+//! - `enumeration`: choosing ports by USB metadata with `PortFilter` (adds `regex`)
+//! - `flash`: flashing firmware to Arduino and ESP32 bootloaders
+//! - `transfer`: XMODEM/YMODEM file transfer
+//! - `testing`: mock ports with fault injection (`MockPortPlugin`) to test decoders and parsers
+//! - `overlay`: on-screen overlay of the port state and RX/TX rates (adds Bevy's UI and rendering)
+//!
+//! ```toml
+//! [dependencies]
+//! bevy_serial = { version = "0.3", features = ["codecs", "enumeration"] }
+//! ```
 //!
 //! ## Usage
 //!
@@ -15,30 +33,31 @@
 //!
 //! Here is a simple example:
 //!
-//! ```rust
+//! ```rust,no_run
 //! use bevy::prelude::*;
 //! use bevy_serial::{SerialPlugin, SerialReadEvent, SerialWriteEvent};
 //!
 //! // to write data to serial port periodically
+//! #[derive(Resource)]
 //! struct SerialWriteTimer(Timer);
 //!
 //! fn main() {
 //!     App::new()
 //!         .add_plugins(MinimalPlugins)
 //!         // simply specify port name and baud rate for `SerialPlugin`
-//!         .add_plugin(SerialPlugin::new("COM5", 115200))
+//!         .add_plugins(SerialPlugin::new("COM5", 115200))
 //!         // to write data to serial port periodically (every 1 second)
-//!         .insert_resource(SerialWriteTimer(Timer::from_seconds(1.0, true)))
+//!         .insert_resource(SerialWriteTimer(Timer::from_seconds(1.0, TimerMode::Repeating)))
 //!         // reading and writing from/to serial port is achieved via bevy's event system
-//!         .add_system(read_serial)
-//!         .add_system(write_serial)
+//!         .add_systems(Update, read_serial)
+//!         .add_systems(Update, write_serial)
 //!         .run();
 //! }
 //!
 //! // reading event for serial port
 //! fn read_serial(mut ev_serial: EventReader<SerialReadEvent>) {
 //!     // you can get label of the port and received data buffer from `SerialReadEvent`
-//!     for SerialReadEvent(label, buffer) in ev_serial.iter() {
+//!     for SerialReadEvent(label, buffer) in ev_serial.read() {
 //!         let s = String::from_utf8(buffer.clone()).unwrap();
 //!         println!("received packet from {}: {}", label, s);
 //!     }
@@ -62,7 +81,7 @@
 //!
 //! You can add multiple serial ports with additional settings.
 //!
-//! ```rust
+//! ```rust,no_run
 //! use bevy::prelude::*;
 //! use bevy_serial::{
 //!     DataBits, FlowControl, Parity, SerialPlugin, SerialReadEvent, SerialSetting, SerialWriteEvent,
@@ -71,13 +90,14 @@
 //! use std::time::Duration;
 //!
 //! // to write data to serial port periodically
+//! #[derive(Resource)]
 //! struct SerialWriteTimer(Timer);
 //!
 //! fn main() {
 //!     App::new()
 //!         .add_plugins(MinimalPlugins)
 //!         // you can specify various configurations for multiple serial ports by this way
-//!         .add_plugin(SerialPlugin {
+//!         .add_plugins(SerialPlugin {
 //!             settings: vec![SerialSetting {
 //!                 label: Some("my_serial".to_string()),
 //!                 port_name: "COM5".to_string(),
@@ -87,20 +107,38 @@
 //!                 parity: Parity::None,
 //!                 stop_bits: StopBits::One,
 //!                 timeout: Duration::from_millis(0),
+//!                 log_bytes: false,
+//!                 escape: None,
+//!                 stale_timeout: None,
+//!                 keepalive: None,
+//!                 on_open: vec![],
+//!                 on_open_delay: Duration::from_millis(0),
+//!                 dtr_on_open: None,
+//!                 rts_on_open: None,
+//!                 open_settle: Duration::from_millis(0),
+//!                 read_buffer_len: 2048,
+//!                 driver_buffer_size: None,
+//!                 backup: None,
+//!                 reconnect_buffer: None,
+//!                 backlog: None,
+//!                 circuit_breaker: None,
+//!                 read_hook: None,
+//!                 io_thread: None,
+//!                 ..Default::default()
 //!             }],
 //!         })
 //!         // to write data to serial port periodically (every 1 second)
-//!         .insert_resource(SerialWriteTimer(Timer::from_seconds(1.0, true)))
+//!         .insert_resource(SerialWriteTimer(Timer::from_seconds(1.0, TimerMode::Repeating)))
 //!         // reading and writing from/to serial port is achieved via bevy's event system
-//!         .add_system(read_serial)
-//!         .add_system(write_serial)
+//!         .add_systems(Update, read_serial)
+//!         .add_systems(Update, write_serial)
 //!         .run();
 //! }
 //!
 //! // reading event for serial port
 //! fn read_serial(mut ev_serial: EventReader<SerialReadEvent>) {
 //!     // you can get label of the port and received data buffer from `SerialReadEvent`
-//!     for SerialReadEvent(label, buffer) in ev_serial.iter() {
+//!     for SerialReadEvent(label, buffer) in ev_serial.read() {
 //!         let s = String::from_utf8(buffer.clone()).unwrap();
 //!         println!("read packet from {}: {}", label, s);
 //!     }
@@ -120,295 +158,3055 @@
 //! }
 //! ```
 //!
-//! ## Supported Versions
+//! ### Typed Port Labels
 //!
-//! | bevy | bevy_serial |
-//! | ---- | ----------- |
-//! | 0.6  | 0.2         |
-//! | 0.5  | 0.1         |
+//! Instead of labels as strings, a port can be identified by a zero-sized marker type implementing `SerialLabel`.
+//! With `SerialPortPlugin`, bytes read from the port are sent as `PortReadEvent<P>` and `PortWriteEvent<P>` is written to it,
+//! so a typo in the label is a compile error, and systems handling the port don't compare labels.
 //!
-//! ## License
+//! ```rust,no_run
+//! use bevy::prelude::*;
+//! use bevy_serial::{PortReadEvent, PortWriteEvent, SerialLabel, SerialPlugin, SerialPortPlugin, SerialSetting};
 //!
-//! Dual-licensed under either
+//! struct ImuPort;
 //!
-//! - MIT
-//! - Apache 2.0
-
-
-pub use mio_serial::{DataBits, FlowControl, Parity, StopBits};
-
-use bevy::app::{App, CoreStage, EventReader, EventWriter, Plugin};
-use bevy::ecs::system::{Res, ResMut};
-use mio::{Events, Interest, Poll, Token};
-use mio_serial::SerialStream;
-use once_cell::sync::OnceCell;
-use std::collections::HashMap;
-use std::io::{ErrorKind, Read, Write};
-use std::sync::Mutex;
-use std::time::Duration;
-
-/// Plugin that can be added to Bevy
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct SerialPlugin {
-    pub settings: Vec<SerialSetting>,
-}
-
-impl SerialPlugin {
-    pub fn new(port_name: &str, baud_rate: u32) -> Self {
-        Self {
-            settings: vec![SerialSetting {
-                port_name: port_name.to_string(),
-                baud_rate,
-                ..Default::default()
-            }],
-        }
-    }
-}
-
-/// Settings for users to initialize this plugin
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct SerialSetting {
-    /// The intuitive name for this serial port
-    pub label: Option<String>,
-    /// The port name, usually the device path
-    pub port_name: String,
-    /// The baud rate in symbols-per-second
-    pub baud_rate: u32,
-    /// Number of bits used to represent a character sent on the line
-    pub data_bits: DataBits,
-    /// The type of signalling to use for controlling data transfer
-    pub flow_control: FlowControl,
-    /// The type of parity to use for error checking
-    pub parity: Parity,
-    /// Number of bits to use to signal the end of a character
-    pub stop_bits: StopBits,
-    /// Amount of time to wait to receive data before timing out
-    pub timeout: Duration,
-}
-
-impl Default for SerialSetting {
-    fn default() -> Self {
-        Self {
-            label: None,
-            port_name: "".to_string(),
-            baud_rate: 115200,
-            data_bits: DataBits::Eight,
-            flow_control: FlowControl::None,
-            parity: Parity::None,
-            stop_bits: StopBits::One,
-            timeout: Duration::from_millis(0),
-        }
-    }
-}
-
-/// Bevy's event type to read serial port
-pub struct SerialReadEvent(pub String, pub Vec<u8>);
-
-/// Bevy's event type to read serial port
-pub struct SerialWriteEvent(pub String, pub Vec<u8>);
-
-/// Serial struct that is used internally for this crate
-#[derive(Debug)]
-struct SerialStreamLabeled {
-    stream: SerialStream,
-    label: String,
-    connected: bool,
-}
-
-/// Module scope global singleton to store serial ports
-static SERIALS: OnceCell<Vec<Mutex<SerialStreamLabeled>>> = OnceCell::new();
-
-/// Component to get an index of serial port based on the label
-struct Indices(HashMap<String, usize>);
-
-/// The size of read buffer for one read system call
-const DEFAULT_READ_BUFFER_LEN: usize = 2048;
-
-impl Plugin for SerialPlugin {
-    fn build(&self, app: &mut App) {
-        let poll = Poll::new().unwrap();
-        let events = Events::with_capacity(self.settings.len());
-        let mut serials: Vec<Mutex<SerialStreamLabeled>> = vec![];
-        let mut indices = Indices(HashMap::new());
-
-        for (i, setting) in self.settings.iter().enumerate() {
-            // create serial port builder from `serialport` crate
-            let port_builder = serialport::new(&setting.port_name, setting.baud_rate)
-                .data_bits(setting.data_bits)
-                .flow_control(setting.flow_control)
-                .parity(setting.parity)
-                .stop_bits(setting.stop_bits)
-                .timeout(setting.timeout);
-
-            // create `mio_serial::SerailStream` from `seriaport` builder
-            let mut stream = SerialStream::open(&port_builder).unwrap_or_else(|e| {
-                panic!("Failed to open serial port {}\n{:?}", setting.port_name, e);
-            });
-
-            // token index is same as index of vec
-            poll.registry()
-                .register(&mut stream, Token(i), Interest::READABLE)
-                .unwrap_or_else(|e| {
-                    panic!("Failed to register stream to poll : {:?}", e);
-                });
-
-            // if label is set, use label as a nickname of serial
-            // if not, use `port_name` as a nickname
-            let label = if let Some(label) = &setting.label {
-                label.clone()
-            } else {
-                setting.port_name.clone()
-            };
-
-            // store indices and serials
-            indices.0.insert(label.clone(), i);
-            serials.push(Mutex::new(SerialStreamLabeled {
-                stream,
-                label,
-                connected: true,
-            }));
-        }
-
-        // set to global variables lazily
-        SERIALS.set(serials).unwrap_or_else(|e| {
-            panic!("Failed to set SerialStream to global variable: {:?}", e);
-        });
-
-        app.insert_resource(poll)
-            .insert_resource(events)
-            .insert_resource(indices)
-            .add_event::<SerialReadEvent>()
-            .add_event::<SerialWriteEvent>()
-            .add_system_to_stage(CoreStage::PreUpdate, read_serial)
-            .add_system_to_stage(CoreStage::PostUpdate, write_serial);
-    }
-}
-
-/// Poll serial read event with `Poll` in `mio` crate.
-/// If any data has come to serial, `SerialReadEvent` is sent to the system subscribing it.
-fn read_serial(
-    mut ev_receive_serial: EventWriter<SerialReadEvent>,
-    mut poll: ResMut<Poll>,
-    mut events: ResMut<Events>,
-    indices: Res<Indices>,
-) {
-    if !indices.0.is_empty() {
-        // poll serial read event (should timeout not to block other systems)
-        poll.poll(&mut events, Some(Duration::from_micros(1)))
-            .unwrap_or_else(|e| {
-                panic!("Failed to poll events: {:?}", e);
-            });
-
-        // if events have occurred, send `SerialReadEvent` with serial labels and read data buffer
-        for event in events.iter() {
-            // get serial instance based on the token index
-            let serials = SERIALS.get().expect("SERIALS are not initialized");
-            let serial_mtx = serials
-                .get(event.token().0) // token index is same as index of vec
-                .expect("SERIALS are not initialized");
-
-            if event.is_readable() {
-                let mut buffer = vec![0_u8; DEFAULT_READ_BUFFER_LEN];
-                let mut bytes_read = 0;
-                loop {
-                    // try to get lock of mutex and send data to event
-                    if let Ok(mut serial) = serial_mtx.lock() {
-                        if serial.connected {
-                            match serial.stream.read(&mut buffer[bytes_read..]) {
-                                Ok(0) => {
-                                    eprintln!("read connection closed");
-                                    serial.connected = false;
-                                    break;
-                                }
-                                // read data successfully
-                                // if buffer is full, maybe there is more data to read
-                                Ok(n) => {
-                                    bytes_read += n;
-                                    if bytes_read == buffer.len() {
-                                        buffer.resize(buffer.len() + DEFAULT_READ_BUFFER_LEN, 0);
-                                    }
-                                }
-                                // would block indicates no more data to read
-                                Err(ref e) if e.kind() == ErrorKind::WouldBlock => {
-                                    let label = serial.label.clone();
-                                    let buffer = buffer.drain(..bytes_read).collect();
-                                    ev_receive_serial.send(SerialReadEvent(label, buffer));
-                                    break;
-                                }
-                                // if interrupted, we should continue readings
-                                Err(ref e) if e.kind() == ErrorKind::Interrupted => {
-                                    continue;
-                                }
-                                // other errors are fatal
-                                Err(e) => {
-                                    eprintln!("Failed to read serial port {}: {}", serial.label, e);
-                                }
-                            }
-                        } else {
-                            eprintln!("{} connection has closed", serial.label);
-                        }
-                    }
-                }
-            }
-        }
-    }
-}
-
-/// Write bytes to serial port.
-/// The bytes are sent via `SerialWriteEvent` with label of serial port.
-fn write_serial(mut ev_write_serial: EventReader<SerialWriteEvent>, indices: Res<Indices>) {
-    if !indices.0.is_empty() {
-        for SerialWriteEvent(label, buffer) in ev_write_serial.iter() {
-            // get index of label
-            let &serial_index = indices
-                .0
-                .get(label)
-                .expect(format!("Label {} is not exist", label).as_str());
-            let serials = SERIALS.get().expect("SERIALS are not initialized");
-            let serial_mtx = serials
-                .get(serial_index)
-                .expect("SERIALS are not initialized");
-
-            // write buffered data to serial
-            let mut bytes_wrote = 0;
-            loop {
-                // try to get lock of mutex and send data to event
-                if let Ok(mut serial) = serial_mtx.lock() {
-                    if serial.connected {
-                        // write the entire buffered data in a single system call
-                        match serial.stream.write(&buffer[bytes_wrote..]) {
-                            // error if returned len is less than expected (same as `io::Write::write_all` does)
-                            Ok(n) if n < buffer.len() => {
-                                eprintln!(
-                                    "write size error {} / {}",
-                                    n,
-                                    buffer.len() - bytes_wrote
-                                );
-                                bytes_wrote += n;
-                            }
-                            // wrote queued data successfully
-                            Ok(_) => {
-                                bytes_wrote += buffer.len();
-                            }
-                            // would block indicates that this port is not ready so try again
-                            Err(ref e) if e.kind() == ErrorKind::WouldBlock => {}
-                            // if interrupted, we should try again
-                            Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
-                            // other errors are fatal
-                            Err(e) => {
-                                eprintln!("Failed to write serial port {}: {}", serial.label, e);
-                            }
-                        }
-                    } else {
-                        eprintln!("{} connection has closed", serial.label);
-                    }
-
-                    if bytes_wrote == buffer.len() {
-                        break;
-                    } else {
-                        continue;
-                    }
-                }
+//! impl SerialLabel for ImuPort {
+//!     const LABEL: &'static str = "imu";
+//! }
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(MinimalPlugins)
+//!         .add_plugins(SerialPlugin {
+//!             settings: vec![SerialSetting {
+//!                 label: Some(ImuPort::LABEL.to_string()),
+//!                 port_name: "COM5".to_string(),
+//!                 ..Default::default()
+//!             }],
+//!         })
+//!         .add_plugins(SerialPortPlugin::<ImuPort>::default())
+//!         .add_systems(Update, echo_imu)
+//!         .run();
+//! }
+//!
+//! fn echo_imu(mut ev_read: EventReader<PortReadEvent<ImuPort>>, mut ev_write: EventWriter<PortWriteEvent<ImuPort>>) {
+//!     for ev in ev_read.read() {
+//!         ev_write.send(PortWriteEvent::<ImuPort>::new(&ev.buffer));
+//!     }
+//! }
+//! ```
+//!
+//! ### Hex Strings
+//!
+//! To debug binary protocols, you can write a hex string via `SerialWriteHexEvent` and get received data as a hex string
+//! from `SerialReadEvent::to_hex`.
+//!
+//! ```rust,no_run
+//! use bevy::prelude::*;
+//! use bevy_serial::{SerialReadEvent, SerialWriteHexEvent};
+//!
+//! fn read_serial(mut ev_serial: EventReader<SerialReadEvent>) {
+//!     for ev in ev_serial.read() {
+//!         println!("received packet from {}: {}", ev.0, ev.to_hex());
+//!     }
+//! }
+//!
+//! fn write_serial(mut ev_serial: EventWriter<SerialWriteHexEvent>) {
+//!     ev_serial.send(SerialWriteHexEvent("COM5".to_string(), "DE AD BE EF".to_string()));
+//! }
+//! ```
+//!
+//! ### Escaping Control Bytes
+//!
+//! For devices which can't handle certain control bytes in-band, set `SerialSetting::escape` to an `EscapeCodec`. Bytes
+//! written are escaped and bytes read are unescaped transparently, so events always carry the original data.
+//! `log_bytes` logs the bytes on the wire.
+//!
+//! ```rust,no_run
+//! use bevy_serial::{EscapeCodec, SerialSetting};
+//!
+//! // XON/XOFF are sent as `#Q`/`#S`, and `#` itself as `#c`
+//! let setting = SerialSetting {
+//!     port_name: "COM5".to_string(),
+//!     escape: Some(EscapeCodec::new(b'#', &[0x11, 0x13])),
+//!     ..Default::default()
+//! };
+//! ```
+//!
+//! ### Read Hooks
+//!
+//! To inspect, transform or drop raw chunks before they are sent as `SerialReadEvent` (and decoded),
+//! add closures with `SerialSetting::read_hook`. A chunk cleared by a hook is dropped.
+//! Hooks run while the port is read in `PreUpdate`, so keep them cheap.
+//!
+//! ```rust,no_run
+//! use bevy::prelude::*;
+//! use bevy_serial::{SerialPlugin, SerialSetting};
+//!
+//! fn main() {
+//!     let mut banner = true;
+//!     let setting = SerialSetting {
+//!         port_name: "COM5".to_string(),
+//!         ..Default::default()
+//!     }
+//!     // strip the bootloader banner until the first empty line
+//!     .read_hook(move |chunk| {
+//!         if banner {
+//!             match chunk.windows(2).position(|w| w == b"\n\n") {
+//!                 Some(end) => {
+//!                     chunk.drain(..end + 2);
+//!                     banner = false;
+//!                 }
+//!                 None => chunk.clear(),
+//!             }
+//!         }
+//!     })
+//!     // discard keepalive bytes from the device
+//!     .read_hook(|chunk| chunk.retain(|&b| b != 0x00));
+//!
+//!     App::new()
+//!         .add_plugins(MinimalPlugins)
+//!         .add_plugins(SerialPlugin {
+//!             settings: vec![setting],
+//!         })
+//!         .run();
+//! }
+//! ```
+//!
+//! ### Plugin Group
+//!
+//! `SerialPlugins` bundles `SerialPlugin` and the optional plugins of this crate. Each of them can be disabled.
+//!
+//! ```rust,no_run
+//! use bevy::prelude::*;
+//! use bevy_serial::{SerialInfoPlugin, SerialPlugins};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(MinimalPlugins)
+//!         .add_plugins(
+//!             SerialPlugins::new("COM5", 115200)
+//!                 .build()
+//!                 .disable::<SerialInfoPlugin>(),
+//!         )
+//!         .run();
+//! }
+//! ```
+//!
+//! ### Adding Ports from Other Plugins
+//!
+//! Other plugins can add their own serial ports after `SerialPlugin` is added via `SerialAppExt::add_serial_port`,
+//! instead of putting all ports into one settings vector.
+//!
+//! ```rust,no_run
+//! use bevy::prelude::*;
+//! use bevy_serial::{SerialAppExt, SerialSetting};
+//!
+//! struct ImuPlugin;
+//!
+//! impl Plugin for ImuPlugin {
+//!     fn build(&self, app: &mut App) {
+//!         app.add_serial_port(SerialSetting {
+//!             label: Some("imu".to_string()),
+//!             port_name: "COM5".to_string(),
+//!             ..Default::default()
+//!         });
+//!     }
+//! }
+//! ```
+//!
+//! ### Adding and Removing Ports at Runtime
+//!
+//! Use the `SerialPorts` system parameter to close a port while the app is running, e.g. so that another program
+//! (or `FlashPlugin`) can open the same device. `SerialClosedEvent` is sent when the port has been removed.
+//!
+//! ```rust,no_run
+//! use bevy::prelude::*;
+//! use bevy_serial::SerialPorts;
+//!
+//! fn release_port(keys: Res<ButtonInput<KeyCode>>, mut ports: SerialPorts) {
+//!     if keys.just_pressed(KeyCode::KeyR) {
+//!         ports.remove("COM5");
+//!     }
+//! }
+//! ```
+//!
+//! `SerialPorts::add` opens a port while the app is running, e.g. one chosen by the user,
+//! and returns the error instead of applying `SerialPanicPolicy`.
+//!
+//! ```rust,no_run
+//! use bevy::prelude::*;
+//! use bevy_serial::{SerialPorts, SerialSetting};
+//!
+//! fn open_port(keys: Res<ButtonInput<KeyCode>>, mut ports: SerialPorts) {
+//!     if keys.just_pressed(KeyCode::KeyO) {
+//!         if let Err(e) = ports.add(SerialSetting {
+//!             port_name: "COM5".to_string(),
+//!             ..Default::default()
+//!         }) {
+//!             error!("{}", e);
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! ### Buffer Sizes
+//!
+//! For high-throughput devices, `read_buffer_len` sets the size of the buffer for one read system call,
+//! and `driver_buffer_size` sets the input and output buffer sizes of the OS driver on Windows (`SetupComm`),
+//! so bytes are not lost during frame hitches. Other platforms have no portable API to resize the driver buffers,
+//! and `VMIN`/`VTIME` don't apply because ports are read without blocking.
+//!
+//! ```rust,no_run
+//! use bevy_serial::SerialSetting;
+//!
+//! let setting = SerialSetting {
+//!     port_name: "COM5".to_string(),
+//!     baud_rate: 921600,
+//!     read_buffer_len: 16384,
+//!     driver_buffer_size: Some((65536, 4096)),
+//!     ..Default::default()
+//! };
+//! ```
+//!
+//! ### Bounded Backlog
+//!
+//! Bevy keeps events for two frames, so at high rates every chunk read is allocated as an event, and a lagging consumer misses data.
+//! Set `SerialSetting::backlog` to keep the chunks of a port in the `SerialBacklog` resource instead of sending `SerialReadEvent`.
+//! It holds up to `capacity` chunks until they are drained. When it is full, `BacklogPolicy::DropOldest` or `DropNewest` drops a chunk,
+//! and `BacklogPolicy::Block` stops reading the port (the bytes wait in the OS driver buffer) until it is drained.
+//! `PortBacklog::drained` and `PortBacklog::dropped` count the chunks drained and dropped.
+//!
+//! ```rust,no_run
+//! use bevy::prelude::*;
+//! use bevy_serial::{BacklogPolicy, BacklogSetting, SerialBacklog, SerialSetting};
+//!
+//! let setting = SerialSetting {
+//!     port_name: "COM5".to_string(),
+//!     backlog: Some(BacklogSetting::new(256, BacklogPolicy::DropOldest)),
+//!     ..Default::default()
+//! };
+//!
+//! fn read_backlog(mut backlog: ResMut<SerialBacklog>) {
+//!     for chunk in backlog.drain("COM5").into_iter().flatten() {
+//!         println!("{} bytes", chunk.len());
+//!     }
+//!     if let Some(port) = backlog.get("COM5") {
+//!         println!("drained {}, dropped {}", port.drained(), port.dropped());
+//!     }
+//! }
+//! ```
+//!
+//! ### Port State
+//!
+//! The current state of each serial port (open or closed, configured settings, settings actually applied by the OS,
+//! queue depths and the last error) can be queried from the `SerialPortInfo` resource, which is provided by
+//! `SerialInfoPlugin` (included in `SerialPlugins`).
+//!
+//! ```rust
+//! use bevy::prelude::*;
+//! use bevy_serial::SerialPortInfo;
+//!
+//! fn print_port_state(info: Res<SerialPortInfo>) {
+//!     for (label, state) in info.iter() {
+//!         println!("{}: open = {}, baud rate = {:?}", label, state.open, state.actual.baud_rate);
+//!     }
+//! }
+//! ```
+//!
+//! When a port is opened (or opened again by failover), `SerialOpenedEvent` is sent with the settings the OS actually applied,
+//! so the configuration can be verified once, e.g. for drivers which coerce the baud rate or flow control.
+//!
+//! ```rust
+//! use bevy::prelude::*;
+//! use bevy_serial::SerialOpenedEvent;
+//!
+//! fn check_opened(mut ev_opened: EventReader<SerialOpenedEvent>) {
+//!     for ev in ev_opened.read() {
+//!         if ev.settings_actual.baud_rate != Some(115200) {
+//!             println!("{} runs at {:?} baud", ev.label, ev.settings_actual.baud_rate);
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! ### Failover Port Pairs
+//!
+//! For installations with redundant links, set `SerialSetting::backup` to the name of a second port.
+//! When the port is disconnected, the backup is opened under the same label, so readers and writers don't notice the switch
+//! except for `SerialFailoverEvent`. If the backup is disconnected later, the first port is opened again.
+//!
+//! ```rust,no_run
+//! use bevy_serial::SerialSetting;
+//!
+//! let setting = SerialSetting {
+//!     label: Some("imu".to_string()),
+//!     port_name: "/dev/ttyUSB0".to_string(),
+//!     backup: Some("/dev/ttyUSB1".to_string()),
+//!     ..Default::default()
+//! };
+//! ```
+//!
+//! ### Writes Across Reconnects
+//!
+//! Set `reconnect_buffer` to keep up to that many writes while the port is disconnected, or removed with `SerialPorts::remove`,
+//! and write them in order when the port with the label is connected again (e.g. added again, or switched to its backup),
+//! so a command stream is not lost across a brief unplug. Writes over the limit fail as usual.
+//!
+//! ```rust,no_run
+//! use bevy_serial::SerialSetting;
+//!
+//! let setting = SerialSetting {
+//!     port_name: "COM5".to_string(),
+//!     reconnect_buffer: Some(64),
+//!     ..Default::default()
+//! };
+//! ```
+//!
+//! ### Detecting Stale Ports
+//!
+//! Set `stale_timeout` to get `SerialStaleEvent` when a port has not read any bytes for the duration,
+//! e.g. to show that a sensor is not responding instead of freezing on the last value.
+//! The event carries the time when bytes were read last, and is sent once until bytes are read again.
+//!
+//! ```rust,no_run
+//! use bevy::prelude::*;
+//! use bevy_serial::{SerialPlugin, SerialSetting, SerialStaleEvent};
+//! use std::time::Duration;
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(MinimalPlugins)
+//!         .add_plugins(SerialPlugin {
+//!             settings: vec![SerialSetting {
+//!                 port_name: "COM5".to_string(),
+//!                 stale_timeout: Some(Duration::from_secs(1)),
+//!                 ..Default::default()
+//!             }],
+//!         })
+//!         .add_systems(Update, show_stale)
+//!         .run();
+//! }
+//!
+//! fn show_stale(mut ev_stale: EventReader<SerialStaleEvent>) {
+//!     for SerialStaleEvent(label, since) in ev_stale.read() {
+//!         println!("{} is not responding for {:?}", label, since.elapsed());
+//!     }
+//! }
+//! ```
+//!
+//! ### Keepalive
+//!
+//! Some devices reset or sleep when the host goes quiet. Set `keepalive` to write bytes at an interval,
+//! which are written only when nothing else has been written to the port for the interval.
+//!
+//! ```rust,no_run
+//! use bevy_serial::{Keepalive, SerialSetting};
+//! use std::time::Duration;
+//!
+//! let setting = SerialSetting {
+//!     port_name: "COM5".to_string(),
+//!     keepalive: Some(Keepalive::new(b"\n", Duration::from_secs(2))),
+//!     ..Default::default()
+//! };
+//! ```
+//!
+//! ### Commands on Open
+//!
+//! Set `on_open` to write commands after the port opens, e.g. to start streaming, so the device state is restored
+//! without the app writing them. The commands are written in order, with `on_open_delay` before each one.
+//!
+//! ```rust,no_run
+//! use bevy_serial::SerialSetting;
+//! use std::time::Duration;
+//!
+//! let setting = SerialSetting {
+//!     port_name: "COM5".to_string(),
+//!     on_open: vec![b"RATE 100\n".to_vec(), b"START\n".to_vec()],
+//!     on_open_delay: Duration::from_millis(50),
+//!     ..Default::default()
+//! };
+//! ```
+//!
+//! ### DTR/RTS on Open
+//!
+//! Some boards (e.g. Arduino Leonardo, ESP32) reset or enter the bootloader depending on DTR and RTS.
+//! Set `dtr_on_open` and `rts_on_open` to set the lines right after the port opens,
+//! and `open_settle` to wait before reading and writing, e.g. until the board has booted.
+//! The bytes read and written meanwhile are held until the port has settled, without blocking the app.
+//! Note that the OS may still toggle DTR while opening the port.
+//!
+//! ```rust,no_run
+//! use bevy_serial::SerialSetting;
+//! use std::time::Duration;
+//!
+//! // keep an ESP32 running instead of entering the bootloader
+//! let setting = SerialSetting {
+//!     port_name: "/dev/ttyUSB0".to_string(),
+//!     dtr_on_open: Some(false),
+//!     rts_on_open: Some(false),
+//!     open_settle: Duration::from_millis(100),
+//!     ..Default::default()
+//! };
+//! ```
+//!
+//! ### Latency
+//!
+//! Add `SerialLatencyPlugin` to record latency histograms of each port in the `SerialLatency` resource:
+//! from the poll to sending `SerialReadEvent`, from sending it to decoding frames, and from sending it to the first consumer.
+//! Call `SerialLatency::consumed` where you read the data to record the last one.
+//!
+//! ```rust
+//! use bevy::prelude::*;
+//! use bevy_serial::{SerialLatency, SerialReadEvent};
+//!
+//! fn read_serial(mut ev_serial: EventReader<SerialReadEvent>, mut latency: ResMut<SerialLatency>) {
+//!     for SerialReadEvent(label, buffer) in ev_serial.read() {
+//!         latency.consumed(label);
+//!         // ...
+//!     }
+//! }
+//!
+//! fn print_latency(latency: Res<SerialLatency>) {
+//!     for (label, port) in latency.iter() {
+//!         println!(
+//!             "{}: poll to consumer p99 = {:?}",
+//!             label,
+//!             port.poll_to_send.percentile(0.99) + port.send_to_consume.percentile(0.99)
+//!         );
+//!     }
+//! }
+//! ```
+//!
+//! ### Aligning Device Clocks
+//!
+//! When multiple devices stream at the same time, the `SerialClock` resource puts their timestamps on the host timeline.
+//! Pass the timestamp from each device (e.g. `millis()`) with the arrival time to `SerialClock::observe`.
+//! It estimates the offset of each device clock from the arrivals with the least delay in the last 10 seconds.
+//! It also follows clock drift and restarts the estimate when a device resets.
+//!
+//! ```rust
+//! use bevy::prelude::*;
+//! use bevy_serial::{SerialClock, SerialReadEvent};
+//! use std::time::{Duration, Instant};
+//!
+//! // lines like "<millis> <value>" from each device
+//! fn align(mut ev_serial: EventReader<SerialReadEvent>, mut clock: ResMut<SerialClock>) {
+//!     for SerialReadEvent(label, buffer) in ev_serial.read() {
+//!         let line = String::from_utf8_lossy(buffer);
+//!         if let Some(Ok(millis)) = line.split(' ').next().map(str::parse::<u64>) {
+//!             let device_time = Duration::from_millis(millis);
+//!             clock.observe(label, device_time, Instant::now());
+//!             let _host_time = clock.to_host(label, device_time);
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! ### Overruns
+//!
+//! On Linux, overruns counted by the driver (`TIOCGICOUNT`) are sent as `SerialOverrunEvent`
+//! and shown in `SerialPortState::overruns`, so you can tell bytes dropped by the device or the driver
+//! from bytes dropped by the app. Not all drivers count overruns, and other platforms are not supported.
+//!
+//! ```rust
+//! use bevy::prelude::*;
+//! use bevy_serial::SerialOverrunEvent;
+//!
+//! fn warn_overruns(mut ev_overrun: EventReader<SerialOverrunEvent>) {
+//!     for ev in ev_overrun.read() {
+//!         println!("{} dropped bytes ({} overruns in total)", ev.label, ev.total);
+//!     }
+//! }
+//! ```
+//!
+//! ### Overlay
+//!
+//! With the `overlay` feature, `SerialOverlayPlugin` shows the state and RX/TX rates of each port
+//! in the bottom right corner, e.g. for apps shipped with hardware. It reads `SerialPortInfo`,
+//! so `SerialInfoPlugin` should also be added. The font is loaded from the assets.
+//!
+//! ```rust,ignore
+//! use bevy::prelude::*;
+//! use bevy_serial::{SerialInfoPlugin, SerialOverlayPlugin, SerialPlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(DefaultPlugins)
+//!         .add_plugins(SerialPlugin::new("COM5", 115200))
+//!         .add_plugins(SerialInfoPlugin)
+//!         .add_plugins(SerialOverlayPlugin::new("fonts/FiraMono-Medium.ttf"))
+//!         .run();
+//! }
+//! ```
+//!
+//! ### Write Results
+//!
+//! If you need to know when a write has completed or failed, send `SerialWriteRequestEvent` with an entity or an id.
+//! The result is sent back as `SerialWriteResultEvent` carrying the same id.
+//! Writes to each port are written in the order they were sent, while different ports are written concurrently
+//! on Bevy's `IoTaskPool`, so a device which is slow to accept bytes doesn't delay the writes to the others.
+//!
+//! ```rust,no_run
+//! use bevy::prelude::*;
+//! use bevy_serial::{SerialWriteId, SerialWriteRequestEvent, SerialWriteResultEvent};
+//!
+//! fn send_request(mut ev_request: EventWriter<SerialWriteRequestEvent>) {
+//!     ev_request.send(SerialWriteRequestEvent {
+//!         label: "COM5".to_string(),
+//!         buffer: b"GET\n".to_vec(),
+//!         id: SerialWriteId::Id(42),
+//!     });
+//! }
+//!
+//! fn check_result(mut ev_result: EventReader<SerialWriteResultEvent>) {
+//!     for ev in ev_result.read() {
+//!         if let Err(e) = &ev.result {
+//!             println!("request {:?} to {} failed: {}", ev.id, ev.label, e);
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! ### I/O Threads
+//!
+//! For a low latency control loop, set `io_thread` to read and write a port on its own thread, with a higher priority
+//! and optionally pinned to a CPU core, instead of the threads of the app. The thread reads the port as soon as bytes arrive
+//! and passes the chunks to `SerialReadEvent` in the next frame, and the writes are queued on it without waiting for them,
+//! so `SerialWriteResultEvent` comes in a later frame. Inserting `IoThread` as a resource runs
+//! the flashing and file transfer threads the same way. The priority is a nice value of -10 or `SCHED_FIFO` on Linux,
+//! which needs `CAP_SYS_NICE` (or an rtprio limit), and `THREAD_PRIORITY_HIGHEST` or `THREAD_PRIORITY_TIME_CRITICAL`
+//! on Windows. What the platform or the permissions don't allow is logged, and the thread runs with the defaults of the OS.
+//!
+//! ```rust,no_run
+//! use bevy_serial::{IoThread, SerialSetting, ThreadPriority};
+//!
+//! let setting = SerialSetting {
+//!     port_name: "/dev/ttyACM0".to_string(),
+//!     io_thread: Some(IoThread::new(ThreadPriority::Realtime).core(3)),
+//!     ..Default::default()
+//! };
+//! ```
+//!
+//! ### Port Leases
+//!
+//! To keep other writers from interleaving bytes with a series of transactions (e.g. a firmware update over the app protocol),
+//! lease the port with `SerialLeases::acquire`. While the port is leased, only `SerialWriteRequestEvent`s with the id of the holder
+//! are written. Other writes are queued until `SerialLeases::release` with `LeasePolicy::Queue`, or dropped with
+//! `SerialLeaseRejectedEvent` (and a failed `SerialWriteResultEvent` for requests) with `LeasePolicy::Reject`.
+//! Keepalive bytes are not written to leased ports.
+//!
+//! ```rust,no_run
+//! use bevy::prelude::*;
+//! use bevy_serial::{LeasePolicy, SerialLeases, SerialWriteId, SerialWriteRequestEvent};
+//!
+//! const UPDATER: SerialWriteId = SerialWriteId::Id(1);
+//!
+//! fn start_update(mut leases: ResMut<SerialLeases>, mut ev_request: EventWriter<SerialWriteRequestEvent>) {
+//!     if leases.acquire("COM5", UPDATER, LeasePolicy::Queue(64)) {
+//!         ev_request.send(SerialWriteRequestEvent {
+//!             label: "COM5".to_string(),
+//!             buffer: b"UPDATE BEGIN\n".to_vec(),
+//!             id: UPDATER,
+//!         });
+//!     }
+//! }
+//!
+//! fn finish_update(mut leases: ResMut<SerialLeases>) {
+//!     // writes queued during the update are written in the next update
+//!     leases.release("COM5", UPDATER);
+//! }
+//! ```
+//!
+//! ### Device Sessions
+//!
+//! Bringing up a device often takes several steps, e.g. a handshake, then configuration, then streaming.
+//! Implement `DeviceSession` with a state for each step and add `SessionPlugin` to run it on a port: each `Transition`
+//! writes the bytes for the next state and sets its timeout, driven by the bytes read and the expired timeouts.
+//! `SessionEvent`s are sent as the session enters each state, completes or fails, and `SerialSession::restart` starts it again.
+//!
+//! ```rust,no_run
+//! use bevy::prelude::*;
+//! use bevy_serial::{DeviceSession, SerialPlugin, SessionEvent, SessionPlugin, Transition};
+//! use std::time::Duration;
+//!
+//! #[derive(Debug, Clone, PartialEq)]
+//! enum Bringup {
+//!     Handshake,
+//!     Configure,
+//! }
+//!
+//! #[derive(Clone)]
+//! struct ImuSession;
+//!
+//! impl DeviceSession for ImuSession {
+//!     type State = Bringup;
+//!
+//!     fn start(&mut self) -> Transition<Bringup> {
+//!         Transition::to(Bringup::Handshake)
+//!             .write(b"HELLO\n")
+//!             .timeout(Duration::from_secs(1))
+//!     }
+//!
+//!     fn on_read(&mut self, state: &Bringup, bytes: &[u8]) -> Transition<Bringup> {
+//!         match state {
+//!             Bringup::Handshake if bytes.starts_with(b"HELLO") => Transition::to(Bringup::Configure)
+//!                 .write(b"RATE 100\n")
+//!                 .timeout(Duration::from_secs(1)),
+//!             Bringup::Configure if bytes.starts_with(b"OK") => Transition::Complete,
+//!             _ => Transition::Stay,
+//!         }
+//!     }
+//! }
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(MinimalPlugins)
+//!         .add_plugins(SerialPlugin::new("COM5", 115200))
+//!         .add_plugins(SessionPlugin::new("COM5", ImuSession))
+//!         .add_systems(Update, show_progress)
+//!         .run();
+//! }
+//!
+//! fn show_progress(mut ev_session: EventReader<SessionEvent<Bringup>>) {
+//!     for ev in ev_session.read() {
+//!         println!("{:?}", ev);
+//!     }
+//! }
+//! ```
+//!
+//! ### Port Groups
+//!
+//! For installations with many devices, define groups of ports in `SerialGroups` (or with `SerialAppExt::add_serial_group`)
+//! and write to the group label to broadcast to every member port. Each `SerialWriteRequestEvent` to a group gets one
+//! `SerialWriteResultEvent` per member, with the member label.
+//!
+//! ```rust,no_run
+//! use bevy::prelude::*;
+//! use bevy_serial::{SerialAppExt, SerialPlugin, SerialSetting, SerialWriteEvent};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(MinimalPlugins)
+//!         .add_plugins(SerialPlugin {
+//!             settings: vec![
+//!                 SerialSetting {
+//!                     label: Some("led_left".to_string()),
+//!                     port_name: "COM5".to_string(),
+//!                     ..Default::default()
+//!                 },
+//!                 SerialSetting {
+//!                     label: Some("led_right".to_string()),
+//!                     port_name: "COM6".to_string(),
+//!                     ..Default::default()
+//!                 },
+//!             ],
+//!         })
+//!         .add_serial_group("all_leds", &["led_left", "led_right"])
+//!         .add_systems(Update, blink)
+//!         .run();
+//! }
+//!
+//! fn blink(mut ev_write: EventWriter<SerialWriteEvent>) {
+//!     ev_write.send(SerialWriteEvent("all_leds".to_string(), b"BLINK\n".to_vec()));
+//! }
+//! ```
+//!
+//! ### Unknown Labels
+//!
+//! By default, a write to a label which no serial port has is dropped and `SerialErrorEvent::UnknownLabel` is sent.
+//! You can change this behavior by inserting the `UnknownLabelPolicy` resource: `Panic` for a strict mode, or
+//! `Buffer(max_len)` to keep writes until a port with that label is added.
+//!
+//! ```rust,no_run
+//! use bevy::prelude::*;
+//! use bevy_serial::{SerialPlugin, UnknownLabelPolicy};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(MinimalPlugins)
+//!         .add_plugins(SerialPlugin::new("COM5", 115200))
+//!         .insert_resource(UnknownLabelPolicy::Panic)
+//!         .run();
+//! }
+//! ```
+//!
+//! ### Error Handling
+//!
+//! Errors opening, polling, reading and writing serial ports are sent as `SerialErrorEvent`, which is the `SerialError` enum
+//! also returned by `SerialPorts::add`. Its variants carry the label of the port and the `std::io::ErrorKind` of read and write errors,
+//! so errors can be handled by matching instead of parsing messages.
+//! By default, the plugin panics if a port fails to open (or its label already exists) or the ports fail to be polled, and logs the other errors, including writes to unknown labels.
+//! To keep running without the failed port, insert `SerialPanicPolicy::LogAndContinue` before adding `SerialPlugin`,
+//! or `SerialPanicPolicy::EmitEventOnly` to handle the errors only by the events, without logging them.
+//!
+//! ```rust,no_run
+//! use bevy::prelude::*;
+//! use bevy_serial::{SerialErrorEvent, SerialPanicPolicy, SerialPlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(MinimalPlugins)
+//!         .insert_resource(SerialPanicPolicy::LogAndContinue)
+//!         .add_plugins(SerialPlugin::new("COM5", 115200))
+//!         .add_systems(Update, show_errors)
+//!         .run();
+//! }
+//!
+//! fn show_errors(mut ev_error: EventReader<SerialErrorEvent>) {
+//!     for error in ev_error.read() {
+//!         println!("{}", error);
+//!     }
+//! }
+//! ```
+//!
+//! ### Closing Ports on Error Storms
+//!
+//! A failing adapter can flood read or write errors every frame, spamming the log and burning CPU.
+//! Set `SerialSetting::circuit_breaker` to close the port when it has more than `max_errors_per_sec` errors
+//! in each second for `storm_secs` seconds in a row. `SerialBreakerEvent::Tripped` is sent when the port is closed,
+//! and with `CircuitBreaker::reopen_after`, the port is opened again with the same setting after the delay
+//! (retrying after the delay while it fails) and `SerialBreakerEvent::Reopened` is sent.
+//!
+//! ```rust,no_run
+//! use bevy::prelude::*;
+//! use bevy_serial::{CircuitBreaker, SerialBreakerEvent, SerialPlugin, SerialSetting};
+//! use std::time::Duration;
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(MinimalPlugins)
+//!         .add_plugins(SerialPlugin {
+//!             settings: vec![SerialSetting {
+//!                 port_name: "COM5".to_string(),
+//!                 // close after 5 seconds of more than 100 errors per second, and retry after 30 seconds
+//!                 circuit_breaker: Some(
+//!                     CircuitBreaker::new(100, 5).reopen_after(Duration::from_secs(30)),
+//!                 ),
+//!                 ..Default::default()
+//!             }],
+//!         })
+//!         .add_systems(Update, show_breakers)
+//!         .run();
+//! }
+//!
+//! fn show_breakers(mut ev_breaker: EventReader<SerialBreakerEvent>) {
+//!     for ev in ev_breaker.read() {
+//!         println!("{:?}", ev);
+//!     }
+//! }
+//! ```
+//!
+//! ### Decoding and Demultiplexing Messages
+//!
+//! With the `codecs` feature, bytes read from a serial port can be split into frames by adding `DecoderPlugin` with a `Decoder` for the label.
+//! Decoded frames are sent as `SerialFrameEvent`.
+//!
+//! Many firmwares interleave several kinds of messages in one stream. If the decoder tags each frame with a message
+//! type (like `TaggedLineDecoder`, which uses the first token of each line), you can register a type implementing
+//! `SerialMessage` for each tag and receive it as `SerialMessageEvent`.
+//!
+//! ```rust,ignore
+//! use bevy::prelude::*;
+//! use bevy_serial::{
+//!     DecoderPlugin, SerialAppExt, SerialMessage, SerialMessageEvent, SerialPlugin, TaggedLineDecoder,
+//! };
+//!
+//! // parsed from lines like "IMU 0.01 0.02 0.98"
+//! struct ImuFrame([f32; 3]);
+//!
+//! impl SerialMessage for ImuFrame {
+//!     const TAG: &'static str = "IMU";
+//!
+//!     fn from_payload(payload: &[u8]) -> Option<Self> {
+//!         let s = std::str::from_utf8(payload).ok()?;
+//!         let v: Vec<f32> = s.split(' ').filter_map(|t| t.parse().ok()).collect();
+//!         Some(ImuFrame([*v.get(0)?, *v.get(1)?, *v.get(2)?]))
+//!     }
+//! }
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(MinimalPlugins)
+//!         .add_plugins(SerialPlugin::new("COM5", 115200))
+//!         .add_plugins(DecoderPlugin::new("COM5", TaggedLineDecoder::default()))
+//!         .add_serial_message::<TaggedLineDecoder, ImuFrame>()
+//!         .add_systems(Update, read_imu)
+//!         .run();
+//! }
+//!
+//! fn read_imu(mut ev_imu: EventReader<SerialMessageEvent<ImuFrame>>) {
+//!     for SerialMessageEvent(label, imu) in ev_imu.read() {
+//!         println!("acceleration from {}: {:?}", label, imu.0);
+//!     }
+//! }
+//! ```
+//!
+//! ### Decoding on the Task Pool
+//!
+//! For CPU-heavy decoders (e.g. checking CRC32 over big frames, or decoding protobuf), call `DecoderPlugin::on_task_pool`
+//! to decode on Bevy's `ComputeTaskPool`, which keeps the time spent in `PreUpdate` bounded.
+//! The frames are sent as `SerialFrameEvent` in the next update, in the order the bytes were read.
+//!
+//! ```rust,ignore
+//! use bevy::prelude::*;
+//! use bevy_serial::{DecoderPlugin, LineDecoder, SerialPlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(MinimalPlugins)
+//!         .add_plugins(SerialPlugin::new("COM5", 921600))
+//!         .add_plugins(DecoderPlugin::new("COM5", LineDecoder::default()).on_task_pool())
+//!         .run();
+//! }
+//! ```
+//!
+//! ### Frames for Fixed Timestep Systems
+//!
+//! For physics-driven games, add `FixedFramePlugin` (`codecs` feature) after `DecoderPlugin` to queue decoded frames in `FixedFrameQueue`.
+//! Frames decoded in each update are queued as one batch in order, and a fixed timestep system takes one batch per tick.
+//!
+//! ```rust,ignore
+//! use bevy::core::FixedTimestep;
+//! use bevy::prelude::*;
+//! use bevy_serial::{DecoderPlugin, FixedFramePlugin, FixedFrameQueue, LineDecoder, SerialPlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(MinimalPlugins)
+//!         .add_plugins(SerialPlugin::new("COM5", 115200))
+//!         .add_plugins(DecoderPlugin::new("COM5", LineDecoder::default()))
+//!         .add_plugins(FixedFramePlugin::<Vec<u8>>::default())
+//!         .add_system_set(
+//!             SystemSet::new()
+//!                 .run_if(FixedTimestep::step(1.0 / 60.0))
+//!                 .with_system(physics),
+//!         )
+//!         .run();
+//! }
+//!
+//! fn physics(mut queue: ResMut<FixedFrameQueue<Vec<u8>>>) {
+//!     for (label, line) in queue.next_batch() {
+//!         println!("{}: {}", label, String::from_utf8_lossy(&line));
+//!     }
+//! }
+//! ```
+//!
+//! ### Recent Frames for Late Systems
+//!
+//! Add `SerialHistoryPlugin` (`codecs` feature) after `DecoderPlugin` to keep the last frames of each port in `SerialHistory`,
+//! so systems added at runtime or panels opened later can show recent frames without having read the events from the start.
+//!
+//! ```rust,ignore
+//! use bevy::prelude::*;
+//! use bevy_serial::{DecoderPlugin, LineDecoder, SerialHistory, SerialHistoryPlugin, SerialPlugin};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(MinimalPlugins)
+//!         .add_plugins(SerialPlugin::new("COM5", 115200))
+//!         .add_plugins(DecoderPlugin::new("COM5", LineDecoder::default()))
+//!         // keep the last 100 lines of each port
+//!         .add_plugins(SerialHistoryPlugin::<Vec<u8>>::new(100))
+//!         .add_systems(Update, show_recent_lines)
+//!         .run();
+//! }
+//!
+//! fn show_recent_lines(history: Res<SerialHistory<Vec<u8>>>) {
+//!     for line in history.frames("COM5") {
+//!         println!("{}", String::from_utf8_lossy(line));
+//!     }
+//! }
+//! ```
+//!
+//! ### IMU Eval Boards
+//!
+//! `BhiFifoDecoder` (`codecs` feature) decodes the FIFO stream of Bosch BHI260/BHI360 sensor hubs into `BhiSample`s with timestamps.
+//! 3-axis accelerometer, gyroscope and magnetometer samples are decoded by default, and other virtual sensors can be added
+//! with their payload sizes. ST's MEMS Studio streaming protocol is not publicly documented, so it is not supported.
+//!
+//! ```rust,ignore
+//! use bevy::prelude::*;
+//! use bevy_serial::{BhiFifoDecoder, BhiSample, DecoderPlugin, SerialFrameEvent};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(MinimalPlugins)
+//!         .add_plugins(SerialPlugin::new("COM5", 115200))
+//!         .add_plugins(DecoderPlugin::new("COM5", BhiFifoDecoder::default()))
+//!         .add_systems(Update, read_accel)
+//!         .run();
+//! }
+//!
+//! fn read_accel(mut ev_sample: EventReader<SerialFrameEvent<BhiSample>>) {
+//!     for SerialFrameEvent(_, sample) in ev_sample.read() {
+//!         if sample.sensor_id == BhiSample::ACCELEROMETER {
+//!             println!("{:.3}s: {:?}", sample.seconds(), sample.xyz());
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! ### u-blox UBX Messages
+//!
+//! `UbxDecoder` (`codecs` feature) splits the stream of u-blox GNSS modules into `UbxFrame`s, checking the sync chars and checksum. Frames are
+//! tagged by message name, so NAV-PVT, NAV-POSLLH, NAV-VELNED, ACK-ACK and ACK-NAK can be received as typed events with
+//! `add_serial_message`. `UbxFrame::encode` builds messages to send, e.g. CFG messages.
+//!
+//! ```rust,ignore
+//! use bevy::prelude::*;
+//! use bevy_serial::{DecoderPlugin, NavPvt, SerialAppExt, SerialMessageEvent, UbxDecoder};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(MinimalPlugins)
+//!         .add_plugins(SerialPlugin::new("COM5", 38400))
+//!         .add_plugins(DecoderPlugin::new("COM5", UbxDecoder::default()))
+//!         .add_serial_message::<UbxDecoder, NavPvt>()
+//!         .add_systems(Update, read_position)
+//!         .run();
+//! }
+//!
+//! fn read_position(mut ev_pvt: EventReader<SerialMessageEvent<NavPvt>>) {
+//!     for SerialMessageEvent(_, pvt) in ev_pvt.read() {
+//!         if pvt.has_fix() {
+//!             println!("position: {:?}", pvt.lat_lon_deg());
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! ### RC Receivers
+//!
+//! `SbusDecoder` and `CrsfDecoder` (`codecs` feature) decode the channels of SBUS and CRSF (Crossfire / ExpressLRS) RC receivers into
+//! `RcChannels`, and `RcChannels::axis` scales a channel to -1.0 to 1.0 to use it like a gamepad axis. Both protocols use
+//! non-standard baud rates, so open the port with `SerialSetting::sbus` or `SerialSetting::crsf`. The SBUS signal is
+//! inverted, which needs an inverter in hardware for most USB serial adapters.
+//!
+//! ```rust,ignore
+//! use bevy::prelude::*;
+//! use bevy_serial::{DecoderPlugin, RcChannels, SbusDecoder, SerialFrameEvent, SerialSetting};
+//!
+//! fn main() {
+//!     App::new()
+//!         .add_plugins(MinimalPlugins)
+//!         .add_plugins(SerialPlugin {
+//!             settings: vec![SerialSetting::sbus("COM5")],
+//!         })
+//!         .add_plugins(DecoderPlugin::new("COM5", SbusDecoder::new()))
+//!         .add_systems(Update, read_sticks)
+//!         .run();
+//! }
+//!
+//! fn read_sticks(mut ev_rc: EventReader<SerialFrameEvent<RcChannels>>) {
+//!     for SerialFrameEvent(_, rc) in ev_rc.read() {
+//!         if !rc.failsafe {
+//!             println!("roll {:.2} pitch {:.2}", rc.axis(0), rc.axis(1));
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! ### Message Schemas for Firmware
+//!
+//! `MessageSchema` (`codegen` feature) describes a binary message sent by a device: sync bytes, fixed-size fields in little
+//! endian without padding and a checksum byte (`Checksum::Xor` or `Checksum::Crc8DvbS2`). `to_arduino` generates a C
+//! header with a packed struct of the fields and functions to frame and send it, and `decoder` returns a `MessageDecoder`
+//! whose frames are the payloads with a valid checksum. Keep the schema in one place and regenerate the header when it
+//! changes, so the firmware and the app can't disagree on the layout.
+//!
+//! ```rust,ignore
+//! use bevy::prelude::*;
+//! use bevy_serial::{DecoderPlugin, FieldType, MessageSchema, SerialFrameEvent};
+//!
+//! fn schema() -> MessageSchema {
+//!     MessageSchema::new("ImuSample", &[0xAA, 0x55])
+//!         .field("timestamp_ms", FieldType::U32)
+//!         .field("ax", FieldType::F32)
+//!         .field("ay", FieldType::F32)
+//!         .field("az", FieldType::F32)
+//! }
+//!
+//! fn main() {
+//!     // e.g. from a build script or a `--gen` option: `imu_sample_write(Serial, sample)` in the sketch
+//!     std::fs::write("firmware/imu_sample.h", schema().to_arduino()).unwrap();
+//!
+//!     App::new()
+//!         .add_plugins(MinimalPlugins)
+//!         .add_plugins(SerialPlugin::new("COM5", 115200))
+//!         .add_plugins(DecoderPlugin::new("COM5", schema().decoder()))
+//!         .add_systems(Update, read_samples)
+//!         .run();
+//! }
+//!
+//! fn read_samples(mut ev_frame: EventReader<SerialFrameEvent<Vec<u8>>>) {
+//!     let offset = schema().offset("ax").unwrap();
+//!     for SerialFrameEvent(_, payload) in ev_frame.read() {
+//!         let ax = f32::from_le_bytes(payload[offset..offset + 4].try_into().unwrap());
+//!         println!("ax: {}", ax);
+//!     }
+//! }
+//! ```
+//!
+//! ### Schema Handshake
+//!
+//! `SchemaHandshakePlugin` (`codegen` feature) checks that the device sends messages of the schema each time the port is
+//! opened, so a firmware update changing the layout is caught instead of misparsed. It writes `SCHEMA_QUERY` (the line
+//! `?schema`), and the firmware answers with `<name>_write_schema` from the generated header, which sends the name and
+//! `MessageSchema::fingerprint`. A different fingerprint, or no answer within the timeout (2 seconds by default), sends
+//! `SchemaMismatchEvent` and closes the port unless `keep_open` is set. `SchemaHandshakes::is_matched` tells whether frames
+//! of a port can be trusted.
+//!
+//! ```rust,ignore
+//! use bevy::prelude::*;
+//! use bevy_serial::{DecoderPlugin, SchemaHandshakePlugin, SchemaMismatchEvent};
+//!
+//! fn main() {
+//!     let schema = imu_sample_schema();
+//!     App::new()
+//!         .add_plugins(MinimalPlugins)
+//!         .add_plugins(SerialPlugin::new("COM5", 115200))
+//!         .add_plugins(DecoderPlugin::new("COM5", schema.decoder()))
+//!         .add_plugins(SchemaHandshakePlugin::new("COM5", &schema))
+//!         .add_systems(Update, report_mismatch)
+//!         .run();
+//! }
+//!
+//! fn report_mismatch(mut ev_mismatch: EventReader<SchemaMismatchEvent>) {
+//!     for e in ev_mismatch.read() {
+//!         match e.received {
+//!             Some(received) => println!("{} has firmware of schema {:08x}, update it", e.label, received),
+//!             None => println!("{} doesn't answer the schema query", e.label),
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! ### Windows Port Names
+//!
+//! `COM10` or above are opened with the `\\.\` prefix automatically, so you can use the same port name shown in
+//! Device Manager. You can also create a setting from the friendly name of the device.
+//!
+//! ```rust,no_run
+//! use bevy_serial::{SerialPlugin, SerialSetting};
+//!
+//! let setting = SerialSetting::from_friendly_name("USB Serial Device (COM12)").expect("port not found");
+//! let plugin = SerialPlugin { settings: vec![setting] };
+//! ```
+//!
+//! ### Enumerating Ports
+//!
+//! `enumerate_ports` lists available serial ports with USB information and platform metadata (driver name, physical
+//! location and interface number), which helps to tell apart the ports of a multi-port adapter.
+//!
+//! ```rust
+//! use bevy_serial::enumerate_ports;
+//!
+//! for port in enumerate_ports() {
+//!     println!("{}: driver = {:?}, interface = {:?}", port.port_name, port.driver, port.interface_number);
+//! }
+//! ```
+//!
+//! ### Choosing Ports by Filter
+//!
+//! With the `enumeration` feature, instead of a fixed port name, you can set `PortFilter` to `SerialSetting::filter` to open the first enumerated port
+//! matching VID/PID, serial number (regular expression), manufacturer and interface number.
+//! With `SerialHotplugPlugin` (included in `SerialPlugins`), if no port matches when the app starts, the port is opened
+//! once a matching device is plugged in, and an unplugged port is closed and opened again wherever the device is plugged
+//! back, under the same label.
+//!
+//! ```rust,ignore
+//! use bevy_serial::{PortFilter, SerialPlugin, SerialSetting};
+//!
+//! let plugin = SerialPlugin {
+//!     settings: vec![SerialSetting {
+//!         label: Some("imu".to_string()),
+//!         filter: Some(PortFilter::new().vid_pid(0x0403, 0x6001).serial_number("^A9")),
+//!         ..Default::default()
+//!     }],
+//! };
+//! ```
+//!
+//! ### Flashing Firmware
+//!
+//! With the `flash` feature, `FlashPlugin` can flash firmware to the attached board via Arduino (stk500v1) or ESP32
+//! (esptool) bootloaders, with the progress sent as `FlashProgressEvent`. The port must not be opened by `SerialPlugin`
+//! while flashing.
+//!
+//! ```rust,ignore
+//! use bevy::prelude::*;
+//! use bevy_serial::{parse_intel_hex, FlashProtocol, FlashRequestEvent};
+//!
+//! fn flash_arduino(mut ev_flash: EventWriter<FlashRequestEvent>) {
+//!     // ATmega328P has 32 KiB of flash
+//!     let (address, image) = parse_intel_hex(include_str!("firmware.hex"), 32 * 1024).unwrap();
+//!     ev_flash.send(FlashRequestEvent {
+//!         port_name: "COM5".to_string(),
+//!         baud_rate: 115200,
+//!         protocol: FlashProtocol::Stk500v1 { page_size: 128 },
+//!         address,
+//!         image,
+//!     });
+//! }
+//! ```
+//!
+//! ### File Transfer
+//!
+//! With the `transfer` feature, `FileTransferPlugin` can send and receive files with XMODEM, XMODEM-1K or YMODEM, with
+//! the progress sent as `TransferProgressEvent` and the file received as `TransferResultEvent`. Blocks are checked by
+//! CRC-16, or by checksum if the receiver asks for it. The port must not be opened by `SerialPlugin` during the transfer.
+//!
+//! ```rust,ignore
+//! use bevy::prelude::*;
+//! use bevy_serial::{FileSendRequestEvent, TransferProtocol, TransferredFile};
+//!
+//! fn send_log(mut ev_send: EventWriter<FileSendRequestEvent>) {
+//!     ev_send.send(FileSendRequestEvent {
+//!         port_name: "COM5".to_string(),
+//!         baud_rate: 115200,
+//!         protocol: TransferProtocol::Ymodem,
+//!         file: TransferredFile {
+//!             name: Some("log.csv".to_string()),
+//!             data: std::fs::read("log.csv").unwrap(),
+//!         },
+//!     });
+//! }
+//! ```
+//!
+//! ### Fault Injection for Tests
+//!
+//! With the `testing` feature, `MockPortPlugin` adds a mock port which sends the bytes fed to `MockPorts` as `SerialReadEvent`,
+//! one chunk per update. `FaultInjection` corrupts bytes, truncates chunks and delays them at random with a fixed seed,
+//! so decoders and parsers can be fuzz-tested with `App::update` in normal tests. The tests of this crate fuzz
+//! `LineDecoder` and `UbxDecoder` this way, run them with `cargo test --features testing,codecs`.
+//!
+//! ```rust,ignore
+//! use bevy::prelude::*;
+//! use bevy_serial::{DecoderPlugin, FaultInjection, LineDecoder, MockPortPlugin, MockPorts, SerialPlugin};
+//!
+//! #[test]
+//! fn lines_survive_corruption() {
+//!     let mut app = App::new();
+//!     app.add_plugins(MinimalPlugins)
+//!         .add_plugins(SerialPlugin { settings: vec![] })
+//!         .add_plugins(MockPortPlugin::new("mock").with_faults(FaultInjection {
+//!             corrupt: 0.01,
+//!             truncate: 0.1,
+//!             delay: 0.1,
+//!             ..Default::default()
+//!         }))
+//!         .add_plugins(DecoderPlugin::new("mock", LineDecoder::default()));
+//!     app.world
+//!         .get_resource_mut::<MockPorts>()
+//!         .unwrap()
+//!         .feed_chunks("mock", &b"0.01 0.02 0.98\n".repeat(1000), 7);
+//!     while app.world.get_resource::<MockPorts>().unwrap().pending("mock") > 0 {
+//!         app.update();
+//!     }
+//! }
+//! ```
+//!
+//! ## Supported Versions
+//!
+//! | bevy | bevy_serial | feature              |
+//! | ---- | ----------- | -------------------- |
+//! | 0.13 | 0.3         | `bevy_013` (default) |
+//! | 0.6  | 0.3         | `bevy_06`            |
+//! | 0.6  | 0.2         |                      |
+//! | 0.5  | 0.1         |                      |
+//!
+//! The crate is written against Bevy 0.13, which the examples and the snippets above use.
+//! For Bevy 0.6, disable the default features and enable `bevy_06`:
+//!
+//! ```toml
+//! [dependencies]
+//! bevy_serial = { version = "0.3", default-features = false, features = ["bevy_06", "codecs"] }
+//! ```
+//!
+//! The API of this crate is the same with `bevy_06`, while the Bevy API around it is the one of 0.6:
+//! events and resources need no derives, `SerialSystem` is a `SystemLabel`, the systems run in `CoreStage::PreUpdate`
+//! and `CoreStage::PostUpdate`, and `SerialPlugins` is configured with `App::add_plugins_with`.
+//!
+//! ## License
+//!
+//! Dual-licensed under either
+//!
+//! - MIT
+//! - Apache 2.0
+
+#[cfg(feature = "bevy_06")]
+extern crate bevy_06 as bevy;
+
+mod backlog;
+#[cfg(any(feature = "flash", feature = "transfer"))]
+mod blocking;
+mod breaker;
+mod clock;
+#[cfg(feature = "codecs")]
+mod codec;
+#[cfg(feature = "codegen")]
+mod codegen;
+mod compat;
+#[cfg(feature = "codecs")]
+mod demux;
+mod escape;
+mod failover;
+#[cfg(feature = "enumeration")]
+mod filter;
+#[cfg(feature = "codecs")]
+mod fixed;
+#[cfg(feature = "flash")]
+mod flash;
+mod group;
+#[cfg(feature = "codegen")]
+mod handshake;
+mod hex;
+#[cfg(feature = "codecs")]
+mod history;
+mod hook;
+#[cfg(feature = "codecs")]
+mod imu;
+mod info;
+mod latency;
+mod lease;
+#[cfg(feature = "testing")]
+mod mock;
+#[cfg(feature = "overlay")]
+mod overlay;
+mod overrun;
+mod port;
+#[cfg(feature = "codecs")]
+mod rc;
+mod reconnect;
+mod session;
+mod thread;
+#[cfg(feature = "transfer")]
+mod transfer;
+mod typed;
+#[cfg(feature = "codecs")]
+mod ubx;
+
+pub use backlog::{BacklogPolicy, BacklogSetting, PortBacklog, SerialBacklog};
+pub use breaker::{CircuitBreaker, SerialBreakerEvent};
+pub use clock::SerialClock;
+#[cfg(feature = "codecs")]
+pub use codec::{Decoder, DecoderPlugin, LineDecoder, SerialFrameEvent};
+#[cfg(feature = "codegen")]
+pub use codegen::{Checksum, FieldType, MessageDecoder, MessageSchema};
+#[cfg(feature = "codecs")]
+pub use demux::{SerialMessage, SerialMessageEvent, TaggedFrame, TaggedLine, TaggedLineDecoder};
+pub use escape::EscapeCodec;
+pub use failover::SerialFailoverEvent;
+#[cfg(feature = "enumeration")]
+pub use filter::{PortFilter, SerialHotplugPlugin};
+#[cfg(feature = "codecs")]
+pub use fixed::{FixedFramePlugin, FixedFrameQueue, FrameBatch};
+#[cfg(feature = "flash")]
+pub use flash::{
+    parse_intel_hex, FlashPlugin, FlashProgressEvent, FlashProtocol, FlashRequestEvent,
+    FlashResultEvent,
+};
+pub use group::SerialGroups;
+#[cfg(feature = "codegen")]
+pub use handshake::{
+    HandshakeState, SchemaHandshakePlugin, SchemaHandshakes, SchemaMismatchEvent, SCHEMA_QUERY,
+};
+pub use hex::{decode_hex, encode_hex, ParseHexError};
+#[cfg(feature = "codecs")]
+pub use history::{SerialHistory, SerialHistoryPlugin};
+pub use hook::ReadHook;
+#[cfg(feature = "codecs")]
+pub use imu::{BhiFifoDecoder, BhiSample};
+pub use info::{SerialInfoPlugin, SerialPortInfo, SerialPortState, SerialSettingActual};
+pub use latency::{LatencyHistogram, PortLatency, SerialLatency, SerialLatencyPlugin};
+pub use lease::{LeasePolicy, SerialLeaseRejectedEvent, SerialLeases};
+pub use mio_serial::{DataBits, FlowControl, Parity, StopBits};
+pub use mio_serial::{SerialPortType, UsbPortInfo};
+#[cfg(feature = "testing")]
+pub use mock::{FaultInjection, MockPortPlugin, MockPorts};
+#[cfg(feature = "overlay")]
+pub use overlay::SerialOverlayPlugin;
+pub use overrun::SerialOverrunEvent;
+pub use port::{
+    enumerate_ports, normalize_port_name, port_name_from_friendly_name, SerialPortDescriptor,
+};
+#[cfg(feature = "codecs")]
+pub use rc::{CrsfDecoder, RcChannels, SbusDecoder};
+pub use session::{DeviceSession, SerialSession, SessionEvent, SessionPlugin, Transition};
+pub use thread::{IoThread, ThreadPriority};
+
+use thread::{PortOutput, PortThread, WriteJob};
+#[cfg(feature = "transfer")]
+pub use transfer::{
+    FileReceiveRequestEvent, FileSendRequestEvent, FileTransferPlugin, TransferProgressEvent,
+    TransferProtocol, TransferResultEvent, TransferredFile,
+};
+pub use typed::{PortReadEvent, PortWriteEvent, SerialLabel, SerialPortPlugin};
+#[cfg(feature = "codecs")]
+pub use ubx::{AckAck, AckNak, NavPosllh, NavPvt, NavVelned, UbxDecoder, UbxFrame};
+
+use reconnect::ReconnectWrites;
+
+use compat::*;
+
+use bevy::app::{App, Plugin, PluginGroup, PluginGroupBuilder};
+use bevy::ecs::entity::Entity;
+use bevy::ecs::system::{Res, ResMut, SystemParam};
+use bevy::log::{debug_span, error, info, warn};
+use bevy::tasks::IoTaskPool;
+use mio::{Interest, Poll, Token};
+use mio_serial::{SerialPort, SerialStream};
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, ErrorKind, Read, Write};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+/// Plugin that can be added to Bevy
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SerialPlugin {
+    pub settings: Vec<SerialSetting>,
+}
+
+impl SerialPlugin {
+    pub fn new(port_name: &str, baud_rate: u32) -> Self {
+        Self {
+            settings: vec![SerialSetting {
+                port_name: port_name.to_string(),
+                baud_rate,
+                ..Default::default()
+            }],
+        }
+    }
+}
+
+/// Plugin group bundling the plugins of this crate:
+///
+/// - `SerialPlugin`: reading and writing serial ports
+/// - `SerialInfoPlugin`: the `SerialPortInfo` resource
+/// - `SerialHotplugPlugin` (`enumeration`): opening ports with a filter when they are plugged in
+/// - `SerialOverlayPlugin` (`overlay`): the overlay of port states and rates, drawn with `overlay_font`
+///
+/// Each plugin can be disabled via `PluginGroupBuilder::disable`, or `App::add_plugins_with` in Bevy 0.6.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SerialPlugins {
+    pub settings: Vec<SerialSetting>,
+    /// Path of the font asset of `SerialOverlayPlugin`
+    #[cfg(feature = "overlay")]
+    pub overlay_font: String,
+}
+
+impl SerialPlugins {
+    pub fn new(port_name: &str, baud_rate: u32) -> Self {
+        Self {
+            settings: SerialPlugin::new(port_name, baud_rate).settings,
+            #[cfg(feature = "overlay")]
+            overlay_font: "fonts/FiraMono-Medium.ttf".to_string(),
+        }
+    }
+}
+
+#[cfg(feature = "bevy_013")]
+impl PluginGroup for SerialPlugins {
+    fn build(self) -> PluginGroupBuilder {
+        let group = PluginGroupBuilder::start::<Self>()
+            .add(SerialPlugin {
+                settings: self.settings,
+            })
+            .add(SerialInfoPlugin);
+        #[cfg(feature = "enumeration")]
+        let group = group.add(SerialHotplugPlugin);
+        #[cfg(feature = "overlay")]
+        let group = group.add(SerialOverlayPlugin::new(&self.overlay_font));
+        group
+    }
+}
+
+#[cfg(feature = "bevy_06")]
+impl PluginGroup for SerialPlugins {
+    fn build(&mut self, group: &mut PluginGroupBuilder) {
+        group
+            .add(SerialPlugin {
+                settings: self.settings.clone(),
+            })
+            .add(SerialInfoPlugin);
+        #[cfg(feature = "enumeration")]
+        group.add(SerialHotplugPlugin);
+        #[cfg(feature = "overlay")]
+        group.add(SerialOverlayPlugin::new(&self.overlay_font));
+    }
+}
+
+/// Settings for users to initialize this plugin
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SerialSetting {
+    /// The intuitive name for this serial port
+    pub label: Option<String>,
+    /// The port name, usually the device path
+    pub port_name: String,
+    /// The baud rate in symbols-per-second
+    pub baud_rate: u32,
+    /// Number of bits used to represent a character sent on the line
+    pub data_bits: DataBits,
+    /// The type of signalling to use for controlling data transfer
+    pub flow_control: FlowControl,
+    /// The type of parity to use for error checking
+    pub parity: Parity,
+    /// Number of bits to use to signal the end of a character
+    pub stop_bits: StopBits,
+    /// Amount of time to wait to receive data before timing out
+    pub timeout: Duration,
+    /// Log every chunk of bytes read from and written to this port as hex
+    pub log_bytes: bool,
+    /// If set, open the first enumerated port matching this filter instead of `port_name`.
+    /// With `SerialHotplugPlugin`, ports of `SerialPlugin` and `add_serial_port` are opened when a matching port is
+    /// plugged in, and closed when unplugged to be opened again, under `label` (or `port_name` if not set).
+    #[cfg(feature = "enumeration")]
+    pub filter: Option<PortFilter>,
+    /// If set, escape bytes written and unescape bytes read with this codec
+    pub escape: Option<EscapeCodec>,
+    /// If set, send `SerialStaleEvent` when no bytes are read for this duration
+    pub stale_timeout: Option<Duration>,
+    /// If set, write bytes periodically while nothing else is written
+    pub keepalive: Option<Keepalive>,
+    /// Commands written in order after the port opens, e.g. to start streaming
+    pub on_open: Vec<Vec<u8>>,
+    /// Delay before each command in `on_open`
+    pub on_open_delay: Duration,
+    /// If set, set DTR to this level right after the port opens
+    pub dtr_on_open: Option<bool>,
+    /// If set, set RTS to this level right after the port opens
+    pub rts_on_open: Option<bool>,
+    /// Time to wait after the port opens (and DTR/RTS are set), e.g. for a board to boot.
+    /// The port is not read or written until then, without blocking the app.
+    pub open_settle: Duration,
+    /// Size of the buffer for one read system call. More bytes are read with more calls.
+    pub read_buffer_len: usize,
+    /// Sizes of the input and output buffers of the OS driver (`SetupComm`).
+    /// This is supported only on Windows and ignored on other platforms,
+    /// where ports are non-blocking so `VMIN`/`VTIME` have no effect either.
+    pub driver_buffer_size: Option<(u32, u32)>,
+    /// If set, open this port under the same label when the port is disconnected, and switch back the same way
+    pub backup: Option<String>,
+    /// If set, keep up to this many writes while the port is disconnected (or removed by `SerialPorts::remove`),
+    /// and write them when the port with the label is connected again, instead of failing them
+    pub reconnect_buffer: Option<usize>,
+    /// If set, keep the chunks read in `SerialBacklog` with this capacity and policy instead of sending `SerialReadEvent`
+    pub backlog: Option<BacklogSetting>,
+    /// If set, close the port when its read and write errors keep flooding, and optionally open it again later
+    pub circuit_breaker: Option<CircuitBreaker>,
+    /// If set, run these closures on each chunk read before it is sent or decoded (see `SerialSetting::read_hook`)
+    pub read_hook: Option<ReadHook>,
+    /// If set, read and write the port on its own thread with this priority and CPU core, instead of the threads of the app
+    pub io_thread: Option<IoThread>,
+}
+
+/// Bytes written to keep a device awake when the app has not written for the interval
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keepalive {
+    /// Bytes to write
+    pub bytes: Vec<u8>,
+    /// Write the bytes if nothing has been written for this duration
+    pub interval: Duration,
+}
+
+impl Keepalive {
+    pub fn new(bytes: &[u8], interval: Duration) -> Self {
+        Self {
+            bytes: bytes.to_vec(),
+            interval,
+        }
+    }
+}
+
+impl SerialSetting {
+    /// Add a closure which inspects, transforms or drops (by clearing) each chunk read from the port
+    /// before it is sent as `SerialReadEvent` or kept in the backlog, so decoders never see it.
+    /// Closures run in the order they were added.
+    pub fn read_hook(mut self, hook: impl FnMut(&mut Vec<u8>) + Send + 'static) -> Self {
+        self.read_hook = Some(self.read_hook.take().unwrap_or_default().then(hook));
+        self
+    }
+
+    /// Create a setting from a friendly name shown in Device Manager, like `"USB Serial Device (COM12)"`.
+    /// Returns `None` if no port matches the friendly name.
+    pub fn from_friendly_name(friendly_name: &str) -> Option<Self> {
+        port_name_from_friendly_name(friendly_name).map(|port_name| Self {
+            port_name,
+            ..Default::default()
+        })
+    }
+
+    /// Create a setting for SBUS receivers: 100000 baud, 8 data bits, even parity and 2 stop bits.
+    /// 100000 baud is a non-standard rate, so the adapter and the OS driver must support custom baud rates.
+    /// The SBUS signal is inverted, which must be handled in hardware (see `SbusDecoder`).
+    pub fn sbus(port_name: &str) -> Self {
+        Self {
+            port_name: port_name.to_string(),
+            baud_rate: 100000,
+            data_bits: DataBits::Eight,
+            parity: Parity::Even,
+            stop_bits: StopBits::Two,
+            ..Default::default()
+        }
+    }
+
+    /// Create a setting for CRSF receivers: 420000 baud, 8 data bits, no parity and 1 stop bit.
+    /// 420000 baud is a non-standard rate, so the adapter and the OS driver must support custom baud rates.
+    pub fn crsf(port_name: &str) -> Self {
+        Self {
+            port_name: port_name.to_string(),
+            baud_rate: 420000,
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for SerialSetting {
+    fn default() -> Self {
+        Self {
+            label: None,
+            port_name: "".to_string(),
+            baud_rate: 115200,
+            data_bits: DataBits::Eight,
+            flow_control: FlowControl::None,
+            parity: Parity::None,
+            stop_bits: StopBits::One,
+            timeout: Duration::from_millis(0),
+            log_bytes: false,
+            #[cfg(feature = "enumeration")]
+            filter: None,
+            escape: None,
+            stale_timeout: None,
+            keepalive: None,
+            on_open: vec![],
+            on_open_delay: Duration::from_millis(0),
+            dtr_on_open: None,
+            rts_on_open: None,
+            open_settle: Duration::from_millis(0),
+            read_buffer_len: DEFAULT_READ_BUFFER_LEN,
+            driver_buffer_size: None,
+            backup: None,
+            reconnect_buffer: None,
+            backlog: None,
+            circuit_breaker: None,
+            read_hook: None,
+            io_thread: None,
+        }
+    }
+}
+
+/// Bevy's event type to read serial port
+#[derive(Event)]
+pub struct SerialReadEvent(pub String, pub Vec<u8>);
+
+/// Bevy's event type to read serial port
+#[derive(Event)]
+pub struct SerialWriteEvent(pub String, pub Vec<u8>);
+
+/// Bevy's event type to write a hex string like `"DE AD BE EF"` to serial port
+#[derive(Event)]
+pub struct SerialWriteHexEvent(pub String, pub String);
+
+/// Identifier to correlate a `SerialWriteRequestEvent` with its `SerialWriteResultEvent`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SerialWriteId {
+    /// An entity which issued the write, e.g. the entity waiting for the response
+    Entity(Entity),
+    /// An arbitrary correlation id chosen by the user
+    Id(u64),
+}
+
+/// Bevy's event type to write serial port and get the result back as `SerialWriteResultEvent`
+#[derive(Event)]
+pub struct SerialWriteRequestEvent {
+    /// Label of the serial port to write
+    pub label: String,
+    /// Data buffer to write
+    pub buffer: Vec<u8>,
+    /// Identifier which is carried back with the result
+    pub id: SerialWriteId,
+}
+
+/// Bevy's event type to notify the completion or failure of `SerialWriteRequestEvent`
+#[derive(Event)]
+pub struct SerialWriteResultEvent {
+    /// Label of the serial port written
+    pub label: String,
+    /// Identifier given to `SerialWriteRequestEvent`
+    pub id: SerialWriteId,
+    /// Number of bytes written, or the error which stopped writing
+    pub result: io::Result<usize>,
+}
+
+/// Bevy's event type to notify that no bytes have been read from the port with the label
+/// for `SerialSetting::stale_timeout`, with the time when bytes were read last (or the port was opened).
+/// This is sent once until bytes are read again.
+#[derive(Debug, Clone, PartialEq, Eq, Event)]
+pub struct SerialStaleEvent(pub String, pub Instant);
+
+/// Bevy's event type to notify that the port with the label has been opened (or opened again, e.g. by failover),
+/// with the settings which the OS actually applied. Drivers may coerce settings like the baud rate or flow control,
+/// so they can differ from `SerialSetting`.
+#[derive(Debug, Clone, PartialEq, Eq, Event)]
+pub struct SerialOpenedEvent {
+    /// Label of the serial port
+    pub label: String,
+    /// Settings reported by the OS right after the port was opened
+    pub settings_actual: SerialSettingActual,
+}
+
+/// Bevy's event type to notify that the port with the label has been removed by `SerialPorts::remove`
+#[derive(Debug, Clone, PartialEq, Eq, Event)]
+pub struct SerialClosedEvent(pub String);
+
+/// Errors of serial ports, sent as `SerialErrorEvent` and returned by `SerialPorts::add`
+#[derive(Debug, Clone, PartialEq, Eq, Error, Event)]
+pub enum SerialError {
+    /// A write was sent to a label which no serial port has
+    #[error("Label {0} is not exist")]
+    UnknownLabel(String),
+    /// Failed to open the port with the name
+    #[error("Failed to open serial port {port_name}: {error}")]
+    Open { port_name: String, error: String },
+    /// Failed to poll serial ports
+    #[error("Failed to poll events: {0}")]
+    Poll(String),
+    /// Failed to read the port with the label
+    #[error("Failed to read serial port {label}: {error}")]
+    Read {
+        label: String,
+        kind: ErrorKind,
+        error: String,
+    },
+    /// Failed to write the port with the label
+    #[error("Failed to write serial port {label}: {error}")]
+    Write {
+        label: String,
+        kind: ErrorKind,
+        error: String,
+    },
+    /// Failed to decode the data to write to the port with the label, e.g. an invalid hex string
+    #[error("Failed to decode data for serial port {label}: {error}")]
+    Decode { label: String, error: String },
+    /// The device of the port with the label has been disconnected (a read returned no bytes)
+    #[error("Serial port {0} has been disconnected")]
+    Disconnected(String),
+}
+
+impl SerialError {
+    fn read(label: &str, e: &io::Error) -> Self {
+        SerialError::Read {
+            label: label.to_string(),
+            kind: e.kind(),
+            error: e.to_string(),
+        }
+    }
+
+    fn write(label: &str, e: &io::Error) -> Self {
+        SerialError::Write {
+            label: label.to_string(),
+            kind: e.kind(),
+            error: e.to_string(),
+        }
+    }
+
+    /// Label of the serial port, if the error is about a port which has been added
+    pub fn label(&self) -> Option<&str> {
+        match self {
+            SerialError::Read { label, .. }
+            | SerialError::Write { label, .. }
+            | SerialError::Decode { label, .. }
+            | SerialError::UnknownLabel(label)
+            | SerialError::Disconnected(label) => Some(label),
+            SerialError::Open { .. } | SerialError::Poll(_) => None,
+        }
+    }
+}
+
+/// Bevy's event type to notify errors on serial ports
+pub type SerialErrorEvent = SerialError;
+
+/// Resource to choose how writes to an unknown label are handled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Resource)]
+pub enum UnknownLabelPolicy {
+    /// Panic immediately (strict mode)
+    Panic,
+    /// Drop the write and send `SerialError::UnknownLabel`
+    #[default]
+    EmitError,
+    /// Keep up to the given number of writes per label until a port with that label is added.
+    /// Writes over the limit are handled as `EmitError`.
+    Buffer(usize),
+}
+
+/// Resource to choose how errors opening, polling, reading and writing serial ports are handled.
+/// `SerialError` is sent for every error regardless of the policy.
+/// Insert this before adding `SerialPlugin` to apply it to the ports opened by the plugin.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Resource)]
+pub enum SerialPanicPolicy {
+    /// Panic on errors opening or polling ports (including a duplicate label, or failing to create the poll),
+    /// and log the other errors (the default)
+    #[default]
+    Panic,
+    /// Log every error and keep running. Ports which failed to open are not added.
+    LogAndContinue,
+    /// Only send `SerialError` without logging
+    EmitEventOnly,
+}
+
+impl SerialPanicPolicy {
+    /// Panic or log the error based on this policy.
+    /// `fatal` errors (opening and polling) panic with `Panic`, and others are logged.
+    fn handle(&self, error: &SerialError, fatal: bool) {
+        match self {
+            SerialPanicPolicy::Panic if fatal => panic!("{}", error),
+            SerialPanicPolicy::Panic | SerialPanicPolicy::LogAndContinue => error!("{}", error),
+            SerialPanicPolicy::EmitEventOnly => {}
+        }
+    }
+}
+
+impl SerialReadEvent {
+    /// Received data buffer as a hex string like `"DE AD BE EF"`
+    pub fn to_hex(&self) -> String {
+        encode_hex(&self.1)
+    }
+}
+
+impl SerialWriteEvent {
+    /// Create a write event from a hex string like `"DE AD BE EF"`
+    pub fn from_hex(label: &str, hex: &str) -> Result<Self, ParseHexError> {
+        Ok(Self(label.to_string(), decode_hex(hex)?))
+    }
+
+    /// Data buffer to write as a hex string like `"DE AD BE EF"`
+    pub fn to_hex(&self) -> String {
+        encode_hex(&self.1)
+    }
+}
+
+/// Sets of the systems added by this crate, to order your systems relative to them
+#[derive(Debug, Clone, PartialEq, Eq, Hash, SystemSet)]
+pub enum SerialSystem {
+    /// Read serial ports and send `SerialReadEvent` (`PreUpdate`), skipped while no port is connected
+    Read,
+    /// Decode `SerialReadEvent` into `SerialFrameEvent` (`PreUpdate`)
+    Decode,
+    /// Write `SerialWriteEvent` and others to serial ports (`PostUpdate`)
+    Write,
+}
+
+/// Serial struct that is used internally for this crate
+#[derive(Debug)]
+struct SerialStreamLabeled {
+    stream: SerialStream,
+    label: String,
+    connected: bool,
+    setting: SerialSetting,
+    last_error: Option<String>,
+    /// The last chunk read ended with the escape byte
+    escape_pending: bool,
+    /// When bytes were read last
+    last_read: Instant,
+    /// `SerialStaleEvent` has been sent since bytes were read last
+    stale: bool,
+    /// When bytes were written last
+    last_write: Instant,
+    /// Commands in `on_open` not written yet
+    on_open: VecDeque<Vec<u8>>,
+    /// When the next command in `on_open` can be written
+    next_command: Instant,
+    /// Overruns counted by the driver when the port was opened, if supported
+    overrun_base: Option<u64>,
+    /// Overruns since the port was opened
+    overruns: u64,
+    /// Bytes read since the port was opened
+    bytes_read: u64,
+    /// Bytes written since the port was opened
+    bytes_written: u64,
+    /// Reading is paused because the port is settling, or the backlog is full with `BacklogPolicy::Block`
+    read_paused: bool,
+    /// When the port has settled after `open_settle`, reads and writes being held until then
+    ready_at: Instant,
+    /// Settings applied when the port was opened, until `SerialOpenedEvent` is sent
+    opened: Option<SerialSettingActual>,
+    /// I/O thread of `SerialSetting::io_thread`, started once the port is connected and stopped when the port is dropped
+    thread: Option<PortThread>,
+    /// The I/O thread failed to start, so the port is read and written by the app
+    thread_failed: bool,
+}
+
+impl SerialStreamLabeled {
+    /// Deregister the stream from the poll of its I/O thread if it has one, or else from the poll of the app
+    fn deregister(&mut self, poll: &Poll) -> io::Result<()> {
+        match &self.thread {
+            Some(thread) => thread.registry().deregister(&mut self.stream),
+            None => poll.registry().deregister(&mut self.stream),
+        }
+    }
+}
+
+/// Resource to store serial ports of the app, indexed by their tokens.
+/// Removed ports are left as `None` so that the indices of other ports don't change,
+/// until a port is added again under the same label (see `FreedSlots`).
+/// This is public only to be used by `SerialPorts`.
+#[doc(hidden)]
+#[derive(Default, Resource)]
+pub struct Serials(Vec<Arc<Mutex<Option<SerialStreamLabeled>>>>);
+
+/// Component to get an index of serial port based on the label.
+/// This is public only to be used by `SerialPorts`.
+#[doc(hidden)]
+#[derive(Resource)]
+pub struct Indices(HashMap<String, usize>);
+
+/// Resource to get the index of the slot in `Serials` freed by `SerialPorts::remove`, based on the label of the removed port.
+/// This is public only to be used by `SerialPorts`.
+#[doc(hidden)]
+#[derive(Default, Resource)]
+pub struct FreedSlots(HashMap<String, usize>);
+
+/// A write which is not written yet
+#[derive(Clone)]
+struct PendingWrite {
+    buffer: Vec<u8>,
+    id: Option<SerialWriteId>,
+}
+
+impl PendingWrite {
+    fn new(buffer: Vec<u8>, id: Option<SerialWriteId>) -> Self {
+        Self { buffer, id }
+    }
+}
+
+/// Writes to unknown labels kept by `UnknownLabelPolicy::Buffer`, and writes to ports which are settling
+#[derive(Default, Resource)]
+struct PendingWrites(HashMap<String, VecDeque<PendingWrite>>);
+
+/// Resource of the `mio::Poll` which serial ports are registered to.
+/// This is public only to be used by `SerialPorts`.
+#[doc(hidden)]
+#[derive(Resource)]
+pub struct SerialPoll(Poll);
+
+/// Resource of the `mio::Events` filled by `SerialPoll`, with a capacity for all ports.
+/// This is public only to be used by `SerialPorts`.
+#[doc(hidden)]
+#[derive(Resource)]
+pub struct PollEvents(mio::Events);
+
+/// System parameter to manage serial ports from systems
+#[derive(SystemParam)]
+pub struct SerialPorts<'w, 's> {
+    // missing if it failed to be created when `SerialPlugin` was added, see `no_poll`
+    poll: Option<Res<'w, SerialPoll>>,
+    events: ResMut<'w, PollEvents>,
+    serials: ResMut<'w, Serials>,
+    indices: ResMut<'w, Indices>,
+    freed: ResMut<'w, FreedSlots>,
+    reconnect: ResMut<'w, ReconnectWrites>,
+    #[cfg(feature = "bevy_013")]
+    ev_closed: EventWriter<'w, SerialClosedEvent>,
+    #[cfg(feature = "bevy_06")]
+    ev_closed: EventWriter<'w, 's, SerialClosedEvent>,
+    // `EventWriter` has no state since Bevy 0.7
+    #[cfg(feature = "bevy_013")]
+    marker: std::marker::PhantomData<&'s ()>,
+}
+
+impl<'w, 's> SerialPorts<'w, 's> {
+    /// Labels of the serial ports currently added
+    pub fn labels(&self) -> impl Iterator<Item = &String> {
+        self.indices.0.keys()
+    }
+
+    /// Open a serial port while the app is running, e.g. after the user has chosen it.
+    /// Returns the label of the port, or the error if it could not be opened or the label already exists.
+    pub fn add(&mut self, setting: SerialSetting) -> Result<String, SerialError> {
+        // token index is same as index of vec.
+        // a port added again takes the slot freed by its label, so closing and opening ports doesn't grow the vec
+        let expected_label = setting.label.as_ref().unwrap_or(&setting.port_name);
+        let index = self
+            .freed
+            .0
+            .get(expected_label)
+            .copied()
+            .unwrap_or(self.serials.0.len());
+        let port_name = setting.port_name.clone();
+        let poll = &self.poll.as_deref().ok_or_else(no_poll)?.0;
+        let mut serial = open_port(setting, Token(index), poll)?;
+        let label = serial.label.clone();
+        if self.indices.0.contains_key(&label) {
+            if let Err(e) = poll.registry().deregister(&mut serial.stream) {
+                warn!("Failed to deregister {} from poll: {:?}", label, e);
+            }
+            return Err(SerialError::Open {
+                port_name,
+                error: format!("Label {} already exists", label),
+            });
+        }
+
+        if index < self.serials.0.len() {
+            // the freed slot is `None`, even if the port was opened under another label by a filter
+            self.freed.0.retain(|_, freed| *freed != index);
+            let mut slot = self.serials.0[index]
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            *slot = Some(serial);
+        } else {
+            self.serials.0.push(Arc::new(Mutex::new(Some(serial))));
+        }
+        self.indices.0.insert(label.clone(), index);
+        // events can be received from all ports at once
+        self.events.0 = mio::Events::with_capacity(self.serials.0.len());
+        info!("{} has been added", label);
+        Ok(label)
+    }
+
+    /// Close the serial port with the label and send `SerialClosedEvent`,
+    /// so that the device can be opened by other programs.
+    /// Returns `false` if no serial port has the label.
+    pub fn remove(&mut self, label: &str) -> bool {
+        let index = match self.indices.0.remove(label) {
+            Some(index) => index,
+            None => return false,
+        };
+        self.freed.0.insert(label.to_string(), index);
+
+        let serial = self
+            .serials
+            .0
+            .get(index)
+            .and_then(|serial_mtx| serial_mtx.lock().ok()?.take());
+        // dropping the stream closes the OS handle
+        if let Some(mut serial) = serial {
+            // a port can only have been added with the poll
+            if let Some(poll) = &self.poll {
+                if let Err(e) = serial.deregister(&poll.0) {
+                    warn!("Failed to deregister {} from poll: {:?}", label, e);
+                }
+            }
+            // keep writes until the port is added again
+            if let Some(max_len) = serial.setting.reconnect_buffer {
+                self.reconnect.watch(label, max_len);
+            }
+        }
+        info!("{} has been removed", label);
+        self.ev_closed.send(SerialClosedEvent(label.to_string()));
+        true
+    }
+}
+
+/// Errors occurred outside of systems (e.g. opening ports), which are sent as events in the next update
+#[derive(Default, Resource)]
+struct PendingErrors(Vec<SerialError>);
+
+/// The default size of read buffer for one read system call
+const DEFAULT_READ_BUFFER_LEN: usize = 2048;
+
+/// Error of opening a port when `Poll` failed to be created, which has been handled by `SerialPanicPolicy`
+fn no_poll() -> SerialError {
+    SerialError::Poll("No poll to register serial ports, it failed to be created".to_string())
+}
+
+impl Plugin for SerialPlugin {
+    fn build(&self, app: &mut App) {
+        // without a poll no port can be opened, but the app keeps running with the other policies:
+        // the resources and events are still added, and opening ports fails with `no_poll`
+        let mut errors = PendingErrors::default();
+        match Poll::new() {
+            Ok(poll) => {
+                app.insert_resource(SerialPoll(poll));
+            }
+            Err(e) => {
+                let error = SerialError::Poll(format!("{:?}", e));
+                app.world
+                    .get_resource::<SerialPanicPolicy>()
+                    .copied()
+                    .unwrap_or_default()
+                    .handle(&error, true);
+                errors.0.push(error);
+            }
+        }
+
+        app.insert_resource(errors)
+            .insert_resource(PollEvents(mio::Events::with_capacity(1)))
+            .init_resource::<Serials>()
+            .insert_resource(Indices(HashMap::new()))
+            .init_resource::<FreedSlots>()
+            .init_resource::<UnknownLabelPolicy>()
+            .init_resource::<PendingWrites>()
+            .init_resource::<SerialPanicPolicy>()
+            .init_resource::<SerialLeases>()
+            .init_resource::<SerialBacklog>()
+            .init_resource::<SerialGroups>()
+            .init_resource::<ReconnectWrites>()
+            .add_event::<SerialReadEvent>()
+            .add_event::<SerialWriteEvent>()
+            .add_event::<SerialWriteHexEvent>()
+            .add_event::<SerialWriteRequestEvent>()
+            .add_event::<SerialWriteResultEvent>()
+            .add_event::<SerialError>()
+            .add_event::<SerialStaleEvent>()
+            .add_event::<SerialOpenedEvent>()
+            .add_event::<SerialClosedEvent>()
+            .add_event::<SerialOverrunEvent>()
+            .add_event::<SerialLeaseRejectedEvent>()
+            .add_event::<SerialFailoverEvent>()
+            .add_event::<SerialBreakerEvent>()
+            // systems servicing open ports are skipped while no port is connected,
+            // while writes are still handled (e.g. kept for `reconnect_buffer` or `UnknownLabelPolicy::Buffer`)
+            .add_systems(
+                PreUpdate,
+                read_serial
+                    .in_set(SerialSystem::Read)
+                    .run_if(any_port_connected),
+            )
+            .add_systems(PreUpdate, run_port_threads.in_set(SerialSystem::Read))
+            .add_systems(
+                PreUpdate,
+                watch_stale
+                    .after(SerialSystem::Read)
+                    .run_if(any_port_connected),
+            )
+            .add_systems(PreUpdate, failover::fail_over.after(SerialSystem::Read))
+            .add_systems(
+                PreUpdate,
+                overrun::detect_overruns
+                    .after(SerialSystem::Read)
+                    .run_if(any_port_connected),
+            )
+            .add_systems(PreUpdate, send_pending_errors)
+            .add_systems(PreUpdate, send_opened.before(SerialSystem::Read))
+            .add_systems(PreUpdate, breaker::trip_breakers.after(SerialSystem::Read))
+            .add_systems(PostUpdate, write_serial.in_set(SerialSystem::Write))
+            .add_systems(
+                PostUpdate,
+                write_on_open
+                    .before(SerialSystem::Write)
+                    .run_if(any_port_connected),
+            )
+            .add_systems(
+                PostUpdate,
+                write_keepalive
+                    .after(SerialSystem::Write)
+                    .run_if(any_port_connected),
+            );
+
+        // kept for `SerialHotplugPlugin`, which opens the ports added with a filter
+        #[cfg(feature = "enumeration")]
+        app.init_resource::<filter::FilteredPorts>();
+
+        for setting in self.settings.iter() {
+            app.add_serial_port(setting.clone());
+        }
+    }
+}
+
+/// Extension trait to add serial ports and message types to `App`
+pub trait SerialAppExt {
+    /// Open a serial port and add it to `SerialPlugin`, which must be added before.
+    /// This lets other plugins register their own ports instead of one central settings vector.
+    /// A port with `SerialSetting::filter` is opened once a matching port is plugged in, and again after it is unplugged,
+    /// by `SerialHotplugPlugin`.
+    /// Failing to open the port, or a label which already exists, is handled by `SerialPanicPolicy`.
+    fn add_serial_port(&mut self, setting: SerialSetting) -> &mut Self;
+
+    /// Define a group of ports, so that a write to the group label is written to every member.
+    /// `SerialPlugin` must be added before.
+    fn add_serial_group(&mut self, group: &str, members: &[&str]) -> &mut Self;
+
+    /// Register a message type `M` demultiplexed from frames decoded by `D`.
+    /// Frames with `M::TAG` are parsed and sent as `SerialMessageEvent<M>`.
+    /// `M` can be registered with decoders of several frame types, and registering it again for the same frame type
+    /// (with the same decoder or another one) has no effect.
+    #[cfg(feature = "codecs")]
+    fn add_serial_message<D, M>(&mut self) -> &mut Self
+    where
+        D: Decoder,
+        D::Frame: TaggedFrame,
+        M: SerialMessage;
+}
+
+impl SerialAppExt for App {
+    fn add_serial_port(&mut self, setting: SerialSetting) -> &mut Self {
+        assert!(
+            self.world.contains_resource::<Serials>(),
+            "SerialPlugin should be added before adding serial ports"
+        );
+        // ports with a filter are opened by `SerialHotplugPlugin` once a matching port is plugged in
+        #[cfg(feature = "enumeration")]
+        let setting = match &setting.filter {
+            Some(filter) => {
+                let setting = self
+                    .world
+                    .get_resource_mut::<filter::FilteredPorts>()
+                    .expect("FilteredPorts are not initialized")
+                    .watch(setting.clone());
+                if filter.find().is_empty() {
+                    info!("Waiting for a serial port matching {:?}", filter);
+                    return self;
+                }
+                setting
+            }
+            None => setting,
+        };
+        // token index is same as index of vec
+        let index = self
+            .world
+            .get_resource::<Serials>()
+            .expect("Serials are not initialized")
+            .0
+            .len();
+        let port_name = setting.port_name.clone();
+        let result = match self.world.get_resource::<SerialPoll>() {
+            Some(SerialPoll(poll)) => {
+                open_port(setting, Token(index), poll).and_then(|mut serial| {
+                    let indices = self
+                        .world
+                        .get_resource::<Indices>()
+                        .expect("Indices are not initialized");
+                    if !indices.0.contains_key(&serial.label) {
+                        return Ok(serial);
+                    }
+                    if let Err(e) = poll.registry().deregister(&mut serial.stream) {
+                        warn!("Failed to deregister {} from poll: {:?}", serial.label, e);
+                    }
+                    Err(SerialError::Open {
+                        port_name,
+                        error: format!("Label {} already exists", serial.label),
+                    })
+                })
+            }
+            None => Err(no_poll()),
+        };
+        let serial = match result {
+            Ok(serial) => serial,
+            Err(e) => {
+                let policy = self
+                    .world
+                    .get_resource::<SerialPanicPolicy>()
+                    .copied()
+                    .unwrap_or_default();
+                policy.handle(&e, true);
+                self.world
+                    .get_resource_mut::<PendingErrors>()
+                    .expect("PendingErrors are not initialized")
+                    .0
+                    .push(e);
+                return self;
+            }
+        };
+        let label = serial.label.clone();
+        let mut serials = self
+            .world
+            .get_resource_mut::<Serials>()
+            .expect("Serials are not initialized");
+        serials.0.push(Arc::new(Mutex::new(Some(serial))));
+        let num_serials = serials.0.len();
+
+        // store index of the label
+        self.world
+            .get_resource_mut::<Indices>()
+            .expect("Indices are not initialized")
+            .0
+            .insert(label, index);
+
+        // events can be received from all ports at once
+        self.insert_resource(PollEvents(mio::Events::with_capacity(num_serials)))
+    }
+
+    fn add_serial_group(&mut self, group: &str, members: &[&str]) -> &mut Self {
+        self.world
+            .get_resource_mut::<SerialGroups>()
+            .expect("SerialPlugin should be added before adding serial groups")
+            .insert(group, members);
+        self
+    }
+
+    #[cfg(feature = "codecs")]
+    fn add_serial_message<D, M>(&mut self) -> &mut Self
+    where
+        D: Decoder,
+        D::Frame: TaggedFrame,
+        M: SerialMessage,
+    {
+        demux::add_serial_message::<D, M>(self);
+        self
+    }
+}
+
+/// Open a serial port from the setting and register it to poll with the token
+fn open_port(
+    setting: SerialSetting,
+    token: Token,
+    poll: &Poll,
+) -> Result<SerialStreamLabeled, SerialError> {
+    // if filter is set, find the port to open from enumerated ports
+    #[cfg(feature = "enumeration")]
+    let setting = filter::resolve_filter(setting)?;
+
+    // create serial port builder from `serialport` crate
+    let port_name = normalize_port_name(&setting.port_name);
+    let port_builder = serialport::new(&port_name, setting.baud_rate)
+        .data_bits(setting.data_bits)
+        .flow_control(setting.flow_control)
+        .parity(setting.parity)
+        .stop_bits(setting.stop_bits)
+        .timeout(setting.timeout);
+
+    // create `mio_serial::SerailStream` from `seriaport` builder
+    let mut stream = SerialStream::open(&port_builder).map_err(|e| SerialError::Open {
+        port_name: setting.port_name.clone(),
+        error: format!("({})\n{:?}", port_name, e),
+    })?;
+
+    // set control lines as soon as possible so that boards which reset on them are not reset
+    if let Some(level) = setting.dtr_on_open {
+        stream
+            .write_data_terminal_ready(level)
+            .map_err(|e| SerialError::Open {
+                port_name: setting.port_name.clone(),
+                error: format!("Failed to set DTR : {:?}", e),
+            })?;
+    }
+    if let Some(level) = setting.rts_on_open {
+        stream
+            .write_request_to_send(level)
+            .map_err(|e| SerialError::Open {
+                port_name: setting.port_name.clone(),
+                error: format!("Failed to set RTS : {:?}", e),
+            })?;
+    }
+    if let Some(size) = setting.driver_buffer_size {
+        set_driver_buffer_size(&stream, size).map_err(|e| SerialError::Open {
+            port_name: setting.port_name.clone(),
+            error: format!("Failed to set driver buffer size : {:?}", e),
+        })?;
+    }
+    poll.registry()
+        .register(&mut stream, token, Interest::READABLE)
+        .map_err(|e| SerialError::Open {
+            port_name: setting.port_name.clone(),
+            error: format!("Failed to register stream to poll : {:?}", e),
+        })?;
+
+    // if label is set, use label as a nickname of serial
+    // if not, use `port_name` as a nickname
+    let label = if let Some(label) = &setting.label {
+        label.clone()
+    } else {
+        setting.port_name.clone()
+    };
+
+    let overrun_base = overrun::read_overruns(&stream);
+    let opened = Some(SerialSettingActual::from_port(&stream));
+    // reads and writes wait for the port to settle in the next frames, instead of blocking this one
+    let ready_at = Instant::now() + setting.open_settle;
+
+    Ok(SerialStreamLabeled {
+        stream,
+        label,
+        connected: true,
+        last_error: None,
+        escape_pending: false,
+        last_read: Instant::now(),
+        stale: false,
+        last_write: Instant::now(),
+        on_open: setting.on_open.iter().cloned().collect(),
+        next_command: ready_at + setting.on_open_delay,
+        overrun_base,
+        overruns: 0,
+        bytes_read: 0,
+        bytes_written: 0,
+        read_paused: false,
+        ready_at,
+        opened,
+        thread: None,
+        thread_failed: false,
+        setting,
+    })
+}
+
+/// Set the sizes of the input and output buffers of the driver
+#[cfg(windows)]
+fn set_driver_buffer_size(stream: &SerialStream, (input, output): (u32, u32)) -> io::Result<()> {
+    use std::os::windows::io::AsRawHandle;
+    // SAFETY: the handle is valid while the stream is alive
+    if unsafe { winapi::um::commapi::SetupComm(stream.as_raw_handle() as _, input, output) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Driver buffer sizes can't be set on this platform
+#[cfg(not(windows))]
+fn set_driver_buffer_size(_stream: &SerialStream, size: (u32, u32)) -> io::Result<()> {
+    warn!("driver_buffer_size {:?} is supported only on Windows", size);
+    Ok(())
+}
+
+/// Send errors occurred outside of systems
+fn send_pending_errors(mut pending: ResMut<PendingErrors>, mut ev_error: EventWriter<SerialError>) {
+    for e in pending.0.drain(..) {
+        ev_error.send(e);
+    }
+}
+
+/// Send `SerialOpenedEvent` for ports opened since the last update, warning about settings the OS has changed
+fn send_opened(mut ev_opened: EventWriter<SerialOpenedEvent>, serials: Res<Serials>) {
+    for serial_mtx in serials.0.iter() {
+        if let Ok(mut serial) = serial_mtx.lock() {
+            let serial = match serial.as_mut() {
+                Some(serial) => serial,
+                None => continue,
+            };
+            let settings_actual = match serial.opened.take() {
+                Some(actual) => actual,
+                None => continue,
+            };
+            if let Some(baud_rate) = settings_actual.baud_rate {
+                if baud_rate != serial.setting.baud_rate {
+                    warn!(
+                        "{} is opened at {} baud instead of {}",
+                        serial.label, baud_rate, serial.setting.baud_rate
+                    );
+                }
+            }
+            ev_opened.send(SerialOpenedEvent {
+                label: serial.label.clone(),
+                settings_actual,
+            });
+        }
+    }
+}
+
+/// Poll serial read event with `Poll` in `mio` crate.
+/// If any data has come to serial, `SerialReadEvent` is sent to the system subscribing it,
+/// or the data is pushed to `SerialBacklog` for ports with `SerialSetting::backlog`.
+#[allow(clippy::too_many_arguments)]
+fn read_serial(
+    mut ev_receive_serial: EventWriter<SerialReadEvent>,
+    mut ev_error: EventWriter<SerialError>,
+    mut poll: ResMut<SerialPoll>,
+    mut events: ResMut<PollEvents>,
+    mut backlog: ResMut<SerialBacklog>,
+    serials: Res<Serials>,
+    indices: Res<Indices>,
+    policy: Res<SerialPanicPolicy>,
+    mut latency: Option<ResMut<SerialLatency>>,
+) {
+    if !indices.0.is_empty() {
+        // poll serial read event (should timeout not to block other systems)
+        if let Err(e) = poll.0.poll(&mut events.0, Some(Duration::from_micros(1))) {
+            let error = SerialError::Poll(format!("{:?}", e));
+            policy.handle(&error, true);
+            ev_error.send(error);
+            return;
+        }
+        let polled = Instant::now();
+
+        // paused ports are read again once they have settled or their backlog has room,
+        // as no new poll event may come for them
+        let mut readable: Vec<usize> = events
+            .0
+            .iter()
+            .filter(|event| event.is_readable())
+            .map(|event| event.token().0) // token index is same as index of vec
+            .collect();
+        // ports with an I/O thread are read by it
+        for (index, serial_mtx) in serials.0.iter().enumerate() {
+            let paused = serial_mtx
+                .lock()
+                .ok()
+                .and_then(|serial| {
+                    let serial = serial.as_ref()?;
+                    Some(serial.read_paused && serial.thread.is_none())
+                })
+                .unwrap_or(false);
+            if paused && !readable.contains(&index) {
+                readable.push(index);
+            }
+        }
+
+        // send `SerialReadEvent` (or push to the backlog) with serial labels and read data buffer
+        for index in readable {
+            // get serial instance based on the token index
+            let serial_mtx = serials.0.get(index).expect("Serials are not initialized");
+
+            let (label, backlog_setting, read) = match serial_mtx.lock() {
+                Ok(mut serial) => {
+                    // the port has been removed
+                    let serial = match serial.as_mut() {
+                        Some(serial) => serial,
+                        None => continue,
+                    };
+                    // don't read ports while they settle, or while their backlog is full with `BacklogPolicy::Block`
+                    serial.read_paused =
+                        Instant::now() < serial.ready_at || backlog.is_blocked(&serial.label);
+                    if serial.read_paused {
+                        continue;
+                    }
+                    let read = read_port(serial);
+                    (serial.label.clone(), serial.setting.backlog, read)
+                }
+                Err(_) => continue,
+            };
+            match read {
+                Some(Ok(buffer)) => {
+                    if backlog_setting.is_none() {
+                        if let Some(latency) = &mut latency {
+                            latency.sent(&label, polled);
+                        }
+                    }
+                    send_chunk(
+                        label,
+                        backlog_setting,
+                        buffer,
+                        &mut backlog,
+                        &mut ev_receive_serial,
+                    );
+                }
+                Some(Err(error)) => {
+                    policy.handle(&error, false);
+                    ev_error.send(error);
+                }
+                None => {}
+            }
+        }
+    }
+}
+
+/// Read the port until it would block, then unescape the bytes and run the read hook on them.
+/// Returns the chunk, the error which stopped reading, or `None` if the port is closed or the hook dropped the chunk.
+fn read_port(serial: &mut SerialStreamLabeled) -> Option<Result<Vec<u8>, SerialError>> {
+    let _span = debug_span!("serial_port", port = %serial.label).entered();
+    if !serial.connected {
+        warn!("{} connection has closed", serial.label);
+        return None;
+    }
+    let mut buffer = vec![];
+    let mut bytes_read = 0;
+    loop {
+        // if buffer is full, maybe there is more data to read
+        if bytes_read == buffer.len() {
+            buffer.resize(bytes_read + serial.setting.read_buffer_len.max(1), 0);
+        }
+        match serial.stream.read(&mut buffer[bytes_read..]) {
+            Ok(0) => {
+                serial.last_error = Some("read connection closed".to_string());
+                serial.connected = false;
+                return Some(Err(SerialError::Disconnected(serial.label.clone())));
+            }
+            // read data successfully
+            Ok(n) => {
+                bytes_read += n;
+                serial.bytes_read += n as u64;
+                serial.last_read = Instant::now();
+                serial.stale = false;
+            }
+            // would block indicates no more data to read
+            Err(ref e) if e.kind() == ErrorKind::WouldBlock => break,
+            // if interrupted, we should continue readings
+            Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+            // other errors are fatal
+            Err(e) => {
+                serial.last_error = Some(e.to_string());
+                return Some(Err(SerialError::read(&serial.label, &e)));
+            }
+        }
+    }
+
+    buffer.truncate(bytes_read);
+    if serial.setting.log_bytes {
+        info!(
+            "read {} bytes from {}: {}",
+            buffer.len(),
+            serial.label,
+            encode_hex(&buffer)
+        );
+    }
+    if let Some(codec) = &serial.setting.escape {
+        let mut pending = serial.escape_pending;
+        buffer = codec.unescape_chunk(&buffer, &mut pending);
+        serial.escape_pending = pending;
+    }
+    // chunks cleared by the hooks are dropped
+    if let Some(hook) = &serial.setting.read_hook {
+        if !buffer.is_empty() && !hook.apply(&mut buffer) {
+            return None;
+        }
+    }
+    Some(Ok(buffer))
+}
+
+/// Push the chunk read from the port to its backlog, or send it as `SerialReadEvent` if it has none
+fn send_chunk(
+    label: String,
+    backlog_setting: Option<BacklogSetting>,
+    buffer: Vec<u8>,
+    backlog: &mut SerialBacklog,
+    ev_receive_serial: &mut EventWriter<SerialReadEvent>,
+) {
+    match backlog_setting {
+        // empty chunks are not kept
+        Some(_) if buffer.is_empty() => {}
+        Some(setting) => backlog.push(&label, setting, buffer),
+        None => {
+            ev_receive_serial.send(SerialReadEvent(label, buffer));
+        }
+    }
+}
+
+/// Start the I/O threads of connected ports with `SerialSetting::io_thread`, pause their reading while the backlog
+/// is full, and send what they have read and written like `read_serial` and `write_serial` do for the other ports.
+/// This runs while no port is connected too, so that the errors of the threads of disconnected ports are sent.
+#[allow(clippy::too_many_arguments)]
+fn run_port_threads(
+    mut ev_receive_serial: EventWriter<SerialReadEvent>,
+    mut ev_write_result: EventWriter<SerialWriteResultEvent>,
+    mut ev_error: EventWriter<SerialError>,
+    poll: Option<Res<SerialPoll>>,
+    mut backlog: ResMut<SerialBacklog>,
+    serials: Res<Serials>,
+    policy: Res<SerialPanicPolicy>,
+    mut latency: Option<ResMut<SerialLatency>>,
+) {
+    for (index, serial_mtx) in serials.0.iter().enumerate() {
+        let (label, backlog_setting, output) = match serial_mtx.lock() {
+            Ok(mut serial) => {
+                let serial = match serial.as_mut() {
+                    Some(serial) => serial,
+                    None => continue,
+                };
+                let io_thread = match serial.setting.io_thread {
+                    Some(io_thread) => io_thread,
+                    None => continue,
+                };
+                if serial.thread.is_none() && serial.connected && !serial.thread_failed {
+                    if let Some(poll) = &poll {
+                        match PortThread::start(
+                            io_thread,
+                            serial_mtx,
+                            serial,
+                            &poll.0,
+                            Token(index),
+                        ) {
+                            Ok(thread) => serial.thread = Some(thread),
+                            Err(e) => {
+                                warn!(
+                                    "Failed to start the I/O thread of {}, it is read and written by the app: {}",
+                                    serial.label, e
+                                );
+                                serial.thread_failed = true;
+                            }
+                        }
+                    }
+                }
+                let thread = match &serial.thread {
+                    Some(thread) => thread,
+                    None => continue,
+                };
+                // the thread waits for the port to settle itself
+                serial.read_paused = backlog.is_blocked(&serial.label);
+                let output: Vec<PortOutput> = thread.output().collect();
+                (serial.label.clone(), serial.setting.backlog, output)
+            }
+            Err(_) => continue,
+        };
+
+        for output in output {
+            match output {
+                PortOutput::Read(buffer, polled) => {
+                    if backlog_setting.is_none() {
+                        if let Some(latency) = &mut latency {
+                            latency.sent(&label, polled);
+                        }
+                    }
+                    send_chunk(
+                        label.clone(),
+                        backlog_setting,
+                        buffer,
+                        &mut backlog,
+                        &mut ev_receive_serial,
+                    );
+                }
+                PortOutput::Error(error) => {
+                    policy.handle(&error, false);
+                    ev_error.send(error);
+                }
+                PortOutput::Written(results) => {
+                    send_write_results(results, &policy, &mut ev_error, &mut ev_write_result);
+                }
+            }
+        }
+    }
+}
+
+/// Run condition of the systems which only service connected ports, so that an app without ports
+/// (or with all of them closed) doesn't poll every frame
+fn any_port_connected(serials: Res<Serials>) -> bool {
+    serials.0.iter().any(|serial_mtx| {
+        serial_mtx
+            .lock()
+            .ok()
+            .and_then(|serial| Some(serial.as_ref()?.connected))
+            .unwrap_or(false)
+    })
+}
+
+/// Send `SerialStaleEvent` for ports which have not read any bytes for their `stale_timeout`
+fn watch_stale(mut ev_stale: EventWriter<SerialStaleEvent>, serials: Res<Serials>) {
+    for serial_mtx in serials.0.iter() {
+        if let Ok(mut serial) = serial_mtx.lock() {
+            let serial = match serial.as_mut() {
+                Some(serial) => serial,
+                None => continue,
+            };
+            let timeout = match serial.setting.stale_timeout {
+                Some(timeout) => timeout,
+                None => continue,
+            };
+            if serial.connected && !serial.stale && serial.last_read.elapsed() >= timeout {
+                warn!("{} has not read any bytes for {:?}", serial.label, timeout);
+                serial.stale = true;
+                ev_stale.send(SerialStaleEvent(serial.label.clone(), serial.last_read));
+            }
+        }
+    }
+}
+
+/// Write bytes to serial port.
+/// The bytes are sent via `SerialWriteEvent`, `SerialWriteHexEvent` or `SerialWriteRequestEvent` with label of serial port.
+/// Writes to a group in `SerialGroups` are written to each member port.
+/// Writes to an unknown label are handled based on `UnknownLabelPolicy`, writes to a leased port based on `LeasePolicy`,
+/// and failed writes are handled based on `SerialPanicPolicy`.
+/// Results of the writes to known ports are sent after the writes to unknown labels.
+#[allow(clippy::too_many_arguments)]
+fn write_serial(
+    mut ev_write_serial: EventReader<SerialWriteEvent>,
+    mut ev_write_hex: EventReader<SerialWriteHexEvent>,
+    mut ev_write_request: EventReader<SerialWriteRequestEvent>,
+    mut ev_write_result: EventWriter<SerialWriteResultEvent>,
+    mut ev_error: EventWriter<SerialError>,
+    mut ev_lease_rejected: EventWriter<SerialLeaseRejectedEvent>,
+    policy: Res<UnknownLabelPolicy>,
+    panic_policy: Res<SerialPanicPolicy>,
+    mut pending: ResMut<PendingWrites>,
+    mut leases: ResMut<SerialLeases>,
+    groups: Res<SerialGroups>,
+    mut reconnect: ResMut<ReconnectWrites>,
+    serials: Res<Serials>,
+    indices: Res<Indices>,
+    #[cfg(feature = "bevy_06")] pool: Option<Res<IoTaskPool>>,
+) {
+    #[cfg(feature = "bevy_013")]
+    let pool = IoTaskPool::try_get();
+    #[cfg(feature = "bevy_06")]
+    let pool = pool.as_deref();
+
+    // writes queued by released leases, writes kept for ports connected again,
+    // and writes buffered for labels which have appeared since are written first
+    let mut writes = leases.take_released();
+    writes.extend(reconnect.take_reconnected(|label| {
+        indices
+            .0
+            .get(label)
+            .is_some_and(|&index| port_connection(&serials, index).0)
+    }));
+    let ready: Vec<String> = pending
+        .0
+        .keys()
+        .filter(|label| {
+            indices
+                .0
+                .get(*label)
+                .is_some_and(|&index| port_settled(&serials, index))
+        })
+        .cloned()
+        .collect();
+    for label in ready {
+        if let Some(queue) = pending.0.remove(&label) {
+            writes.extend(queue.into_iter().map(|write| (label.clone(), write)));
+        }
+    }
+
+    for SerialWriteEvent(label, buffer) in ev_write_serial.read() {
+        writes.push((label.clone(), PendingWrite::new(buffer.clone(), None)));
+    }
+
+    for SerialWriteHexEvent(label, hex) in ev_write_hex.read() {
+        match decode_hex(hex) {
+            Ok(buffer) => writes.push((label.clone(), PendingWrite::new(buffer, None))),
+            Err(e) => {
+                let error = SerialError::Decode {
+                    label: label.clone(),
+                    error: e.to_string(),
+                };
+                panic_policy.handle(&error, false);
+                ev_error.send(error);
+            }
+        }
+    }
+
+    for SerialWriteRequestEvent { label, buffer, id } in ev_write_request.read() {
+        writes.push((label.clone(), PendingWrite::new(buffer.clone(), Some(*id))));
+    }
+
+    // writes to groups are written to each member
+    let writes = groups.expand(writes, |label| indices.0.contains_key(label));
+
+    // writes to each known port in order
+    let mut port_writes: Vec<(usize, WriteJob)> = vec![];
+    for (label, write) in writes {
+        let write = match leases.admit(&label, write) {
+            Ok(Some(write)) => write,
+            // queued until the lease is released
+            Ok(None) => continue,
+            Err((holder, write)) => {
+                warn!(
+                    "Write to {} is rejected because it is leased to {:?}",
+                    label, holder
+                );
+                ev_lease_rejected.send(SerialLeaseRejectedEvent {
+                    label: label.clone(),
+                    holder,
+                    id: write.id,
+                });
+                if let Some(id) = write.id {
+                    let result = Err(lease::leased_error(&label, holder));
+                    ev_write_result.send(SerialWriteResultEvent { label, id, result });
+                }
+                continue;
+            }
+        };
+        // keep writes to disconnected (or removed) ports with `reconnect_buffer` until they are connected again
+        let (connected, max_len) = match indices.0.get(&label) {
+            Some(&index) => port_connection(&serials, index),
+            None => (false, None),
+        };
+        let write = if connected {
+            write
+        } else {
+            match reconnect.keep(&label, max_len, write) {
+                Ok(()) => continue,
+                Err(write) => write,
+            }
+        };
+
+        let result = match indices.0.get(&label) {
+            // kept in order until the port has settled after `open_settle`
+            Some(&serial_index) if !port_settled(&serials, serial_index) => {
+                pending.0.entry(label).or_default().push_back(write);
+                continue;
+            }
+            Some(&serial_index) => {
+                match port_writes
+                    .iter_mut()
+                    .find(|(index, _)| *index == serial_index)
+                {
+                    Some((_, queue)) => queue.push((label, write)),
+                    None => port_writes.push((serial_index, vec![(label, write)])),
+                }
+                continue;
+            }
+            None => match *policy {
+                UnknownLabelPolicy::Panic => panic!("Label {} is not exist", label),
+                UnknownLabelPolicy::Buffer(max_len) => {
+                    let queue = pending.0.entry(label.clone()).or_default();
+                    if queue.len() < max_len {
+                        queue.push_back(write);
+                        continue;
+                    }
+                    let error = SerialError::UnknownLabel(label.clone());
+                    panic_policy.handle(&error, false);
+                    ev_error.send(error);
+                    Err(unknown_label_error(&label))
+                }
+                UnknownLabelPolicy::EmitError => {
+                    let error = SerialError::UnknownLabel(label.clone());
+                    panic_policy.handle(&error, false);
+                    ev_error.send(error);
+                    Err(unknown_label_error(&label))
+                }
+            },
+        };
+
+        if let Some(id) = write.id {
+            ev_write_result.send(SerialWriteResultEvent { label, id, result });
+        }
+    }
+
+    let results = write_ports(&serials, port_writes, pool);
+    send_write_results(results, &panic_policy, &mut ev_error, &mut ev_write_result);
+}
+
+/// Send the errors of failed writes, and the results of the requests with their ids
+fn send_write_results(
+    results: WriteResults,
+    panic_policy: &SerialPanicPolicy,
+    ev_error: &mut EventWriter<SerialError>,
+    ev_write_result: &mut EventWriter<SerialWriteResultEvent>,
+) {
+    for (label, id, result) in results {
+        // closed connections are already warned
+        match &result {
+            Err(e) if e.kind() != ErrorKind::NotConnected => {
+                let error = SerialError::write(&label, e);
+                panic_policy.handle(&error, false);
+                ev_error.send(error);
+            }
+            _ => {}
+        }
+        // report the result of each request back with its id
+        if let Some(id) = id {
+            ev_write_result.send(SerialWriteResultEvent { label, id, result });
+        }
+    }
+}
+
+/// Results of writes with the labels and ids they were sent with
+type WriteResults = Vec<(String, Option<SerialWriteId>, io::Result<usize>)>;
+
+/// Write the buffers to each port in order, with different ports written concurrently on `IoTaskPool`
+/// so that a port which is slow to accept bytes doesn't delay the writes to the others.
+/// Ports are written one by one without the task pool. The writes to ports with an I/O thread are queued on it,
+/// and their results are sent by `run_port_threads` once they are written.
+fn write_ports(
+    serials: &Serials,
+    port_writes: Vec<(usize, WriteJob)>,
+    pool: Option<&IoTaskPool>,
+) -> WriteResults {
+    let mut pooled = vec![];
+    for (index, writes) in port_writes {
+        match queue_on_thread(serials, index, writes) {
+            Ok(()) => {}
+            // the port has no thread, or it has stopped, so the port is written here
+            Err(writes) => pooled.push((index, writes)),
+        }
+    }
+
+    let write_port = |(index, writes): (usize, WriteJob)| {
+        writes
+            .into_iter()
+            .map(|(label, write)| {
+                let result = write_buffer(serials, index, &write.buffer);
+                (label, write.id, result)
+            })
+            .collect::<WriteResults>()
+    };
+    match pool {
+        Some(pool) if pooled.len() > 1 => pool
+            .scope(|scope| {
+                for writes in pooled {
+                    scope.spawn(async move { write_port(writes) });
+                }
+            })
+            .into_iter()
+            .flatten()
+            .collect(),
+        _ => pooled.into_iter().flat_map(write_port).collect(),
+    }
+}
+
+/// Queue the writes on the I/O thread of the port at the index, or give them back if it has no running thread
+fn queue_on_thread(
+    serials: &Serials,
+    serial_index: usize,
+    writes: WriteJob,
+) -> Result<(), WriteJob> {
+    let serial = match serials
+        .0
+        .get(serial_index)
+        .map(|serial_mtx| serial_mtx.lock())
+    {
+        Some(Ok(serial)) => serial,
+        _ => return Err(writes),
+    };
+    match serial.as_ref().and_then(|serial| serial.thread.as_ref()) {
+        Some(thread) => thread.write(writes),
+        None => Err(writes),
+    }
+}
+
+/// Write the commands in `on_open` one by one with `on_open_delay` before each
+fn write_on_open(
+    mut ev_error: EventWriter<SerialError>,
+    policy: Res<SerialPanicPolicy>,
+    serials: Res<Serials>,
+) {
+    // collect commands first because `write_buffer` locks each port
+    let due: Vec<(usize, String, Vec<u8>)> = serials
+        .0
+        .iter()
+        .enumerate()
+        .filter_map(|(index, serial_mtx)| {
+            let mut serial = serial_mtx.lock().ok()?;
+            let serial = serial.as_mut()?;
+            if !serial.connected || Instant::now() < serial.next_command {
+                return None;
+            }
+            let command = serial.on_open.pop_front()?;
+            serial.next_command = Instant::now() + serial.setting.on_open_delay;
+            Some((index, serial.label.clone(), command))
+        })
+        .collect();
+
+    for (index, label, command) in due {
+        if let Err(e) = write_buffer(&serials, index, &command) {
+            let error = SerialError::write(&label, &e);
+            policy.handle(&error, false);
+            ev_error.send(error);
+        }
+    }
+}
+
+/// Write the keepalive bytes to ports which have not been written for their keepalive interval.
+/// Leased ports are skipped so that the bytes don't interleave with the transactions of the holder.
+fn write_keepalive(
+    mut ev_error: EventWriter<SerialError>,
+    policy: Res<SerialPanicPolicy>,
+    leases: Res<SerialLeases>,
+    serials: Res<Serials>,
+) {
+    // collect ports first because `write_buffer` locks each port
+    let due: Vec<(usize, String, Vec<u8>)> = serials
+        .0
+        .iter()
+        .enumerate()
+        .filter_map(|(index, serial_mtx)| {
+            let serial = serial_mtx.lock().ok()?;
+            let serial = serial.as_ref()?;
+            let keepalive = serial.setting.keepalive.as_ref()?;
+            let leased = leases.holder(&serial.label).is_some();
+            let settled = Instant::now() >= serial.ready_at;
+            let idle = serial.last_write.elapsed() >= keepalive.interval;
+            (serial.connected && settled && !leased && idle)
+                .then(|| (index, serial.label.clone(), keepalive.bytes.clone()))
+        })
+        .collect();
+
+    for (index, label, bytes) in due {
+        if let Err(e) = write_buffer(&serials, index, &bytes) {
+            let error = SerialError::write(&label, &e);
+            policy.handle(&error, false);
+            ev_error.send(error);
+        }
+    }
+}
+
+/// Whether the port at the index is connected, and its `reconnect_buffer`
+fn port_connection(serials: &Serials, serial_index: usize) -> (bool, Option<usize>) {
+    serials
+        .0
+        .get(serial_index)
+        .and_then(|serial_mtx| {
+            let serial = serial_mtx.lock().ok()?;
+            let serial = serial.as_ref()?;
+            Some((serial.connected, serial.setting.reconnect_buffer))
+        })
+        .unwrap_or((false, None))
+}
+
+/// Whether the port at the index has settled after `open_settle`, so that it can be written
+fn port_settled(serials: &Serials, serial_index: usize) -> bool {
+    serials
+        .0
+        .get(serial_index)
+        .and_then(|serial_mtx| {
+            let serial = serial_mtx.lock().ok()?;
+            Some(Instant::now() >= serial.as_ref()?.ready_at)
+        })
+        .unwrap_or(true)
+}
+
+fn unknown_label_error(label: &str) -> io::Error {
+    io::Error::new(ErrorKind::NotFound, format!("Label {} is not exist", label))
+}
+
+/// Write the entire buffer to the serial port at the index.
+/// Returns the number of bytes written (before escaping), or the error which stopped writing.
+fn write_buffer(serials: &Serials, serial_index: usize, buffer: &[u8]) -> io::Result<usize> {
+    let serial_mtx = serials
+        .0
+        .get(serial_index)
+        .expect("Serials are not initialized");
+    write_slot(serial_mtx, buffer)
+}
+
+/// Write the entire buffer to the serial port in the slot of `Serials`
+fn write_slot(serial_mtx: &Mutex<Option<SerialStreamLabeled>>, buffer: &[u8]) -> io::Result<usize> {
+    // escape the whole buffer once if the port has an escape codec
+    let len = buffer.len();
+    let escaped = serial_mtx.lock().ok().and_then(|serial| {
+        serial
+            .as_ref()?
+            .setting
+            .escape
+            .as_ref()
+            .map(|codec| codec.escape(buffer))
+    });
+    let buffer = escaped.as_deref().unwrap_or(buffer);
+
+    // write buffered data to serial
+    let mut bytes_wrote = 0;
+    loop {
+        // try to get lock of mutex and send data to event
+        if let Ok(mut serial) = serial_mtx.lock() {
+            let serial = match serial.as_mut() {
+                Some(serial) => serial,
+                None => {
+                    return Err(io::Error::new(
+                        ErrorKind::NotConnected,
+                        "serial port has been removed",
+                    ))
+                }
+            };
+            let _span = debug_span!("serial_port", port = %serial.label).entered();
+            if serial.connected {
+                // write the entire buffered data in a single system call
+                match serial.stream.write(&buffer[bytes_wrote..]) {
+                    // error if returned len is less than expected (same as `io::Write::write_all` does)
+                    Ok(n) if n < buffer.len() - bytes_wrote => {
+                        warn!("write size error {} / {}", n, buffer.len() - bytes_wrote);
+                        bytes_wrote += n;
+                        serial.bytes_written += n as u64;
+                    }
+                    // wrote queued data successfully
+                    Ok(_) => {
+                        if serial.setting.log_bytes {
+                            info!(
+                                "wrote {} bytes to {}: {}",
+                                buffer.len(),
+                                serial.label,
+                                encode_hex(buffer)
+                            );
+                        }
+                        serial.bytes_written += (buffer.len() - bytes_wrote) as u64;
+                        bytes_wrote = buffer.len();
+                        serial.last_write = Instant::now();
+                    }
+                    // would block indicates that this port is not ready so try again
+                    Err(ref e) if e.kind() == ErrorKind::WouldBlock => {}
+                    // if interrupted, we should try again
+                    Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+                    // other errors are fatal
+                    Err(e) => {
+                        serial.last_error = Some(e.to_string());
+                        return Err(e);
+                    }
+                }
+            } else {
+                warn!("{} connection has closed", serial.label);
+                return Err(io::Error::new(
+                    ErrorKind::NotConnected,
+                    format!("{} connection has closed", serial.label),
+                ));
+            }
+
+            if bytes_wrote == buffer.len() {
+                return Ok(len);
             }
         }
     }
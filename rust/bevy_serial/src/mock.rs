@@ -0,0 +1,335 @@
+use crate::compat::*;
+use crate::{SerialReadEvent, SerialSystem};
+use bevy::app::{App, Plugin};
+use bevy::ecs::system::ResMut;
+use std::collections::{HashMap, VecDeque};
+
+/// Faults injected into the chunks read from a mock port, each with its probability from 0.0 to 1.0
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FaultInjection {
+    /// Probability of flipping a random bit of each byte
+    pub corrupt: f32,
+    /// Probability of cutting each chunk at a random length, dropping the rest
+    pub truncate: f32,
+    /// Probability of holding each chunk (and the chunks after it) for 1 to `max_delay` updates
+    pub delay: f32,
+    /// Longest delay in updates
+    pub max_delay: u32,
+    /// Seed of the random generator, so a failing run can be repeated
+    pub seed: u64,
+}
+
+impl Default for FaultInjection {
+    fn default() -> Self {
+        Self {
+            corrupt: 0.0,
+            truncate: 0.0,
+            delay: 0.0,
+            max_delay: 5,
+            seed: 1,
+        }
+    }
+}
+
+/// Small xorshift generator, good enough to pick faults
+#[derive(Debug, Clone)]
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        // zero would stay zero forever
+        Self(seed.max(1))
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// Uniform in 0.0..1.0
+    fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// Uniform in 0..n
+    fn below(&mut self, n: u64) -> u64 {
+        self.next_u64() % n.max(1)
+    }
+}
+
+struct MockPort {
+    /// Chunks to be read, one per update
+    chunks: VecDeque<Vec<u8>>,
+    faults: FaultInjection,
+    rng: Rng,
+    /// Updates left before the next chunk is read
+    delay: u32,
+}
+
+impl MockPort {
+    /// Next chunk with the faults applied, or `None` if nothing is read in this update
+    fn read(&mut self) -> Option<Vec<u8>> {
+        if self.delay > 0 {
+            self.delay -= 1;
+            return None;
+        }
+        if self.chunks.is_empty() {
+            return None;
+        }
+        let faults = self.faults;
+        if faults.max_delay > 0 && self.rng.next_f32() < faults.delay {
+            // this update is the first one of the delay
+            self.delay = self.rng.below(faults.max_delay as u64) as u32;
+            return None;
+        }
+        let mut chunk = self.chunks.pop_front()?;
+        if !chunk.is_empty() && self.rng.next_f32() < faults.truncate {
+            let len = self.rng.below(chunk.len() as u64) as usize;
+            chunk.truncate(len);
+        }
+        for byte in chunk.iter_mut() {
+            if self.rng.next_f32() < faults.corrupt {
+                *byte ^= 1 << self.rng.below(8);
+            }
+        }
+        Some(chunk)
+    }
+}
+
+/// Resource of the mock ports, to feed the bytes which are read from them
+#[derive(Default, Resource)]
+pub struct MockPorts(HashMap<String, MockPort>);
+
+impl MockPorts {
+    /// Queue bytes to be read from the mock port with the label as one chunk, one chunk per update.
+    /// Returns `false` if no mock port has the label.
+    pub fn feed(&mut self, label: &str, bytes: &[u8]) -> bool {
+        match self.0.get_mut(label) {
+            Some(port) => {
+                port.chunks.push_back(bytes.to_vec());
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Queue bytes split into chunks of `chunk_len`, like a device streaming them
+    pub fn feed_chunks(&mut self, label: &str, bytes: &[u8], chunk_len: usize) -> bool {
+        bytes
+            .chunks(chunk_len.max(1))
+            .all(|chunk| self.feed(label, chunk))
+    }
+
+    /// Number of chunks not read yet
+    pub fn pending(&self, label: &str) -> usize {
+        self.0.get(label).map_or(0, |port| port.chunks.len())
+    }
+}
+
+/// Plugin adding a mock port which sends the bytes fed to `MockPorts` as `SerialReadEvent`,
+/// with random corruption, truncation and delays from `FaultInjection`, to test the robustness
+/// of decoders and parsers in a normal Bevy app. `SerialPlugin` should be added before this plugin.
+pub struct MockPortPlugin {
+    pub label: String,
+    pub faults: FaultInjection,
+}
+
+impl MockPortPlugin {
+    pub fn new(label: &str) -> Self {
+        Self {
+            label: label.to_string(),
+            faults: FaultInjection::default(),
+        }
+    }
+
+    /// Inject the faults into the chunks read
+    pub fn with_faults(mut self, faults: FaultInjection) -> Self {
+        self.faults = faults;
+        self
+    }
+}
+
+impl Plugin for MockPortPlugin {
+    fn build(&self, app: &mut App) {
+        // the reading system is shared by all mock ports
+        if !app.world.contains_resource::<MockPorts>() {
+            app.init_resource::<MockPorts>()
+                .add_systems(PreUpdate, read_mock_ports.in_set(SerialSystem::Read));
+        }
+
+        app.world
+            .get_resource_mut::<MockPorts>()
+            .expect("MockPorts are not initialized")
+            .0
+            .insert(
+                self.label.clone(),
+                MockPort {
+                    chunks: VecDeque::new(),
+                    faults: self.faults,
+                    rng: Rng::new(self.faults.seed),
+                    delay: 0,
+                },
+            );
+    }
+}
+
+/// Send the next chunk of each mock port
+fn read_mock_ports(mut ev_serial: EventWriter<SerialReadEvent>, mut ports: ResMut<MockPorts>) {
+    for (label, port) in ports.0.iter_mut() {
+        if let Some(chunk) = port.read() {
+            ev_serial.send(SerialReadEvent(label.clone(), chunk));
+        }
+    }
+}
+
+// the apps of the tests are built with the API of Bevy 0.13
+#[cfg(all(test, feature = "codecs", feature = "bevy_013"))]
+mod tests {
+    use super::*;
+    use crate::{
+        Decoder, DecoderPlugin, LineDecoder, NavPvt, SerialAppExt, SerialFrameEvent,
+        SerialMessageEvent, SerialPlugin, UbxDecoder, UbxFrame,
+    };
+    use bevy::MinimalPlugins;
+
+    /// Lines fed to the mock port
+    const LINES: usize = 1000;
+    const LINE: &[u8] = b"0.01 0.02 0.98\n";
+    /// Longest line decoded, shorter than two lines merged by a corrupted delimiter
+    const MAX_LINE: usize = 24;
+    /// NAV-PVT messages fed to the mock port
+    const MESSAGES: usize = 200;
+
+    /// Frames of type `F` decoded from the mock port, collected by `collect`
+    #[derive(Resource)]
+    struct Collected<F>(Vec<F>);
+
+    fn collect<F: Clone + Send + Sync + 'static>(
+        mut ev_frame: EventReader<SerialFrameEvent<F>>,
+        mut collected: ResMut<Collected<F>>,
+    ) {
+        collected.0.extend(
+            ev_frame
+                .read()
+                .map(|SerialFrameEvent(_, frame)| frame.clone()),
+        );
+    }
+
+    /// Number of NAV-PVT messages demultiplexed from the mock port
+    #[derive(Default, Resource)]
+    struct PvtCount(usize);
+
+    fn count_pvt(mut ev_pvt: EventReader<SerialMessageEvent<NavPvt>>, mut count: ResMut<PvtCount>) {
+        count.0 += ev_pvt.read().count();
+    }
+
+    fn faults(seed: u64) -> FaultInjection {
+        FaultInjection {
+            corrupt: 0.01,
+            truncate: 0.1,
+            delay: 0.1,
+            max_delay: 5,
+            seed,
+        }
+    }
+
+    /// App with a mock port labeled `"mock"` decoded by `decoder`, collecting its frames
+    fn app<D>(faults: FaultInjection, decoder: D) -> App
+    where
+        D: Decoder + Clone,
+        D::Frame: Clone,
+    {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugins(SerialPlugin { settings: vec![] })
+            .add_plugins(MockPortPlugin::new("mock").with_faults(faults))
+            .add_plugins(DecoderPlugin::new("mock", decoder))
+            .insert_resource(Collected::<D::Frame>(vec![]))
+            .add_systems(Update, collect::<D::Frame>);
+        app
+    }
+
+    /// Feed the bytes in chunks of `chunk_len` and update until every chunk is read and decoded
+    fn run(app: &mut App, bytes: &[u8], chunk_len: usize) {
+        assert!(app
+            .world
+            .get_resource_mut::<MockPorts>()
+            .unwrap()
+            .feed_chunks("mock", bytes, chunk_len));
+        while app
+            .world
+            .get_resource::<MockPorts>()
+            .unwrap()
+            .pending("mock")
+            > 0
+        {
+            app.update();
+        }
+    }
+
+    fn frames<F: Clone + Send + Sync + 'static>(app: &App) -> Vec<F> {
+        app.world.get_resource::<Collected<F>>().unwrap().0.clone()
+    }
+
+    fn nav_pvt(itow: u32) -> Vec<u8> {
+        let mut payload = vec![0; 92];
+        payload[..4].copy_from_slice(&itow.to_le_bytes());
+        UbxFrame::new(0x01, 0x07, &payload).encode()
+    }
+
+    #[test]
+    fn clean_lines_are_decoded_in_order() {
+        let mut app = app(FaultInjection::default(), LineDecoder::default());
+        run(&mut app, &LINE.repeat(LINES), 7);
+
+        let lines = frames::<Vec<u8>>(&app);
+        assert_eq!(lines.len(), LINES);
+        assert!(lines.iter().all(|line| line == &LINE[..LINE.len() - 1]));
+    }
+
+    #[test]
+    fn lines_survive_faults() {
+        let mut app = app(faults(7), LineDecoder::new(b'\n', MAX_LINE));
+        run(&mut app, &LINE.repeat(LINES), 7);
+
+        let lines = frames::<Vec<u8>>(&app);
+        // corrupted and truncated delimiters merge or drop lines, but never add any
+        assert!(
+            !lines.is_empty() && lines.len() <= LINES,
+            "{} lines",
+            lines.len()
+        );
+        assert!(lines.iter().all(|line| line.len() <= MAX_LINE));
+        assert!(lines.iter().any(|line| line == &LINE[..LINE.len() - 1]));
+    }
+
+    #[test]
+    fn ubx_messages_survive_faults() {
+        let mut app = app(faults(11), UbxDecoder::new(128));
+        app.init_resource::<PvtCount>()
+            .add_serial_message::<UbxDecoder, NavPvt>()
+            .add_systems(Update, count_pvt);
+        let bytes: Vec<u8> = (0..MESSAGES as u32).flat_map(nav_pvt).collect();
+        run(&mut app, &bytes, 16);
+
+        // messages with a corrupted checksum or a cut are skipped, the decoder resyncs on the next one
+        let messages = frames::<UbxFrame>(&app);
+        assert!(!messages.is_empty() && messages.len() <= MESSAGES);
+        assert!(messages.iter().all(|frame| frame.payload.len() <= 128));
+        let pvt = app.world.get_resource::<PvtCount>().unwrap().0;
+        assert!(pvt > 0 && pvt <= messages.len());
+    }
+
+    #[test]
+    fn faults_are_repeated_with_the_same_seed() {
+        let decode = |seed| {
+            let mut app = app(faults(seed), LineDecoder::default());
+            run(&mut app, &LINE.repeat(LINES), 7);
+            frames::<Vec<u8>>(&app)
+        };
+        assert_eq!(decode(3), decode(3));
+        assert_ne!(decode(3), decode(4));
+    }
+}
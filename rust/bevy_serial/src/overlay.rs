@@ -0,0 +1,134 @@
+use crate::compat::*;
+use crate::SerialPortInfo;
+use bevy::prelude::*;
+use std::collections::HashMap;
+
+/// Interval to update the rates in seconds
+const UPDATE_INTERVAL: f64 = 1.0;
+
+/// Plugin to show the connection state and RX/TX rates of each port in the bottom right corner.
+/// This reads `SerialPortInfo`, so `SerialInfoPlugin` should also be added.
+pub struct SerialOverlayPlugin {
+    /// Path of the font asset to draw the text with
+    pub font: String,
+    /// Size of the font
+    pub font_size: f32,
+}
+
+impl SerialOverlayPlugin {
+    pub fn new(font: &str) -> Self {
+        Self {
+            font: font.to_string(),
+            font_size: 14.0,
+        }
+    }
+}
+
+/// Font of the overlay given by the plugin
+#[derive(Resource)]
+struct OverlayFont {
+    path: String,
+    size: f32,
+}
+
+#[derive(Component)]
+struct SerialOverlayText;
+
+impl Plugin for SerialOverlayPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(OverlayFont {
+            path: self.font.clone(),
+            size: self.font_size,
+        })
+        .add_systems(Startup, setup_overlay)
+        .add_systems(Update, update_overlay);
+    }
+}
+
+fn setup_overlay(mut commands: Commands, asset_server: Res<AssetServer>, font: Res<OverlayFont>) {
+    let style = TextStyle {
+        font: asset_server.load(font.path.clone()),
+        font_size: font.size,
+        color: Color::WHITE,
+    };
+    #[cfg(feature = "bevy_013")]
+    commands.spawn((
+        TextBundle::from_section("", style).with_style(Style {
+            position_type: PositionType::Absolute,
+            right: Val::Px(8.0),
+            bottom: Val::Px(32.0),
+            ..Default::default()
+        }),
+        SerialOverlayText,
+    ));
+    #[cfg(feature = "bevy_06")]
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    right: Val::Px(8.0),
+                    bottom: Val::Px(32.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section("", style, Default::default()),
+            ..Default::default()
+        })
+        .insert(SerialOverlayText);
+}
+
+/// Bytes read and written at the last update
+#[derive(Default)]
+struct LastCounts {
+    time: f64,
+    bytes: HashMap<String, (u64, u64)>,
+}
+
+fn update_overlay(
+    time: Res<Time>,
+    info: Res<SerialPortInfo>,
+    mut last: Local<LastCounts>,
+    mut text: Query<&mut Text, With<SerialOverlayText>>,
+) {
+    #[cfg(feature = "bevy_013")]
+    let now = time.elapsed_seconds_f64();
+    #[cfg(feature = "bevy_06")]
+    let now = time.seconds_since_startup();
+    let elapsed = now - last.time;
+    if elapsed < UPDATE_INTERVAL {
+        return;
+    }
+
+    let mut labels: Vec<&String> = info.0.keys().collect();
+    labels.sort();
+    let lines: Vec<String> = labels
+        .into_iter()
+        .map(|label| {
+            let state = &info.0[label];
+            let (read, written) = last
+                .bytes
+                .get(label)
+                .copied()
+                .unwrap_or((state.bytes_read, state.bytes_written));
+            format!(
+                "{} {} RX {:.0} B/s TX {:.0} B/s",
+                label,
+                if state.open { "open" } else { "closed" },
+                state.bytes_read.saturating_sub(read) as f64 / elapsed,
+                state.bytes_written.saturating_sub(written) as f64 / elapsed,
+            )
+        })
+        .collect();
+
+    last.time = now;
+    last.bytes = info
+        .iter()
+        .map(|(label, state)| (label.clone(), (state.bytes_read, state.bytes_written)))
+        .collect();
+
+    for mut text in text.iter_mut() {
+        text.sections[0].value = lines.join("\n");
+    }
+}
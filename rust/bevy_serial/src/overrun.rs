@@ -0,0 +1,85 @@
+use crate::compat::*;
+use crate::Serials;
+use bevy::ecs::system::Res;
+use bevy::log::warn;
+use mio_serial::SerialStream;
+
+/// Bevy's event type to notify that the driver or the UART of the port with the label has dropped bytes,
+/// which are not the bytes dropped by this plugin.
+/// This is supported only on Linux (`TIOCGICOUNT`), and only by drivers which count overruns.
+#[derive(Debug, Clone, PartialEq, Eq, Event)]
+pub struct SerialOverrunEvent {
+    /// Label of the serial port
+    pub label: String,
+    /// Number of overruns since the last event
+    pub overruns: u64,
+    /// Number of overruns since the port was opened
+    pub total: u64,
+}
+
+/// Read the number of hardware and buffer overruns counted by the driver since it was loaded
+#[cfg(target_os = "linux")]
+pub(crate) fn read_overruns(stream: &SerialStream) -> Option<u64> {
+    use std::os::unix::io::AsRawFd;
+
+    /// `struct serial_icounter_struct` in `linux/serial.h`
+    #[repr(C)]
+    #[derive(Default)]
+    struct SerialIcounter {
+        cts: libc::c_int,
+        dsr: libc::c_int,
+        rng: libc::c_int,
+        dcd: libc::c_int,
+        rx: libc::c_int,
+        tx: libc::c_int,
+        frame: libc::c_int,
+        overrun: libc::c_int,
+        parity: libc::c_int,
+        brk: libc::c_int,
+        buf_overrun: libc::c_int,
+        reserved: [libc::c_int; 9],
+    }
+
+    let mut counter = SerialIcounter::default();
+    // SAFETY: the fd is valid while the stream is alive, and the kernel writes only `serial_icounter_struct`
+    let ret = unsafe { libc::ioctl(stream.as_raw_fd(), libc::TIOCGICOUNT, &mut counter) };
+    if ret < 0 {
+        return None;
+    }
+    Some(counter.overrun as u64 + counter.buf_overrun as u64)
+}
+
+/// Overruns can't be read on this platform
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn read_overruns(_stream: &SerialStream) -> Option<u64> {
+    None
+}
+
+/// Send `SerialOverrunEvent` for ports whose overrun counters have increased
+pub(crate) fn detect_overruns(
+    mut ev_overrun: EventWriter<SerialOverrunEvent>,
+    serials: Res<Serials>,
+) {
+    for serial_mtx in serials.0.iter() {
+        if let Ok(mut serial) = serial_mtx.lock() {
+            let serial = match serial.as_mut() {
+                Some(serial) => serial,
+                None => continue,
+            };
+            let (base, count) = match (serial.overrun_base, read_overruns(&serial.stream)) {
+                (Some(base), Some(count)) => (base, count),
+                _ => continue,
+            };
+            let total = count.saturating_sub(base);
+            if total > serial.overruns {
+                warn!("{} overrun {} times", serial.label, total - serial.overruns);
+                ev_overrun.send(SerialOverrunEvent {
+                    label: serial.label.clone(),
+                    overruns: total - serial.overruns,
+                    total,
+                });
+                serial.overruns = total;
+            }
+        }
+    }
+}
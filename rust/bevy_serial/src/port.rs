@@ -0,0 +1,140 @@
+use mio_serial::{available_ports, SerialPortType};
+
+/// Normalize a port name so that it can be opened on this platform.
+///
+/// On Windows, only `COM1` to `COM9` can be opened by their plain names, so `COM10` or above are
+/// prefixed with the device namespace `\\.\`. Names on other platforms are returned as they are.
+pub fn normalize_port_name(port_name: &str) -> String {
+    let port_name = port_name.trim();
+    if cfg!(windows) && !port_name.starts_with(r"\\") && is_com_port(port_name) {
+        format!(r"\\.\{}", port_name.to_ascii_uppercase())
+    } else {
+        port_name.to_string()
+    }
+}
+
+/// Find the port name from a friendly name shown in Device Manager, like `"USB Serial Device (COM12)"`.
+///
+/// The friendly name is first looked up among enumerated ports (which Windows reports from the
+/// registry as the product name), then the `(COMx)` suffix of the friendly name is used as a fallback.
+pub fn port_name_from_friendly_name(friendly_name: &str) -> Option<String> {
+    let friendly_name = friendly_name.trim();
+    let found = available_ports().ok().and_then(|ports| {
+        ports.into_iter().find_map(|port| match &port.port_type {
+            SerialPortType::UsbPort(info) if info.product.as_deref() == Some(friendly_name) => {
+                Some(port.port_name)
+            }
+            _ => None,
+        })
+    });
+    if found.is_some() {
+        return found;
+    }
+
+    let start = friendly_name.rfind('(')?;
+    let end = start + friendly_name[start..].find(')')?;
+    let port_name = &friendly_name[start + 1..end];
+    if is_com_port(port_name) {
+        Some(port_name.to_string())
+    } else {
+        None
+    }
+}
+
+/// Check if the name is like `COM12`
+fn is_com_port(name: &str) -> bool {
+    match (name.get(..3), name.get(3..)) {
+        (Some(prefix), Some(number)) => {
+            prefix.eq_ignore_ascii_case("COM")
+                && !number.is_empty()
+                && number.chars().all(|c| c.is_ascii_digit())
+        }
+        _ => false,
+    }
+}
+
+/// Serial port found by `enumerate_ports`, with metadata from the platform
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SerialPortDescriptor {
+    /// The port name, usually the device path
+    pub port_name: String,
+    /// The hardware device type, with VID/PID, serial number, manufacturer and product for USB ports
+    pub port_type: SerialPortType,
+    /// Name of the kernel driver, e.g. `ftdi_sio` or `cdc_acm` (Linux only)
+    pub driver: Option<String>,
+    /// Physical location of the USB interface, e.g. `1-1.2:1.0` (Linux only)
+    pub physical_location: Option<String>,
+    /// Interface number to disambiguate ports of a multi-port adapter
+    pub interface_number: Option<u8>,
+}
+
+/// Enumerate serial ports available on this system with platform metadata.
+///
+/// On Linux the metadata is read from sysfs (the same attributes udev exposes). On macOS and Windows,
+/// the interface number comes from IOKit and SetupAPI via `serialport`, and the other fields are `None`.
+pub fn enumerate_ports() -> Vec<SerialPortDescriptor> {
+    available_ports()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|port| {
+            let interface_number = match &port.port_type {
+                SerialPortType::UsbPort(info) => info.interface,
+                _ => None,
+            };
+            let mut descriptor = SerialPortDescriptor {
+                port_name: port.port_name,
+                port_type: port.port_type,
+                driver: None,
+                physical_location: None,
+                interface_number,
+            };
+            #[cfg(target_os = "linux")]
+            sysfs::fill_metadata(&mut descriptor);
+            descriptor
+        })
+        .collect()
+}
+
+#[cfg(target_os = "linux")]
+mod sysfs {
+    use super::SerialPortDescriptor;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    /// Fill driver, physical location and interface number from `/sys/class/tty/<name>/device`
+    pub(super) fn fill_metadata(descriptor: &mut SerialPortDescriptor) {
+        let name = match Path::new(&descriptor.port_name).file_name() {
+            Some(name) => name.to_owned(),
+            None => return,
+        };
+        let device = match fs::canonicalize(Path::new("/sys/class/tty").join(name).join("device")) {
+            Ok(device) => device,
+            Err(_) => return,
+        };
+
+        descriptor.driver = fs::read_link(device.join("driver"))
+            .ok()
+            .and_then(|driver| driver.file_name().map(|n| n.to_string_lossy().into_owned()));
+
+        // the USB interface is the device itself (cdc_acm) or its parent (usb-serial drivers)
+        if let Some(interface) = usb_interface(&device) {
+            descriptor.physical_location = interface
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned());
+            if descriptor.interface_number.is_none() {
+                descriptor.interface_number =
+                    fs::read_to_string(interface.join("bInterfaceNumber"))
+                        .ok()
+                        .and_then(|n| u8::from_str_radix(n.trim(), 16).ok());
+            }
+        }
+    }
+
+    fn usb_interface(device: &Path) -> Option<PathBuf> {
+        device
+            .ancestors()
+            .take(2)
+            .find(|dir| dir.join("bInterfaceNumber").exists())
+            .map(Path::to_path_buf)
+    }
+}
@@ -0,0 +1,259 @@
+use crate::codec::Decoder;
+use bevy::log::debug;
+
+/// Channel values received from an RC receiver
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RcChannels {
+    /// 16 proportional channels in 11 bits (172 to 1811, 992 at center)
+    pub channels: [u16; 16],
+    /// The receiver lost some frames from the transmitter (SBUS only)
+    pub frame_lost: bool,
+    /// The receiver is in failsafe (SBUS only, CRSF receivers stop sending channels instead)
+    pub failsafe: bool,
+}
+
+impl RcChannels {
+    /// Value of the channel (0 to 15) scaled to -1.0 to 1.0, e.g. to use as a gamepad axis
+    pub fn axis(&self, channel: usize) -> f32 {
+        let value = (self.channels[channel] as f32 - 992.0) / 819.5;
+        value.clamp(-1.0, 1.0)
+    }
+
+    /// Unpack 16 channels of 11 bits, LSB first
+    fn unpack(bytes: &[u8]) -> [u16; 16] {
+        let mut channels = [0_u16; 16];
+        let (mut bits, mut num_bits, mut bytes) = (0_u32, 0, bytes.iter());
+        for channel in channels.iter_mut() {
+            while num_bits < 11 {
+                bits |= (*bytes.next().unwrap_or(&0) as u32) << num_bits;
+                num_bits += 8;
+            }
+            *channel = (bits & 0x07FF) as u16;
+            bits >>= 11;
+            num_bits -= 11;
+        }
+        channels
+    }
+}
+
+const SBUS_HEADER: u8 = 0x0F;
+const SBUS_FRAME_LEN: usize = 25;
+
+/// Decoder for SBUS frames of RC receivers.
+///
+/// SBUS runs at 100000 baud with 8E2 (see `SerialSetting::sbus`), and the signal is inverted. Most USB serial adapters
+/// can't invert the signal, so an inverter is needed between the receiver and the adapter unless the receiver has an
+/// uninverted output.
+#[derive(Debug, Clone, Default)]
+pub struct SbusDecoder {
+    buf: Vec<u8>,
+}
+
+impl SbusDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// Footer is `0x00` for SBUS, or `0x04`, `0x14`, `0x24` and `0x34` for SBUS2
+fn is_sbus_footer(b: u8) -> bool {
+    b == 0x00 || b & 0x0F == 0x04
+}
+
+impl Decoder for SbusDecoder {
+    type Frame = RcChannels;
+
+    fn decode(&mut self, bytes: &[u8], frames: &mut Vec<Self::Frame>) {
+        self.buf.extend_from_slice(bytes);
+
+        let mut pos = 0;
+        while self.buf.len() >= pos + SBUS_FRAME_LEN {
+            let frame = &self.buf[pos..pos + SBUS_FRAME_LEN];
+            if frame[0] != SBUS_HEADER || !is_sbus_footer(frame[24]) {
+                // lost sync, so look for the next header
+                pos += 1;
+                continue;
+            }
+            frames.push(RcChannels {
+                channels: RcChannels::unpack(&frame[1..23]),
+                frame_lost: frame[23] & 0x04 != 0,
+                failsafe: frame[23] & 0x08 != 0,
+            });
+            pos += SBUS_FRAME_LEN;
+        }
+        self.buf.drain(..pos);
+    }
+}
+
+/// Sync bytes which start CRSF frames: the flight controller address and the legacy sync byte
+const CRSF_SYNC: [u8; 2] = [0xC8, 0xEE];
+const CRSF_MAX_FRAME_LEN: usize = 64;
+const CRSF_RC_CHANNELS_PACKED: u8 = 0x16;
+
+/// CRC-8/DVB-S2 (polynomial 0xD5) over type and payload
+pub(crate) fn crc8_dvb_s2(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0_u8, |crc, &b| {
+        (0..8).fold(crc ^ b, |crc, _| {
+            if crc & 0x80 != 0 {
+                (crc << 1) ^ 0xD5
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+/// Decoder for RC channels in CRSF (Crossfire / ExpressLRS) frames of RC receivers.
+///
+/// CRSF runs at 420000 baud with 8N1 (see `SerialSetting::crsf`). Frames other than RC channels (e.g. link statistics)
+/// are skipped.
+#[derive(Debug, Clone, Default)]
+pub struct CrsfDecoder {
+    buf: Vec<u8>,
+}
+
+impl CrsfDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Decoder for CrsfDecoder {
+    type Frame = RcChannels;
+
+    fn decode(&mut self, bytes: &[u8], frames: &mut Vec<Self::Frame>) {
+        self.buf.extend_from_slice(bytes);
+
+        let mut pos = 0;
+        // sync, length (type + payload + crc) and the rest of the frame
+        while self.buf.len() >= pos + 2 {
+            let len = self.buf[pos + 1] as usize;
+            if !CRSF_SYNC.contains(&self.buf[pos]) || len < 2 || len + 2 > CRSF_MAX_FRAME_LEN {
+                pos += 1;
+                continue;
+            }
+            let end = pos + 2 + len;
+            if self.buf.len() < end {
+                break;
+            }
+
+            let (body, crc) = (&self.buf[pos + 2..end - 1], self.buf[end - 1]);
+            if crc8_dvb_s2(body) != crc {
+                debug!("CRSF CRC mismatch");
+                pos += 1;
+                continue;
+            }
+            if body[0] == CRSF_RC_CHANNELS_PACKED && body.len() == 23 {
+                frames.push(RcChannels {
+                    channels: RcChannels::unpack(&body[1..]),
+                    frame_lost: false,
+                    failsafe: false,
+                });
+            }
+            pos = end;
+        }
+        self.buf.drain(..pos);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Minimum, center and maximum of the RC range, the ends of 11 bits and values around them
+    const CHANNELS: [u16; 16] = [
+        172, 992, 1811, 0, 2047, 1, 1024, 1500, 172, 992, 1811, 0, 2047, 1, 1024, 1500,
+    ];
+    /// `CHANNELS` packed in 11 bits, LSB first
+    const PACKED: [u8; 22] = [
+        0xAC, 0x00, 0xDF, 0xC4, 0x01, 0xF0, 0xFF, 0x00, 0x00, 0x90, 0xBB, 0xAC, 0x00, 0xDF, 0xC4,
+        0x01, 0xF0, 0xFF, 0x00, 0x00, 0x90, 0xBB,
+    ];
+
+    fn sbus_frame(flags: u8) -> Vec<u8> {
+        let mut frame = vec![SBUS_HEADER];
+        frame.extend_from_slice(&PACKED);
+        frame.extend_from_slice(&[flags, 0x00]);
+        frame
+    }
+
+    fn crsf_frame() -> Vec<u8> {
+        let mut frame = vec![0xC8, 0x18, CRSF_RC_CHANNELS_PACKED];
+        frame.extend_from_slice(&PACKED);
+        frame.push(0xA1);
+        frame
+    }
+
+    #[test]
+    fn crc8_dvb_s2_check_value() {
+        assert_eq!(crc8_dvb_s2(b"123456789"), 0xBC);
+    }
+
+    #[test]
+    fn unpacks_11_bit_channels() {
+        assert_eq!(RcChannels::unpack(&PACKED), CHANNELS);
+    }
+
+    #[test]
+    fn decodes_sbus_channels_and_flags() {
+        let mut bytes = vec![0x55, 0xAA];
+        bytes.extend(sbus_frame(0x00));
+        bytes.extend(sbus_frame(0x04));
+        bytes.extend(sbus_frame(0x0C));
+
+        let mut decoder = SbusDecoder::new();
+        let mut frames = vec![];
+        for chunk in bytes.chunks(7) {
+            decoder.decode(chunk, &mut frames);
+        }
+        let flags: Vec<(bool, bool)> = frames
+            .iter()
+            .map(|frame| (frame.frame_lost, frame.failsafe))
+            .collect();
+        assert_eq!(flags, [(false, false), (true, false), (true, true)]);
+        assert!(frames.iter().all(|frame| frame.channels == CHANNELS));
+        assert_eq!(frames[0].axis(0), -1.0);
+        assert_eq!(frames[0].axis(1), 0.0);
+        assert!((frames[0].axis(2) - 1.0).abs() < 1e-3);
+        // values beyond the RC range are clamped
+        assert_eq!(frames[0].axis(4), 1.0);
+    }
+
+    #[test]
+    fn decodes_crsf_rc_channels() {
+        // link statistics are skipped
+        let mut bytes = vec![0xC8, 0x0C, 0x14];
+        let stats = [0x40, 0x40, 0x64, 0x05, 0x00, 0x02, 0x01, 0x50, 0x00, 0x64];
+        bytes.extend_from_slice(&stats);
+        let mut body = vec![0x14];
+        body.extend_from_slice(&stats);
+        bytes.push(crc8_dvb_s2(&body));
+        bytes.extend(crsf_frame());
+
+        let mut decoder = CrsfDecoder::new();
+        let mut frames = vec![];
+        for chunk in bytes.chunks(5) {
+            decoder.decode(chunk, &mut frames);
+        }
+        assert_eq!(
+            frames,
+            [RcChannels {
+                channels: CHANNELS,
+                frame_lost: false,
+                failsafe: false,
+            }]
+        );
+    }
+
+    #[test]
+    fn skips_crsf_frames_with_bad_crcs() {
+        let mut corrupted = crsf_frame();
+        corrupted[10] ^= 0x01;
+        corrupted.extend(crsf_frame());
+
+        let mut frames = vec![];
+        CrsfDecoder::new().decode(&corrupted, &mut frames);
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].channels, CHANNELS);
+    }
+}
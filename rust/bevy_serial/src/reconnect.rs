@@ -0,0 +1,67 @@
+use crate::compat::*;
+use crate::PendingWrite;
+use std::collections::{HashMap, VecDeque};
+
+/// Writes kept for ports with `SerialSetting::reconnect_buffer` while they are disconnected or removed,
+/// written when a port with the label is connected again.
+/// This is public only to be used by `SerialPorts`.
+#[doc(hidden)]
+#[derive(Default, Resource)]
+pub struct ReconnectWrites(HashMap<String, ReconnectQueue>);
+
+struct ReconnectQueue {
+    max_len: usize,
+    writes: VecDeque<PendingWrite>,
+}
+
+impl ReconnectWrites {
+    /// Keep writes to the label until a port with it is added again, e.g. when its port is removed
+    pub(crate) fn watch(&mut self, label: &str, max_len: usize) {
+        self.0
+            .entry(label.to_string())
+            .or_insert_with(|| ReconnectQueue {
+                max_len,
+                writes: VecDeque::new(),
+            });
+    }
+
+    /// Keep the write to a disconnected port, with `max_len` of its setting (`None` for removed ports).
+    /// Returns the write back if writes to the label are not kept or the queue is full.
+    pub(crate) fn keep(
+        &mut self,
+        label: &str,
+        max_len: Option<usize>,
+        write: PendingWrite,
+    ) -> Result<(), PendingWrite> {
+        if let Some(max_len) = max_len {
+            self.watch(label, max_len);
+        }
+        match self.0.get_mut(label) {
+            Some(queue) if queue.writes.len() < queue.max_len => {
+                queue.writes.push_back(write);
+                Ok(())
+            }
+            _ => Err(write),
+        }
+    }
+
+    /// Take the writes kept for labels whose ports are connected again
+    pub(crate) fn take_reconnected(
+        &mut self,
+        is_connected: impl Fn(&str) -> bool,
+    ) -> Vec<(String, PendingWrite)> {
+        let labels: Vec<String> = self
+            .0
+            .keys()
+            .filter(|label| is_connected(label))
+            .cloned()
+            .collect();
+        let mut writes = vec![];
+        for label in labels {
+            if let Some(queue) = self.0.remove(&label) {
+                writes.extend(queue.writes.into_iter().map(|write| (label.clone(), write)));
+            }
+        }
+        writes
+    }
+}
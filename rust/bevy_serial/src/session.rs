@@ -0,0 +1,226 @@
+use crate::compat::*;
+use crate::{SerialReadEvent, SerialSystem, SerialWriteEvent};
+use bevy::app::{App, Plugin};
+use bevy::ecs::system::ResMut;
+use bevy::log::{info, warn};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// What a session does after an input
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Transition<S> {
+    /// Stay in the current state, keeping its deadline
+    Stay,
+    /// Write the bytes and enter the state, failing or retrying (see `DeviceSession::on_timeout`)
+    /// if it is not left within the timeout
+    Enter {
+        state: S,
+        write: Vec<u8>,
+        timeout: Option<Duration>,
+    },
+    /// The session has completed
+    Complete,
+    /// The session has failed with the reason
+    Fail(String),
+}
+
+impl<S> Transition<S> {
+    /// Enter the state without writing and without a timeout
+    pub fn to(state: S) -> Self {
+        Transition::Enter {
+            state,
+            write: vec![],
+            timeout: None,
+        }
+    }
+
+    /// Write the bytes when entering the state
+    pub fn write(mut self, bytes: &[u8]) -> Self {
+        if let Transition::Enter { write, .. } = &mut self {
+            *write = bytes.to_vec();
+        }
+        self
+    }
+
+    /// Leave the state within the timeout
+    pub fn timeout(mut self, duration: Duration) -> Self {
+        if let Transition::Enter { timeout, .. } = &mut self {
+            *timeout = Some(duration);
+        }
+        self
+    }
+}
+
+/// Multi-step exchange with a device (e.g. handshake, configure, then stream) as a state machine
+/// driven by the bytes read from the port and the timeouts of the states
+pub trait DeviceSession: Clone + Send + Sync + 'static {
+    /// States of the session, usually an enum
+    type State: fmt::Debug + Clone + PartialEq + Send + Sync + 'static;
+
+    /// First transition when the session starts or restarts
+    fn start(&mut self) -> Transition<Self::State>;
+
+    /// Transition for the bytes read in the state.
+    /// The bytes are passed as read, so a reply may be split across calls.
+    fn on_read(&mut self, state: &Self::State, bytes: &[u8]) -> Transition<Self::State>;
+
+    /// Transition when the timeout of the state expires. The session fails by default.
+    fn on_timeout(&mut self, state: &Self::State) -> Transition<Self::State> {
+        Transition::Fail(format!("timed out in {:?}", state))
+    }
+}
+
+/// Bevy's event type to notify the progress of the session `T` on the port with the label
+#[derive(Debug, Clone, PartialEq, Eq, Event)]
+pub enum SessionEvent<S> {
+    /// The session has entered the state
+    Entered { label: String, state: S },
+    /// The session has completed
+    Completed { label: String },
+    /// The session has failed in the state (`None` if it failed to start)
+    Failed {
+        label: String,
+        state: Option<S>,
+        reason: String,
+    },
+}
+
+/// Resource holding the session `T` on a port, to query its state or restart it
+#[derive(Resource)]
+pub struct SerialSession<T: DeviceSession> {
+    label: String,
+    session: T,
+    state: Option<T::State>,
+    deadline: Option<Instant>,
+    /// The session is started in the next update
+    start: bool,
+}
+
+impl<T: DeviceSession> SerialSession<T> {
+    /// Label of the serial port of the session
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    /// Current state, or `None` if the session has completed or failed
+    pub fn state(&self) -> Option<&T::State> {
+        self.state.as_ref()
+    }
+
+    /// Start the session again from `DeviceSession::start` in the next update, e.g. after the port is reopened
+    pub fn restart(&mut self) {
+        self.start = true;
+    }
+}
+
+/// Plugin to run the session `T` on the port with the label.
+/// The session starts in the first update, and `SessionEvent`s are sent as it enters each state, completes or fails.
+/// One session of each type can be added.
+pub struct SessionPlugin<T: DeviceSession> {
+    pub label: String,
+    pub session: T,
+}
+
+impl<T: DeviceSession> SessionPlugin<T> {
+    pub fn new(label: &str, session: T) -> Self {
+        Self {
+            label: label.to_string(),
+            session,
+        }
+    }
+}
+
+impl<T: DeviceSession> Plugin for SessionPlugin<T> {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(SerialSession {
+            label: self.label.clone(),
+            session: self.session.clone(),
+            state: None,
+            deadline: None,
+            start: true,
+        })
+        .add_event::<SessionEvent<T::State>>()
+        .add_systems(PreUpdate, run_session::<T>.after(SerialSystem::Read));
+    }
+}
+
+/// Start the session if requested, then apply the transitions for the bytes read and the expired timeout
+fn run_session<T: DeviceSession>(
+    mut ev_serial: EventReader<SerialReadEvent>,
+    mut ev_write: EventWriter<SerialWriteEvent>,
+    mut ev_session: EventWriter<SessionEvent<T::State>>,
+    mut session: ResMut<SerialSession<T>>,
+) {
+    let session = &mut *session;
+    if session.start {
+        session.start = false;
+        session.state = None;
+        let transition = match session.session.start() {
+            // a session which has not started has no state to stay in
+            Transition::Stay => Transition::Fail("no state to start in".to_string()),
+            transition => transition,
+        };
+        apply(session, transition, &mut ev_write, &mut ev_session);
+    }
+
+    for SerialReadEvent(label, buffer) in ev_serial.read() {
+        if *label != session.label {
+            continue;
+        }
+        let transition = match &session.state {
+            Some(state) => session.session.on_read(state, buffer),
+            None => continue,
+        };
+        apply(session, transition, &mut ev_write, &mut ev_session);
+    }
+
+    let expired = session
+        .deadline
+        .is_some_and(|deadline| Instant::now() >= deadline);
+    if expired {
+        session.deadline = None;
+        if let Some(state) = &session.state {
+            let transition = session.session.on_timeout(state);
+            apply(session, transition, &mut ev_write, &mut ev_session);
+        }
+    }
+}
+
+fn apply<T: DeviceSession>(
+    session: &mut SerialSession<T>,
+    transition: Transition<T::State>,
+    ev_write: &mut EventWriter<SerialWriteEvent>,
+    ev_session: &mut EventWriter<SessionEvent<T::State>>,
+) {
+    let label = session.label.clone();
+    match transition {
+        Transition::Stay => {}
+        Transition::Enter {
+            state,
+            write,
+            timeout,
+        } => {
+            if !write.is_empty() {
+                ev_write.send(SerialWriteEvent(label.clone(), write));
+            }
+            session.deadline = timeout.map(|timeout| Instant::now() + timeout);
+            session.state = Some(state.clone());
+            ev_session.send(SessionEvent::Entered { label, state });
+        }
+        Transition::Complete => {
+            info!("Session on {} has completed", label);
+            session.state = None;
+            session.deadline = None;
+            ev_session.send(SessionEvent::Completed { label });
+        }
+        Transition::Fail(reason) => {
+            warn!("Session on {} has failed: {}", label, reason);
+            session.deadline = None;
+            ev_session.send(SessionEvent::Failed {
+                label,
+                state: session.state.take(),
+                reason,
+            });
+        }
+    }
+}
@@ -0,0 +1,360 @@
+use crate::compat::*;
+use crate::{read_port, write_slot, PendingWrite, SerialError, SerialStreamLabeled, WriteResults};
+use bevy::log::warn;
+use mio::{Interest, Poll, Registry, Token, Waker};
+use std::io::{self, ErrorKind};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Nice value of threads with `ThreadPriority::High` on Linux (needs `CAP_SYS_NICE`)
+#[cfg(target_os = "linux")]
+const HIGH_NICE: libc::c_int = -10;
+/// `SCHED_FIFO` priority of threads with `ThreadPriority::Realtime` on Linux (needs `CAP_SYS_NICE` or an rtprio limit)
+#[cfg(target_os = "linux")]
+const REALTIME_PRIORITY: libc::c_int = 50;
+
+/// Token of the stream in the poll of a port thread
+const STREAM: Token = Token(0);
+/// Token of the waker of a port thread, woken for queued writes and to stop
+const WAKER: Token = Token(1);
+/// How often a port thread checks again whether its port can be read while reading is paused
+const PAUSED_POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// Priority of the I/O threads of a port
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ThreadPriority {
+    /// The default priority of the OS
+    #[default]
+    Normal,
+    /// Above the threads of the app: a nice value of -10 on Linux, `THREAD_PRIORITY_HIGHEST` on Windows
+    High,
+    /// Real-time scheduling: `SCHED_FIFO` on Linux, `THREAD_PRIORITY_TIME_CRITICAL` on Windows
+    Realtime,
+}
+
+/// Scheduling of the threads doing the I/O of a port, e.g. for a low latency control loop.
+/// Settings which the platform or the permissions of the process don't allow are logged and skipped,
+/// so the thread still runs with the defaults of the OS.
+///
+/// Set it to `SerialSetting::io_thread` to read and write the port on its own thread,
+/// or insert it as a resource to run the flashing and file transfer threads with it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Resource)]
+pub struct IoThread {
+    pub priority: ThreadPriority,
+    /// If set, pin the thread to this CPU core (Linux and Windows)
+    pub core: Option<usize>,
+}
+
+impl IoThread {
+    pub fn new(priority: ThreadPriority) -> Self {
+        Self {
+            priority,
+            core: None,
+        }
+    }
+
+    /// Pin the thread to the CPU core
+    pub fn core(mut self, core: usize) -> Self {
+        self.core = Some(core);
+        self
+    }
+
+    /// Spawn a thread named `name` with this scheduling
+    pub(crate) fn spawn<F, T>(self, name: String, f: F) -> io::Result<JoinHandle<T>>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        thread::Builder::new().name(name.clone()).spawn(move || {
+            self.apply(&name);
+            f()
+        })
+    }
+
+    /// Apply the scheduling to the current thread
+    fn apply(&self, name: &str) {
+        if let Err(e) = set_priority(self.priority) {
+            warn!(
+                "Failed to set the priority of {} to {:?}, it runs at the default priority: {}",
+                name, self.priority, e
+            );
+        }
+        if let Some(core) = self.core {
+            if let Err(e) = set_core(core) {
+                warn!(
+                    "Failed to pin {} to core {}, it runs on any core: {}",
+                    name, core, e
+                );
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn set_priority(priority: ThreadPriority) -> io::Result<()> {
+    match priority {
+        ThreadPriority::Normal => Ok(()),
+        ThreadPriority::High => {
+            // the nice value of a single thread is set by its thread id on Linux
+            // SAFETY: gettid has no arguments and always succeeds
+            let tid = unsafe { libc::syscall(libc::SYS_gettid) } as libc::id_t;
+            // SAFETY: setpriority only reads its arguments
+            if unsafe { libc::setpriority(libc::PRIO_PROCESS, tid, HIGH_NICE) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        }
+        ThreadPriority::Realtime => {
+            let param = libc::sched_param {
+                sched_priority: REALTIME_PRIORITY,
+            };
+            // SAFETY: the parameter outlives the call, and the thread is the current one
+            let result = unsafe {
+                libc::pthread_setschedparam(libc::pthread_self(), libc::SCHED_FIFO, &param)
+            };
+            match result {
+                0 => Ok(()),
+                e => Err(io::Error::from_raw_os_error(e)),
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn set_core(core: usize) -> io::Result<()> {
+    if core >= libc::CPU_SETSIZE as usize {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "no such core"));
+    }
+    // SAFETY: the set is a plain bit mask, zeroed before the core is set
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_SET(core, &mut set);
+        // 0 is the current thread
+        if libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn set_priority(priority: ThreadPriority) -> io::Result<()> {
+    use winapi::um::processthreadsapi::{GetCurrentThread, SetThreadPriority};
+    use winapi::um::winbase::{THREAD_PRIORITY_HIGHEST, THREAD_PRIORITY_TIME_CRITICAL};
+
+    let level = match priority {
+        ThreadPriority::Normal => return Ok(()),
+        ThreadPriority::High => THREAD_PRIORITY_HIGHEST,
+        ThreadPriority::Realtime => THREAD_PRIORITY_TIME_CRITICAL,
+    };
+    // SAFETY: the pseudo handle of the current thread is always valid
+    if unsafe { SetThreadPriority(GetCurrentThread(), level as i32) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn set_core(core: usize) -> io::Result<()> {
+    use winapi::um::processthreadsapi::GetCurrentThread;
+    use winapi::um::winbase::SetThreadAffinityMask;
+
+    if core >= usize::BITS as usize {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "no such core"));
+    }
+    // SAFETY: the pseudo handle of the current thread is always valid
+    if unsafe { SetThreadAffinityMask(GetCurrentThread(), 1 << core) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+fn set_priority(priority: ThreadPriority) -> io::Result<()> {
+    match priority {
+        ThreadPriority::Normal => Ok(()),
+        _ => Err(unsupported()),
+    }
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+fn set_core(_core: usize) -> io::Result<()> {
+    Err(unsupported())
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+fn unsupported() -> io::Error {
+    io::Error::new(io::ErrorKind::Unsupported, "not supported on this platform")
+}
+
+/// Writes of a frame queued on the I/O thread of a port, with the labels they were sent to
+pub(crate) type WriteJob = Vec<(String, PendingWrite)>;
+
+/// What the I/O thread of a port passes to the systems of the app, in order
+pub(crate) enum PortOutput {
+    /// Chunk read (unescaped and passed through the read hook), with when the port was polled
+    Read(Vec<u8>, Instant),
+    /// Read or poll error
+    Error(SerialError),
+    /// Results of a `WriteJob`
+    Written(WriteResults),
+}
+
+/// I/O thread of a port with `SerialSetting::io_thread`. The thread polls the port with its own `mio::Poll`
+/// instead of `SerialPoll`, reads it as soon as bytes arrive, and writes the writes queued for it.
+/// It stops when the port is disconnected, or when this handle is dropped with the port.
+#[derive(Debug)]
+pub(crate) struct PortThread {
+    /// Registry of the poll of the thread, which the stream is registered to
+    registry: Registry,
+    waker: Arc<Waker>,
+    writes: Sender<WriteJob>,
+    output: Receiver<PortOutput>,
+    stop: Arc<AtomicBool>,
+}
+
+impl PortThread {
+    /// Move the port in the slot from the poll of the app to a new thread with the scheduling.
+    /// The port is still registered to the poll of the app (with the token) if the thread can't be started.
+    pub(crate) fn start(
+        io_thread: IoThread,
+        serial_mtx: &Arc<Mutex<Option<SerialStreamLabeled>>>,
+        serial: &mut SerialStreamLabeled,
+        app_poll: &Poll,
+        token: Token,
+    ) -> io::Result<Self> {
+        let poll = Poll::new()?;
+        let registry = poll.registry().try_clone()?;
+        let waker = Arc::new(Waker::new(poll.registry(), WAKER)?);
+        app_poll.registry().deregister(&mut serial.stream)?;
+
+        let (writes, jobs) = mpsc::channel();
+        let (sender, output) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let spawned = registry
+            .register(&mut serial.stream, STREAM, Interest::READABLE)
+            .and_then(|()| {
+                let serial_mtx = Arc::clone(serial_mtx);
+                let stop = Arc::clone(&stop);
+                io_thread.spawn(format!("serial {}", serial.label), move || {
+                    run(poll, &serial_mtx, jobs, sender, &stop)
+                })
+            });
+        if let Err(e) = spawned {
+            // a failed registration leaves nothing to deregister
+            let _ = registry.deregister(&mut serial.stream);
+            app_poll
+                .registry()
+                .register(&mut serial.stream, token, Interest::READABLE)?;
+            return Err(e);
+        }
+        Ok(Self {
+            registry,
+            waker,
+            writes,
+            output,
+            stop,
+        })
+    }
+
+    /// Registry the stream of the port is registered to
+    pub(crate) fn registry(&self) -> &Registry {
+        &self.registry
+    }
+
+    /// Queue the writes on the thread, or give them back if it has stopped
+    pub(crate) fn write(&self, job: WriteJob) -> Result<(), WriteJob> {
+        self.writes.send(job).map_err(|mpsc::SendError(job)| job)?;
+        // the job is still written when the thread is woken by the next one
+        if let Err(e) = self.waker.wake() {
+            warn!("Failed to wake a serial port thread: {:?}", e);
+        }
+        Ok(())
+    }
+
+    /// What the thread has read and written since the last call
+    pub(crate) fn output(&self) -> impl Iterator<Item = PortOutput> + '_ {
+        self.output.try_iter()
+    }
+}
+
+impl Drop for PortThread {
+    fn drop(&mut self) {
+        // dropped with the port while its slot is locked, so the thread sees it before it locks the slot again
+        self.stop.store(true, Ordering::Release);
+        let _ = self.waker.wake();
+    }
+}
+
+/// Loop of the I/O thread of a port, until it is stopped or the port is disconnected
+fn run(
+    mut poll: Poll,
+    serial_mtx: &Mutex<Option<SerialStreamLabeled>>,
+    jobs: Receiver<WriteJob>,
+    output: Sender<PortOutput>,
+    stop: &AtomicBool,
+) {
+    let mut events = mio::Events::with_capacity(2);
+    // paused ports are read again once they have settled or their backlog has room,
+    // as no new poll event may come for them
+    let mut paused = false;
+    loop {
+        let timeout = paused.then_some(PAUSED_POLL_INTERVAL);
+        match poll.poll(&mut events, timeout) {
+            Ok(()) => {}
+            Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => {
+                let _ = output.send(PortOutput::Error(SerialError::Poll(format!("{:?}", e))));
+                return;
+            }
+        }
+        let polled = Instant::now();
+
+        for job in jobs.try_iter() {
+            if stop.load(Ordering::Acquire) {
+                return;
+            }
+            let results = job
+                .into_iter()
+                .map(|(label, write)| {
+                    let result = write_slot(serial_mtx, &write.buffer);
+                    (label, write.id, result)
+                })
+                .collect();
+            if output.send(PortOutput::Written(results)).is_err() {
+                return;
+            }
+        }
+
+        if !paused && !events.iter().any(|event| event.token() == STREAM) {
+            continue;
+        }
+        let mut serial = match serial_mtx.lock() {
+            Ok(serial) => serial,
+            Err(_) => return,
+        };
+        if stop.load(Ordering::Acquire) {
+            return;
+        }
+        let serial = match serial.as_mut() {
+            Some(serial) => serial,
+            None => return,
+        };
+        // don't read the port while it settles, or while its backlog is full with `BacklogPolicy::Block`
+        paused = Instant::now() < serial.ready_at || serial.read_paused;
+        if paused {
+            continue;
+        }
+        let sent = match read_port(serial) {
+            Some(Ok(buffer)) => output.send(PortOutput::Read(buffer, polled)),
+            Some(Err(error)) => output.send(PortOutput::Error(error)),
+            None => Ok(()),
+        };
+        if sent.is_err() || !serial.connected {
+            return;
+        }
+    }
+}
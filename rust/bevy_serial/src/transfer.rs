@@ -0,0 +1,642 @@
+use crate::blocking::{protocol_error, read_exact_timeout};
+use crate::compat::*;
+use crate::IoThread;
+use bevy::app::{App, Plugin};
+use bevy::ecs::system::Res;
+use bevy::log::{error, info};
+use mio_serial::{ClearBuffer, SerialPort};
+use std::io::{self, ErrorKind};
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// File transfer protocol
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransferProtocol {
+    /// XMODEM with 128 byte blocks, using CRC-16 if the receiver supports it
+    Xmodem,
+    /// XMODEM-1K with 1024 byte blocks and CRC-16
+    Xmodem1k,
+    /// YMODEM (batch mode with a single file), which also sends the file name and size
+    Ymodem,
+}
+
+/// File sent or received by a transfer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransferredFile {
+    /// File name, only available for YMODEM
+    pub name: Option<String>,
+    /// File contents
+    pub data: Vec<u8>,
+}
+
+/// Bevy's event type to start sending a file
+#[derive(Debug, Clone, Event)]
+pub struct FileSendRequestEvent {
+    /// The port name, usually the device path
+    pub port_name: String,
+    /// The baud rate in symbols-per-second
+    pub baud_rate: u32,
+    /// Protocol to send the file with
+    pub protocol: TransferProtocol,
+    /// File to send. The name is only used by YMODEM.
+    pub file: TransferredFile,
+}
+
+/// Bevy's event type to start receiving a file
+#[derive(Debug, Clone, Event)]
+pub struct FileReceiveRequestEvent {
+    /// The port name, usually the device path
+    pub port_name: String,
+    /// The baud rate in symbols-per-second
+    pub baud_rate: u32,
+    /// Protocol to receive the file with
+    pub protocol: TransferProtocol,
+}
+
+/// Bevy's event type to notify the progress of a transfer
+#[derive(Debug, Clone, PartialEq, Eq, Event)]
+pub struct TransferProgressEvent {
+    /// The port name of the request
+    pub port_name: String,
+    /// Number of bytes transferred so far
+    pub transferred: usize,
+    /// Total number of bytes if known
+    pub total: Option<usize>,
+}
+
+/// Bevy's event type to notify the completion or failure of a transfer.
+/// The result has the file sent or received.
+#[derive(Event)]
+pub struct TransferResultEvent {
+    /// The port name of the request
+    pub port_name: String,
+    /// File transferred, or the error which stopped the transfer
+    pub result: io::Result<TransferredFile>,
+}
+
+/// Plugin to send and receive files with XMODEM/YMODEM via `FileSendRequestEvent` and `FileReceiveRequestEvent`.
+///
+/// A transfer blocks the serial port until it finishes, so it runs on its own thread which opens the port by itself.
+/// The port must not be opened by `SerialPlugin` during the transfer.
+pub struct FileTransferPlugin;
+
+impl Plugin for FileTransferPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TransferJobs>()
+            .add_event::<FileSendRequestEvent>()
+            .add_event::<FileReceiveRequestEvent>()
+            .add_event::<TransferProgressEvent>()
+            .add_event::<TransferResultEvent>()
+            .add_systems(Update, start_transfer)
+            .add_systems(Update, poll_transfer);
+    }
+}
+
+/// Message sent from a transfer thread
+enum TransferMessage {
+    Progress(usize, Option<usize>),
+    Done(io::Result<TransferredFile>),
+}
+
+/// Transfer threads in progress, with their port names
+#[derive(Default, Resource)]
+struct TransferJobs(Mutex<Vec<(String, Receiver<TransferMessage>)>>);
+
+/// Spawn a transfer thread for each request, with the `IoThread` resource if it is inserted
+fn start_transfer(
+    mut ev_send: EventReader<FileSendRequestEvent>,
+    mut ev_receive: EventReader<FileReceiveRequestEvent>,
+    jobs: Res<TransferJobs>,
+    io_thread: Option<Res<IoThread>>,
+) {
+    let io_thread = io_thread.map(|io_thread| *io_thread).unwrap_or_default();
+    let requests = ev_send
+        .read()
+        .map(|r| {
+            (
+                r.port_name.clone(),
+                r.baud_rate,
+                r.protocol,
+                Some(r.file.clone()),
+            )
+        })
+        .chain(
+            ev_receive
+                .read()
+                .map(|r| (r.port_name.clone(), r.baud_rate, r.protocol, None)),
+        );
+
+    for (port_name, baud_rate, protocol, file) in requests {
+        let (tx, rx) = channel();
+        jobs.0
+            .lock()
+            .expect("Failed to lock TransferJobs")
+            .push((port_name.clone(), rx));
+
+        info!("Start transfer on {}", port_name);
+        let name = format!("transfer {}", port_name);
+        // the job is dropped with its channel if the thread fails to start
+        let spawned = io_thread.spawn(name, move || {
+            let result = mio_serial::new(&port_name, baud_rate)
+                .timeout(Duration::from_millis(100))
+                .open()
+                .map_err(io::Error::from)
+                .and_then(|mut port| {
+                    let mut transfer = Transfer {
+                        port: &mut *port,
+                        protocol,
+                        tx: &tx,
+                    };
+                    match file {
+                        Some(file) => transfer.send(file),
+                        None => transfer.receive(),
+                    }
+                });
+            let _ = tx.send(TransferMessage::Done(result));
+        });
+        if let Err(e) = spawned {
+            error!("Failed to start the transfer thread: {}", e);
+        }
+    }
+}
+
+/// Send progress and result of transfer threads as events
+fn poll_transfer(
+    mut ev_progress: EventWriter<TransferProgressEvent>,
+    mut ev_result: EventWriter<TransferResultEvent>,
+    jobs: Res<TransferJobs>,
+) {
+    let mut jobs = jobs.0.lock().expect("Failed to lock TransferJobs");
+    jobs.retain(|(port_name, rx)| loop {
+        match rx.try_recv() {
+            Ok(TransferMessage::Progress(transferred, total)) => {
+                ev_progress.send(TransferProgressEvent {
+                    port_name: port_name.clone(),
+                    transferred,
+                    total,
+                });
+            }
+            Ok(TransferMessage::Done(result)) => {
+                match &result {
+                    Ok(file) => info!("Transferred {} bytes on {}", file.data.len(), port_name),
+                    Err(e) => error!("Failed to transfer on {}: {}", port_name, e),
+                }
+                ev_result.send(TransferResultEvent {
+                    port_name: port_name.clone(),
+                    result,
+                });
+                break false;
+            }
+            Err(TryRecvError::Empty) => break true,
+            Err(TryRecvError::Disconnected) => break false,
+        }
+    });
+}
+
+const SOH: u8 = 0x01;
+const STX: u8 = 0x02;
+const EOT: u8 = 0x04;
+const ACK: u8 = 0x06;
+const NAK: u8 = 0x15;
+const CAN: u8 = 0x18;
+const CRC: u8 = b'C';
+const PAD: u8 = 0x1A;
+
+const MAX_RETRIES: usize = 10;
+const BLOCK_TIMEOUT: Duration = Duration::from_secs(10);
+const START_TIMEOUT: Duration = Duration::from_secs(60);
+const START_INTERVAL: Duration = Duration::from_secs(3);
+
+/// CRC-16/XMODEM (polynomial 0x1021, initial value 0)
+fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0_u16, |crc, &b| {
+        (0..8).fold(crc ^ (b as u16) << 8, |crc, _| {
+            if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0_u8, |acc, b| acc.wrapping_add(*b))
+}
+
+fn cancelled() -> io::Error {
+    io::Error::new(
+        ErrorKind::ConnectionAborted,
+        "transfer cancelled by the peer",
+    )
+}
+
+/// One transfer running on a blocking port
+struct Transfer<'a> {
+    port: &'a mut dyn SerialPort,
+    protocol: TransferProtocol,
+    tx: &'a Sender<TransferMessage>,
+}
+
+impl<'a> Transfer<'a> {
+    fn progress(&self, transferred: usize, total: Option<usize>) {
+        let _ = self.tx.send(TransferMessage::Progress(transferred, total));
+    }
+
+    fn read_byte(&mut self, timeout: Duration) -> io::Result<u8> {
+        let mut b = [0_u8; 1];
+        read_exact_timeout(self.port, &mut b, timeout)?;
+        Ok(b[0])
+    }
+
+    fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        self.port.write_all(data)?;
+        self.port.flush()
+    }
+
+    /// Send a file as the sender
+    fn send(&mut self, file: TransferredFile) -> io::Result<TransferredFile> {
+        let ymodem = self.protocol == TransferProtocol::Ymodem;
+        let block_size = match self.protocol {
+            TransferProtocol::Xmodem => 128,
+            TransferProtocol::Xmodem1k | TransferProtocol::Ymodem => 1024,
+        };
+
+        let use_crc = self.wait_start()?;
+        if ymodem {
+            // block 0 has the file name and size
+            let mut header = file.name.clone().unwrap_or_default().into_bytes();
+            header.push(0);
+            header.extend_from_slice(file.data.len().to_string().as_bytes());
+            header.push(0);
+            self.send_block(0, &header, 128, use_crc)?;
+            self.wait_start()?;
+        }
+
+        let mut sent = 0;
+        for (i, chunk) in file.data.chunks(block_size).enumerate() {
+            // short last block can be sent as a 128 byte block
+            let size = if chunk.len() <= 128 { 128 } else { block_size };
+            self.send_block((i + 1) as u8, chunk, size, use_crc)?;
+            sent += chunk.len();
+            self.progress(sent, Some(file.data.len()));
+        }
+        self.send_eot()?;
+
+        if ymodem {
+            // empty block 0 ends the batch
+            self.wait_start()?;
+            self.send_block(0, &[], 128, use_crc)?;
+        }
+        Ok(file)
+    }
+
+    /// Wait for the receiver to request CRC (`'C'`) or checksum (`NAK`) mode
+    fn wait_start(&mut self) -> io::Result<bool> {
+        loop {
+            match self.read_byte(START_TIMEOUT)? {
+                CRC => return Ok(true),
+                NAK => return Ok(false),
+                CAN => return Err(cancelled()),
+                _ => {}
+            }
+        }
+    }
+
+    fn send_block(&mut self, num: u8, data: &[u8], size: usize, use_crc: bool) -> io::Result<()> {
+        // YMODEM block 0 is padded with zeros, data blocks with SUB
+        let pad = if num == 0 && self.protocol == TransferProtocol::Ymodem {
+            0
+        } else {
+            PAD
+        };
+        let mut payload = data.to_vec();
+        payload.resize(size, pad);
+
+        let mut block = vec![if size == 128 { SOH } else { STX }, num, !num];
+        block.extend_from_slice(&payload);
+        if use_crc {
+            block.extend_from_slice(&crc16(&payload).to_be_bytes());
+        } else {
+            block.push(checksum(&payload));
+        }
+
+        for _ in 0..MAX_RETRIES {
+            self.write(&block)?;
+            match self.read_byte(BLOCK_TIMEOUT) {
+                Ok(ACK) => return Ok(()),
+                Ok(CAN) => return Err(cancelled()),
+                Ok(_) => {}
+                Err(ref e) if e.kind() == ErrorKind::TimedOut => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Err(protocol_error(format!(
+            "block {} was not acknowledged",
+            num
+        )))
+    }
+
+    fn send_eot(&mut self) -> io::Result<()> {
+        // receivers may NAK the first EOT to make sure it is not noise
+        for _ in 0..MAX_RETRIES {
+            self.write(&[EOT])?;
+            match self.read_byte(BLOCK_TIMEOUT) {
+                Ok(ACK) => return Ok(()),
+                Ok(CAN) => return Err(cancelled()),
+                Ok(_) => {}
+                Err(ref e) if e.kind() == ErrorKind::TimedOut => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Err(protocol_error("EOT was not acknowledged".to_string()))
+    }
+
+    /// Receive a file as the receiver, always in CRC mode
+    fn receive(&mut self) -> io::Result<TransferredFile> {
+        let ymodem = self.protocol == TransferProtocol::Ymodem;
+        let mut file = TransferredFile {
+            name: None,
+            data: vec![],
+        };
+        let mut size = None;
+
+        if ymodem {
+            let header = self.receive_first_block(0)?;
+            let mut fields = header.split(|&b| b == 0);
+            let name = fields.next().unwrap_or_default();
+            if name.is_empty() {
+                return Err(protocol_error("no file was sent".to_string()));
+            }
+            file.name = Some(String::from_utf8_lossy(name).into_owned());
+            size = fields
+                .next()
+                .and_then(|f| std::str::from_utf8(f).ok())
+                .and_then(|f| f.split(' ').next())
+                .and_then(|f| f.parse::<usize>().ok());
+            self.write(&[ACK])?;
+        }
+
+        let mut expected = 1_u8;
+        let mut block = Some(self.receive_first_block(expected)?);
+        loop {
+            if let Some(data) = block.take() {
+                file.data.extend_from_slice(&data);
+                expected = expected.wrapping_add(1);
+                self.write(&[ACK])?;
+                self.progress(file.data.len(), size);
+            }
+
+            match self.receive_block(expected)? {
+                Received::Block(data) => block = Some(data),
+                Received::Duplicate => self.write(&[ACK])?,
+                Received::Eot => break,
+            }
+        }
+
+        if ymodem {
+            // NAK the first EOT, then ACK the second one
+            self.write(&[NAK])?;
+            if self.read_byte(BLOCK_TIMEOUT)? != EOT {
+                return Err(protocol_error("EOT was not resent".to_string()));
+            }
+            self.write(&[ACK])?;
+            // the batch ends with an empty block 0
+            self.receive_first_block(0)?;
+            self.write(&[ACK])?;
+        } else {
+            self.write(&[ACK])?;
+        }
+
+        // XMODEM has no file size, so only padding can be removed
+        match size {
+            Some(size) => file.data.truncate(size),
+            None => {
+                while file.data.last() == Some(&PAD) {
+                    file.data.pop();
+                }
+            }
+        }
+        Ok(file)
+    }
+
+    /// Request the sender to start with `'C'` until the block arrives, and request a corrupted block again with `NAK`
+    fn receive_first_block(&mut self, expected: u8) -> io::Result<Vec<u8>> {
+        let deadline = Instant::now() + START_TIMEOUT;
+        let mut request = CRC;
+        while let Some(left) = deadline.checked_duration_since(Instant::now()) {
+            // drop the rest of a corrupted block (or noise) before the request
+            self.port.clear(ClearBuffer::Input)?;
+            self.write(&[request])?;
+            match self.receive_block_within(expected, left.min(START_INTERVAL)) {
+                Ok(Received::Block(data)) => return Ok(data),
+                Ok(_) => request = CRC,
+                Err(ref e) if e.kind() == ErrorKind::TimedOut => request = CRC,
+                Err(ref e) if e.kind() == ErrorKind::InvalidData => request = NAK,
+                Err(e) => return Err(e),
+            }
+        }
+        Err(io::Error::new(ErrorKind::TimedOut, "sender did not start"))
+    }
+
+    fn receive_block(&mut self, expected: u8) -> io::Result<Received> {
+        for _ in 0..MAX_RETRIES {
+            match self.receive_block_within(expected, BLOCK_TIMEOUT) {
+                Ok(received) => return Ok(received),
+                Err(ref e) if e.kind() == ErrorKind::InvalidData => {
+                    // drop the rest of the corrupted block before requesting it again
+                    self.port.clear(ClearBuffer::Input)?;
+                    self.write(&[NAK])?;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Err(protocol_error(format!(
+            "failed to receive block {}",
+            expected
+        )))
+    }
+
+    fn receive_block_within(&mut self, expected: u8, timeout: Duration) -> io::Result<Received> {
+        let size = match self.read_byte(timeout)? {
+            SOH => 128,
+            STX => 1024,
+            EOT => return Ok(Received::Eot),
+            CAN => return Err(cancelled()),
+            b => return Err(protocol_error(format!("unexpected byte {:02X}", b))),
+        };
+
+        // block number, its complement, data and CRC
+        let mut block = vec![0_u8; size + 4];
+        read_exact_timeout(self.port, &mut block, BLOCK_TIMEOUT)?;
+        let (num, data, crc) = (block[0], &block[2..size + 2], &block[size + 2..]);
+        if num != !block[1] || crc16(data).to_be_bytes() != crc {
+            return Err(protocol_error(format!("corrupted block {}", num)));
+        }
+
+        if num == expected {
+            Ok(Received::Block(data.to_vec()))
+        } else if num == expected.wrapping_sub(1) {
+            Ok(Received::Duplicate)
+        } else {
+            Err(protocol_error(format!(
+                "block {} received while expecting {}",
+                num, expected
+            )))
+        }
+    }
+}
+
+/// Result of receiving one block
+enum Received {
+    Block(Vec<u8>),
+    /// The previous block was sent again because our ACK was lost
+    Duplicate,
+    Eot,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc16_check_value() {
+        // CRC-16/XMODEM of the standard check input
+        assert_eq!(crc16(b"123456789"), 0x31C3);
+        assert_eq!(crc16(&[]), 0);
+        assert_eq!(checksum(&[0xFF, 0x02, 0x10]), 0x11);
+    }
+
+    /// Blocks and responses exchanged with the transfer over a pseudo terminal
+    #[cfg(unix)]
+    mod pty {
+        use super::*;
+        use serialport::TTYPort;
+        use std::io::Write;
+        use std::thread;
+
+        const TIMEOUT: Duration = Duration::from_secs(5);
+
+        /// XMODEM block with 128 bytes of the data padded with SUB and CRC-16
+        fn block(num: u8, data: &[u8]) -> Vec<u8> {
+            let mut payload = data.to_vec();
+            payload.resize(128, PAD);
+            let mut block = vec![SOH, num, !num];
+            block.extend_from_slice(&payload);
+            block.extend_from_slice(&crc16(&payload).to_be_bytes());
+            block
+        }
+
+        fn read_bytes(port: &mut TTYPort, len: usize) -> Vec<u8> {
+            let mut bytes = vec![0; len];
+            read_exact_timeout(port, &mut bytes, TIMEOUT).unwrap();
+            bytes
+        }
+
+        fn write_bytes(port: &mut TTYPort, bytes: &[u8]) {
+            port.write_all(bytes).unwrap();
+            port.flush().unwrap();
+        }
+
+        /// Run the transfer on one end of a pseudo terminal while `peer` plays the other end
+        fn transfer<T>(
+            peer: impl FnOnce(&mut TTYPort) + Send + 'static,
+            run: impl FnOnce(&mut Transfer) -> io::Result<T>,
+        ) -> io::Result<T> {
+            let (mut peer_port, mut port) = TTYPort::pair().unwrap();
+            // the end of the peer is kept open until the transfer is done, as flushing fails once it is closed
+            let peer = thread::spawn(move || {
+                peer(&mut peer_port);
+                peer_port
+            });
+            let (tx, _rx) = channel();
+            let result = run(&mut Transfer {
+                port: &mut port,
+                protocol: TransferProtocol::Xmodem,
+                tx: &tx,
+            });
+            drop(peer.join().unwrap());
+            result
+        }
+
+        #[test]
+        fn receiver_naks_a_corrupted_first_block() {
+            let received = transfer(
+                |peer| {
+                    assert_eq!(read_bytes(peer, 1), [CRC]);
+                    let mut corrupted = block(1, b"hello");
+                    corrupted[10] ^= 0xFF;
+                    write_bytes(peer, &corrupted);
+                    assert_eq!(read_bytes(peer, 1), [NAK]);
+                    write_bytes(peer, &block(1, b"hello"));
+                    assert_eq!(read_bytes(peer, 1), [ACK]);
+                    write_bytes(peer, &[EOT]);
+                    assert_eq!(read_bytes(peer, 1), [ACK]);
+                },
+                |transfer| transfer.receive(),
+            );
+            assert_eq!(received.unwrap().data, b"hello");
+        }
+
+        #[test]
+        fn receiver_naks_a_corrupted_block() {
+            let received = transfer(
+                |peer| {
+                    assert_eq!(read_bytes(peer, 1), [CRC]);
+                    write_bytes(peer, &block(1, b"first"));
+                    assert_eq!(read_bytes(peer, 1), [ACK]);
+                    // wrong complement of the block number
+                    let mut corrupted = block(2, b"second");
+                    corrupted[2] = 0;
+                    write_bytes(peer, &corrupted);
+                    assert_eq!(read_bytes(peer, 1), [NAK]);
+                    write_bytes(peer, &block(2, b"second"));
+                    assert_eq!(read_bytes(peer, 1), [ACK]);
+                    write_bytes(peer, &[EOT]);
+                    assert_eq!(read_bytes(peer, 1), [ACK]);
+                },
+                |transfer| transfer.receive(),
+            );
+            let mut expected = b"first".to_vec();
+            expected.resize(128, PAD);
+            expected.extend_from_slice(b"second");
+            assert_eq!(received.unwrap().data, expected);
+        }
+
+        #[test]
+        fn sender_resends_a_naked_block() {
+            let sent = transfer(
+                |peer| {
+                    write_bytes(peer, &[CRC]);
+                    let expected = block(1, b"hello");
+                    assert_eq!(read_bytes(peer, expected.len()), expected);
+                    write_bytes(peer, &[NAK]);
+                    assert_eq!(read_bytes(peer, expected.len()), expected);
+                    write_bytes(peer, &[ACK]);
+                    assert_eq!(read_bytes(peer, 1), [EOT]);
+                    write_bytes(peer, &[ACK]);
+                },
+                |transfer| {
+                    transfer.send(TransferredFile {
+                        name: None,
+                        data: b"hello".to_vec(),
+                    })
+                },
+            );
+            assert!(sent.is_ok());
+        }
+
+        #[test]
+        fn cancel_stops_the_receiver() {
+            let received = transfer(
+                |peer| {
+                    assert_eq!(read_bytes(peer, 1), [CRC]);
+                    write_bytes(peer, &[CAN]);
+                },
+                |transfer| transfer.receive(),
+            );
+            assert_eq!(received.unwrap_err().kind(), ErrorKind::ConnectionAborted);
+        }
+    }
+}
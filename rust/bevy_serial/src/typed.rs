@@ -0,0 +1,82 @@
+use crate::compat::*;
+use crate::{SerialReadEvent, SerialSystem, SerialWriteEvent};
+use bevy::app::{App, Plugin};
+use std::marker::PhantomData;
+
+/// Zero-sized marker type identifying a serial port at compile time by its label
+pub trait SerialLabel: Send + Sync + 'static {
+    /// Label of the port, which must match `SerialSetting::label` (or the port name)
+    const LABEL: &'static str;
+}
+
+/// Bevy's event type of bytes read from the port `P`
+#[derive(Event)]
+pub struct PortReadEvent<P: SerialLabel> {
+    pub buffer: Vec<u8>,
+    marker: PhantomData<P>,
+}
+
+impl<P: SerialLabel> PortReadEvent<P> {
+    fn new(buffer: Vec<u8>) -> Self {
+        Self {
+            buffer,
+            marker: PhantomData,
+        }
+    }
+}
+
+/// Bevy's event type to write bytes to the port `P`
+#[derive(Event)]
+pub struct PortWriteEvent<P: SerialLabel> {
+    pub buffer: Vec<u8>,
+    marker: PhantomData<P>,
+}
+
+impl<P: SerialLabel> PortWriteEvent<P> {
+    pub fn new(bytes: &[u8]) -> Self {
+        Self {
+            buffer: bytes.to_vec(),
+            marker: PhantomData,
+        }
+    }
+}
+
+/// Plugin to read and write the port `P` with `PortReadEvent<P>` and `PortWriteEvent<P>` instead of labels.
+/// Reads are routed once after `SerialSystem::Read`, so systems reading the port don't compare labels,
+/// and writes go through `SerialSystem::Write` like `SerialWriteEvent` (groups, leases and reconnect buffers apply).
+pub struct SerialPortPlugin<P: SerialLabel>(PhantomData<P>);
+
+impl<P: SerialLabel> Default for SerialPortPlugin<P> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<P: SerialLabel> Plugin for SerialPortPlugin<P> {
+    fn build(&self, app: &mut App) {
+        app.add_event::<PortReadEvent<P>>()
+            .add_event::<PortWriteEvent<P>>()
+            .add_systems(PreUpdate, route_reads::<P>.after(SerialSystem::Read))
+            .add_systems(PostUpdate, forward_writes::<P>.before(SerialSystem::Write));
+    }
+}
+
+fn route_reads<P: SerialLabel>(
+    mut ev_serial: EventReader<SerialReadEvent>,
+    mut ev_read: EventWriter<PortReadEvent<P>>,
+) {
+    for SerialReadEvent(label, buffer) in ev_serial.read() {
+        if label == P::LABEL {
+            ev_read.send(PortReadEvent::new(buffer.clone()));
+        }
+    }
+}
+
+fn forward_writes<P: SerialLabel>(
+    mut ev_write: EventReader<PortWriteEvent<P>>,
+    mut ev_serial: EventWriter<SerialWriteEvent>,
+) {
+    for PortWriteEvent { buffer, .. } in ev_write.read() {
+        ev_serial.send(SerialWriteEvent(P::LABEL.to_string(), buffer.clone()));
+    }
+}
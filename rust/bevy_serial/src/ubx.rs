@@ -0,0 +1,445 @@
+use crate::codec::Decoder;
+use crate::demux::{SerialMessage, TaggedFrame};
+use bevy::log::debug;
+
+const SYNC_1: u8 = 0xB5;
+const SYNC_2: u8 = 0x62;
+/// Sync chars, class, id and length
+const HEADER_LEN: usize = 6;
+
+/// Message of the u-blox UBX protocol
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UbxFrame {
+    /// Message class, e.g. `0x01` for NAV
+    pub class: u8,
+    /// Message ID within the class
+    pub id: u8,
+    /// Payload without the header and checksum
+    pub payload: Vec<u8>,
+}
+
+impl UbxFrame {
+    pub fn new(class: u8, id: u8, payload: &[u8]) -> Self {
+        Self {
+            class,
+            id,
+            payload: payload.to_vec(),
+        }
+    }
+
+    /// Encode this message with sync chars and checksum, e.g. to send a CFG message via `SerialWriteEvent`
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = vec![SYNC_1, SYNC_2, self.class, self.id];
+        bytes.extend_from_slice(&(self.payload.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(&self.payload);
+        let (ck_a, ck_b) = checksum(&bytes[2..]);
+        bytes.push(ck_a);
+        bytes.push(ck_b);
+        bytes
+    }
+}
+
+/// Tags are the message names like `"NAV-PVT"`, so typed messages can be demultiplexed by `add_serial_message`.
+/// Messages without a typed counterpart in this crate are tagged `"UBX"`.
+impl TaggedFrame for UbxFrame {
+    fn tag(&self) -> &str {
+        match (self.class, self.id) {
+            (0x01, 0x02) => NavPosllh::TAG,
+            (0x01, 0x07) => NavPvt::TAG,
+            (0x01, 0x12) => NavVelned::TAG,
+            (0x05, 0x00) => AckNak::TAG,
+            (0x05, 0x01) => AckAck::TAG,
+            _ => "UBX",
+        }
+    }
+
+    fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+}
+
+/// 8-bit Fletcher checksum over class, id, length and payload
+fn checksum(bytes: &[u8]) -> (u8, u8) {
+    bytes.iter().fold((0_u8, 0_u8), |(a, b), &x| {
+        let a = a.wrapping_add(x);
+        (a, b.wrapping_add(a))
+    })
+}
+
+/// Decoder which splits bytes into UBX messages.
+/// Messages with invalid checksums and bytes between messages (e.g. NMEA sentences) are skipped.
+#[derive(Debug, Clone)]
+pub struct UbxDecoder {
+    max_len: usize,
+    buf: Vec<u8>,
+}
+
+impl UbxDecoder {
+    /// Create a decoder which skips messages with payloads longer than `max_len`
+    pub fn new(max_len: usize) -> Self {
+        Self {
+            max_len,
+            buf: vec![],
+        }
+    }
+}
+
+impl Default for UbxDecoder {
+    fn default() -> Self {
+        Self::new(1024)
+    }
+}
+
+impl Decoder for UbxDecoder {
+    type Frame = UbxFrame;
+
+    fn decode(&mut self, bytes: &[u8], frames: &mut Vec<Self::Frame>) {
+        self.buf.extend_from_slice(bytes);
+
+        let mut pos = 0;
+        loop {
+            // find sync chars
+            match self.buf[pos..]
+                .windows(2)
+                .position(|w| w == [SYNC_1, SYNC_2])
+            {
+                Some(i) => pos += i,
+                None => {
+                    // keep the last byte in case it is the first sync char
+                    pos = self.buf.len().saturating_sub(1).max(pos);
+                    break;
+                }
+            }
+
+            let header = match self.buf.get(pos..pos + HEADER_LEN) {
+                Some(header) => header,
+                None => break,
+            };
+            let len = u16::from_le_bytes([header[4], header[5]]) as usize;
+            if len > self.max_len {
+                debug!("UBX payload length {} exceeds {}", len, self.max_len);
+                pos += 1;
+                continue;
+            }
+            let end = pos + HEADER_LEN + len + 2;
+            if self.buf.len() < end {
+                break;
+            }
+
+            let (ck_a, ck_b) = checksum(&self.buf[pos + 2..end - 2]);
+            if [ck_a, ck_b] == self.buf[end - 2..end] {
+                frames.push(UbxFrame::new(
+                    self.buf[pos + 2],
+                    self.buf[pos + 3],
+                    &self.buf[pos + HEADER_LEN..end - 2],
+                ));
+                pos = end;
+            } else {
+                debug!("UBX checksum mismatch");
+                pos += 1;
+            }
+        }
+        self.buf.drain(..pos);
+    }
+}
+
+fn u16_at(p: &[u8], i: usize) -> u16 {
+    u16::from_le_bytes([p[i], p[i + 1]])
+}
+
+fn u32_at(p: &[u8], i: usize) -> u32 {
+    u32::from_le_bytes([p[i], p[i + 1], p[i + 2], p[i + 3]])
+}
+
+fn i32_at(p: &[u8], i: usize) -> i32 {
+    u32_at(p, i) as i32
+}
+
+/// UBX-NAV-PVT: navigation position velocity time solution.
+/// Values are in the units of the protocol, e.g. 1e-7 degrees for `lat` and `lon`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NavPvt {
+    /// GPS time of week of the navigation epoch in ms
+    pub itow: u32,
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub min: u8,
+    pub sec: u8,
+    /// Validity flags of date and time
+    pub valid: u8,
+    /// Fraction of second in ns (-1e9 to 1e9)
+    pub nano: i32,
+    /// 0: no fix, 1: dead reckoning, 2: 2D, 3: 3D, 4: GNSS + dead reckoning, 5: time only
+    pub fix_type: u8,
+    /// Fix status flags, bit 0 is `gnssFixOK`
+    pub flags: u8,
+    /// Number of satellites used
+    pub num_sv: u8,
+    /// Longitude in 1e-7 degrees
+    pub lon: i32,
+    /// Latitude in 1e-7 degrees
+    pub lat: i32,
+    /// Height above ellipsoid in mm
+    pub height: i32,
+    /// Height above mean sea level in mm
+    pub h_msl: i32,
+    /// Horizontal accuracy estimate in mm
+    pub h_acc: u32,
+    /// Vertical accuracy estimate in mm
+    pub v_acc: u32,
+    /// North velocity in mm/s
+    pub vel_n: i32,
+    /// East velocity in mm/s
+    pub vel_e: i32,
+    /// Down velocity in mm/s
+    pub vel_d: i32,
+    /// Ground speed in mm/s
+    pub g_speed: i32,
+    /// Heading of motion in 1e-5 degrees
+    pub head_mot: i32,
+    /// Position DOP in 0.01
+    pub p_dop: u16,
+}
+
+impl NavPvt {
+    /// Whether the receiver has a valid fix
+    pub fn has_fix(&self) -> bool {
+        self.flags & 0x01 != 0
+    }
+
+    /// Latitude and longitude in degrees
+    pub fn lat_lon_deg(&self) -> (f64, f64) {
+        (self.lat as f64 * 1e-7, self.lon as f64 * 1e-7)
+    }
+}
+
+impl SerialMessage for NavPvt {
+    const TAG: &'static str = "NAV-PVT";
+
+    fn from_payload(p: &[u8]) -> Option<Self> {
+        if p.len() < 92 {
+            return None;
+        }
+        Some(Self {
+            itow: u32_at(p, 0),
+            year: u16_at(p, 4),
+            month: p[6],
+            day: p[7],
+            hour: p[8],
+            min: p[9],
+            sec: p[10],
+            valid: p[11],
+            nano: i32_at(p, 16),
+            fix_type: p[20],
+            flags: p[21],
+            num_sv: p[23],
+            lon: i32_at(p, 24),
+            lat: i32_at(p, 28),
+            height: i32_at(p, 32),
+            h_msl: i32_at(p, 36),
+            h_acc: u32_at(p, 40),
+            v_acc: u32_at(p, 44),
+            vel_n: i32_at(p, 48),
+            vel_e: i32_at(p, 52),
+            vel_d: i32_at(p, 56),
+            g_speed: i32_at(p, 60),
+            head_mot: i32_at(p, 64),
+            p_dop: u16_at(p, 76),
+        })
+    }
+}
+
+/// UBX-NAV-POSLLH: geodetic position solution
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NavPosllh {
+    /// GPS time of week of the navigation epoch in ms
+    pub itow: u32,
+    /// Longitude in 1e-7 degrees
+    pub lon: i32,
+    /// Latitude in 1e-7 degrees
+    pub lat: i32,
+    /// Height above ellipsoid in mm
+    pub height: i32,
+    /// Height above mean sea level in mm
+    pub h_msl: i32,
+    /// Horizontal accuracy estimate in mm
+    pub h_acc: u32,
+    /// Vertical accuracy estimate in mm
+    pub v_acc: u32,
+}
+
+impl SerialMessage for NavPosllh {
+    const TAG: &'static str = "NAV-POSLLH";
+
+    fn from_payload(p: &[u8]) -> Option<Self> {
+        if p.len() < 28 {
+            return None;
+        }
+        Some(Self {
+            itow: u32_at(p, 0),
+            lon: i32_at(p, 4),
+            lat: i32_at(p, 8),
+            height: i32_at(p, 12),
+            h_msl: i32_at(p, 16),
+            h_acc: u32_at(p, 20),
+            v_acc: u32_at(p, 24),
+        })
+    }
+}
+
+/// UBX-NAV-VELNED: velocity solution in NED frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NavVelned {
+    /// GPS time of week of the navigation epoch in ms
+    pub itow: u32,
+    /// North velocity in cm/s
+    pub vel_n: i32,
+    /// East velocity in cm/s
+    pub vel_e: i32,
+    /// Down velocity in cm/s
+    pub vel_d: i32,
+    /// 3D speed in cm/s
+    pub speed: u32,
+    /// Ground speed in cm/s
+    pub g_speed: u32,
+    /// Heading of motion in 1e-5 degrees
+    pub heading: i32,
+    /// Speed accuracy estimate in cm/s
+    pub s_acc: u32,
+    /// Heading accuracy estimate in 1e-5 degrees
+    pub c_acc: u32,
+}
+
+impl SerialMessage for NavVelned {
+    const TAG: &'static str = "NAV-VELNED";
+
+    fn from_payload(p: &[u8]) -> Option<Self> {
+        if p.len() < 36 {
+            return None;
+        }
+        Some(Self {
+            itow: u32_at(p, 0),
+            vel_n: i32_at(p, 4),
+            vel_e: i32_at(p, 8),
+            vel_d: i32_at(p, 12),
+            speed: u32_at(p, 16),
+            g_speed: u32_at(p, 20),
+            heading: i32_at(p, 24),
+            s_acc: u32_at(p, 28),
+            c_acc: u32_at(p, 32),
+        })
+    }
+}
+
+/// UBX-ACK-ACK: a CFG message was accepted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AckAck {
+    /// Class of the acknowledged message
+    pub class: u8,
+    /// ID of the acknowledged message
+    pub id: u8,
+}
+
+impl SerialMessage for AckAck {
+    const TAG: &'static str = "ACK-ACK";
+
+    fn from_payload(p: &[u8]) -> Option<Self> {
+        match *p {
+            [class, id] => Some(Self { class, id }),
+            _ => None,
+        }
+    }
+}
+
+/// UBX-ACK-NAK: a CFG message was rejected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AckNak {
+    /// Class of the rejected message
+    pub class: u8,
+    /// ID of the rejected message
+    pub id: u8,
+}
+
+impl SerialMessage for AckNak {
+    const TAG: &'static str = "ACK-NAK";
+
+    fn from_payload(p: &[u8]) -> Option<Self> {
+        match *p {
+            [class, id] => Some(Self { class, id }),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// UBX-MON-VER poll, as in the u-blox protocol specification
+    const MON_VER_POLL: [u8; 8] = [0xB5, 0x62, 0x0A, 0x04, 0x00, 0x00, 0x0E, 0x34];
+    /// UBX-CFG-RATE with a 200 ms measurement rate
+    const CFG_RATE: [u8; 14] = [
+        0xB5, 0x62, 0x06, 0x08, 0x06, 0x00, 0xC8, 0x00, 0x01, 0x00, 0x01, 0x00, 0xDE, 0x6A,
+    ];
+    /// UBX-ACK-ACK of a UBX-CFG-PRT message
+    const ACK_CFG_PRT: [u8; 10] = [0xB5, 0x62, 0x05, 0x01, 0x02, 0x00, 0x06, 0x00, 0x0E, 0x37];
+
+    #[test]
+    fn encodes_the_fletcher_checksum() {
+        assert_eq!(UbxFrame::new(0x0A, 0x04, &[]).encode(), MON_VER_POLL);
+        let rate = UbxFrame::new(0x06, 0x08, &[0xC8, 0x00, 0x01, 0x00, 0x01, 0x00]);
+        assert_eq!(rate.encode(), CFG_RATE);
+    }
+
+    #[test]
+    fn decodes_messages_split_between_nmea_sentences() {
+        let mut bytes = b"$GPGGA,,,,,,0,,,,,,,,*66\r\n".to_vec();
+        bytes.extend_from_slice(&ACK_CFG_PRT);
+        bytes.extend_from_slice(b"$GPGSA,A,1,,,,,,,,,,,,,,,*1E\r\n");
+        bytes.extend_from_slice(&CFG_RATE);
+
+        let mut decoder = UbxDecoder::default();
+        let mut frames = vec![];
+        for chunk in bytes.chunks(5) {
+            decoder.decode(chunk, &mut frames);
+        }
+        assert_eq!(
+            frames,
+            [
+                UbxFrame::new(0x05, 0x01, &[0x06, 0x00]),
+                UbxFrame::new(0x06, 0x08, &[0xC8, 0x00, 0x01, 0x00, 0x01, 0x00]),
+            ]
+        );
+        assert_eq!(frames[0].tag(), AckAck::TAG);
+        assert_eq!(
+            AckAck::from_payload(frames[0].payload()),
+            Some(AckAck {
+                class: 0x06,
+                id: 0x00
+            })
+        );
+    }
+
+    #[test]
+    fn skips_messages_with_bad_checksums() {
+        let mut corrupted = ACK_CFG_PRT;
+        corrupted[9] ^= 0xFF;
+        let mut bytes = corrupted.to_vec();
+        bytes.extend_from_slice(&MON_VER_POLL);
+
+        let mut frames = vec![];
+        UbxDecoder::default().decode(&bytes, &mut frames);
+        assert_eq!(frames, [UbxFrame::new(0x0A, 0x04, &[])]);
+    }
+
+    #[test]
+    fn skips_payloads_longer_than_the_maximum() {
+        let mut frames = vec![];
+        let mut decoder = UbxDecoder::new(4);
+        decoder.decode(&CFG_RATE, &mut frames);
+        decoder.decode(&MON_VER_POLL, &mut frames);
+        assert_eq!(frames, [UbxFrame::new(0x0A, 0x04, &[])]);
+    }
+}
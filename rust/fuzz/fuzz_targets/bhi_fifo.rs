@@ -0,0 +1,18 @@
+#![no_main]
+use bevy_serial::{BhiFifoDecoder, Decoder};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let (chunk_len, data) = match data.split_first() {
+        Some((&len, data)) => (len as usize + 1, data),
+        None => return,
+    };
+    let mut decoder = BhiFifoDecoder::default();
+    let mut samples = vec![];
+    for chunk in data.chunks(chunk_len) {
+        decoder.decode(chunk, &mut samples);
+    }
+    for sample in samples.iter() {
+        let _ = (sample.seconds(), sample.xyz());
+    }
+});
@@ -0,0 +1,10 @@
+#![no_main]
+use bevy_serial::EscapeCodec;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let codec = EscapeCodec::new(b'#', &[0x11, 0x13]);
+    // arbitrary bytes on the wire must not panic, and escaping must round trip
+    let _ = codec.unescape(data);
+    assert_eq!(codec.unescape(&codec.escape(data)), data);
+});
@@ -0,0 +1,10 @@
+#![no_main]
+use bevy_serial::{decode_hex, encode_hex};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(s) = std::str::from_utf8(data) {
+        let _ = decode_hex(s);
+    }
+    assert_eq!(decode_hex(&encode_hex(data)).ok().as_deref(), Some(data));
+});
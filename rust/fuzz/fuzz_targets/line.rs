@@ -0,0 +1,20 @@
+#![no_main]
+use bevy_serial::{Decoder, LineDecoder, TaggedLineDecoder};
+use libfuzzer_sys::fuzz_target;
+
+// the first byte splits the input into chunks, as reads split the stream
+fuzz_target!(|data: &[u8]| {
+    let (chunk_len, data) = match data.split_first() {
+        Some((&len, data)) => (len as usize + 1, data),
+        None => return,
+    };
+    let mut lines = LineDecoder::new(b'\n', 64);
+    let mut tagged = TaggedLineDecoder::default();
+    let mut frames = vec![];
+    let mut tagged_frames = vec![];
+    for chunk in data.chunks(chunk_len) {
+        lines.decode(chunk, &mut frames);
+        tagged.decode(chunk, &mut tagged_frames);
+    }
+    assert!(frames.iter().all(|line| line.len() <= 64));
+});
@@ -0,0 +1,22 @@
+#![no_main]
+use bevy_serial::{CrsfDecoder, Decoder, SbusDecoder};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let (chunk_len, data) = match data.split_first() {
+        Some((&len, data)) => (len as usize + 1, data),
+        None => return,
+    };
+    let mut sbus = SbusDecoder::new();
+    let mut crsf = CrsfDecoder::new();
+    let mut frames = vec![];
+    for chunk in data.chunks(chunk_len) {
+        sbus.decode(chunk, &mut frames);
+        crsf.decode(chunk, &mut frames);
+    }
+    for rc in frames.iter() {
+        for channel in 0..rc.channels.len() {
+            assert!((-1.0..=1.0).contains(&rc.axis(channel)));
+        }
+    }
+});
@@ -0,0 +1,33 @@
+#![no_main]
+use bevy_serial::{
+    AckAck, AckNak, Decoder, NavPosllh, NavPvt, NavVelned, SerialMessage, TaggedFrame, UbxDecoder,
+};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let (chunk_len, data) = match data.split_first() {
+        Some((&len, data)) => (len as usize + 1, data),
+        None => return,
+    };
+    let mut decoder = UbxDecoder::default();
+    let mut frames = vec![];
+    for chunk in data.chunks(chunk_len) {
+        decoder.decode(chunk, &mut frames);
+    }
+    // frames with valid checksums must parse or be rejected without panicking
+    for frame in frames.iter() {
+        let payload = frame.payload();
+        match frame.tag() {
+            NavPvt::TAG => drop(NavPvt::from_payload(payload)),
+            NavPosllh::TAG => drop(NavPosllh::from_payload(payload)),
+            NavVelned::TAG => drop(NavVelned::from_payload(payload)),
+            AckAck::TAG => drop(AckAck::from_payload(payload)),
+            AckNak::TAG => drop(AckNak::from_payload(payload)),
+            _ => {}
+        }
+    }
+    // the payloads are also parsed as any message type
+    let _ = NavPvt::from_payload(data);
+    let _ = NavPosllh::from_payload(data);
+    let _ = NavVelned::from_payload(data);
+});
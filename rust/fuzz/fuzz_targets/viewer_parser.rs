@@ -0,0 +1,32 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+// the parser of the viewer is part of the binary, so its modules are compiled here
+#[allow(dead_code)]
+#[path = "../../src/parser.rs"]
+mod parser;
+#[allow(dead_code)]
+#[path = "../../src/units.rs"]
+mod units;
+
+use parser::ParserRegistry;
+
+fuzz_target!(|data: &[u8]| {
+    let (chunk_len, data) = match data.split_first() {
+        Some((&len, data)) => (len as usize + 1, data),
+        None => return,
+    };
+    let mut registry = ParserRegistry::default();
+    let _ = registry.detect(data);
+    let names: Vec<&str> = registry.names().collect();
+    for name in names {
+        registry.select(name);
+        let mut packets = vec![];
+        for chunk in data.chunks(chunk_len) {
+            registry.parse(chunk, &mut packets);
+        }
+        assert!(packets.iter().all(|p| p.accel.is_finite()
+            && p.gyro.map_or(true, |g| g.is_finite())
+            && p.mag.map_or(true, |m| m.is_finite())));
+    }
+});
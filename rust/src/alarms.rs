@@ -0,0 +1,358 @@
+use bevy::prelude::*;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::locale::Locale;
+use crate::parser::{Packet, PacketEvent};
+use crate::theme::Theme;
+use crate::units::{AccelUnit, SensorUnits};
+
+/// Keys to enable or disable the alarms with Alt
+const ALARM_KEYS: [KeyCode; 9] = [
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+];
+
+/// Value checked by an alarm
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlarmKind {
+    /// Magnitude of the acceleration in the unit of the alarm
+    Accel,
+    /// Angle between the z axis of the device and gravity in degrees
+    Tilt,
+    /// Battery voltage in volts, active below the threshold
+    Battery,
+}
+
+impl AlarmKind {
+    /// Value of the packet, with the magnitude of the acceleration converted to `unit`,
+    /// or `None` if the packet doesn't have it
+    fn value(&self, packet: &Packet, unit: AccelUnit) -> Option<f32> {
+        match self {
+            AlarmKind::Accel => Some(unit.in_unit(packet.accel.length())),
+            AlarmKind::Tilt => Some(packet.accel.angle_between(Vec3::Z).to_degrees()),
+            AlarmKind::Battery => packet.battery,
+        }
+    }
+
+    fn unit(&self, unit: AccelUnit) -> &'static str {
+        match self {
+            AlarmKind::Accel => unit.symbol(),
+            AlarmKind::Tilt => "°",
+            AlarmKind::Battery => " V",
+        }
+    }
+
+    /// `<` for alarms active below the threshold, `>` for the others
+    fn comparison(&self) -> &'static str {
+        match self {
+            AlarmKind::Battery => "<",
+            _ => ">",
+        }
+    }
+}
+
+/// Alarm which becomes active when the value exceeds the threshold,
+/// and inactive when it falls below the threshold minus the hysteresis (the other way round for `AlarmKind::Battery`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alarm {
+    pub kind: AlarmKind,
+    pub threshold: f32,
+    pub hysteresis: f32,
+    /// Unit of the threshold and hysteresis of `AlarmKind::Accel`
+    pub unit: AccelUnit,
+    pub enabled: bool,
+    pub active: bool,
+}
+
+impl Alarm {
+    pub fn new(kind: AlarmKind, threshold: f32, hysteresis: f32) -> Self {
+        Alarm {
+            kind,
+            threshold,
+            hysteresis,
+            unit: AccelUnit::G,
+            enabled: true,
+            active: false,
+        }
+    }
+
+    /// Update the state with the value, returning true if it has changed
+    fn update(&mut self, value: f32) -> bool {
+        let (value, threshold) = match self.kind {
+            AlarmKind::Battery => (-value, -self.threshold),
+            _ => (value, self.threshold),
+        };
+        let active = if self.active {
+            value > threshold - self.hysteresis
+        } else {
+            value > threshold
+        };
+        let changed = active != self.active;
+        self.active = active;
+        changed
+    }
+
+    /// Symbol of the unit of the value
+    fn symbol(&self) -> &'static str {
+        self.kind.unit(self.unit)
+    }
+}
+
+/// Written like `tilt > 45°` or `battery < 3.5 V`
+impl fmt::Display for Alarm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self.kind {
+            AlarmKind::Accel => "|a|",
+            AlarmKind::Tilt => "tilt",
+            AlarmKind::Battery => "battery",
+        };
+        write!(
+            f,
+            "{} {} {}{}",
+            name,
+            self.kind.comparison(),
+            self.threshold,
+            self.symbol()
+        )
+    }
+}
+
+/// Parsed from `<accel|tilt|battery>:<threshold>[:<hysteresis>]`, e.g. `accel:3`, `tilt:45:5` or `battery:3.5`.
+/// The threshold of `accel` is in the unit of the sensor, which `AlarmPlugin` sets.
+impl FromStr for Alarm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(':').collect();
+        let (kind, default_hysteresis) = match parts[0] {
+            "accel" => (AlarmKind::Accel, 0.2),
+            "tilt" => (AlarmKind::Tilt, 5.0),
+            "battery" => (AlarmKind::Battery, 0.1),
+            _ => return Err(format!("unknown alarm {}", parts[0])),
+        };
+        let number = |i: usize| {
+            parts
+                .get(i)
+                .map(|v| v.parse::<f32>().map_err(|e| e.to_string()))
+        };
+        let threshold = number(1).ok_or_else(|| format!("no threshold in {}", s))??;
+        let hysteresis = number(2).unwrap_or(Ok(default_hysteresis))?;
+        Ok(Alarm::new(kind, threshold, hysteresis))
+    }
+}
+
+/// Saved like `tilt:45:5` in the settings file, as on the command line
+impl Serialize for Alarm {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let kind = match self.kind {
+            AlarmKind::Accel => "accel",
+            AlarmKind::Tilt => "tilt",
+            AlarmKind::Battery => "battery",
+        };
+        serializer.serialize_str(&format!("{}:{}:{}", kind, self.threshold, self.hysteresis))
+    }
+}
+
+impl<'de> Deserialize<'de> for Alarm {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+/// Alarms checked against every sample
+pub struct Alarms(pub Vec<Alarm>);
+
+impl Alarms {
+    /// The alarms with thresholds in `unit`, or alarms at 3 g and 45° if there are none
+    pub fn new(alarms: &[Alarm], unit: AccelUnit) -> Self {
+        if alarms.is_empty() {
+            let mut accel = Alarm::new(AlarmKind::Accel, unit.in_unit(3.0), unit.in_unit(0.2));
+            accel.unit = unit;
+            return Alarms(vec![accel, Alarm::new(AlarmKind::Tilt, 45.0, 5.0)]);
+        }
+        Alarms(
+            alarms
+                .iter()
+                .map(|alarm| Alarm {
+                    unit,
+                    ..alarm.clone()
+                })
+                .collect(),
+        )
+    }
+}
+
+/// Sent when an alarm becomes active or inactive
+#[derive(Debug, Clone)]
+pub struct AlarmEvent {
+    pub alarm: Alarm,
+    /// The value which changed the state
+    pub value: f32,
+}
+
+/// Check the alarms, and list them at the top with active ones in red.
+/// Alt + 1-9 enables or disables each alarm.
+pub struct AlarmPlugin {
+    /// Alarms with thresholds in the unit of the sensor
+    pub alarms: Vec<Alarm>,
+    pub units: SensorUnits,
+}
+
+impl Plugin for AlarmPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Alarms::new(&self.alarms, self.units.unit))
+            .add_event::<AlarmEvent>()
+            .add_startup_system(setup_alarm_text)
+            .add_system(toggle_alarms)
+            .add_system(check_alarms)
+            .add_system(log_alarms)
+            .add_system(show_alarms);
+    }
+}
+
+#[derive(Component)]
+struct AlarmText;
+
+fn setup_alarm_text(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    alarms: Res<Alarms>,
+    theme: Res<Theme>,
+) {
+    let font = asset_server.load("fonts/FiraMono-Medium.ttf");
+    let sections = alarms
+        .0
+        .iter()
+        .map(|_| TextSection {
+            value: String::new(),
+            style: TextStyle {
+                font: font.clone(),
+                font_size: 16.0,
+                color: theme.alarm_inactive,
+            },
+        })
+        .collect();
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Percent(40.0),
+                    top: Val::Px(8.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text {
+                sections,
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(AlarmText);
+}
+
+fn toggle_alarms(keys: Res<Input<KeyCode>>, mut alarms: ResMut<Alarms>) {
+    if !keys.pressed(KeyCode::LAlt) && !keys.pressed(KeyCode::RAlt) {
+        return;
+    }
+    for (alarm, key) in alarms.0.iter_mut().zip(ALARM_KEYS.iter()) {
+        if keys.just_pressed(*key) {
+            alarm.enabled = !alarm.enabled;
+            alarm.active = false;
+            info!(
+                "alarm {} {}",
+                alarm,
+                if alarm.enabled { "enabled" } else { "disabled" }
+            );
+        }
+    }
+}
+
+fn check_alarms(
+    mut ev_packet: EventReader<PacketEvent>,
+    mut ev_alarm: EventWriter<AlarmEvent>,
+    mut alarms: ResMut<Alarms>,
+) {
+    for PacketEvent(packet, _) in ev_packet.iter() {
+        for alarm in alarms.0.iter_mut() {
+            if !alarm.enabled {
+                continue;
+            }
+            let value = match alarm.kind.value(packet, alarm.unit) {
+                Some(value) => value,
+                None => continue,
+            };
+            if alarm.update(value) {
+                ev_alarm.send(AlarmEvent {
+                    alarm: alarm.clone(),
+                    value,
+                });
+            }
+        }
+    }
+}
+
+fn log_alarms(mut ev_alarm: EventReader<AlarmEvent>) {
+    for AlarmEvent { alarm, value } in ev_alarm.iter() {
+        if alarm.active {
+            warn!("alarm {}: {:.2}{}", alarm, value, alarm.symbol());
+        } else {
+            info!("alarm {} cleared: {:.2}{}", alarm, value, alarm.symbol());
+        }
+    }
+}
+
+fn show_alarms(
+    alarms: Res<Alarms>,
+    locale: Res<Locale>,
+    theme: Res<Theme>,
+    mut text: Query<&mut Text, With<AlarmText>>,
+) {
+    if !alarms.is_changed() && !theme.is_changed() {
+        return;
+    }
+    let (on, off) = (locale.text("alarm_on"), locale.text("alarm_off"));
+    let width = on.chars().count().max(off.chars().count());
+    for mut text in text.iter_mut() {
+        // one section per alarm, whose number changes when the settings file is reloaded
+        if let Some(section) = text.sections.first().cloned() {
+            text.sections.resize(alarms.0.len(), section);
+        }
+        for (i, (section, alarm)) in text.sections.iter_mut().zip(alarms.0.iter()).enumerate() {
+            let name = match alarm.kind {
+                AlarmKind::Accel => "|a|",
+                AlarmKind::Tilt => locale.text("tilt"),
+                AlarmKind::Battery => locale.text("battery"),
+            };
+            section.value = format!(
+                "{} {:<width$} {} {} {}{}\n",
+                i + 1,
+                if alarm.enabled { on } else { off },
+                name,
+                alarm.kind.comparison(),
+                alarm.threshold,
+                alarm.symbol(),
+                width = width
+            );
+            section.style.color = if !alarm.enabled {
+                theme.alarm_disabled
+            } else if alarm.active {
+                theme.alarm_active
+            } else {
+                theme.alarm_inactive
+            };
+        }
+    }
+}
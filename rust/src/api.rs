@@ -0,0 +1,313 @@
+use bevy::{app::Events, prelude::*};
+use bevy_serial::SerialPortInfo;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use crate::connect::PortCommand;
+use crate::plot::AccelSampleEvent;
+use crate::recording::{is_confined, RecordCommand, Recorder, RecorderConfig};
+use crate::units::SensorUnits;
+
+/// Clients which send no request for this long are dropped
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+/// Longest wait for the app to answer a request
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+const USAGE: &str = "\
+GET  /sample                            latest acceleration
+GET  /ports                             serial ports and the file being recorded
+POST /port/open?name=<port>&baud=<rate> open the port (baud optional)
+POST /port/close                        close the port
+POST /port/baud?rate=<rate>             reopen the port with the baud rate
+POST /recording/start?path=<file>       start recording (path optional, relative to the recording directory)
+POST /recording/stop                    stop recording
+";
+
+/// HTTP API on `http://<addr>` to control the running app remotely.
+/// Requests are turned into the `PortCommand`s and `RecordCommand`s also sent by the port selection screen,
+/// and answered with JSON (`GET /` lists the endpoints).
+pub struct ApiPlugin {
+    pub addr: String,
+}
+
+/// Request read by the server thread, answered by `handle_requests` through `reply`
+struct ApiRequest {
+    method: String,
+    path: String,
+    query: Vec<(String, String)>,
+    reply: Sender<(u16, String)>,
+}
+
+impl ApiRequest {
+    fn param(&self, name: &str) -> Option<&str> {
+        self.query
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Requests received from the server thread
+struct ApiRequests(Mutex<Receiver<ApiRequest>>);
+
+/// Latest sample in g, with the time it was received
+#[derive(Default)]
+struct LatestSample(Option<(f64, Vec3)>);
+
+impl Plugin for ApiPlugin {
+    fn build(&self, app: &mut App) {
+        let (sender, receiver) = mpsc::channel();
+        match TcpListener::bind(&self.addr) {
+            Ok(listener) => {
+                info!("serving the HTTP API on http://{}", self.addr);
+                thread::spawn(move || serve(listener, sender));
+            }
+            Err(e) => error!("failed to serve the HTTP API on {}: {}", self.addr, e),
+        }
+        app.insert_resource(ApiRequests(Mutex::new(receiver)))
+            .init_resource::<LatestSample>()
+            .add_system(keep_latest_sample)
+            .add_system(handle_requests);
+    }
+}
+
+fn keep_latest_sample(
+    mut ev_sample: EventReader<AccelSampleEvent>,
+    mut latest: ResMut<LatestSample>,
+    time: Res<Time>,
+) {
+    if let Some(AccelSampleEvent(accel)) = ev_sample.iter().last() {
+        latest.0 = Some((time.seconds_since_startup(), *accel));
+    }
+}
+
+/// Answer the requests received since the last frame.
+/// The port commands and port info are optional because replays have no ports.
+#[allow(clippy::too_many_arguments)]
+fn handle_requests(
+    requests: Res<ApiRequests>,
+    latest: Res<LatestSample>,
+    units: Res<SensorUnits>,
+    info: Option<Res<SerialPortInfo>>,
+    recorder: Option<Res<Recorder>>,
+    recorder_config: Option<Res<RecorderConfig>>,
+    mut port_commands: Option<ResMut<Events<PortCommand>>>,
+    mut record_commands: Option<ResMut<Events<RecordCommand>>>,
+    time: Res<Time>,
+) {
+    let receiver = match requests.0.lock() {
+        Ok(receiver) => receiver,
+        Err(_) => return,
+    };
+    for request in receiver.try_iter() {
+        let mut port_command = |command: PortCommand| match &mut port_commands {
+            Some(events) => {
+                events.send(command);
+                (202, r#"{"ok":true}"#.to_string())
+            }
+            None => error(409, "no serial port in this mode"),
+        };
+        let response = match (request.method.as_str(), request.path.as_str()) {
+            ("GET", "/") => (200, json_string(USAGE)),
+            ("GET", "/sample") => match latest.0 {
+                Some((received, accel)) => {
+                    let accel = accel * units.unit.in_unit(1.0);
+                    (
+                        200,
+                        format!(
+                            r#"{{"x":{},"y":{},"z":{},"unit":{},"age":{:.3}}}"#,
+                            accel.x,
+                            accel.y,
+                            accel.z,
+                            json_string(units.unit.symbol()),
+                            time.seconds_since_startup() - received
+                        ),
+                    )
+                }
+                None => error(404, "no sample received yet"),
+            },
+            ("GET", "/ports") => {
+                let ports: Vec<String> = info
+                    .iter()
+                    .flat_map(|info| info.iter())
+                    .map(|(label, state)| {
+                        format!(
+                            r#"{{"label":{},"open":{},"baud":{}}}"#,
+                            json_string(label),
+                            state.open,
+                            state.setting.baud_rate
+                        )
+                    })
+                    .collect();
+                let recording = recorder
+                    .as_ref()
+                    .map_or("null".to_string(), |r| json_string(r.path()));
+                (
+                    200,
+                    format!(r#"{{"ports":[{}],"recording":{}}}"#, ports.join(","), recording),
+                )
+            }
+            ("POST", "/port/open") => match request.param("name") {
+                Some(name) => match parse_baud(request.param("baud")) {
+                    Ok(baud_rate) => port_command(PortCommand::Open {
+                        port_name: name.to_string(),
+                        baud_rate,
+                    }),
+                    Err(response) => response,
+                },
+                None => error(400, "missing name"),
+            },
+            ("POST", "/port/close") => port_command(PortCommand::Close),
+            ("POST", "/port/baud") => match parse_baud(request.param("rate")) {
+                Ok(Some(rate)) => port_command(PortCommand::SetBaudRate(rate)),
+                Ok(None) => error(400, "missing rate"),
+                Err(response) => response,
+            },
+            ("POST", "/recording/start") | ("POST", "/recording/stop") => {
+                let command = if request.path.ends_with("start") {
+                    RecordCommand::Start(request.param("path").map(str::to_string))
+                } else {
+                    RecordCommand::Stop
+                };
+                let refused = match (&command, &recorder_config) {
+                    (RecordCommand::Start(Some(path)), Some(config)) => {
+                        !is_confined(&config.settings.path, path)
+                    }
+                    _ => false,
+                };
+                match &mut record_commands {
+                    Some(_) if refused => error(403, "path must stay in the recording directory"),
+                    Some(events) => {
+                        events.send(command);
+                        (202, r#"{"ok":true}"#.to_string())
+                    }
+                    None => error(409, "recording is not available"),
+                }
+            }
+            _ => error(404, "unknown endpoint, GET / lists them"),
+        };
+        // the client may have gone
+        let _ = request.reply.send(response);
+    }
+}
+
+fn parse_baud(value: Option<&str>) -> Result<Option<u32>, (u16, String)> {
+    value
+        .map(|v| v.parse().map_err(|_| error(400, &format!("invalid baud rate {}", v))))
+        .transpose()
+}
+
+fn error(status: u16, message: &str) -> (u16, String) {
+    (status, format!(r#"{{"error":{}}}"#, json_string(message)))
+}
+
+/// Quote a JSON string
+fn json_string(value: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn serve(listener: TcpListener, sender: Sender<ApiRequest>) {
+    for stream in listener.incoming() {
+        let result = stream.and_then(|stream| respond(stream, &sender));
+        if let Err(e) = result {
+            warn!("failed to answer API request: {}", e);
+        }
+    }
+}
+
+/// Read the request line, pass it to the app and write its answer.
+/// Headers and bodies are ignored, as all parameters are in the query.
+fn respond(mut stream: TcpStream, sender: &Sender<ApiRequest>) -> io::Result<()> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let target = parts.next().unwrap_or_default();
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let query = query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(key), percent_decode(value))
+        })
+        .collect();
+
+    let (reply, answer) = mpsc::channel();
+    let request = ApiRequest {
+        method,
+        path: path.to_string(),
+        query,
+        reply,
+    };
+    let (status, body) = if sender.send(request).is_ok() {
+        answer
+            .recv_timeout(RESPONSE_TIMEOUT)
+            .unwrap_or_else(|_| error(503, "the app did not answer"))
+    } else {
+        error(503, "the app has stopped")
+    };
+    let reason = match status {
+        200 => "OK",
+        202 => "Accepted",
+        400 => "Bad Request",
+        403 => "Forbidden",
+        404 => "Not Found",
+        409 => "Conflict",
+        503 => "Service Unavailable",
+        _ => "Unknown",
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason,
+        body.len(),
+        body
+    )
+}
+
+/// Decode `%XX` escapes and `+` of a query parameter
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                let byte = bytes
+                    .get(i + 1..i + 3)
+                    .and_then(|hex| std::str::from_utf8(hex).ok())
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+                match byte {
+                    Some(byte) => {
+                        decoded.push(byte);
+                        i += 3;
+                        continue;
+                    }
+                    None => decoded.push(b'%'),
+                }
+            }
+            b'+' => decoded.push(b' '),
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
@@ -0,0 +1,269 @@
+use std::str::FromStr;
+
+use crate::alarms::Alarm;
+use crate::audio::AudioMode;
+use crate::axes::AxisMapping;
+use crate::frame::Frame;
+use crate::locale::Language;
+use crate::model::Model;
+use crate::orientation::OrientationSource;
+use crate::osc::OscSettings;
+use crate::plotter::{PlotterOutput, TELEPLOT_PORT};
+use crate::recording::RecorderSettings;
+use crate::ros::RosSettings;
+use crate::settings::DEFAULT_SETTINGS_PATH;
+use crate::smoothing::SmoothingMode;
+use crate::theme::ThemeName;
+use crate::units::{AccelRange, AccelUnit};
+
+const USAGE: &str = "\
+options:
+  --axes <mapping>        device axes of world x, y and z like y,-x,z (default x,y,z)
+  --model <model>         board, cube, phone, or a .gltf/.glb file (default board)
+  --model-scale <s>       scale of a glTF model, e.g. 100 for models in meters (default 1)
+  --alarm <alarm>         alarm like accel:3 (|a| > 3 g), tilt:45:5 (tilt > 45 degrees, 5 degrees hysteresis)
+                          or battery:3.5 (battery < 3.5 V), can be repeated (default accel:3 and tilt:45)
+  --audio <tilt|shake>    play tones higher with more tilt, or a click per shake, and a sound on alarms (K mutes)
+  --playground            move the model through a room by the position integrated from the acceleration
+  --haptics               send vibrate commands to the device, e.g. when the model hits a wall of the playground
+  --format <format>       packet format: space (default), csv, binary f32, binary i16, binary f32 status,
+                          binary i16 status, binary f32 mag, binary i16 mag, binary f32 quat or binary i16 quat
+  --gen-arduino <format>  print the C header with the struct and framing of the binary format for the firmware,
+                          and exit
+  --settings <file>       settings file, created with the defaults if missing (default settings.toml)
+  --range <range>         accelerometer range: 2g, 4g, 8g or 16g (overrides the settings file)
+  --unit <unit>           acceleration unit sent by the device and shown: g or m/s2 (overrides the settings file)
+  --lang <language>       language of the viewer: en or es (overrides the settings file)
+  --theme <theme>         colors of the viewer: default, high_contrast or color_blind (overrides the settings file)
+  --orientation <source>  follow the quaternion of devices sending one (device) or the filter of the viewer (host),
+                          for devices without their own setting (overrides the settings file)
+  --frame <frame>         earth frame of the rotations written out and of the quaternions of devices without their
+                          own frame: nwu (x north, y west, z up, default), enu or ned (overrides the settings file)
+  --smoothing <mode>      smoothing of the model: off (default), slerp or damped (a critically damped spring,
+                          smoother for the same lag), with --smoothing-time <s> seconds to catch up (default 0.1)
+                          (override the settings file)
+  --split-window          show the 3D view in a window of its own, with the plots, console and panels in the main
+                          window (like split = true in [windows] of the settings file)
+  --tune                  open an inspector window to tune the low-pass cutoff, the beta of the Madgwick filter and
+                          the alarm thresholds while data streams
+  --port <name>           open this serial port instead of choosing one on the selection screen
+  --headless              capture from --port without a window, logging a summary every second (with --record to save it)
+  --exit-on-error         in headless mode, exit with code 2 if the port can't be opened or 3 if it fails, instead of retrying
+  --watchdog <file>       in headless mode, rewrite the file every second while samples arrive
+  --metrics <addr>        in headless mode, serve the serial stats for Prometheus on http://<addr>/metrics, e.g. 0.0.0.0:9184
+  --record <file>         record the serial data with timestamps
+  --api <addr>            serve an HTTP API on <addr> (e.g. 127.0.0.1:8080) to open or close the port, change the baud rate,
+                          start or stop recording and fetch the latest sample
+  --rosbridge <url>       publish sensor_msgs/msg/Imu through the rosbridge websocket, e.g. ws://localhost:9090
+  --ros-topic <topic>     topic of the IMU messages (default /imu)
+  --ros-frame <frame>     frame_id of the IMU messages (default imu_link)
+  --osc <host:port>       send OSC messages of the samples over UDP, e.g. 127.0.0.1:9000
+  --osc-rate <hz>         messages per second of each OSC address (default 30)
+  --osc-map <mapping>     OSC address of each channel like accel=/accel,roll=/tilt/roll, from accel, gyro,
+                          temperature, battery, roll, pitch and magnitude (default all but magnitude at /<channel>)
+  --teleplot <host:port>  send the samples to Teleplot over UDP (default 127.0.0.1:47269)
+  --serial-plotter        print the samples to stdout in the Arduino Serial Plotter format
+  --stream <addr>         stream the parsed packets to TCP clients of <addr> as length-prefixed protobuf (proto/accel.proto)
+  --record-max-mb <mb>    start a new recording file at this size
+  --record-max-secs <s>   start a new recording file after this many seconds
+  --record-gzip           compress rotated recording files
+  --record-fsync <s>      sync the recording to disk at this interval (default 1)
+  --replay <file>         replay a recording (.gz too), or a CSV, PuTTY or SavvyCAN log, instead of opening the serial port
+  --replay-step <s>       replay this many seconds of the recording per frame instead of following the wall clock,
+                          for the same output on every run";
+
+/// Command line options
+pub struct Args {
+    pub record: Option<RecorderSettings>,
+    pub replay: Option<String>,
+    pub replay_step: Option<f64>,
+    pub port: Option<String>,
+    pub headless: bool,
+    pub exit_on_error: bool,
+    pub watchdog: Option<String>,
+    pub metrics: Option<String>,
+    pub api: Option<String>,
+    pub stream: Option<String>,
+    pub ros: Option<RosSettings>,
+    pub osc: Option<OscSettings>,
+    pub plotter: Vec<PlotterOutput>,
+    pub axes: Option<AxisMapping>,
+    pub model: Model,
+    pub model_scale: f32,
+    pub alarms: Vec<Alarm>,
+    pub audio: Option<AudioMode>,
+    pub playground: bool,
+    pub haptics: bool,
+    pub format: Option<String>,
+    pub gen_arduino: Option<String>,
+    pub settings: String,
+    pub range: Option<AccelRange>,
+    pub unit: Option<AccelUnit>,
+    pub language: Option<Language>,
+    pub theme: Option<ThemeName>,
+    pub orientation: Option<OrientationSource>,
+    pub frame: Option<Frame>,
+    pub smoothing: Option<SmoothingMode>,
+    pub smoothing_time: Option<f32>,
+    pub split_window: bool,
+    pub tune: bool,
+}
+
+impl Default for Args {
+    fn default() -> Self {
+        Args {
+            record: None,
+            replay: None,
+            replay_step: None,
+            port: None,
+            headless: false,
+            exit_on_error: false,
+            watchdog: None,
+            metrics: None,
+            api: None,
+            stream: None,
+            ros: None,
+            osc: None,
+            plotter: Vec::new(),
+            axes: None,
+            model: Model::default(),
+            model_scale: 1.0,
+            alarms: Vec::new(),
+            audio: None,
+            playground: false,
+            haptics: false,
+            format: None,
+            gen_arduino: None,
+            settings: DEFAULT_SETTINGS_PATH.to_string(),
+            range: None,
+            unit: None,
+            language: None,
+            theme: None,
+            orientation: None,
+            frame: None,
+            smoothing: None,
+            smoothing_time: None,
+            split_window: false,
+            tune: false,
+        }
+    }
+}
+
+impl Args {
+    pub fn parse() -> Args {
+        let mut args = Args::default();
+        let mut record = RecorderSettings::default();
+        let mut recording = false;
+        let mut ros = RosSettings::default();
+        let mut publishing = false;
+        let mut osc = OscSettings::default();
+        let mut sending_osc = false;
+        let mut iter = std::env::args().skip(1);
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--record" => {
+                    recording = true;
+                    record.path = iter.next().unwrap_or(record.path);
+                }
+                "--record-max-mb" => {
+                    record.max_bytes = value::<f64>(&arg, iter.next()).map(|mb| (mb * 1e6) as u64)
+                }
+                "--record-max-secs" => record.max_seconds = value(&arg, iter.next()),
+                "--record-gzip" => record.gzip = true,
+                "--record-fsync" => {
+                    if let Some(interval) = value(&arg, iter.next()) {
+                        record.fsync_interval = interval;
+                    }
+                }
+                "--replay" => args.replay = iter.next(),
+                "--replay-step" => args.replay_step = value(&arg, iter.next()),
+                "--port" => args.port = iter.next(),
+                "--headless" => args.headless = true,
+                "--exit-on-error" => args.exit_on_error = true,
+                "--watchdog" => args.watchdog = iter.next(),
+                "--metrics" => args.metrics = iter.next(),
+                "--api" => args.api = iter.next(),
+                "--stream" => args.stream = iter.next(),
+                "--rosbridge" => {
+                    publishing = true;
+                    ros.url = iter.next().unwrap_or(ros.url);
+                }
+                "--ros-topic" => ros.topic = iter.next().unwrap_or(ros.topic),
+                "--ros-frame" => ros.frame_id = iter.next().unwrap_or(ros.frame_id),
+                "--osc" => {
+                    sending_osc = true;
+                    osc.target = iter.next().unwrap_or(osc.target);
+                }
+                "--osc-rate" => {
+                    if let Some(rate) = value(&arg, iter.next()) {
+                        osc.rate = rate;
+                    }
+                }
+                "--osc-map" => {
+                    if let Some(mapping) = value(&arg, iter.next()) {
+                        osc.mapping = mapping;
+                    }
+                }
+                "--teleplot" => {
+                    let target = iter
+                        .next()
+                        .unwrap_or_else(|| format!("127.0.0.1:{}", TELEPLOT_PORT));
+                    args.plotter.push(PlotterOutput::Teleplot(target));
+                }
+                "--serial-plotter" => args.plotter.push(PlotterOutput::SerialPlotter),
+                "--model" => {
+                    if let Some(model) = value(&arg, iter.next()) {
+                        args.model = model;
+                    }
+                }
+                "--model-scale" => {
+                    if let Some(scale) = value(&arg, iter.next()) {
+                        args.model_scale = scale;
+                    }
+                }
+                "--alarm" => {
+                    if let Some(alarm) = value(&arg, iter.next()) {
+                        args.alarms.push(alarm);
+                    }
+                }
+                "--audio" => args.audio = value(&arg, iter.next()),
+                "--playground" => args.playground = true,
+                "--haptics" => args.haptics = true,
+                "--format" => args.format = iter.next(),
+                "--gen-arduino" => args.gen_arduino = iter.next(),
+                "--settings" => args.settings = iter.next().unwrap_or(args.settings),
+                "--range" => args.range = value(&arg, iter.next()),
+                "--unit" => args.unit = value(&arg, iter.next()),
+                "--lang" => args.language = value(&arg, iter.next()),
+                "--theme" => args.theme = value(&arg, iter.next()),
+                "--orientation" => args.orientation = value(&arg, iter.next()),
+                "--frame" => args.frame = value(&arg, iter.next()),
+                "--smoothing" => args.smoothing = value(&arg, iter.next()),
+                "--smoothing-time" => args.smoothing_time = value(&arg, iter.next()),
+                "--split-window" => args.split_window = true,
+                "--tune" => args.tune = true,
+                "--axes" => args.axes = value(&arg, iter.next()),
+                _ => eprintln!("unknown argument {}\n{}", arg, USAGE),
+            }
+        }
+        if recording {
+            args.record = Some(record);
+        }
+        if publishing {
+            args.ros = Some(ros);
+        }
+        if sending_osc {
+            args.osc = Some(osc);
+        }
+        args
+    }
+}
+
+/// Parse the value of an option, printing the usage if it is missing or invalid
+fn value<T: FromStr>(option: &str, value: Option<String>) -> Option<T> {
+    let parsed = value.as_deref().and_then(|v| v.parse().ok());
+    if parsed.is_none() {
+        eprintln!("invalid value {:?} for {}\n{}", value, option, USAGE);
+    }
+    parsed
+}
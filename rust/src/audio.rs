@@ -0,0 +1,129 @@
+use bevy::prelude::*;
+use std::str::FromStr;
+
+use crate::alarms::AlarmEvent;
+use crate::plot::AccelSampleEvent;
+
+/// Number of tones `sounds/tone_<i>.wav` (a C major scale)
+const TONE_COUNT: usize = 8;
+/// Interval between tones in tilt mode in seconds
+const TONE_INTERVAL: f64 = 0.2;
+/// Tilt mapped to the highest tone in degrees
+const MAX_TILT: f32 = 90.0;
+/// Deviation from 1 g detected as a shake in g
+const SHAKE_THRESHOLD: f32 = 0.5;
+/// Time after a click in which no other click is played in seconds
+const SHAKE_REFRACTORY: f64 = 0.15;
+
+/// What the sound is driven by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioMode {
+    /// Play a tone periodically, higher while the device is tilted more
+    Tilt,
+    /// Play a click on each shake or step
+    Shake,
+}
+
+impl FromStr for AudioMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tilt" => Ok(AudioMode::Tilt),
+            "shake" => Ok(AudioMode::Shake),
+            _ => Err(format!("unknown audio mode {}", s)),
+        }
+    }
+}
+
+/// Sound driven by the sensor data for demos and accessibility, and a sound on alarms.
+/// K mutes and unmutes.
+pub struct AudioFeedbackPlugin {
+    pub mode: AudioMode,
+}
+
+struct AudioFeedback {
+    mode: AudioMode,
+    muted: bool,
+    tones: Vec<Handle<AudioSource>>,
+    click: Handle<AudioSource>,
+    alarm: Handle<AudioSource>,
+    /// Tilt of the last sample in degrees
+    tilt: f32,
+    /// When the last tone or click was played
+    last_played: f64,
+    shaking: bool,
+}
+
+impl Plugin for AudioFeedbackPlugin {
+    fn build(&self, app: &mut App) {
+        let asset_server = app.world.get_resource::<AssetServer>().unwrap();
+        let feedback = AudioFeedback {
+            mode: self.mode,
+            muted: false,
+            tones: (0..TONE_COUNT)
+                .map(|i| asset_server.load(format!("sounds/tone_{}.wav", i).as_str()))
+                .collect(),
+            click: asset_server.load("sounds/click.wav"),
+            alarm: asset_server.load("sounds/alarm.wav"),
+            tilt: 0.0,
+            last_played: 0.0,
+            shaking: false,
+        };
+        app.insert_resource(feedback)
+            .add_system(toggle_mute)
+            .add_system(play_feedback)
+            .add_system(play_alarms);
+    }
+}
+
+fn toggle_mute(keys: Res<Input<KeyCode>>, mut feedback: ResMut<AudioFeedback>) {
+    if keys.just_pressed(KeyCode::K) {
+        feedback.muted = !feedback.muted;
+        info!("audio {}", if feedback.muted { "muted" } else { "unmuted" });
+    }
+}
+
+fn play_feedback(
+    mut ev_sample: EventReader<AccelSampleEvent>,
+    mut feedback: ResMut<AudioFeedback>,
+    audio: Res<Audio>,
+    time: Res<Time>,
+) {
+    let now = time.seconds_since_startup();
+    for AccelSampleEvent(accel) in ev_sample.iter() {
+        feedback.tilt = accel.angle_between(Vec3::Z).to_degrees();
+        // click once when the magnitude leaves 1 g
+        let shaking = (accel.length() - 1.0).abs() > SHAKE_THRESHOLD;
+        if feedback.mode == AudioMode::Shake
+            && shaking
+            && !feedback.shaking
+            && now - feedback.last_played > SHAKE_REFRACTORY
+        {
+            if !feedback.muted {
+                audio.play(feedback.click.clone());
+            }
+            feedback.last_played = now;
+        }
+        feedback.shaking = shaking;
+    }
+
+    if feedback.mode == AudioMode::Tilt && now - feedback.last_played > TONE_INTERVAL {
+        if !feedback.muted && feedback.tilt.is_finite() {
+            let ratio = (feedback.tilt / MAX_TILT).clamp(0.0, 1.0);
+            let tone = (ratio * (TONE_COUNT - 1) as f32).round() as usize;
+            audio.play(feedback.tones[tone].clone());
+        }
+        feedback.last_played = now;
+    }
+}
+
+fn play_alarms(
+    mut ev_alarm: EventReader<AlarmEvent>,
+    feedback: Res<AudioFeedback>,
+    audio: Res<Audio>,
+) {
+    if ev_alarm.iter().any(|ev| ev.alarm.active) && !feedback.muted {
+        audio.play(feedback.alarm.clone());
+    }
+}
@@ -0,0 +1,146 @@
+use bevy::prelude::*;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+
+const AXIS_NAMES: [char; 3] = ['x', 'y', 'z'];
+/// Keys to change the mapping of world x, y and z
+const AXIS_KEYS: [KeyCode; 3] = [KeyCode::F1, KeyCode::F2, KeyCode::F3];
+
+/// Mapping of device axes to world axes, because every accelerometer breakout is mounted differently.
+/// World axis `i` is device axis `axes[i]` multiplied by `signs[i]`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AxisMapping {
+    pub axes: [usize; 3],
+    pub signs: [f32; 3],
+}
+
+impl Default for AxisMapping {
+    fn default() -> Self {
+        AxisMapping {
+            axes: [0, 1, 2],
+            signs: [1.0; 3],
+        }
+    }
+}
+
+impl AxisMapping {
+    /// Device acceleration in world axes
+    pub fn apply(&self, v: Vec3) -> Vec3 {
+        Vec3::new(
+            v[self.axes[0]] * self.signs[0],
+            v[self.axes[1]] * self.signs[1],
+            v[self.axes[2]] * self.signs[2],
+        )
+    }
+
+    /// Rotation of the device in the earth frame, e.g. fused on the device, turned to start from the world axes.
+    /// A mapping with an odd number of flips mirrors, which a rotation can't, so all the axes are flipped back for it.
+    pub fn apply_rotation(&self, rotation: Quat) -> Quat {
+        let mut columns = [Vec3::ZERO; 3];
+        for (world, column) in columns.iter_mut().enumerate() {
+            column[self.axes[world]] = self.signs[world];
+        }
+        // from world axes to device axes
+        let mut inverse = Mat3::from_cols(columns[0], columns[1], columns[2]);
+        if inverse.determinant() < 0.0 {
+            inverse = inverse * -1.0;
+        }
+        rotation * Quat::from_mat3(&inverse)
+    }
+
+    /// Use the next device axis for the world axis, swapping it with the world axis which used it
+    fn cycle(&mut self, world: usize) {
+        let next = (self.axes[world] + 1) % 3;
+        if let Some(other) = self.axes.iter().position(|&axis| axis == next) {
+            self.axes.swap(world, other);
+            self.signs.swap(world, other);
+        }
+    }
+}
+
+/// Written as the device axes of world x, y and z, e.g. `y,-x,z`
+impl fmt::Display for AxisMapping {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for world in 0..3 {
+            if world > 0 {
+                write!(f, ",")?;
+            }
+            if self.signs[world] < 0.0 {
+                write!(f, "-")?;
+            }
+            write!(f, "{}", AXIS_NAMES[self.axes[world]])?;
+        }
+        Ok(())
+    }
+}
+
+/// Saved like `y,-x,z` in the settings file, as on the command line
+impl Serialize for AxisMapping {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for AxisMapping {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+impl FromStr for AxisMapping {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut mapping = AxisMapping::default();
+        let parts: Vec<&str> = s.split(',').map(str::trim).collect();
+        if parts.len() != 3 {
+            return Err(format!("expected 3 axes like y,-x,z but got {}", s));
+        }
+        for (world, part) in parts.iter().enumerate() {
+            let (sign, name) = match part.strip_prefix('-') {
+                Some(name) => (-1.0, name),
+                None => (1.0, *part),
+            };
+            mapping.axes[world] = AXIS_NAMES
+                .iter()
+                .position(|axis| name == axis.to_string())
+                .ok_or_else(|| format!("unknown axis {}", part))?;
+            mapping.signs[world] = sign;
+        }
+        let mut used = mapping.axes;
+        used.sort_unstable();
+        if used != [0, 1, 2] {
+            return Err(format!("each axis should be used once in {}", s));
+        }
+        Ok(mapping)
+    }
+}
+
+/// Change the axis mapping with F1/F2/F3 (next device axis for world x/y/z),
+/// and flip the sign with Shift + F1/F2/F3
+pub struct AxisMappingPlugin {
+    pub mapping: AxisMapping,
+}
+
+impl Plugin for AxisMappingPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.mapping).add_system(change_mapping);
+    }
+}
+
+fn change_mapping(keys: Res<Input<KeyCode>>, mut mapping: ResMut<AxisMapping>) {
+    let shift = keys.pressed(KeyCode::LShift) || keys.pressed(KeyCode::RShift);
+    for (world, key) in AXIS_KEYS.iter().enumerate() {
+        if keys.just_pressed(*key) {
+            if shift {
+                mapping.signs[world] = -mapping.signs[world];
+            } else {
+                mapping.cycle(world);
+            }
+            info!("axis mapping {}", *mapping);
+        }
+    }
+}
@@ -0,0 +1,246 @@
+//! Decode recordings and captures to CSV without the viewer, e.g. to batch process a folder of captures:
+//! `capture2csv [--format <format>] [--range <range>] [--unit <unit>] [--out <dir>] <file>...`
+//! writes `<name>.decoded.csv` next to each file (or in `--out`), with one row per packet.
+
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::str::FromStr;
+
+use bevy_accel::import;
+use bevy_accel::parser::{Packet, ParserRegistry};
+use bevy_accel::recording::Recording;
+use bevy_accel::units::{AccelRange, AccelUnit, SensorUnits};
+
+const USAGE: &str = "\
+usage: capture2csv [options] <file>...
+  decode recordings of the viewer (or captures it can replay) to <name>.decoded.csv, one row per packet
+
+options:
+  --format <format>  packet format, like --format of the viewer (default the format of the recording, or detected)
+  --range <range>    accelerometer range: 2g, 4g, 8g or 16g (default the range of the recording, or 2g)
+  --unit <unit>      acceleration unit of the device and the output: g or m/s2 (default the unit of the recording, or g)
+  --out <dir>        directory of the CSV files (default next to each file)";
+
+/// Seconds from the start of a recording inspected to detect its format, like the viewer
+const DETECT_SECONDS: f64 = 2.0;
+/// Bytes inspected at most
+const MAX_BYTES: usize = 8192;
+
+/// Options of the command line, `None` taking the value from the header of each recording
+#[derive(Debug, Default)]
+struct Options {
+    format: Option<String>,
+    range: Option<AccelRange>,
+    unit: Option<AccelUnit>,
+    out: Option<PathBuf>,
+    files: Vec<PathBuf>,
+}
+
+fn main() {
+    let options = parse_args();
+    if options.files.is_empty() {
+        eprintln!("{}", USAGE);
+        process::exit(1);
+    }
+    if let Some(out) = &options.out {
+        if let Err(e) = fs::create_dir_all(out) {
+            eprintln!("failed to create {}: {}", out.display(), e);
+            process::exit(1);
+        }
+    }
+    let mut failed = 0;
+    for path in options.files.iter() {
+        match convert(path, &options) {
+            Ok((output, packets, format)) => {
+                println!(
+                    "{}: {} packets of {} to {}",
+                    path.display(),
+                    packets,
+                    format,
+                    output.display()
+                )
+            }
+            Err(e) => {
+                eprintln!("{}: {}", path.display(), e);
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        eprintln!("{} of {} files failed", failed, options.files.len());
+        process::exit(1);
+    }
+}
+
+fn parse_args() -> Options {
+    let mut options = Options::default();
+    let mut iter = std::env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--format" => options.format = Some(required(&arg, iter.next())),
+            "--range" => options.range = Some(value(&arg, iter.next())),
+            "--unit" => options.unit = Some(value(&arg, iter.next())),
+            "--out" => options.out = Some(required(&arg, iter.next()).into()),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            _ if arg.starts_with("--") => {
+                eprintln!("unknown argument {}\n{}", arg, USAGE);
+                process::exit(1);
+            }
+            _ => options.files.push(arg.into()),
+        }
+    }
+    options
+}
+
+/// The value of an option, exiting with the usage if it is missing
+fn required(option: &str, value: Option<String>) -> String {
+    value.unwrap_or_else(|| {
+        eprintln!("missing value for {}\n{}", option, USAGE);
+        process::exit(1);
+    })
+}
+
+/// Parse the value of an option, exiting with the usage if it is missing or invalid.
+/// Unlike the viewer, a batch run stops rather than converting every file with the wrong units.
+fn value<T: FromStr>(option: &str, value: Option<String>) -> T {
+    let value = required(option, value);
+    value.parse().unwrap_or_else(|_| {
+        eprintln!("invalid value {:?} for {}\n{}", value, option, USAGE);
+        process::exit(1);
+    })
+}
+
+/// Decode a file, returning the CSV file written, the number of packets and the format
+fn convert(path: &Path, options: &Options) -> io::Result<(PathBuf, usize, String)> {
+    let recording = import::load(path)?;
+    let header = &recording.header;
+    // like a replay, the options override the header of the recording
+    let units = SensorUnits {
+        range: options.range.or(header.range).unwrap_or_default(),
+        unit: options.unit.or(header.unit).unwrap_or_default(),
+    };
+    let mut registry = ParserRegistry::default();
+    registry.set_units(units);
+    let format = match options.format.as_deref().or(header.format.as_deref()) {
+        Some(format) => format.to_string(),
+        None => detect(&registry, &recording)?,
+    };
+    if !registry.select(&format) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "unknown format {}, choose from {}",
+                format,
+                registry.names().collect::<Vec<_>>().join(", ")
+            ),
+        ));
+    }
+
+    let output = output_path(path, options.out.as_deref());
+    let mut writer = BufWriter::new(File::create(&output)?);
+    write_header(&mut writer, units.unit)?;
+    let mut packets = Vec::new();
+    let mut count = 0;
+    for chunk in recording.chunks.iter() {
+        registry.parse(&chunk.bytes, &mut packets);
+        // the packets completed by a chunk have its time, as in the viewer
+        for packet in packets.drain(..) {
+            write_row(&mut writer, chunk.time, &packet, units.unit)?;
+            count += 1;
+        }
+    }
+    writer.flush()?;
+    if registry.rejected() > 0 {
+        eprintln!(
+            "{}: {} malformed packets skipped",
+            path.display(),
+            registry.rejected()
+        );
+    }
+    Ok((output, count, format))
+}
+
+/// The format with the highest score for the first seconds of the recording
+fn detect(registry: &ParserRegistry, recording: &Recording) -> io::Result<String> {
+    let start = recording.chunks.first().map_or(0.0, |chunk| chunk.time);
+    let mut bytes = Vec::new();
+    for chunk in recording.chunks.iter() {
+        if chunk.time - start >= DETECT_SECONDS || bytes.len() >= MAX_BYTES {
+            break;
+        }
+        bytes.extend_from_slice(&chunk.bytes);
+    }
+    match registry.detect(&bytes) {
+        Some((format, _)) => Ok(format.to_string()),
+        None => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "no known format in the first {} bytes, give one with --format",
+                bytes.len()
+            ),
+        )),
+    }
+}
+
+/// `<name>.decoded.csv` in the directory, or next to the file, without the `.gz` and extension of the file
+fn output_path(path: &Path, out: Option<&Path>) -> PathBuf {
+    let name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("capture");
+    let name = name.strip_suffix(".gz").unwrap_or(name);
+    let stem = Path::new(name)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or(name);
+    let directory = out
+        .or_else(|| path.parent())
+        .unwrap_or_else(|| Path::new(""));
+    directory.join(format!("{}.decoded.csv", stem))
+}
+
+fn write_header(writer: &mut impl Write, unit: AccelUnit) -> io::Result<()> {
+    writeln!(
+        writer,
+        "time,x ({0}),y ({0}),z ({0}),gx (deg/s),gy (deg/s),gz (deg/s),mx (uT),my (uT),mz (uT),qw,qx,qy,qz,\
+         temperature (C),battery (V)",
+        unit.symbol()
+    )
+}
+
+/// One row of the packet, in the axes of the device, with empty columns for the values the format doesn't have
+fn write_row(
+    writer: &mut impl Write,
+    time: f64,
+    packet: &Packet,
+    unit: AccelUnit,
+) -> io::Result<()> {
+    let accel = packet.accel.to_array().map(|g| unit.in_unit(g));
+    let gyro = optional(packet.gyro.map(|g| g.to_array()));
+    let mag = optional(packet.mag.map(|m| m.to_array()));
+    let quat = optional(packet.quat.map(|q| [q.w, q.x, q.y, q.z]));
+    let temperature = optional(packet.temperature.map(|t| [t]));
+    let battery = optional(packet.battery.map(|b| [b]));
+    writeln!(
+        writer,
+        "{:.6},{},{},{},{},{},{},{},{}",
+        time, accel[0], accel[1], accel[2], gyro, mag, quat, temperature, battery
+    )
+}
+
+/// Comma separated values, or as many empty columns
+fn optional<const N: usize>(values: Option<[f32; N]>) -> String {
+    match values {
+        Some(values) => values
+            .iter()
+            .map(|v| v.to_string())
+            .collect::<Vec<_>>()
+            .join(","),
+        None => ",".repeat(N - 1),
+    }
+}
@@ -0,0 +1,198 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::locale::Locale;
+use crate::parser::PacketEvent;
+use crate::settings::SettingsFile;
+
+/// Fewest magnetometer samples to finish the calibration with
+const MIN_SAMPLES: usize = 200;
+
+/// Hard-iron offset and soft-iron scale of the magnetometer, in world axes,
+/// so the calibrated field is `(mag - offset) * scale`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MagCalibration {
+    /// Field of the magnets and the iron moving with the device, in microtesla
+    pub offset: [f32; 3],
+    /// Scale of each axis to make the ellipsoid of the field a sphere
+    pub scale: [f32; 3],
+}
+
+impl Default for MagCalibration {
+    fn default() -> Self {
+        MagCalibration {
+            offset: [0.0; 3],
+            scale: [1.0; 3],
+        }
+    }
+}
+
+impl MagCalibration {
+    pub fn apply(&self, mag: Vec3) -> Vec3 {
+        (mag - Vec3::from(self.offset)) * Vec3::from(self.scale)
+    }
+
+    /// Center and radii of the box around the samples, with the radii scaled to their mean
+    fn from_bounds(min: Vec3, max: Vec3) -> Self {
+        let radii = (max - min) / 2.0;
+        let mean = (radii.x + radii.y + radii.z) / 3.0;
+        MagCalibration {
+            offset: ((max + min) / 2.0).into(),
+            scale: (Vec3::splat(mean) / radii).into(),
+        }
+    }
+}
+
+/// Calibration wizard: `N` starts collecting the magnetometer while the device is rotated through every orientation,
+/// `N` again finishes and saves the calibration to the settings file, `Shift` + `N` cancels it or clears the calibration
+pub struct CalibrationPlugin {
+    pub calibration: MagCalibration,
+}
+
+impl Plugin for CalibrationPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.calibration)
+            .init_resource::<MagWizard>()
+            .add_startup_system(setup_wizard_text)
+            .add_system(control_wizard)
+            .add_system(collect_samples)
+            .add_system(show_wizard);
+    }
+}
+
+/// Bounds of the magnetic field while the wizard is collecting
+#[derive(Default)]
+struct MagWizard {
+    collecting: bool,
+    min: Vec3,
+    max: Vec3,
+    count: usize,
+}
+
+#[derive(Component)]
+struct WizardText;
+
+fn setup_wizard_text(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(8.0),
+                    top: Val::Px(40.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraMono-Medium.ttf"),
+                    font_size: 16.0,
+                    color: Color::WHITE,
+                },
+                Default::default(),
+            ),
+            visibility: Visibility { is_visible: false },
+            ..Default::default()
+        })
+        .insert(WizardText);
+}
+
+fn control_wizard(
+    keys: Res<Input<KeyCode>>,
+    mut wizard: ResMut<MagWizard>,
+    mut calibration: ResMut<MagCalibration>,
+    settings: Option<ResMut<SettingsFile>>,
+) {
+    if !keys.just_pressed(KeyCode::N) {
+        return;
+    }
+    let shift = keys.pressed(KeyCode::LShift) || keys.pressed(KeyCode::RShift);
+    let next = match (wizard.collecting, shift) {
+        (false, false) => {
+            info!("magnetometer calibration started, rotate the device through every orientation");
+            *wizard = MagWizard {
+                collecting: true,
+                ..Default::default()
+            };
+            return;
+        }
+        (true, true) => {
+            info!("magnetometer calibration cancelled");
+            wizard.collecting = false;
+            return;
+        }
+        (false, true) => {
+            info!("magnetometer calibration cleared");
+            MagCalibration::default()
+        }
+        (true, false) => {
+            let span = wizard.max - wizard.min;
+            if wizard.count < MIN_SAMPLES || span.min_element() <= 0.0 {
+                warn!(
+                    "{} magnetometer samples spanning {:.1} µT, keep rotating the device",
+                    wizard.count, span
+                );
+                return;
+            }
+            wizard.collecting = false;
+            let next = MagCalibration::from_bounds(wizard.min, wizard.max);
+            info!(
+                "magnetometer calibrated from {} samples: offset {:?} µT, scale {:?}",
+                wizard.count, next.offset, next.scale
+            );
+            next
+        }
+    };
+    *calibration = next;
+
+    if let Some(mut settings) = settings {
+        settings.settings.magnetometer = next;
+        settings.save();
+    }
+}
+
+fn collect_samples(mut ev_packet: EventReader<PacketEvent>, mut wizard: ResMut<MagWizard>) {
+    // read every frame so old packets aren't collected when the wizard starts
+    let samples: Vec<Vec3> = ev_packet.iter().filter_map(|ev| ev.0.mag).collect();
+    if !wizard.collecting {
+        return;
+    }
+    for mag in samples {
+        if wizard.count == 0 {
+            wizard.min = mag;
+            wizard.max = mag;
+        }
+        wizard.min = wizard.min.min(mag);
+        wizard.max = wizard.max.max(mag);
+        wizard.count += 1;
+    }
+}
+
+fn show_wizard(
+    wizard: Res<MagWizard>,
+    locale: Res<Locale>,
+    mut text: Query<(&mut Text, &mut Visibility), With<WizardText>>,
+) {
+    if !wizard.is_changed() {
+        return;
+    }
+    let span = wizard.max - wizard.min;
+    for (mut text, mut visibility) in text.iter_mut() {
+        visibility.is_visible = wizard.collecting;
+        text.sections[0].value = format!(
+            "{}\n{}",
+            locale.fill("mag_calibration", &[&wizard.count, &MIN_SAMPLES]),
+            locale.fill(
+                "mag_span",
+                &[
+                    &format!("{:.0}", span.x),
+                    &format!("{:.0}", span.y),
+                    &format!("{:.0}", span.z)
+                ]
+            )
+        );
+    }
+}
@@ -0,0 +1,99 @@
+use bevy::prelude::*;
+
+use crate::locale::Locale;
+use crate::orientation::Orientation;
+
+/// Characters of the compass tape, an odd number so the heading is in the middle
+const TAPE_WIDTH: i32 = 37;
+/// Degrees per character of the tape
+const TAPE_STEP: i32 = 5;
+
+/// Compass tape of the heading at the bottom, shown while the packets have the magnetic field
+pub struct CompassPlugin;
+
+impl Plugin for CompassPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(setup_compass)
+            .add_system(update_compass);
+    }
+}
+
+#[derive(Component)]
+struct CompassText;
+
+fn setup_compass(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Percent(40.0),
+                    bottom: Val::Px(8.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraMono-Medium.ttf"),
+                    font_size: 16.0,
+                    color: Color::WHITE,
+                },
+                Default::default(),
+            ),
+            visibility: Visibility { is_visible: false },
+            ..Default::default()
+        })
+        .insert(CompassText);
+}
+
+fn update_compass(
+    orientation: Res<Orientation>,
+    locale: Res<Locale>,
+    mut text: Query<(&mut Text, &mut Visibility), With<CompassText>>,
+) {
+    if !orientation.is_changed() {
+        return;
+    }
+    let points: Vec<&str> = locale.text("compass_points").split_whitespace().collect();
+    for (mut text, mut visibility) in text.iter_mut() {
+        visibility.is_visible = orientation.heading.is_some();
+        if let Some(heading) = orientation.heading {
+            let degrees = heading.to_degrees();
+            text.sections[0].value = format!(
+                "{}\n{:>width$} {:.0}° {}",
+                tape(degrees, &points),
+                "^",
+                degrees,
+                point(degrees, &points),
+                width = (TAPE_WIDTH / 2 + 1) as usize
+            );
+        }
+    }
+}
+
+/// Ticks every 15° and the compass points every 45° around the heading in degrees
+fn tape(heading: f32, points: &[&str]) -> String {
+    let center = (heading / TAPE_STEP as f32).round() as i32 * TAPE_STEP;
+    let angle = |i: i32| (center + (i - TAPE_WIDTH / 2) * TAPE_STEP).rem_euclid(360);
+    let mut tape: Vec<char> = (0..TAPE_WIDTH)
+        .map(|i| if angle(i) % 15 == 0 { '|' } else { '·' })
+        .collect();
+    for i in 0..TAPE_WIDTH {
+        if angle(i) % 45 == 0 {
+            for (j, c) in point(angle(i) as f32, points).chars().enumerate() {
+                if let Some(slot) = tape.get_mut(i as usize + j) {
+                    *slot = c;
+                }
+            }
+        }
+    }
+    tape.into_iter().collect()
+}
+
+/// Nearest of the eight compass points to the heading in degrees
+fn point<'a>(heading: f32, points: &[&'a str]) -> &'a str {
+    let index = (heading / 45.0).round() as usize % 8;
+    points.get(index).copied().unwrap_or_default()
+}
@@ -0,0 +1,372 @@
+use bevy::prelude::*;
+use bevy_serial::{
+    enumerate_ports, SerialErrorEvent, SerialPortDescriptor, SerialPortInfo, SerialPortType,
+    SerialPorts, SerialSetting,
+};
+use std::time::Duration;
+
+use crate::locale::Locale;
+
+/// Baud rate of the sensor unless another one is set in the settings file or chosen with `PortCommand`
+pub(crate) const BAUD_RATE: u32 = 115200;
+/// Grey out the model if the sensor sends nothing for this duration
+const STALE_TIMEOUT: Duration = Duration::from_secs(1);
+
+const NUMBER_KEYS: [KeyCode; 9] = [
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+];
+
+/// States of the viewer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AppState {
+    /// Choosing the serial port of the sensor
+    SelectPort,
+    /// Reading the sensor (or replaying a recording)
+    Streaming,
+    /// The port has been closed or failed, waiting to reconnect
+    Disconnected,
+}
+
+/// Commands to the serial port of the sensor, sent by the port selection screen and the HTTP API
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PortCommand {
+    /// Open the port instead of the one streamed, with the baud rate or the current one
+    Open {
+        port_name: String,
+        baud_rate: Option<u32>,
+    },
+    /// Close the port streamed
+    Close,
+    /// Reopen the port streamed with the baud rate
+    SetBaudRate(u32),
+}
+
+/// Open the serial port chosen on the port selection screen, and go back to it when the port fails,
+/// instead of panicking when the device path is wrong
+pub struct ConnectPlugin {
+    /// Port to open at startup, skipping the selection screen if it opens
+    pub port: Option<String>,
+    /// Baud rate of the port until another one is chosen with `PortCommand`
+    pub baud_rate: u32,
+}
+
+/// The port being streamed, and the ports found on the selection screen
+#[derive(Default)]
+struct Connection {
+    port_name: Option<String>,
+    baud_rate: u32,
+    label: Option<String>,
+    ports: Vec<SerialPortDescriptor>,
+    error: Option<String>,
+}
+
+#[derive(Component)]
+struct ConnectScreen;
+
+impl Plugin for ConnectPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Connection {
+            port_name: self.port.clone(),
+            baud_rate: self.baud_rate,
+            ..Default::default()
+        })
+        .add_event::<PortCommand>()
+        .add_state(AppState::SelectPort)
+        .add_system(apply_port_commands)
+        .add_system_set(SystemSet::on_enter(AppState::SelectPort).with_system(enter_select_port))
+        .add_system_set(SystemSet::on_update(AppState::SelectPort).with_system(select_port))
+        .add_system_set(SystemSet::on_exit(AppState::SelectPort).with_system(despawn_screen))
+        .add_system_set(SystemSet::on_update(AppState::Streaming).with_system(watch_connection))
+        .add_system_set(SystemSet::on_enter(AppState::Disconnected).with_system(enter_disconnected))
+        .add_system_set(SystemSet::on_update(AppState::Disconnected).with_system(reconnect))
+        .add_system_set(SystemSet::on_exit(AppState::Disconnected).with_system(despawn_screen));
+    }
+}
+
+fn spawn_screen(commands: &mut Commands, asset_server: &AssetServer, text: String) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(8.0),
+                    top: Val::Px(40.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                text,
+                TextStyle {
+                    font: asset_server.load("fonts/FiraMono-Medium.ttf"),
+                    font_size: 20.0,
+                    color: Color::WHITE,
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(ConnectScreen);
+}
+
+fn despawn_screen(mut commands: Commands, screens: Query<Entity, With<ConnectScreen>>) {
+    for entity in screens.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Setting of the sensor port
+pub(crate) fn port_setting(port_name: &str, baud_rate: u32) -> SerialSetting {
+    SerialSetting {
+        port_name: port_name.to_string(),
+        baud_rate,
+        stale_timeout: Some(STALE_TIMEOUT),
+        ..Default::default()
+    }
+}
+
+/// Open the port and go to `Streaming`, or keep the error to show it
+fn connect(
+    port_name: &str,
+    connection: &mut Connection,
+    ports: &mut SerialPorts,
+    state: &mut State<AppState>,
+) {
+    let result = ports.add(port_setting(port_name, connection.baud_rate));
+    connection.port_name = Some(port_name.to_string());
+    match result {
+        Ok(label) => {
+            info!("streaming from {}", label);
+            connection.label = Some(label);
+            connection.error = None;
+            if *state.current() != AppState::Streaming {
+                if let Err(e) = state.set(AppState::Streaming) {
+                    warn!("Failed to start streaming: {:?}", e);
+                }
+            }
+        }
+        Err(e) => {
+            error!("{}", e);
+            connection.error = Some(e.to_string());
+        }
+    }
+}
+
+fn port_description(port: &SerialPortDescriptor) -> String {
+    match &port.port_type {
+        SerialPortType::UsbPort(info) => format!(
+            "{} ({})",
+            port.port_name,
+            info.product.as_deref().unwrap_or("USB")
+        ),
+        _ => port.port_name.clone(),
+    }
+}
+
+fn enter_select_port(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    locale: Res<Locale>,
+    mut connection: ResMut<Connection>,
+    mut ports: SerialPorts,
+    mut state: ResMut<State<AppState>>,
+) {
+    // try the port given on the command line only once
+    if connection.label.is_none() && connection.error.is_none() {
+        if let Some(port_name) = connection.port_name.clone() {
+            connect(&port_name, &mut connection, &mut ports, &mut state);
+            if connection.label.is_some() {
+                return;
+            }
+        }
+    }
+
+    connection.ports = enumerate_ports();
+    let mut text = format!("{}\n\n", locale.text("select_port"));
+    if connection.ports.is_empty() {
+        text.push_str(&format!("{}\n", locale.text("no_ports")));
+    }
+    for (i, port) in connection.ports.iter().take(NUMBER_KEYS.len()).enumerate() {
+        text.push_str(&format!("{}: {}\n", i + 1, port_description(port)));
+    }
+    text.push_str(&format!("\n{}", locale.text("refresh")));
+    if let Some(error) = &connection.error {
+        text.push_str(&format!("\n\n{}", error));
+    }
+    spawn_screen(&mut commands, &asset_server, text);
+}
+
+fn select_port(
+    keys: Res<Input<KeyCode>>,
+    connection: Res<Connection>,
+    mut ev_command: EventWriter<PortCommand>,
+    mut state: ResMut<State<AppState>>,
+) {
+    if keys.just_pressed(KeyCode::R) {
+        // enter the state again to list the ports again
+        if let Err(e) = state.restart() {
+            warn!("Failed to refresh ports: {:?}", e);
+        }
+        return;
+    }
+    let chosen = NUMBER_KEYS
+        .iter()
+        .position(|key| keys.just_pressed(*key))
+        .and_then(|i| connection.ports.get(i))
+        .map(|port| port.port_name.clone());
+    if let Some(port_name) = chosen {
+        ev_command.send(PortCommand::Open {
+            port_name,
+            baud_rate: None,
+        });
+    }
+}
+
+/// Apply the port commands in any state, showing the error on the current screen if the port fails to open
+fn apply_port_commands(
+    mut ev_command: EventReader<PortCommand>,
+    mut connection: ResMut<Connection>,
+    mut ports: SerialPorts,
+    mut state: ResMut<State<AppState>>,
+) {
+    for command in ev_command.iter() {
+        let port_name = match command {
+            PortCommand::Open {
+                port_name,
+                baud_rate,
+            } => {
+                if let Some(baud_rate) = baud_rate {
+                    connection.baud_rate = *baud_rate;
+                }
+                port_name.clone()
+            }
+            PortCommand::SetBaudRate(baud_rate) => {
+                connection.baud_rate = *baud_rate;
+                match (&connection.label, &connection.port_name) {
+                    (Some(_), Some(port_name)) => port_name.clone(),
+                    // used when the port is opened next
+                    _ => continue,
+                }
+            }
+            PortCommand::Close => {
+                if connection.label.is_some() {
+                    connection.error = Some("closed by request".to_string());
+                    // the port is closed when entering the state
+                    if let Err(e) = state.set(AppState::Disconnected) {
+                        warn!("Failed to stop streaming: {:?}", e);
+                    }
+                }
+                continue;
+            }
+        };
+
+        // close the port streamed so that it can be opened again
+        if let Some(label) = connection.label.take() {
+            ports.remove(&label);
+        }
+        connect(&port_name, &mut connection, &mut ports, &mut state);
+        if connection.label.is_none() {
+            // show the error
+            let result = if *state.current() == AppState::Streaming {
+                state.set(AppState::Disconnected)
+            } else {
+                state.restart()
+            };
+            if let Err(e) = result {
+                warn!("Failed to show the error: {:?}", e);
+            }
+        }
+    }
+}
+
+/// Go to `Disconnected` when the port is closed or fails to read
+fn watch_connection(
+    mut ev_error: EventReader<SerialErrorEvent>,
+    info: Res<SerialPortInfo>,
+    mut connection: ResMut<Connection>,
+    mut state: ResMut<State<AppState>>,
+) {
+    let label = match &connection.label {
+        Some(label) => label.clone(),
+        // replaying a recording
+        None => return,
+    };
+    let mut error = None;
+    for e in ev_error.iter() {
+        let failed = matches!(
+            e,
+            SerialErrorEvent::Read { .. } | SerialErrorEvent::Disconnected(_)
+        );
+        if failed && e.label() == Some(label.as_str()) {
+            error = Some(e.to_string());
+        }
+    }
+    if let Some(port) = info.get(&label) {
+        if !port.open {
+            error = Some(
+                port.last_error
+                    .clone()
+                    .unwrap_or_else(|| "port closed".to_string()),
+            );
+        }
+    }
+
+    if let Some(error) = error {
+        warn!("{} disconnected: {}", label, error);
+        connection.error = Some(error);
+        if let Err(e) = state.set(AppState::Disconnected) {
+            warn!("Failed to stop streaming: {:?}", e);
+        }
+    }
+}
+
+fn enter_disconnected(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    locale: Res<Locale>,
+    mut connection: ResMut<Connection>,
+    mut ports: SerialPorts,
+) {
+    // close the port so that it can be opened again
+    if let Some(label) = connection.label.take() {
+        ports.remove(&label);
+    }
+    let text = format!(
+        "{}\n{}\n\n{}",
+        locale.fill(
+            "disconnected",
+            &[&connection.port_name.as_deref().unwrap_or_default()]
+        ),
+        connection.error.as_deref().unwrap_or_default(),
+        locale.text("reconnect_keys"),
+    );
+    spawn_screen(&mut commands, &asset_server, text);
+}
+
+fn reconnect(
+    keys: Res<Input<KeyCode>>,
+    connection: Res<Connection>,
+    mut ev_command: EventWriter<PortCommand>,
+    mut state: ResMut<State<AppState>>,
+) {
+    if keys.just_pressed(KeyCode::P) {
+        if let Err(e) = state.set(AppState::SelectPort) {
+            warn!("Failed to select port: {:?}", e);
+        }
+    } else if keys.just_pressed(KeyCode::Return) {
+        if let Some(port_name) = connection.port_name.clone() {
+            ev_command.send(PortCommand::Open {
+                port_name,
+                baud_rate: None,
+            });
+        }
+    }
+}
@@ -0,0 +1,320 @@
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+use bevy_serial::{
+    SerialPortInfo, SerialReadEvent, SerialWriteId, SerialWriteRequestEvent, SerialWriteResultEvent,
+};
+use std::collections::VecDeque;
+
+/// Commands on the buttons of the console
+const CANNED_COMMANDS: [&str; 6] = [
+    "rate 50",
+    "rate 100",
+    "range 2",
+    "range 8",
+    "filter on",
+    "filter off",
+];
+/// Lines of the log shown
+const LOG_LINES: usize = 10;
+/// Commands kept in the history
+const HISTORY_LEN: usize = 50;
+/// Longest response line kept
+const MAX_LINE_LEN: usize = 256;
+
+/// Console to send configuration commands (rate, range, filters) to the firmware, toggled with `
+pub struct ConsolePlugin;
+
+#[derive(Default)]
+struct Console {
+    open: bool,
+    input: String,
+    /// Commands sent, the newest last
+    history: Vec<String>,
+    /// Index in `history` while browsing it with Up and Down
+    browsing: Option<usize>,
+    /// Commands, results and responses, the newest last
+    log: VecDeque<String>,
+    /// Id of the next write request
+    next_id: u64,
+    /// Bytes of an incomplete response line
+    line: Vec<u8>,
+}
+
+impl Console {
+    fn push_log(&mut self, line: String) {
+        self.log.push_back(line);
+        while self.log.len() > LOG_LINES {
+            self.log.pop_front();
+        }
+    }
+}
+
+#[derive(Component)]
+struct ConsolePanel;
+
+#[derive(Component)]
+struct ConsoleText;
+
+#[derive(Component)]
+struct CannedButton(&'static str);
+
+impl Plugin for ConsolePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Console>()
+            .add_startup_system(setup_console)
+            // take the keys before other systems see them while typing
+            .add_system_to_stage(CoreStage::PreUpdate, type_command.after(InputSystem))
+            .add_system(press_canned_button)
+            .add_system(show_results)
+            .add_system(show_responses)
+            .add_system(update_console);
+    }
+}
+
+fn setup_console(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let font = asset_server.load("fonts/FiraMono-Medium.ttf");
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Px(480.0), Val::Auto),
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(8.0),
+                    top: Val::Px(40.0),
+                    ..Default::default()
+                },
+                // top to bottom
+                flex_direction: FlexDirection::ColumnReverse,
+                padding: Rect::all(Val::Px(4.0)),
+                display: Display::None,
+                ..Default::default()
+            },
+            color: Color::rgba(0.1, 0.1, 0.1, 0.9).into(),
+            ..Default::default()
+        })
+        .insert(ConsolePanel)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        "",
+                        TextStyle {
+                            font: font.clone(),
+                            font_size: 14.0,
+                            color: Color::WHITE,
+                        },
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                })
+                .insert(ConsoleText);
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        flex_wrap: FlexWrap::Wrap,
+                        margin: Rect {
+                            top: Val::Px(4.0),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    },
+                    color: Color::NONE.into(),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    for command in CANNED_COMMANDS {
+                        parent
+                            .spawn_bundle(ButtonBundle {
+                                style: Style {
+                                    size: Size::new(Val::Px(76.0), Val::Px(22.0)),
+                                    margin: Rect::all(Val::Px(2.0)),
+                                    justify_content: JustifyContent::Center,
+                                    align_items: AlignItems::Center,
+                                    ..Default::default()
+                                },
+                                color: Color::rgb(0.25, 0.25, 0.25).into(),
+                                ..Default::default()
+                            })
+                            .insert(CannedButton(command))
+                            .with_children(|parent| {
+                                parent.spawn_bundle(TextBundle {
+                                    text: Text::with_section(
+                                        command,
+                                        TextStyle {
+                                            font: font.clone(),
+                                            font_size: 12.0,
+                                            color: Color::WHITE,
+                                        },
+                                        Default::default(),
+                                    ),
+                                    ..Default::default()
+                                });
+                            });
+                    }
+                });
+        });
+}
+
+/// Send the command as a line to all open ports
+fn send_command(
+    command: &str,
+    console: &mut Console,
+    info: Option<&SerialPortInfo>,
+    ev_request: &mut EventWriter<SerialWriteRequestEvent>,
+) {
+    let labels: Vec<&String> = info
+        .map(|info| {
+            info.iter()
+                .filter(|(_, state)| state.open)
+                .map(|(label, _)| label)
+                .collect()
+        })
+        .unwrap_or_default();
+    if labels.is_empty() {
+        console.push_log(format!("{}: no open port", command));
+        return;
+    }
+    for label in labels {
+        ev_request.send(SerialWriteRequestEvent {
+            label: label.clone(),
+            buffer: format!("{}\n", command).into_bytes(),
+            id: SerialWriteId::Id(console.next_id),
+        });
+        console.push_log(format!("> {} ({})", command, label));
+    }
+    console.next_id += 1;
+    if console.history.last().map(String::as_str) != Some(command) {
+        console.history.push(command.to_string());
+        if console.history.len() > HISTORY_LEN {
+            console.history.remove(0);
+        }
+    }
+}
+
+fn type_command(
+    mut keys: ResMut<Input<KeyCode>>,
+    mut ev_char: EventReader<ReceivedCharacter>,
+    mut ev_request: EventWriter<SerialWriteRequestEvent>,
+    mut console: ResMut<Console>,
+    info: Option<Res<SerialPortInfo>>,
+) {
+    if keys.just_pressed(KeyCode::Grave) {
+        console.open = !console.open;
+        keys.clear();
+        // don't type the backquote
+        ev_char.iter().for_each(drop);
+        return;
+    }
+    if !console.open {
+        return;
+    }
+
+    for ReceivedCharacter { char, .. } in ev_char.iter() {
+        if !char.is_control() {
+            console.input.push(*char);
+        }
+    }
+    if keys.just_pressed(KeyCode::Back) {
+        console.input.pop();
+    }
+    if keys.just_pressed(KeyCode::Up) && !console.history.is_empty() {
+        let index = console
+            .browsing
+            .map_or(console.history.len() - 1, |i| i.saturating_sub(1));
+        console.browsing = Some(index);
+        console.input = console.history[index].clone();
+    }
+    if keys.just_pressed(KeyCode::Down) {
+        if let Some(index) = console.browsing {
+            if index + 1 < console.history.len() {
+                console.browsing = Some(index + 1);
+                console.input = console.history[index + 1].clone();
+            } else {
+                console.browsing = None;
+                console.input.clear();
+            }
+        }
+    }
+    if keys.just_pressed(KeyCode::Return) {
+        let command = std::mem::take(&mut console.input);
+        let command = command.trim();
+        if !command.is_empty() {
+            send_command(command, &mut console, info.as_deref(), &mut ev_request);
+        }
+        console.browsing = None;
+    }
+    // other systems should not react to the keys typed
+    keys.clear();
+}
+
+fn press_canned_button(
+    buttons: Query<(&Interaction, &CannedButton), Changed<Interaction>>,
+    mut ev_request: EventWriter<SerialWriteRequestEvent>,
+    mut console: ResMut<Console>,
+    info: Option<Res<SerialPortInfo>>,
+) {
+    for (interaction, CannedButton(command)) in buttons.iter() {
+        if *interaction == Interaction::Clicked {
+            send_command(command, &mut console, info.as_deref(), &mut ev_request);
+        }
+    }
+}
+
+/// Log failed writes of the commands
+fn show_results(mut ev_result: EventReader<SerialWriteResultEvent>, mut console: ResMut<Console>) {
+    for SerialWriteResultEvent { label, result, .. } in ev_result.iter() {
+        if let Err(e) = result {
+            console.push_log(format!("{}: write failed: {}", label, e));
+        }
+    }
+}
+
+/// Log lines which are not packets of numbers, i.e. responses of the firmware
+fn show_responses(mut ev_serial: EventReader<SerialReadEvent>, mut console: ResMut<Console>) {
+    if !console.open {
+        ev_serial.iter().for_each(drop);
+        return;
+    }
+    for SerialReadEvent(_, buffer) in ev_serial.iter() {
+        for &byte in buffer {
+            if byte != b'\n' {
+                if console.line.len() < MAX_LINE_LEN {
+                    console.line.push(byte);
+                }
+                continue;
+            }
+            let line = String::from_utf8_lossy(&console.line).trim().to_string();
+            console.line.clear();
+            let is_packet = line
+                .split([' ', ','])
+                .filter(|v| !v.is_empty())
+                .all(|v| v.parse::<f32>().is_ok());
+            if !is_packet {
+                console.push_log(format!("< {}", line));
+            }
+        }
+    }
+}
+
+fn update_console(
+    console: Res<Console>,
+    mut panel: Query<&mut Style, With<ConsolePanel>>,
+    mut text: Query<&mut Text, With<ConsoleText>>,
+) {
+    if !console.is_changed() {
+        return;
+    }
+    for mut style in panel.iter_mut() {
+        style.display = if console.open {
+            Display::Flex
+        } else {
+            Display::None
+        };
+    }
+    for mut text in text.iter_mut() {
+        let mut lines: Vec<&str> = console.log.iter().map(String::as_str).collect();
+        let input = format!("> {}_", console.input);
+        lines.push(&input);
+        text.sections[0].value = lines.join("\n");
+    }
+}
@@ -0,0 +1,84 @@
+use bevy::prelude::*;
+use bevy_serial::SerialReadEvent;
+
+use crate::parser::ParserRegistry;
+
+/// Seconds of traffic inspected from the first byte
+const DETECT_SECONDS: f64 = 2.0;
+/// Bytes inspected at most
+const MAX_BYTES: usize = 8192;
+
+/// Sent when the packet format has been detected from the first seconds of the serial data
+#[derive(Debug, Clone, Copy)]
+pub struct FormatDetectedEvent {
+    /// Name of the format in `ParserRegistry`
+    pub format: &'static str,
+    /// Score of the format from 0 to 1
+    pub confidence: f32,
+}
+
+/// Bytes collected to detect the format
+#[derive(Default)]
+pub struct FormatDetection {
+    running: bool,
+    bytes: Vec<u8>,
+    started: Option<f64>,
+}
+
+impl FormatDetection {
+    /// Detect the format again from the next bytes
+    pub fn restart(&mut self) {
+        *self = FormatDetection {
+            running: true,
+            ..Default::default()
+        };
+    }
+
+    pub fn stop(&mut self) {
+        *self = FormatDetection::default();
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+}
+
+/// Inspect the first seconds of the serial data and send `FormatDetectedEvent` with the most likely format
+pub(crate) fn detect_format(
+    mut ev_serial: EventReader<SerialReadEvent>,
+    mut ev_detected: EventWriter<FormatDetectedEvent>,
+    mut detection: ResMut<FormatDetection>,
+    registry: Res<ParserRegistry>,
+    time: Res<Time>,
+) {
+    if !detection.running {
+        return;
+    }
+    let now = time.seconds_since_startup();
+    for SerialReadEvent(_, buffer) in ev_serial.iter() {
+        if buffer.is_empty() {
+            continue;
+        }
+        detection.started.get_or_insert(now);
+        detection.bytes.extend_from_slice(buffer);
+    }
+    let elapsed = detection.started.map_or(0.0, |started| now - started);
+    if elapsed < DETECT_SECONDS && detection.bytes.len() < MAX_BYTES {
+        return;
+    }
+
+    match registry.detect(&detection.bytes) {
+        Some((format, confidence)) => {
+            info!("detected format {} ({:.0}%)", format, confidence * 100.0);
+            ev_detected.send(FormatDetectedEvent { format, confidence });
+            detection.stop();
+        }
+        None => {
+            warn!(
+                "no known format in {} bytes, trying again",
+                detection.bytes.len()
+            );
+            detection.restart();
+        }
+    }
+}
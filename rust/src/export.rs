@@ -0,0 +1,175 @@
+use bevy::prelude::*;
+use image::{Rgb, RgbImage};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::locale::Locale;
+use crate::plot::{Sample, SampleHistory};
+use crate::theme::Theme;
+use crate::units::SensorUnits;
+
+const IMAGE_WIDTH: u32 = 800;
+const IMAGE_HEIGHT: u32 = 400;
+
+/// Export the visible plot to PNG and its samples to CSV with the export button or `E`.
+/// Files are written to the current directory as `export-<unix time>.png` and `.csv`.
+pub struct ExportPlugin;
+
+impl Plugin for ExportPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(setup_export_button)
+            .add_system(export_plot);
+    }
+}
+
+#[derive(Component)]
+struct ExportButton;
+
+fn setup_export_button(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    locale: Res<Locale>,
+) {
+    commands
+        .spawn_bundle(ButtonBundle {
+            style: Style {
+                size: Size::new(Val::Px(80.0), Val::Px(24.0)),
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    right: Val::Px(8.0),
+                    top: Val::Px(176.0),
+                    ..Default::default()
+                },
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            color: Color::rgb(0.25, 0.25, 0.25).into(),
+            ..Default::default()
+        })
+        .insert(ExportButton)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    locale.text("export"),
+                    TextStyle {
+                        font: asset_server.load("fonts/FiraMono-Medium.ttf"),
+                        font_size: 16.0,
+                        color: Color::WHITE,
+                    },
+                    Default::default(),
+                ),
+                ..Default::default()
+            });
+        });
+}
+
+fn export_plot(
+    keys: Res<Input<KeyCode>>,
+    buttons: Query<&Interaction, (Changed<Interaction>, With<ExportButton>)>,
+    history: Res<SampleHistory>,
+    units: Res<SensorUnits>,
+    theme: Res<Theme>,
+) {
+    let clicked = buttons.iter().any(|i| *i == Interaction::Clicked);
+    if !clicked && !keys.just_pressed(KeyCode::E) {
+        return;
+    }
+
+    let samples: Vec<Sample> = history.visible().copied().collect();
+    let stamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let csv = format!("export-{}.csv", stamp);
+    let png = format!("export-{}.png", stamp);
+
+    match write_csv(&csv, &samples, &units) {
+        Ok(()) => info!("exported {} samples to {}", samples.len(), csv),
+        Err(e) => error!("failed to export {}: {}", csv, e),
+    }
+    match plot_image(&samples, &units, &theme).save(&png) {
+        Ok(()) => info!("exported plot to {}", png),
+        Err(e) => error!("failed to export {}: {}", png, e),
+    }
+}
+
+/// Write the samples in the unit of the sensor, which is given in the header
+fn write_csv(path: &str, samples: &[Sample], units: &SensorUnits) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    let symbol = units.unit.symbol();
+    writeln!(writer, "time,x ({0}),y ({0}),z ({0})", symbol)?;
+    for s in samples {
+        let accel = s.accel * units.unit.in_unit(1.0);
+        writeln!(writer, "{:.6},{},{},{}", s.time, accel.x, accel.y, accel.z)?;
+    }
+    writer.flush()
+}
+
+/// Draw the samples as lines in the same layout and colors as the plot panel
+fn plot_image(samples: &[Sample], units: &SensorUnits, theme: &Theme) -> RgbImage {
+    let mut image = RgbImage::from_pixel(IMAGE_WIDTH, IMAGE_HEIGHT, Rgb([26, 26, 26]));
+    let center = IMAGE_HEIGHT / 2;
+    for x in 0..IMAGE_WIDTH {
+        image.put_pixel(x, center, Rgb([80, 80, 80]));
+    }
+
+    let (start, end) = match (samples.first(), samples.last()) {
+        (Some(first), Some(last)) if last.time > first.time => (first.time, last.time),
+        _ => return image,
+    };
+    let range = units.range.full_scale();
+    let to_pixel = |s: &Sample, axis: usize| {
+        let value = s.accel[axis].clamp(-range, range);
+        let x = (s.time - start) / (end - start) * (IMAGE_WIDTH - 1) as f64;
+        let y = (1.0 - (value / range + 1.0) / 2.0) * (IMAGE_HEIGHT - 1) as f32;
+        (x as i32, y as i32)
+    };
+
+    for (axis, color) in theme.axes.iter().enumerate() {
+        let color = Rgb([
+            (color.r() * 255.0) as u8,
+            (color.g() * 255.0) as u8,
+            (color.b() * 255.0) as u8,
+        ]);
+        for pair in samples.windows(2) {
+            draw_line(
+                &mut image,
+                to_pixel(&pair[0], axis),
+                to_pixel(&pair[1], axis),
+                color,
+            );
+        }
+    }
+    image
+}
+
+/// Bresenham's line algorithm
+fn draw_line(
+    image: &mut RgbImage,
+    (mut x0, mut y0): (i32, i32),
+    (x1, y1): (i32, i32),
+    color: Rgb<u8>,
+) {
+    let (dx, dy) = ((x1 - x0).abs(), -(y1 - y0).abs());
+    let (sx, sy) = (if x0 < x1 { 1 } else { -1 }, if y0 < y1 { 1 } else { -1 });
+    let mut err = dx + dy;
+    loop {
+        if x0 >= 0 && y0 >= 0 && (x0 as u32) < image.width() && (y0 as u32) < image.height() {
+            image.put_pixel(x0 as u32, y0 as u32, color);
+        }
+        if x0 == x1 && y0 == y1 {
+            break;
+        }
+        let e2 = 2 * err;
+        if e2 >= dy {
+            err += dy;
+            x0 += sx;
+        }
+        if e2 <= dx {
+            err += dx;
+            y0 += sy;
+        }
+    }
+}
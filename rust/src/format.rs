@@ -0,0 +1,264 @@
+use bevy::prelude::*;
+
+use crate::detect::{detect_format, FormatDetectedEvent, FormatDetection};
+use crate::locale::Locale;
+use crate::parser::{PacketEvent, ParserRegistry};
+use crate::units::SensorUnits;
+
+const BUTTON_COLOR: Color = Color::rgb(0.25, 0.25, 0.25);
+const SELECTED_COLOR: Color = Color::rgb(0.35, 0.35, 0.5);
+
+/// Parsers of the serial data, with the format detected from the first seconds of the data unless it is given.
+/// Used without a window by the headless mode.
+pub struct ParserPlugin {
+    /// Name of the format selected at startup
+    pub format: Option<String>,
+}
+
+/// Dropdown below the export button to choose the packet format of the serial data,
+/// which can override the detected format
+pub struct FormatPlugin {
+    /// Name of the format selected at startup
+    pub format: Option<String>,
+}
+
+/// Whether the list of formats is open, and the confidence of the detected format
+#[derive(Default)]
+struct FormatMenu {
+    open: bool,
+    detected: Option<f32>,
+}
+
+#[derive(Component)]
+enum FormatButton {
+    Header,
+    Auto,
+    Format(&'static str),
+}
+
+#[derive(Component)]
+struct FormatHeaderText;
+
+impl Plugin for ParserPlugin {
+    fn build(&self, app: &mut App) {
+        let mut registry = ParserRegistry::default();
+        if let Some(format) = &self.format {
+            if !registry.select(format) {
+                let names: Vec<&str> = registry.names().collect();
+                warn!("unknown format {}, choose from {:?}", format, names);
+            }
+        }
+        let mut detection = FormatDetection::default();
+        if self.format.is_none() {
+            detection.restart();
+        }
+        app.insert_resource(registry)
+            .insert_resource(detection)
+            .add_event::<FormatDetectedEvent>()
+            .add_event::<PacketEvent>()
+            .add_system(apply_units)
+            .add_system(detect_format)
+            .add_system(apply_detected_format);
+    }
+}
+
+impl Plugin for FormatPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(ParserPlugin {
+            format: self.format.clone(),
+        })
+        .init_resource::<FormatMenu>()
+        .add_startup_system(setup_format_menu)
+        .add_system(show_detected_confidence)
+        .add_system(choose_format)
+        .add_system(update_format_menu);
+    }
+}
+
+fn spawn_button(
+    parent: &mut ChildBuilder,
+    font: Handle<Font>,
+    button: FormatButton,
+    text: &str,
+    display: Display,
+) {
+    let is_header = matches!(button, FormatButton::Header);
+    parent
+        .spawn_bundle(ButtonBundle {
+            style: Style {
+                size: Size::new(Val::Px(200.0), Val::Px(24.0)),
+                display,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            color: BUTTON_COLOR.into(),
+            ..Default::default()
+        })
+        .insert(button)
+        .with_children(|parent| {
+            let mut text = parent.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    text,
+                    TextStyle {
+                        font,
+                        font_size: 16.0,
+                        color: Color::WHITE,
+                    },
+                    Default::default(),
+                ),
+                ..Default::default()
+            });
+            if is_header {
+                text.insert(FormatHeaderText);
+            }
+        });
+}
+
+fn setup_format_menu(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    locale: Res<Locale>,
+    registry: Res<ParserRegistry>,
+) {
+    let font = asset_server.load("fonts/FiraMono-Medium.ttf");
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    right: Val::Px(8.0),
+                    top: Val::Px(208.0),
+                    ..Default::default()
+                },
+                // top to bottom
+                flex_direction: FlexDirection::ColumnReverse,
+                ..Default::default()
+            },
+            color: Color::NONE.into(),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            spawn_button(
+                parent,
+                font.clone(),
+                FormatButton::Header,
+                "",
+                Display::Flex,
+            );
+            spawn_button(
+                parent,
+                font.clone(),
+                FormatButton::Auto,
+                locale.text("auto_detect"),
+                Display::None,
+            );
+            for name in registry.names() {
+                spawn_button(
+                    parent,
+                    font.clone(),
+                    FormatButton::Format(name),
+                    name,
+                    Display::None,
+                );
+            }
+        });
+}
+
+/// Convert the values with the range and unit from the settings
+fn apply_units(units: Res<SensorUnits>, mut registry: ResMut<ParserRegistry>) {
+    if units.is_changed() {
+        registry.set_units(*units);
+    }
+}
+
+fn apply_detected_format(
+    mut ev_detected: EventReader<FormatDetectedEvent>,
+    mut registry: ResMut<ParserRegistry>,
+) {
+    for FormatDetectedEvent { format, .. } in ev_detected.iter() {
+        registry.select(format);
+    }
+}
+
+fn show_detected_confidence(
+    mut ev_detected: EventReader<FormatDetectedEvent>,
+    mut menu: ResMut<FormatMenu>,
+) {
+    for FormatDetectedEvent { confidence, .. } in ev_detected.iter() {
+        menu.detected = Some(*confidence);
+    }
+}
+
+fn choose_format(
+    buttons: Query<(&Interaction, &FormatButton), Changed<Interaction>>,
+    mut menu: ResMut<FormatMenu>,
+    mut registry: ResMut<ParserRegistry>,
+    mut detection: ResMut<FormatDetection>,
+) {
+    for (interaction, button) in buttons.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+        match button {
+            FormatButton::Header => menu.open = !menu.open,
+            FormatButton::Auto => {
+                detection.restart();
+                menu.detected = None;
+                menu.open = false;
+            }
+            FormatButton::Format(name) => {
+                registry.select(name);
+                info!("packet format {}", name);
+                detection.stop();
+                menu.detected = None;
+                menu.open = false;
+            }
+        }
+    }
+}
+
+fn update_format_menu(
+    menu: Res<FormatMenu>,
+    registry: Res<ParserRegistry>,
+    detection: Res<FormatDetection>,
+    locale: Res<Locale>,
+    mut buttons: Query<(&FormatButton, &mut Style, &mut UiColor)>,
+    mut header: Query<&mut Text, With<FormatHeaderText>>,
+) {
+    if !menu.is_changed() && !registry.is_changed() && !detection.is_changed() {
+        return;
+    }
+    for (button, mut style, mut color) in buttons.iter_mut() {
+        let selected = match button {
+            FormatButton::Header => continue,
+            FormatButton::Auto => detection.is_running(),
+            FormatButton::Format(name) => *name == registry.selected(),
+        };
+        style.display = if menu.open {
+            Display::Flex
+        } else {
+            Display::None
+        };
+        *color = if selected {
+            SELECTED_COLOR
+        } else {
+            BUTTON_COLOR
+        }
+        .into();
+    }
+    let format = if detection.is_running() {
+        locale.text("detecting").to_string()
+    } else if let Some(confidence) = menu.detected {
+        format!("{} ({:.0}%)", registry.selected(), confidence * 100.0)
+    } else {
+        registry.selected().to_string()
+    };
+    for mut text in header.iter_mut() {
+        text.sections[0].value = format!(
+            "{} {}",
+            locale.fill("format", &[&format]),
+            if menu.open { "^" } else { "v" }
+        );
+    }
+}
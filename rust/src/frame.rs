@@ -0,0 +1,86 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::f32::consts::{FRAC_PI_2, PI};
+use std::fmt;
+use std::str::FromStr;
+
+/// Convention of the earth frame of a rotation. The filter of the viewer uses `Nwu`,
+/// and the axes of the device are chosen with the axis mapping.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Frame {
+    /// x north, y west, z up
+    #[default]
+    Nwu,
+    /// x east, y north, z up, like ROS (REP 103)
+    Enu,
+    /// x north, y east, z down, like aircraft and PX4
+    Ned,
+}
+
+impl Frame {
+    /// Rotation from the axes of `Nwu` to the axes of this frame
+    fn from_nwu_axes(self) -> Quat {
+        match self {
+            Frame::Nwu => Quat::IDENTITY,
+            // east is -west, north is x
+            Frame::Enu => Quat::from_rotation_z(FRAC_PI_2),
+            // east is -west, down is -up
+            Frame::Ned => Quat::from_rotation_x(PI),
+        }
+    }
+
+    /// Rotation of the device in this frame from the rotation in `Nwu`
+    pub fn from_nwu(self, rotation: Quat) -> Quat {
+        self.from_nwu_axes() * rotation
+    }
+
+    /// Rotation of the device in `Nwu` from the rotation in this frame
+    pub fn to_nwu(self, rotation: Quat) -> Quat {
+        self.from_nwu_axes().inverse() * rotation
+    }
+}
+
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Frame::Nwu => "nwu",
+            Frame::Enu => "enu",
+            Frame::Ned => "ned",
+        })
+    }
+}
+
+/// Parsed from `nwu`, `enu` or `ned`
+impl FromStr for Frame {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "nwu" => Ok(Frame::Nwu),
+            "enu" => Ok(Frame::Enu),
+            "ned" => Ok(Frame::Ned),
+            _ => Err(format!("unknown frame {}, choose from nwu, enu or ned", s)),
+        }
+    }
+}
+
+/// Frames of the rotations read and written by the viewer
+#[derive(Debug, Clone, Default)]
+pub struct Frames {
+    /// Frame of the rotations streamed and published, and of the quaternions of devices without their own frame
+    pub frame: Frame,
+    /// Frame of the quaternion of each device by the label of its port
+    pub devices: BTreeMap<String, Frame>,
+}
+
+impl Frames {
+    /// Frame of the quaternion of the device with the label
+    pub fn device(&self, label: Option<&str>) -> Frame {
+        label
+            .and_then(|label| self.devices.get(label))
+            .copied()
+            .unwrap_or(self.frame)
+    }
+}
@@ -0,0 +1,187 @@
+use bevy::prelude::*;
+use bevy_serial::encode_hex;
+use std::collections::VecDeque;
+
+use crate::locale::Locale;
+use crate::parser::{Packet, ParserRegistry};
+
+/// Samples kept while frozen, newer ones are dropped over this
+const MAX_FROZEN_SAMPLES: usize = 100_000;
+/// Raw bytes shown of a sample, e.g. when junk came before it
+const MAX_RAW_SHOWN: usize = 32;
+
+/// A sample parsed while frozen, with its time and the bytes read since the previous sample
+struct FrozenSample {
+    packet: Packet,
+    time: f64,
+    raw: Vec<u8>,
+}
+
+/// Whether the views are frozen, with the samples parsed since then to step through
+#[derive(Default)]
+pub struct Freeze {
+    pub frozen: bool,
+    samples: VecDeque<FrozenSample>,
+    /// Bytes read since the last sample parsed while frozen
+    raw: Vec<u8>,
+    /// Samples to release in this update
+    steps: usize,
+    /// The sample released last, and its number since the views were frozen
+    shown: Option<(usize, FrozenSample)>,
+    dropped: usize,
+}
+
+impl Freeze {
+    /// Parse the bytes one at a time, so each sample keeps the bytes it was parsed from.
+    /// The samples are kept with `push` once they are timed.
+    pub fn parse(&mut self, parsers: &mut ParserRegistry, bytes: &[u8]) -> Vec<(Packet, Vec<u8>)> {
+        let mut packets = Vec::new();
+        let mut parsed = Vec::new();
+        for &byte in bytes {
+            self.raw.push(byte);
+            parsers.parse(&[byte], &mut packets);
+            for packet in packets.drain(..) {
+                parsed.push((packet, std::mem::take(&mut self.raw)));
+            }
+        }
+        parsed
+    }
+
+    /// Keep a sample parsed while frozen to step through
+    pub fn push(&mut self, packet: Packet, time: f64, raw: Vec<u8>) {
+        if self.samples.len() < MAX_FROZEN_SAMPLES {
+            self.samples.push_back(FrozenSample { packet, time, raw });
+        } else {
+            self.dropped += 1;
+        }
+    }
+
+    /// Samples stepped to since the last update, in order, with their times
+    pub fn take_steps(&mut self) -> Vec<(Packet, f64)> {
+        let mut packets = Vec::new();
+        while self.steps > 0 {
+            self.steps -= 1;
+            let sample = match self.samples.pop_front() {
+                Some(sample) => sample,
+                None => break,
+            };
+            packets.push((sample.packet, sample.time));
+            let number = self.shown.as_ref().map_or(1, |(number, _)| number + 1);
+            self.shown = Some((number, sample));
+        }
+        self.steps = 0;
+        packets
+    }
+}
+
+/// `H` freezes the views to inspect the orientation, and `.` steps through the samples parsed since then
+/// one at a time, showing the raw bytes of each. The samples left are dropped when unfrozen.
+pub struct FreezePlugin;
+
+impl Plugin for FreezePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Freeze>()
+            .add_startup_system(setup_freeze_text)
+            .add_system(control_freeze)
+            .add_system(show_freeze);
+    }
+}
+
+#[derive(Component)]
+struct FreezeText;
+
+fn setup_freeze_text(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Percent(40.0),
+                    bottom: Val::Px(56.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraMono-Medium.ttf"),
+                    font_size: 16.0,
+                    color: Color::CYAN,
+                },
+                Default::default(),
+            ),
+            visibility: Visibility { is_visible: false },
+            ..Default::default()
+        })
+        .insert(FreezeText);
+}
+
+fn control_freeze(keys: Res<Input<KeyCode>>, mut freeze: ResMut<Freeze>) {
+    if keys.just_pressed(KeyCode::H) {
+        if freeze.frozen {
+            info!(
+                "unfrozen, {} samples not stepped through are dropped",
+                freeze.samples.len() + freeze.dropped
+            );
+            *freeze = Freeze::default();
+        } else {
+            info!("frozen, step through the samples with .");
+            freeze.frozen = true;
+        }
+    }
+    if freeze.frozen && keys.just_pressed(KeyCode::Period) {
+        freeze.steps += 1;
+    }
+}
+
+fn show_freeze(
+    freeze: Res<Freeze>,
+    locale: Res<Locale>,
+    mut text: Query<(&mut Text, &mut Visibility), With<FreezeText>>,
+) {
+    if !freeze.is_changed() {
+        return;
+    }
+    let mut lines = vec![locale.fill("frozen", &[&freeze.samples.len()])];
+    if freeze.dropped > 0 {
+        lines.push(locale.fill("frozen_dropped", &[&freeze.dropped]));
+    }
+    if let Some((number, sample)) = &freeze.shown {
+        let accel = sample.packet.accel;
+        lines.push(format!(
+            "{}: x {:.3} y {:.3} z {:.3} g",
+            locale.fill("frozen_sample", &[number]),
+            accel.x,
+            accel.y,
+            accel.z
+        ));
+        if let Some(gyro) = sample.packet.gyro {
+            lines.push(format!(
+                "gyro x {:.1} y {:.1} z {:.1} °/s",
+                gyro.x, gyro.y, gyro.z
+            ));
+        }
+        let raw = &sample.raw[sample.raw.len().saturating_sub(MAX_RAW_SHOWN)..];
+        let printable: String = raw
+            .iter()
+            .map(|&b| {
+                if b.is_ascii_graphic() || b == b' ' {
+                    b as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        lines.push(format!(
+            "{}: {}",
+            locale.fill("frozen_bytes", &[&sample.raw.len()]),
+            encode_hex(raw)
+        ));
+        lines.push(format!("{}: {}", locale.text("frozen_text"), printable));
+    }
+    for (mut text, mut visibility) in text.iter_mut() {
+        visibility.is_visible = freeze.frozen;
+        text.sections[0].value = lines.join("\n");
+    }
+}
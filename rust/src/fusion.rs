@@ -0,0 +1,142 @@
+use bevy::prelude::*;
+
+/// Default gain of the accelerometer and magnetometer correction, in rad/s,
+/// from Madgwick's report for a gyro error of about 5 °/s
+pub const DEFAULT_BETA: f32 = 0.1;
+
+/// Madgwick's gradient descent orientation filter (S. Madgwick, "An efficient orientation filter for inertial and
+/// inertial/magnetic sensor arrays", 2010), in the device axes with z up while the device lies flat.
+/// The gyro turns the rotation between packets, and `beta` pulls it towards gravity (and magnetic north).
+#[derive(Debug, Clone, Copy, Reflect)]
+pub struct Madgwick {
+    pub beta: f32,
+    /// Rotation of the device in the earth frame: x magnetic north (or the initial heading), z up
+    pub rotation: Quat,
+}
+
+impl Default for Madgwick {
+    fn default() -> Self {
+        Madgwick {
+            beta: DEFAULT_BETA,
+            rotation: Quat::IDENTITY,
+        }
+    }
+}
+
+impl Madgwick {
+    /// Update with the angular rate in degrees per second, the acceleration in g and optionally the calibrated
+    /// magnetic field, `dt` seconds after the previous packet
+    pub fn update(&mut self, gyro: Vec3, accel: Vec3, mag: Option<Vec3>, dt: f32) {
+        let r = self.rotation;
+        // `w, x, y, z` like the report
+        let q = Vec4::new(r.w, r.x, r.y, r.z);
+        let (q0, q1, q2, q3) = (r.w, r.x, r.y, r.z);
+        let g = gyro * std::f32::consts::PI / 180.0;
+        // rate of change of the rotation from the gyro
+        let mut dq = 0.5
+            * Vec4::new(
+                -q1 * g.x - q2 * g.y - q3 * g.z,
+                q0 * g.x + q2 * g.z - q3 * g.y,
+                q0 * g.y - q1 * g.z + q3 * g.x,
+                q0 * g.z + q1 * g.y - q2 * g.x,
+            );
+        // a free fall has no direction of gravity to correct with
+        if accel.length_squared() > 0.0 {
+            let step = match mag.filter(|mag| mag.length_squared() > 0.0) {
+                Some(mag) => marg_step(q, accel.normalize(), mag.normalize()),
+                None => imu_step(q, accel.normalize()),
+            };
+            if step.length_squared() > 0.0 {
+                dq -= self.beta * step.normalize();
+            }
+        }
+        let [q0, q1, q2, q3] = (q + dq * dt).normalize().to_array();
+        self.rotation = Quat::from_xyzw(q1, q2, q3, q0);
+    }
+}
+
+/// Gradient of the error between gravity in the device axes and the acceleration, as `w, x, y, z`
+fn imu_step(q: Vec4, a: Vec3) -> Vec4 {
+    let [q0, q1, q2, q3] = q.to_array();
+    let f = Vec3::new(
+        2.0 * (q1 * q3 - q0 * q2) - a.x,
+        2.0 * (q0 * q1 + q2 * q3) - a.y,
+        1.0 - 2.0 * (q1 * q1 + q2 * q2) - a.z,
+    );
+    Vec4::new(
+        -2.0 * q2 * f.x + 2.0 * q1 * f.y,
+        2.0 * q3 * f.x + 2.0 * q0 * f.y - 4.0 * q1 * f.z,
+        -2.0 * q0 * f.x + 2.0 * q3 * f.y - 4.0 * q2 * f.z,
+        2.0 * q1 * f.x + 2.0 * q2 * f.y,
+    )
+}
+
+/// Gradient of the errors of gravity and of the magnetic field, whose reference is the measured field turned into
+/// the earth frame with its horizontal part on x, as `w, x, y, z`
+fn marg_step(q: Vec4, a: Vec3, m: Vec3) -> Vec4 {
+    let [q0, q1, q2, q3] = q.to_array();
+    // field in the earth frame
+    let h = Vec3::new(
+        m.x * (q0 * q0 + q1 * q1 - q2 * q2 - q3 * q3)
+            + 2.0 * m.y * (q1 * q2 - q0 * q3)
+            + 2.0 * m.z * (q0 * q2 + q1 * q3),
+        2.0 * m.x * (q0 * q3 + q1 * q2)
+            + m.y * (q0 * q0 - q1 * q1 + q2 * q2 - q3 * q3)
+            + 2.0 * m.z * (q2 * q3 - q0 * q1),
+        2.0 * m.x * (q1 * q3 - q0 * q2)
+            + 2.0 * m.y * (q0 * q1 + q2 * q3)
+            + m.z * (q0 * q0 - q1 * q1 - q2 * q2 + q3 * q3),
+    );
+    let bx = (h.x * h.x + h.y * h.y).sqrt();
+    let bz = h.z;
+    let f = [
+        2.0 * (q1 * q3 - q0 * q2) - a.x,
+        2.0 * (q0 * q1 + q2 * q3) - a.y,
+        1.0 - 2.0 * (q1 * q1 + q2 * q2) - a.z,
+        2.0 * bx * (0.5 - q2 * q2 - q3 * q3) + 2.0 * bz * (q1 * q3 - q0 * q2) - m.x,
+        2.0 * bx * (q1 * q2 - q0 * q3) + 2.0 * bz * (q0 * q1 + q2 * q3) - m.y,
+        2.0 * bx * (q0 * q2 + q1 * q3) + 2.0 * bz * (0.5 - q1 * q1 - q2 * q2) - m.z,
+    ];
+    // rows of the transposed Jacobian of `f`
+    let jacobian = [
+        [
+            -2.0 * q2,
+            2.0 * q1,
+            0.0,
+            -2.0 * bz * q2,
+            -2.0 * bx * q3 + 2.0 * bz * q1,
+            2.0 * bx * q2,
+        ],
+        [
+            2.0 * q3,
+            2.0 * q0,
+            -4.0 * q1,
+            2.0 * bz * q3,
+            2.0 * bx * q2 + 2.0 * bz * q0,
+            2.0 * bx * q3 - 4.0 * bz * q1,
+        ],
+        [
+            -2.0 * q0,
+            2.0 * q3,
+            -4.0 * q2,
+            -4.0 * bx * q2 - 2.0 * bz * q0,
+            2.0 * bx * q1 + 2.0 * bz * q3,
+            2.0 * bx * q0 - 4.0 * bz * q2,
+        ],
+        [
+            2.0 * q1,
+            2.0 * q2,
+            0.0,
+            -4.0 * bx * q3 + 2.0 * bz * q1,
+            -2.0 * bx * q0 + 2.0 * bz * q2,
+            2.0 * bx * q1,
+        ],
+    ];
+    let row = |j: &[f32; 6]| j.iter().zip(f.iter()).map(|(j, f)| j * f).sum::<f32>();
+    Vec4::new(
+        row(&jacobian[0]),
+        row(&jacobian[1]),
+        row(&jacobian[2]),
+        row(&jacobian[3]),
+    )
+}
@@ -0,0 +1,73 @@
+use bevy::input::gamepad::{GamepadEventRaw, GamepadEventType};
+use bevy::prelude::*;
+
+use crate::orientation::Orientation;
+
+/// Virtual gamepad driven by the tilt of the device.
+/// Roll is sent as the left stick X axis and pitch as the left stick Y axis, so games reading
+/// `Axis<GamepadAxis>` can be played by tilting the device.
+pub struct VirtualGamepad {
+    pub enabled: bool,
+    /// Gamepad id of the virtual gamepad, away from the ids of real gamepads
+    pub gamepad: Gamepad,
+    /// Tilt in radians which moves the stick to the end
+    pub max_tilt: f32,
+}
+
+impl Default for VirtualGamepad {
+    fn default() -> Self {
+        VirtualGamepad {
+            enabled: false,
+            gamepad: Gamepad(16),
+            max_tilt: std::f32::consts::FRAC_PI_4,
+        }
+    }
+}
+
+/// Toggle the virtual gamepad with `G`
+pub struct VirtualGamepadPlugin;
+
+impl Plugin for VirtualGamepadPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<VirtualGamepad>()
+            .add_system(toggle_virtual_gamepad)
+            .add_system(update_virtual_gamepad);
+    }
+}
+
+fn toggle_virtual_gamepad(
+    keys: Res<Input<KeyCode>>,
+    mut vg: ResMut<VirtualGamepad>,
+    mut ev_gamepad: EventWriter<GamepadEventRaw>,
+) {
+    if keys.just_pressed(KeyCode::G) {
+        vg.enabled = !vg.enabled;
+        let event = if vg.enabled {
+            GamepadEventType::Connected
+        } else {
+            GamepadEventType::Disconnected
+        };
+        info!("virtual gamepad {:?}", event);
+        ev_gamepad.send(GamepadEventRaw(vg.gamepad, event));
+    }
+}
+
+fn update_virtual_gamepad(
+    vg: Res<VirtualGamepad>,
+    orientation: Res<Orientation>,
+    mut ev_gamepad: EventWriter<GamepadEventRaw>,
+) {
+    if !vg.enabled {
+        return;
+    }
+    let x = (orientation.roll / vg.max_tilt).clamp(-1.0, 1.0);
+    let y = (orientation.pitch / vg.max_tilt).clamp(-1.0, 1.0);
+    ev_gamepad.send(GamepadEventRaw(
+        vg.gamepad,
+        GamepadEventType::AxisChanged(GamepadAxisType::LeftStickX, x),
+    ));
+    ev_gamepad.send(GamepadEventRaw(
+        vg.gamepad,
+        GamepadEventType::AxisChanged(GamepadAxisType::LeftStickY, y),
+    ));
+}
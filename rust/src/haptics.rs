@@ -0,0 +1,114 @@
+use bevy::prelude::*;
+use bevy_serial::{SerialPortInfo, SerialWriteId, SerialWriteRequestEvent, SerialWriteResultEvent};
+use std::collections::HashMap;
+
+/// Ids of the write requests start here, away from the ids of the console
+const FIRST_ID: u64 = 1 << 32;
+
+/// Command for the actuators of the sensor board, written to the device as a line like the console commands
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceCommand {
+    /// Run the haptic motor for `duration_ms` at `strength` (0 to 255)
+    Vibrate { duration_ms: u32, strength: u8 },
+}
+
+impl DeviceCommand {
+    /// Line sent to the firmware, e.g. `vibrate 120 200`
+    pub fn encode(&self) -> Vec<u8> {
+        match self {
+            DeviceCommand::Vibrate {
+                duration_ms,
+                strength,
+            } => format!("vibrate {} {}\n", duration_ms, strength).into_bytes(),
+        }
+    }
+}
+
+/// Send this to write a command to all open ports
+#[derive(Debug, Clone, Copy)]
+pub struct DeviceCommandEvent(pub DeviceCommand);
+
+/// Write `DeviceCommandEvent`s back to the device (e.g. vibrate on collisions in the playground)
+/// through write requests, and log the ones which fail.
+/// A vibration sent while a stronger one is still running is dropped, so collisions don't flood the port.
+pub struct HapticsPlugin;
+
+#[derive(Default)]
+struct DeviceWrites {
+    /// Id of the next write request
+    next_id: u64,
+    /// Commands waiting for their result, by request id
+    pending: HashMap<u64, DeviceCommand>,
+    /// End of the running vibration in seconds since startup, and its strength
+    vibrating: Option<(f64, u8)>,
+}
+
+impl Plugin for HapticsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<DeviceCommandEvent>()
+            .insert_resource(DeviceWrites {
+                next_id: FIRST_ID,
+                ..Default::default()
+            })
+            .add_system(write_commands)
+            .add_system(check_results);
+    }
+}
+
+fn write_commands(
+    mut ev_command: EventReader<DeviceCommandEvent>,
+    mut ev_request: EventWriter<SerialWriteRequestEvent>,
+    mut writes: ResMut<DeviceWrites>,
+    info: Option<Res<SerialPortInfo>>,
+    time: Res<Time>,
+) {
+    let now = time.seconds_since_startup();
+    for DeviceCommandEvent(command) in ev_command.iter() {
+        let DeviceCommand::Vibrate {
+            duration_ms,
+            strength,
+        } = *command;
+        if let Some((end, running)) = writes.vibrating {
+            if now < end && strength <= running {
+                continue;
+            }
+        }
+        writes.vibrating = Some((now + duration_ms as f64 / 1000.0, strength));
+
+        // replays have no ports
+        let labels = info
+            .iter()
+            .flat_map(|info| info.iter())
+            .filter(|(_, state)| state.open)
+            .map(|(label, _)| label);
+        for label in labels {
+            let id = writes.next_id;
+            writes.next_id += 1;
+            writes.pending.insert(id, *command);
+            ev_request.send(SerialWriteRequestEvent {
+                label: label.clone(),
+                buffer: command.encode(),
+                id: SerialWriteId::Id(id),
+            });
+        }
+    }
+}
+
+fn check_results(
+    mut ev_result: EventReader<SerialWriteResultEvent>,
+    mut writes: ResMut<DeviceWrites>,
+) {
+    for SerialWriteResultEvent { label, id, result } in ev_result.iter() {
+        // results of other writers (e.g. the console) have ids unknown here
+        let command = match id {
+            SerialWriteId::Id(id) => match writes.pending.remove(id) {
+                Some(command) => command,
+                None => continue,
+            },
+            _ => continue,
+        };
+        if let Err(e) = result {
+            warn!("failed to write {:?} to {}: {}", command, label, e);
+        }
+    }
+}
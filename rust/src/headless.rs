@@ -0,0 +1,260 @@
+use bevy::{app::AppExit, prelude::*};
+use bevy_serial::{SerialPortInfo, SerialPorts};
+use std::fs;
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::connect::{port_setting, PortCommand};
+use crate::plot::AccelSampleEvent;
+use crate::units::SensorUnits;
+
+/// Seconds between attempts to open the port
+const RETRY_SECONDS: f32 = 2.0;
+/// Seconds between the summaries logged
+const SUMMARY_SECONDS: f32 = 1.0;
+
+/// Exit code after SIGTERM, SIGINT or Ctrl+C
+pub const EXIT_TERMINATED: i32 = 0;
+/// Exit code when the port can't be opened, with `--exit-on-error`
+pub const EXIT_OPEN_FAILED: i32 = 2;
+/// Exit code when the port is closed or fails, with `--exit-on-error`
+pub const EXIT_DISCONNECTED: i32 = 3;
+
+/// Keep the port open without a window, reopening it when it fails (or exiting with `exit_on_error`),
+/// apply the `PortCommand`s of the HTTP API, and log the sample rate and the last sample once per second
+pub struct HeadlessPlugin {
+    pub port: String,
+    pub baud_rate: u32,
+    /// Exit with `EXIT_OPEN_FAILED` or `EXIT_DISCONNECTED` instead of retrying, so a service manager restarts it
+    pub exit_on_error: bool,
+    /// File rewritten every second while samples arrive, for a watchdog checking its modification time
+    pub watchdog: Option<String>,
+    pub status: ExitStatus,
+}
+
+/// Termination request from the signal handler and the exit code of the app,
+/// shared with `main` which exits with the code after the app stops
+#[derive(Clone, Default)]
+pub struct ExitStatus {
+    terminate: Arc<AtomicBool>,
+    code: Arc<AtomicI32>,
+}
+
+impl ExitStatus {
+    /// Stop the app cleanly on SIGTERM, SIGINT and SIGHUP (Ctrl+C and closing the console on Windows)
+    pub fn handle_termination(&self) -> Result<(), ctrlc::Error> {
+        let terminate = self.terminate.clone();
+        ctrlc::set_handler(move || terminate.store(true, Ordering::SeqCst))
+    }
+
+    pub fn code(&self) -> i32 {
+        self.code.load(Ordering::SeqCst)
+    }
+
+    fn exit(&self, code: i32, ev_exit: &mut EventWriter<AppExit>) {
+        self.code.store(code, Ordering::SeqCst);
+        ev_exit.send(AppExit);
+    }
+}
+
+/// The port being read and the timer of the next attempt to open it
+struct HeadlessPort {
+    port_name: String,
+    baud_rate: u32,
+    label: Option<String>,
+    /// Closed with `PortCommand::Close`, so it isn't reopened
+    closed: bool,
+    retry: Timer,
+    exit_on_error: bool,
+}
+
+impl HeadlessPort {
+    /// Close the port, which is opened again on the next frame unless `closed` is set
+    fn close(&mut self, ports: &mut SerialPorts) {
+        if let Some(label) = self.label.take() {
+            ports.remove(&label);
+        }
+        let duration = self.retry.duration();
+        self.retry.set_elapsed(duration);
+    }
+}
+
+/// Samples since the last summary
+struct CaptureSummary {
+    samples: usize,
+    last: Option<Vec3>,
+    timer: Timer,
+    watchdog: Option<String>,
+}
+
+impl Plugin for HeadlessPlugin {
+    fn build(&self, app: &mut App) {
+        let mut retry = Timer::from_seconds(RETRY_SECONDS, true);
+        // try to open the port on the first frame
+        retry.set_elapsed(retry.duration());
+        app.insert_resource(HeadlessPort {
+            port_name: self.port.clone(),
+            baud_rate: self.baud_rate,
+            label: None,
+            closed: false,
+            retry,
+            exit_on_error: self.exit_on_error,
+        })
+        .add_event::<PortCommand>()
+        .insert_resource(CaptureSummary {
+            samples: 0,
+            last: None,
+            timer: Timer::from_seconds(SUMMARY_SECONDS, true),
+            watchdog: self.watchdog.clone(),
+        })
+        .insert_resource(self.status.clone())
+        .add_system(watch_termination)
+        .add_system(apply_port_commands)
+        .add_system(keep_port_open)
+        .add_system(log_summary);
+    }
+}
+
+fn watch_termination(status: Res<ExitStatus>, mut ev_exit: EventWriter<AppExit>) {
+    if status.terminate.swap(false, Ordering::SeqCst) {
+        info!("terminating");
+        status.exit(EXIT_TERMINATED, &mut ev_exit);
+    }
+}
+
+fn apply_port_commands(
+    mut ev_command: EventReader<PortCommand>,
+    mut port: ResMut<HeadlessPort>,
+    mut ports: SerialPorts,
+) {
+    for command in ev_command.iter() {
+        match command {
+            PortCommand::Open {
+                port_name,
+                baud_rate,
+            } => {
+                port.port_name = port_name.clone();
+                port.baud_rate = baud_rate.unwrap_or(port.baud_rate);
+                port.closed = false;
+            }
+            PortCommand::SetBaudRate(baud_rate) => port.baud_rate = *baud_rate,
+            PortCommand::Close => port.closed = true,
+        }
+        port.close(&mut ports);
+    }
+}
+
+fn keep_port_open(
+    mut port: ResMut<HeadlessPort>,
+    mut ports: SerialPorts,
+    info: Res<SerialPortInfo>,
+    status: Res<ExitStatus>,
+    mut ev_exit: EventWriter<AppExit>,
+    time: Res<Time>,
+) {
+    if let Some(label) = port.label.clone() {
+        let state = info.get(&label);
+        // the info is updated at the end of the frame the port was opened in
+        if state.map_or(true, |state| state.open) {
+            return;
+        }
+        warn!(
+            "{} disconnected: {}",
+            label,
+            state
+                .and_then(|state| state.last_error.clone())
+                .unwrap_or_else(|| "port closed".to_string())
+        );
+        // close the port so that it can be opened again
+        ports.remove(&label);
+        port.label = None;
+        if port.exit_on_error {
+            status.exit(EXIT_DISCONNECTED, &mut ev_exit);
+            return;
+        }
+    }
+
+    if port.closed || !port.retry.tick(time.delta()).just_finished() {
+        return;
+    }
+    match ports.add(port_setting(&port.port_name, port.baud_rate)) {
+        Ok(label) => {
+            info!("capturing from {}", label);
+            port.label = Some(label);
+            notify_systemd("READY=1");
+        }
+        Err(e) if port.exit_on_error => {
+            error!("{}", e);
+            status.exit(EXIT_OPEN_FAILED, &mut ev_exit);
+        }
+        Err(e) => error!("{}, retrying in {} seconds", e, RETRY_SECONDS),
+    }
+}
+
+fn log_summary(
+    mut ev_sample: EventReader<AccelSampleEvent>,
+    mut summary: ResMut<CaptureSummary>,
+    units: Res<SensorUnits>,
+    time: Res<Time>,
+) {
+    for AccelSampleEvent(accel) in ev_sample.iter() {
+        summary.samples += 1;
+        summary.last = Some(*accel);
+    }
+    if !summary.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    if summary.samples > 0 {
+        touch_watchdog(summary.watchdog.as_deref());
+    }
+    let rate = summary.samples as f32 / SUMMARY_SECONDS;
+    match summary.last.take() {
+        Some(accel) => {
+            let accel = accel * units.unit.in_unit(1.0);
+            info!(
+                "{:.0} samples/s, last {:.3} {:.3} {:.3} {}",
+                rate,
+                accel.x,
+                accel.y,
+                accel.z,
+                units.unit.symbol()
+            );
+        }
+        None => info!("no samples"),
+    }
+    summary.samples = 0;
+}
+
+/// Show that samples are still arriving: rewrite the watchdog file with the unix time,
+/// and ping the systemd watchdog (`WatchdogSec=`) if it is enabled
+fn touch_watchdog(path: Option<&str>) {
+    if let Some(path) = path {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        if let Err(e) = fs::write(path, format!("{}\n", now)) {
+            warn!("failed to touch watchdog {}: {}", path, e);
+        }
+    }
+    notify_systemd("WATCHDOG=1");
+}
+
+/// Send a state to systemd through `NOTIFY_SOCKET`, which is set for `Type=notify` services
+#[cfg(unix)]
+fn notify_systemd(state: &str) {
+    use std::os::unix::net::UnixDatagram;
+
+    let socket = match std::env::var_os("NOTIFY_SOCKET") {
+        Some(socket) => socket,
+        None => return,
+    };
+    let result = UnixDatagram::unbound().and_then(|s| s.send_to(state.as_bytes(), &socket));
+    if let Err(e) = result {
+        warn!("failed to notify systemd at {:?}: {}", socket, e);
+    }
+}
+
+#[cfg(not(unix))]
+fn notify_systemd(_state: &str) {}
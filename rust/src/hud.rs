@@ -0,0 +1,153 @@
+use bevy::prelude::*;
+
+use crate::axes::AxisMapping;
+use crate::locale::Locale;
+use crate::orientation::{Orientation, OrientationSources};
+use crate::plot::AccelSampleEvent;
+use crate::units::SensorUnits;
+use crate::SerialStorage;
+
+/// Interval to update the sample rate in seconds
+const RATE_INTERVAL: f64 = 1.0;
+
+/// Numeric roll, pitch and yaw (the magnetic heading), acceleration in the unit of the sensor, range, sample rate, temperature and battery voltage
+/// in the bottom left corner
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SampleRate>()
+            .add_startup_system(setup_hud)
+            .add_system(update_sample_rate)
+            .add_system(update_hud);
+    }
+}
+
+/// Samples per second parsed from the serial port
+#[derive(Default)]
+pub struct SampleRate {
+    pub rate: f64,
+    count: usize,
+    since: f64,
+}
+
+#[derive(Component)]
+struct HudText;
+
+fn setup_hud(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(8.0),
+                    bottom: Val::Px(56.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraMono-Medium.ttf"),
+                    font_size: 16.0,
+                    color: Color::WHITE,
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(HudText);
+}
+
+fn update_sample_rate(
+    mut ev_sample: EventReader<AccelSampleEvent>,
+    mut rate: ResMut<SampleRate>,
+    time: Res<Time>,
+) {
+    rate.count += ev_sample.iter().count();
+    let now = time.seconds_since_startup();
+    let elapsed = now - rate.since;
+    if elapsed >= RATE_INTERVAL {
+        rate.rate = rate.count as f64 / elapsed;
+        rate.count = 0;
+        rate.since = now;
+    }
+}
+
+fn update_hud(
+    ss: Res<SerialStorage>,
+    orientation: Res<Orientation>,
+    sources: Res<OrientationSources>,
+    rate: Res<SampleRate>,
+    mapping: Res<AxisMapping>,
+    units: Res<SensorUnits>,
+    locale: Res<Locale>,
+    mut text: Query<&mut Text, With<HudText>>,
+) {
+    // the names are padded to the longest one, so the values line up
+    let names = ["roll", "pitch", "yaw"].map(|key| locale.text(key));
+    let width = names
+        .iter()
+        .map(|name| name.chars().count())
+        .max()
+        .unwrap_or(0);
+    let mut lines = vec![
+        format!(
+            "{:<width$} {:7.1}°",
+            names[0],
+            orientation.roll.to_degrees(),
+            width = width
+        ),
+        format!(
+            "{:<width$} {:7.1}°",
+            names[1],
+            orientation.pitch.to_degrees(),
+            width = width
+        ),
+    ];
+    match orientation.heading {
+        Some(heading) => lines.push(format!(
+            "{:<width$} {:7.1}°",
+            names[2],
+            heading.to_degrees(),
+            width = width
+        )),
+        // yaw can't be estimated from gravity alone
+        None => lines.push(format!("{:<width$}      --", names[2], width = width)),
+    }
+    let symbol = units.unit.symbol();
+    match ss.latest {
+        Some(accel) => {
+            let accel = accel * units.unit.in_unit(1.0);
+            lines.push(format!(
+                "x {:6.3} {3}  y {:6.3} {3}  z {:6.3} {3}",
+                accel.x, accel.y, accel.z, symbol
+            ))
+        }
+        None => lines.push(locale.text("no_samples").to_string()),
+    }
+    if let Some(gyro) = ss.gyro {
+        lines.push(format!(
+            "x {:6.1} °/s y {:6.1} °/s z {:6.1} °/s",
+            gyro.x, gyro.y, gyro.z
+        ));
+    }
+    if ss.quat.is_some() {
+        let source = sources.current().to_string();
+        lines.push(locale.fill("orientation_source", &[&locale.text(&source)]));
+    }
+    lines.push(locale.fill("sample_rate", &[&format!("{:.0}", rate.rate)]));
+    lines.push(locale.fill("axes", &[&*mapping]));
+    lines.push(locale.fill("range", &[&units.range]));
+    if let Some(temperature) = ss.temperature {
+        lines.push(format!("{:.1} °C", temperature));
+    }
+    if let Some(battery) = ss.battery {
+        lines.push(format!("{:.2} V", battery));
+    }
+
+    for mut text in text.iter_mut() {
+        text.sections[0].value = lines.join("\n");
+    }
+}
@@ -0,0 +1,225 @@
+use bevy::prelude::*;
+use std::io::{self, BufRead};
+use std::path::Path;
+
+use crate::recording::{open_text, RecordedChunk, Recording, RecordingHeader, RECORDING_MAGIC};
+use crate::units::AccelUnit;
+
+/// Lines per second of captures without timestamps
+pub const UNTIMED_RATE: f64 = 100.0;
+
+/// Start of the first line of a PuTTY session log
+const PUTTY_MAGIC: &str = "=~=~=~=~=~=~=~=~=~=~=~= PuTTY log";
+
+/// Kind of a capture file replayed with `--replay`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureKind {
+    /// Recording of this viewer, with or without a header
+    Recording,
+    /// Lines of comma separated values, with an optional header row and time column
+    Csv,
+    /// Session log of PuTTY, with the text received from the device
+    Putty,
+    /// CAN frames exported by SavvyCAN (GVRET CSV), whose data bytes are replayed
+    SavvyCan,
+}
+
+impl CaptureKind {
+    /// Guess the kind from the first line of the file, or the extension for plain CSV
+    pub fn sniff(path: &Path, first_line: &str) -> CaptureKind {
+        let extension = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .map(|name| name.trim_end_matches(".gz").to_lowercase())
+            .unwrap_or_default();
+        if first_line.starts_with(RECORDING_MAGIC) {
+            CaptureKind::Recording
+        } else if first_line.starts_with(PUTTY_MAGIC) {
+            CaptureKind::Putty
+        } else if first_line.to_lowercase().starts_with("time stamp,id,") {
+            CaptureKind::SavvyCan
+        } else if extension.ends_with(".csv") {
+            CaptureKind::Csv
+        } else {
+            CaptureKind::Recording
+        }
+    }
+}
+
+/// Load a recording of this viewer, or a capture of another tool converted to a recording.
+/// Times of imported captures start at zero.
+pub fn load(path: &Path) -> io::Result<Recording> {
+    let mut lines = open_text(path)?.lines();
+    let first = match lines.next() {
+        Some(line) => line?,
+        None => return Ok(Recording::default()),
+    };
+    let kind = CaptureKind::sniff(path, &first);
+    info!("replaying {} as {:?}", path.display(), kind);
+    let lines = std::iter::once(Ok(first)).chain(lines);
+    let source = path.display().to_string();
+    match kind {
+        CaptureKind::Recording => Recording::read(lines, &source),
+        CaptureKind::Csv => import_csv(lines),
+        CaptureKind::Putty => import_putty(lines),
+        CaptureKind::SavvyCan => import_savvycan(lines, &source),
+    }
+}
+
+/// Recording of text lines, with the format of the lines if it is known
+fn text_recording(format: Option<&str>) -> Recording {
+    Recording {
+        header: RecordingHeader {
+            format: format.map(str::to_string),
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+/// Push a line at the time, or at `UNTIMED_RATE` after the previous one
+fn push_line(recording: &mut Recording, time: Option<f64>, line: &str) {
+    let time = time.unwrap_or(recording.chunks.len() as f64 / UNTIMED_RATE);
+    recording.chunks.push(RecordedChunk {
+        time,
+        bytes: format!("{}\n", line).into_bytes(),
+    });
+}
+
+/// Seconds per unit of a time column named like `time`, `time (ms)` or `timestamp_us`
+fn time_scale(column: &str) -> Option<f64> {
+    let column = column.trim().to_lowercase();
+    if !(column.starts_with("time") || column == "t") {
+        return None;
+    }
+    if column.contains("us") || column.contains("µs") {
+        Some(1e-6)
+    } else if column.contains("ms") {
+        Some(1e-3)
+    } else {
+        Some(1.0)
+    }
+}
+
+/// Plain CSV like the one written by the export, `time,x (g),y (g),z (g)`.
+/// The columns other than time are replayed as lines of the `csv` format, and the unit in parentheses of the first
+/// of them in the header row (`x (m/s²)`) is used as the unit of the values.
+fn import_csv(mut lines: impl Iterator<Item = io::Result<String>>) -> io::Result<Recording> {
+    let mut recording = text_recording(Some("csv"));
+    let mut time_column = None;
+    let mut first = None;
+    if let Some(line) = lines.next() {
+        let line = line?;
+        let is_header = line.split(',').any(|v| v.trim().parse::<f64>().is_err());
+        if is_header {
+            time_column = line
+                .split(',')
+                .enumerate()
+                .find_map(|(i, column)| time_scale(column).map(|scale| (i, scale)));
+            // the unit of the values, not of the time (`time (ms)`)
+            let time_index = time_column.map(|(column, _)| column);
+            recording.header.unit = line
+                .split(',')
+                .enumerate()
+                .filter(|(i, _)| Some(*i) != time_index)
+                .find_map(|(_, column)| column.split(['(', ')']).nth(1))
+                .and_then(|unit| unit.trim().parse::<AccelUnit>().ok());
+        } else {
+            first = Some(line);
+        }
+    }
+
+    let mut start = None;
+    for line in first.map(Ok).into_iter().chain(lines) {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (time, values) = match time_column {
+            Some((column, scale)) => {
+                let time = line
+                    .split(',')
+                    .nth(column)
+                    .and_then(|t| t.trim().parse::<f64>().ok())
+                    .map(|t| t * scale);
+                let values: Vec<&str> = line
+                    .split(',')
+                    .enumerate()
+                    .filter(|(i, _)| *i != column)
+                    .map(|(_, v)| v)
+                    .collect();
+                (time, values.join(","))
+            }
+            None => (None, line.clone()),
+        };
+        let time = time.map(|t| t - *start.get_or_insert(t));
+        push_line(&mut recording, time, &values);
+    }
+    Ok(recording)
+}
+
+/// PuTTY session log: the first line is the PuTTY header, the rest is the text received.
+/// The log has no timestamps, so the lines are replayed at `UNTIMED_RATE`
+/// and the format is detected like on a live port.
+fn import_putty(lines: impl Iterator<Item = io::Result<String>>) -> io::Result<Recording> {
+    let mut recording = text_recording(None);
+    for line in lines.skip(1) {
+        let line = line?;
+        push_line(&mut recording, None, line.trim_end_matches('\r'));
+    }
+    Ok(recording)
+}
+
+/// SavvyCAN (GVRET) CSV: `Time Stamp,ID,Extended,Dir,Bus,LEN,D1,...,D8` with the time stamp in
+/// microseconds and the data bytes in hex. The data bytes of each frame are replayed as a chunk,
+/// so packets split across frames are joined by the parser.
+fn import_savvycan(
+    mut lines: impl Iterator<Item = io::Result<String>>,
+    source: &str,
+) -> io::Result<Recording> {
+    let header = lines.next().transpose()?.unwrap_or_default();
+    let columns: Vec<String> = header.split(',').map(|c| c.trim().to_lowercase()).collect();
+    let column = |name: &str| {
+        columns.iter().position(|c| c == name).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("no {} column in {}", name, source),
+            )
+        })
+    };
+    let (time_column, len_column, data_column) =
+        (column("time stamp")?, column("len")?, column("d1")?);
+
+    let mut recording = text_recording(None);
+    let mut start = None;
+    for line in lines {
+        let line = line?;
+        let values: Vec<&str> = line.split(',').map(str::trim).collect();
+        match parse_frame(&values, time_column, len_column, data_column) {
+            Some((time, bytes)) => recording.chunks.push(RecordedChunk {
+                time: time - *start.get_or_insert(time),
+                bytes,
+            }),
+            None if line.trim().is_empty() => {}
+            None => warn!("skipping malformed line in {}: {}", source, line),
+        }
+    }
+    Ok(recording)
+}
+
+/// Time in seconds and data bytes of a SavvyCAN frame
+fn parse_frame(
+    values: &[&str],
+    time_column: usize,
+    len_column: usize,
+    data_column: usize,
+) -> Option<(f64, Vec<u8>)> {
+    let time = values.get(time_column)?.parse::<f64>().ok()? * 1e-6;
+    let len: usize = values.get(len_column)?.parse().ok()?;
+    let bytes = values
+        .get(data_column..data_column + len)?
+        .iter()
+        .map(|b| u8::from_str_radix(b, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    Some((time, bytes))
+}
@@ -0,0 +1,241 @@
+use bevy::prelude::*;
+use bevy_serial::{encode_hex, SerialReadEvent};
+use std::collections::VecDeque;
+
+use crate::axes::AxisMapping;
+use crate::locale::Locale;
+use crate::orientation::Orientation;
+use crate::parser::{ParseRecord, ParserRegistry};
+use crate::theme::Theme;
+use crate::units::SensorUnits;
+
+/// Packets listed in the panel
+const MAX_RECORDS: usize = 12;
+/// Width of the raw column in characters
+const RAW_WIDTH: usize = 40;
+
+const PANEL_COLOR: Color = Color::rgba(0.0, 0.0, 0.0, 0.7);
+
+/// `I` toggles a panel of the last packets, with the raw bytes of each next to its parsed fields,
+/// the acceleration in world axes and the rotation of the model. Malformed packets are shown in red.
+pub struct InspectorPlugin;
+
+impl Plugin for InspectorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Inspector>()
+            .add_startup_system(setup_inspector)
+            .add_system(toggle_inspector)
+            .add_system(trace_packets)
+            .add_system(show_inspector);
+    }
+}
+
+/// The panel parses the serial data again with its own parsers, so the data shown doesn't slow down the views
+struct Inspector {
+    open: bool,
+    parsers: ParserRegistry,
+    /// Bytes of the packet being parsed
+    raw: Vec<u8>,
+    records: VecDeque<ParseRecord>,
+}
+
+impl Default for Inspector {
+    fn default() -> Self {
+        Self {
+            open: false,
+            parsers: ParserRegistry::default(),
+            raw: Vec::new(),
+            records: VecDeque::with_capacity(MAX_RECORDS),
+        }
+    }
+}
+
+#[derive(Component)]
+struct InspectorPanel;
+
+#[derive(Component)]
+struct InspectorText;
+
+fn setup_inspector(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let font = asset_server.load("fonts/FiraMono-Medium.ttf");
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Percent(20.0),
+                    top: Val::Px(120.0),
+                    ..Default::default()
+                },
+                padding: Rect::all(Val::Px(8.0)),
+                display: Display::None,
+                ..Default::default()
+            },
+            color: PANEL_COLOR.into(),
+            ..Default::default()
+        })
+        .insert(InspectorPanel)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        "",
+                        TextStyle {
+                            font,
+                            font_size: 14.0,
+                            color: Color::WHITE,
+                        },
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                })
+                .insert(InspectorText);
+        });
+}
+
+fn toggle_inspector(
+    keys: Res<Input<KeyCode>>,
+    mut inspector: ResMut<Inspector>,
+    mut panel: Query<&mut Style, With<InspectorPanel>>,
+) {
+    if !keys.just_pressed(KeyCode::I) {
+        return;
+    }
+    inspector.open = !inspector.open;
+    // start from the next packet, with the format and units of the views
+    inspector.records.clear();
+    inspector.raw.clear();
+    inspector.parsers.reset();
+    for mut style in panel.iter_mut() {
+        style.display = if inspector.open {
+            Display::Flex
+        } else {
+            Display::None
+        };
+    }
+}
+
+fn trace_packets(
+    mut ev_serial: EventReader<SerialReadEvent>,
+    mut inspector: ResMut<Inspector>,
+    parsers: Res<ParserRegistry>,
+    units: Res<SensorUnits>,
+) {
+    if !inspector.open {
+        return;
+    }
+    let inspector = &mut *inspector;
+    if inspector.parsers.selected() != parsers.selected() {
+        inspector.parsers.select(parsers.selected());
+        inspector.raw.clear();
+    }
+    inspector.parsers.set_units(*units);
+
+    let mut records = Vec::new();
+    for SerialReadEvent(_, buffer) in ev_serial.iter() {
+        inspector
+            .parsers
+            .trace(buffer, &mut inspector.raw, &mut records);
+    }
+    for record in records {
+        if inspector.records.len() == MAX_RECORDS {
+            inspector.records.pop_front();
+        }
+        inspector.records.push_back(record);
+    }
+}
+
+fn show_inspector(
+    inspector: Res<Inspector>,
+    mapping: Res<AxisMapping>,
+    mut text: Query<&mut Text, With<InspectorText>>,
+    asset_server: Res<AssetServer>,
+    locale: Res<Locale>,
+    theme: Res<Theme>,
+) {
+    if !inspector.open || (!inspector.is_changed() && !theme.is_changed()) {
+        return;
+    }
+    let font = asset_server.load("fonts/FiraMono-Medium.ttf");
+    let style = |color| TextStyle {
+        font: font.clone(),
+        font_size: 14.0,
+        color,
+    };
+    let mut sections = vec![TextSection {
+        value: format!(
+            "{:<width$} | {}\n",
+            locale.text("raw"),
+            locale.text("inspector_fields"),
+            width = RAW_WIDTH
+        ),
+        style: style(Color::GRAY),
+    }];
+    for record in inspector.records.iter() {
+        let parsed = match &record.packet {
+            Some(packet) => {
+                let mut fields = vec![];
+                if let Some(quat) = packet.quat {
+                    fields.extend([quat.w, quat.x, quat.y, quat.z]);
+                }
+                if let Some(gyro) = packet.gyro {
+                    fields.extend([gyro.x, gyro.y, gyro.z]);
+                }
+                fields.extend([packet.accel.x, packet.accel.y, packet.accel.z]);
+                if let Some(mag) = packet.mag {
+                    fields.extend([mag.x, mag.y, mag.z]);
+                }
+                fields.extend(packet.temperature);
+                fields.extend(packet.battery);
+                let fields: Vec<String> = fields.iter().map(|v| format!("{:.3}", v)).collect();
+                let accel = mapping.apply(packet.accel);
+                let q = Orientation::from_accel(accel).quat();
+                format!(
+                    "{} -> ({:.3}, {:.3}, {:.3}), quat ({:.3}, {:.3}, {:.3}, {:.3})",
+                    fields.join(" "),
+                    accel.x,
+                    accel.y,
+                    accel.z,
+                    q.x,
+                    q.y,
+                    q.z,
+                    q.w
+                )
+            }
+            None => locale.text("parse_failed").to_string(),
+        };
+        sections.push(TextSection {
+            value: format!(
+                "{:<width$} | {}\n",
+                raw_column(&record.raw),
+                parsed,
+                width = RAW_WIDTH
+            ),
+            style: style(if record.packet.is_some() {
+                Color::WHITE
+            } else {
+                theme.error
+            }),
+        });
+    }
+
+    for mut text in text.iter_mut() {
+        text.sections = sections.clone();
+    }
+}
+
+/// Text lines as they are, and binary packets in hex, cut to the width of the column
+fn raw_column(raw: &[u8]) -> String {
+    let text = raw
+        .iter()
+        .all(|b| b.is_ascii_graphic() || b.is_ascii_whitespace());
+    let mut column = if text {
+        String::from_utf8_lossy(raw).trim().to_string()
+    } else {
+        encode_hex(raw)
+    };
+    if column.chars().count() > RAW_WIDTH {
+        column = column.chars().take(RAW_WIDTH - 3).collect::<String>() + "...";
+    }
+    column
+}
@@ -0,0 +1,8 @@
+//! The recordings and packet parsers of the viewer, shared by its binaries:
+//! the viewer itself and the `capture2csv` converter of src/bin.
+
+pub mod import;
+pub mod markers;
+pub mod parser;
+pub mod recording;
+pub mod units;
@@ -0,0 +1,171 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Language of the text shown by the viewer. Log lines and the messages of serial errors stay in English.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Language {
+    #[default]
+    #[serde(rename = "en")]
+    English,
+    #[serde(rename = "es")]
+    Spanish,
+}
+
+/// Parsed from `en` or `es`
+impl FromStr for Language {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "en" => Ok(Language::English),
+            "es" => Ok(Language::Spanish),
+            _ => Err(format!("unknown language {}, choose from en or es", s)),
+        }
+    }
+}
+
+/// Key, English and Spanish text. `{}` are replaced in order by `Locale::fill`.
+const STRINGS: &[(&str, &str, &str)] = &[
+    // port selection and disconnected screens
+    (
+        "select_port",
+        "select the serial port of the sensor",
+        "elige el puerto serie del sensor",
+    ),
+    (
+        "no_ports",
+        "no serial ports found",
+        "no se encontró ningún puerto serie",
+    ),
+    ("refresh", "R: refresh", "R: actualizar"),
+    ("disconnected", "disconnected from {}", "desconectado de {}"),
+    (
+        "reconnect_keys",
+        "Enter: reconnect\nP: select another port",
+        "Enter: reconectar\nP: elegir otro puerto",
+    ),
+    (
+        "not_responding",
+        "sensor not responding",
+        "el sensor no responde",
+    ),
+    // HUD
+    ("roll", "roll", "alabeo"),
+    ("pitch", "pitch", "cabeceo"),
+    ("yaw", "yaw", "guiñada"),
+    ("no_samples", "no samples", "sin muestras"),
+    (
+        "orientation_source",
+        "orientation from the {} (O)",
+        "orientación del {} (O)",
+    ),
+    ("device", "device", "dispositivo"),
+    ("host", "viewer", "visor"),
+    ("sample_rate", "{} samples/s", "{} muestras/s"),
+    ("axes", "axes {}", "ejes {}"),
+    ("range", "range {}", "rango {}"),
+    // buttons and menus
+    ("export", "Export", "Exportar"),
+    ("format", "format: {}", "formato: {}"),
+    ("detecting", "detecting", "detectando"),
+    ("auto_detect", "auto detect", "detección auto"),
+    // alarms
+    ("alarm_on", "on", "sí"),
+    ("alarm_off", "off", "no"),
+    ("tilt", "tilt", "inclinación"),
+    ("battery", "battery", "batería"),
+    // compass and magnetometer calibration
+    ("compass_points", "N NE E SE S SW W NW", "N NE E SE S SO O NO"),
+    (
+        "mag_calibration",
+        "magnetometer calibration: rotate the device slowly through every orientation, {}/{} samples (N: finish, Shift + N: cancel)",
+        "calibración del magnetómetro: gira el dispositivo despacio en todas las orientaciones, {}/{} muestras (N: terminar, Mayús + N: cancelar)",
+    ),
+    (
+        "mag_span",
+        "span x {} µT  y {} µT  z {} µT",
+        "amplitud x {} µT  y {} µT  z {} µT",
+    ),
+    // motion capture
+    (
+        "mocap",
+        "capturing motion: {} s, {} poses (B: stop and export)",
+        "capturando movimiento: {} s, {} poses (B: parar y exportar)",
+    ),
+    // replay timeline
+    ("paused", "paused", "en pausa"),
+    // spectrum
+    ("peak", "peak {} Hz, 0 - {} Hz", "pico {} Hz, 0 - {} Hz"),
+    (
+        "collecting",
+        "collecting {} samples",
+        "recogiendo {} muestras",
+    ),
+    // toasts
+    (
+        "settings_restart",
+        "{} changed in the settings file, restart to apply it",
+        "{} ha cambiado en el archivo de ajustes, reinicia para aplicarlo",
+    ),
+    ("reconnected", "{} reconnected", "{} reconectado"),
+    (
+        "rejected",
+        "{} packets failed to parse as {}",
+        "{} paquetes no se pudieron leer como {}",
+    ),
+    // freeze mode
+    (
+        "frozen",
+        "frozen, {} samples buffered (H: resume, .: next sample)",
+        "congelado, {} muestras en espera (H: continuar, .: siguiente muestra)",
+    ),
+    (
+        "frozen_dropped",
+        "{} samples dropped, the buffer is full",
+        "{} muestras descartadas, el búfer está lleno",
+    ),
+    ("frozen_sample", "sample {}", "muestra {}"),
+    ("frozen_bytes", "{} bytes", "{} bytes"),
+    ("frozen_text", "text", "texto"),
+    // packet inspector
+    ("raw", "raw", "bytes"),
+    (
+        "inspector_fields",
+        "fields -> world accel (g), rotation (I: close)",
+        "campos -> acel. en el mundo (g), rotación (I: cerrar)",
+    ),
+    ("parse_failed", "failed to parse", "no se pudo leer"),
+];
+
+/// Text of the language chosen with `--lang` or in the settings file
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Locale {
+    pub language: Language,
+}
+
+impl Locale {
+    /// Text of the key, or the key itself if it is missing from `STRINGS`
+    pub fn text<'a>(&self, key: &'a str) -> &'a str {
+        match STRINGS.iter().find(|(k, _, _)| *k == key) {
+            Some((_, english, spanish)) => match self.language {
+                Language::English => *english,
+                Language::Spanish => *spanish,
+            },
+            None => key,
+        }
+    }
+
+    /// Text of the key with each `{}` replaced by the next value
+    pub fn fill(&self, key: &str, values: &[&dyn fmt::Display]) -> String {
+        let mut parts = self.text(key).split("{}");
+        let mut text = parts.next().unwrap_or_default().to_string();
+        for (i, part) in parts.enumerate() {
+            if let Some(value) = values.get(i) {
+                text.push_str(&value.to_string());
+            }
+            text.push_str(part);
+        }
+        text
+    }
+}
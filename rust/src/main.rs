@@ -1,131 +1,534 @@
 use bevy::{
     prelude::*,
+    app::ScheduleRunnerSettings,
     core::FixedTimestep,
     diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin},
-    render::mesh::Mesh, transform
+    log::LogPlugin,
+    transform
 };
-use bevy_serial::{SerialPlugin, SerialReadEvent, SerialWriteEvent};
+use std::collections::HashMap;
+use std::time::Duration;
+use bevy_serial::{
+    SerialInfoPlugin, SerialLatency, SerialOverlayPlugin, SerialPlugin, SerialReadEvent,
+    SerialWriteEvent,
+};
+// the recordings and parsers are in the library, shared with the converter of src/bin
+use bevy_accel::{import, markers, parser, recording, units};
+
+mod alarms;
+mod api;
+mod args;
+mod audio;
+mod axes;
+mod calibration;
+mod compass;
+mod connect;
+mod console;
+mod detect;
+mod export;
+mod format;
+mod frame;
+mod freeze;
+mod fusion;
+mod gamepad;
+mod haptics;
+mod headless;
+mod hud;
+mod inspect;
+mod locale;
+mod metrics;
+mod mocap;
+mod model;
+mod orientation;
+mod osc;
+mod playground;
+mod plot;
+mod plotter;
+mod reload;
+mod replay;
+mod ros;
+mod screenshot;
+mod settings;
+mod smoothing;
+mod spectrum;
+mod stale;
+mod stream;
+mod tare;
+mod theme;
+mod toast;
+mod tuning;
+mod windows;
+
+use alarms::AlarmPlugin;
+use api::ApiPlugin;
+use args::Args;
+use audio::AudioFeedbackPlugin;
+use axes::{AxisMapping, AxisMappingPlugin};
+use calibration::CalibrationPlugin;
+use compass::CompassPlugin;
+use connect::{AppState, ConnectPlugin, BAUD_RATE};
+use console::ConsolePlugin;
+use export::ExportPlugin;
+use format::{FormatPlugin, ParserPlugin};
+use frame::Frames;
+use freeze::{Freeze, FreezePlugin};
+use gamepad::VirtualGamepadPlugin;
+use haptics::HapticsPlugin;
+use headless::{ExitStatus, HeadlessPlugin};
+use hud::HudPlugin;
+use inspect::InspectorPlugin;
+use locale::Locale;
+use markers::{MarkerEvent, MarkerPlugin};
+use metrics::MetricsPlugin;
+use mocap::MocapPlugin;
+use model::ModelPlugin;
+use orientation::OrientationPlugin;
+use osc::OscPlugin;
+use parser::{packet_times, Packet, PacketEvent, ParserRegistry};
+use playground::PlaygroundPlugin;
+use fusion::Madgwick;
+use plot::{AccelSampleEvent, LowPass, PlotPlugin};
+use plotter::PlotterPlugin;
+use recording::{RecorderPlugin, RecordingHeader};
+use reload::ReloadPlugin;
+use replay::{ReplayChunkEvent, ReplayClock, ReplayPlugin, REPLAY_LABEL};
+use ros::RosBridgePlugin;
+use screenshot::ScreenshotPlugin;
+use settings::{Settings, SettingsFile};
+use smoothing::{SmoothedRotation, Smoothing, SmoothingPlugin};
+use spectrum::SpectrumPlugin;
+use stale::StalePlugin;
+use stream::StreamPlugin;
+use tare::{Tare, TarePlugin};
+use theme::ThemePlugin;
+use toast::ToastPlugin;
+use tuning::TuningPlugin;
+use units::SensorUnits;
+use windows::{SceneWindow, WindowLayoutPlugin};
 
 // to write data to serial port periodically
 // struct SerialWriteTimer(Timer);
 
 const TIME_STEP: f32 = 1.0 / 30.0;
+/// Seconds between frames in the headless mode, so it doesn't spin a core
+const HEADLESS_STEP: f64 = 1.0 / 100.0;
 
 #[derive(Component)]
 struct MyObject();
 
 fn main() {
-    App::new()
+    let args = Args::parse();
+    if let Some(format) = &args.gen_arduino {
+        match parser::arduino_header(format) {
+            Some(header) => print!("{}", header),
+            None => {
+                eprintln!("{} is not a binary format, use one of the binary formats of --format like \"binary f32\"", format);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    let loaded = Settings::load_or_create(std::path::Path::new(&args.settings));
+    // changes made in the viewer are saved, unless the file couldn't be read so it isn't overwritten
+    let settings_file = loaded.as_ref().ok().map(|settings| SettingsFile {
+        path: args.settings.clone().into(),
+        settings: settings.clone(),
+    });
+    let settings = loaded.unwrap_or_else(|e| {
+        eprintln!("Failed to read settings {}, using the defaults: {}", args.settings, e);
+        Settings::default()
+    });
+    // range and unit of the sensor, used by the parsers, plot, HUD and alarms
+    let mut units = SensorUnits {
+        range: args.range.unwrap_or(settings.sensor.range),
+        unit: args.unit.unwrap_or(settings.sensor.unit),
+    };
+    // frames of the quaternions sent by the devices and of the rotations written out
+    let frames = Frames {
+        frame: args.frame.unwrap_or(settings.frame),
+        devices: settings
+            .devices
+            .iter()
+            .filter_map(|(device, settings)| Some((device.clone(), settings.frame?)))
+            .collect(),
+    };
+    let mut format = args.format.clone();
+
+    if args.headless {
+        return run_headless(&args, &settings, units, frames);
+    }
+
+    // positions and sizes of the windows, and whether the 3D view has its own
+    let mut layout = settings.windows;
+    layout.split |= args.split_window;
+
+    let mut app = App::new();
+    app
+        .insert_resource(layout.main_descriptor())
         //.add_plugins(MinimalPlugins)
         .add_plugins(DefaultPlugins)
         .add_plugin(FrameTimeDiagnosticsPlugin::default())
-        .add_plugin(LogDiagnosticsPlugin::default())
-        // simply specify port name and baud rate for `SerialPlugin`
-        .add_plugin(SerialPlugin::new("/dev/tty.usbserial-71D22653AC", 115200))
+        .add_plugin(LogDiagnosticsPlugin::default());
+
+    if let Some(path) = &args.replay {
+        let recording = import::load(std::path::Path::new(path))
+            .unwrap_or_else(|e| panic!("Failed to load recording {}: {}", path, e));
+        // replay with the range, unit and format of the recording, unless they are given
+        let header = &recording.header;
+        if let (None, Some(range)) = (args.range, header.range) {
+            units.range = range;
+        }
+        if let (None, Some(unit)) = (args.unit, header.unit) {
+            units.unit = unit;
+        }
+        format = format.or_else(|| header.format.clone());
+        if let Some(step) = args.replay_step {
+            app.insert_resource(ReplayClock::Fixed(step));
+        }
+        // no ports are opened, but the serial events are still needed
+        app.add_plugin(SerialPlugin { settings: vec![] })
+            .add_plugin(ReplayPlugin { recording })
+            .add_state(AppState::Streaming);
+    } else {
+        // the port is opened on the port selection screen (or from --port)
+        app.add_plugin(SerialPlugin { settings: vec![] })
+        .add_plugin(ConnectPlugin {
+            port: args.port.clone(),
+            baud_rate: settings.baud_rate.unwrap_or(BAUD_RATE),
+        })
+        // grey out the model if the sensor sends nothing for a second
+        .add_plugin(StalePlugin)
+        // show the port state and RX/TX rates in the bottom right corner
+        .add_plugin(SerialInfoPlugin)
+        .add_plugin(SerialOverlayPlugin::new("fonts/FiraMono-Medium.ttf"));
+    }
+    if args.playground {
+        // move the model through a room by the integrated position
+        app.add_plugin(PlaygroundPlugin);
+    }
+    if args.haptics {
+        // vibrate commands written back to the device
+        app.add_plugin(HapticsPlugin);
+    }
+    if let Some(mode) = args.audio {
+        // sound driven by the tilt or shakes, and on alarms
+        app.add_plugin(AudioFeedbackPlugin { mode });
+    }
+    // record from startup with --record, or when started from the HTTP API
+    app.add_plugin(RecorderPlugin {
+        settings: args.record.clone().unwrap_or_default(),
+        header: RecordingHeader::new(units, format.clone()),
+        start: args.record.is_some(),
+    });
+    if let Some(addr) = &args.api {
+        // control the ports and recording remotely
+        app.add_plugin(ApiPlugin { addr: addr.clone() });
+    }
+    if let Some(addr) = &args.stream {
+        // parsed packets as protobuf for other tools
+        app.add_plugin(StreamPlugin { addr: addr.clone() });
+    }
+    if let Some(settings) = &args.ros {
+        // sensor_msgs/msg/Imu for a ROS 2 stack
+        app.add_plugin(RosBridgePlugin { settings: settings.clone() });
+    }
+    if let Some(settings) = &args.osc {
+        // OSC messages for TouchDesigner, Max or Pure Data
+        app.add_plugin(OscPlugin { settings: settings.clone() });
+    }
+    if !args.plotter.is_empty() {
+        // Teleplot or Arduino Serial Plotter lines to cross-check the plots
+        app.add_plugin(PlotterPlugin { outputs: args.plotter.clone() });
+    }
+
+    if let Some(settings_file) = settings_file {
+        app.insert_resource(settings_file);
+    }
+    app
+        .insert_resource(units)
+        .insert_resource(frames)
+        // parameters of the filters, before the plugins which would insert the defaults
+        .insert_resource(LowPass { cutoff: settings.filter.cutoff })
+        .insert_resource(Madgwick { beta: settings.filter.beta, ..Default::default() })
+        // language of the text shown, for the screens, HUD, panels and toasts
+        .insert_resource(Locale { language: args.language.unwrap_or(settings.language) })
+        // colors of the plots, gizmos, alarms and panels, including color-blind-safe ones (T)
+        .add_plugin(ThemePlugin { theme: args.theme.unwrap_or(settings.theme) })
         .init_resource::<SerialStorage>()
+        .add_event::<ReplayChunkEvent>()
         .add_system(read_serial)
+        // packet format of the serial data, detected or chosen with the dropdown below the export button
+        .add_plugin(FormatPlugin { format })
+        // remap device axes to world axes (F1/F2/F3, Shift to flip)
+        .add_plugin(AxisMappingPlugin { mapping: args.axes.or(settings.axes).unwrap_or_default() })
+        .add_plugin(ModelPlugin { model: args.model.clone(), scale: args.model_scale })
+        // 3D view in a second window, e.g. on another monitor, and the window layout saved to the settings file
+        .add_plugin(WindowLayoutPlugin { layout })
+        // hard and soft-iron calibration of the magnetometer for the heading (N to start and finish)
+        .add_plugin(CalibrationPlugin { calibration: settings.magnetometer })
+        // quaternion of the device or the filter of the viewer, for each device (O)
+        .add_plugin(OrientationPlugin {
+            source: args.orientation.unwrap_or(settings.orientation),
+            devices: settings
+                .devices
+                .iter()
+                .map(|(device, settings)| (device.clone(), settings.orientation))
+                .collect(),
+        })
+        // smoothing of the rotation of the model, for noisy or slow sensors
+        .add_plugin(SmoothingPlugin {
+            smoothing: Smoothing {
+                mode: args.smoothing.unwrap_or(settings.smoothing.mode),
+                time_constant: args.smoothing_time.unwrap_or(settings.smoothing.time_constant),
+            },
+        })
+        // heading tape at the bottom when the packets have the magnetic field
+        .add_plugin(CompassPlugin)
+        .add_plugin(MarkerPlugin)
+        .add_plugin(PlotPlugin { plots: settings.plots.clone() })
+        .add_plugin(HudPlugin)
+        // serial errors, reconnects and malformed packets in the top left corner
+        .add_plugin(ToastPlugin)
+        // freeze the views and step through the samples with their raw bytes (H, .)
+        .add_plugin(FreezePlugin)
+        // raw bytes of the last packets next to what they were parsed into (toggle with I)
+        .add_plugin(InspectorPlugin)
+        // send configuration commands to the firmware (toggle with `)
+        .add_plugin(ConsolePlugin)
+        // threshold alarms on the acceleration and tilt (Alt + 1-9 to enable or disable)
+        .add_plugin(AlarmPlugin {
+            alarms: if args.alarms.is_empty() { settings.alarms.clone() } else { args.alarms.clone() },
+            units,
+        })
+        // spectrum of the acceleration magnitude for vibration diagnostics (toggle with F)
+        .add_plugin(SpectrumPlugin)
+        .add_plugin(ExportPlugin)
+        // PNG of the window (F12) and GIF clips (hold F11)
+        .add_plugin(ScreenshotPlugin)
+        // orientation track for animation, exported as BVH and CSV (B to start and stop)
+        .add_plugin(MocapPlugin)
+        // tilt the device to drive a virtual gamepad (toggle with G)
+        .add_plugin(VirtualGamepadPlugin)
+        // set the current orientation as zero (Z, Shift + Z to clear)
+        .add_plugin(TarePlugin)
+        // apply the changes to the settings file while the viewer runs
+        .add_plugin(ReloadPlugin)
         .add_system_set(
             SystemSet::new()
                 .with_run_criteria(FixedTimestep::step(TIME_STEP as f64))
                 .with_system(box_movement_system),
         )
-        .add_startup_system(setup)
-        .run();
+        .add_startup_system(setup);
+    if args.tune {
+        // inspector window of the filter, fusion and alarm parameters, after their plugins so it starts from them
+        app.add_plugin(TuningPlugin);
+    }
+    app.run();
 }
 
+/// Capture without a window (e.g. on a Raspberry Pi): read the port given with `--port`,
+/// parse and record the data with the same plugins as the viewer, and log a summary every second.
+/// Exits with the code of `ExitStatus` when terminated or, with `--exit-on-error`, when the port fails.
+fn run_headless(args: &Args, settings: &Settings, units: SensorUnits, frames: Frames) {
+    let port = match &args.port {
+        Some(port) => port.clone(),
+        None => {
+            eprintln!("--headless needs the port to capture with --port <name>");
+            std::process::exit(1);
+        }
+    };
+
+    let status = ExitStatus::default();
+    if let Err(e) = status.handle_termination() {
+        eprintln!("Failed to handle termination signals: {}", e);
+    }
+
+    let mut app = App::new();
+    app.insert_resource(ScheduleRunnerSettings::run_loop(Duration::from_secs_f64(
+        HEADLESS_STEP,
+    )))
+    .add_plugins(MinimalPlugins)
+    .add_plugin(LogPlugin::default())
+    .add_plugin(SerialPlugin { settings: vec![] })
+    .add_plugin(SerialInfoPlugin)
+    .add_plugin(HeadlessPlugin {
+        port,
+        baud_rate: settings.baud_rate.unwrap_or(BAUD_RATE),
+        exit_on_error: args.exit_on_error,
+        watchdog: args.watchdog.clone(),
+        status: status.clone(),
+    })
+    .add_plugin(ParserPlugin { format: args.format.clone() })
+    // events and resources otherwise added by the plot, markers and axis mapping plugins
+    .add_event::<AccelSampleEvent>()
+    .add_event::<MarkerEvent>()
+    .insert_resource(args.axes.or(settings.axes).unwrap_or_default())
+    .insert_resource(units)
+    .insert_resource(frames)
+    .init_resource::<SerialStorage>()
+    .add_event::<ReplayChunkEvent>()
+    .add_system(read_serial);
+    app.add_plugin(RecorderPlugin {
+        settings: args.record.clone().unwrap_or_default(),
+        header: RecordingHeader::new(units, args.format.clone()),
+        start: args.record.is_some(),
+    });
+    if let Some(addr) = &args.api {
+        app.add_plugin(ApiPlugin { addr: addr.clone() });
+    }
+    if let Some(addr) = &args.stream {
+        app.add_plugin(StreamPlugin { addr: addr.clone() });
+    }
+    if let Some(settings) = &args.ros {
+        app.add_plugin(RosBridgePlugin { settings: settings.clone() });
+    }
+    if let Some(settings) = &args.osc {
+        app.add_plugin(OscPlugin { settings: settings.clone() });
+    }
+    if !args.plotter.is_empty() {
+        app.add_plugin(PlotterPlugin { outputs: args.plotter.clone() });
+    }
+    if let Some(addr) = &args.metrics {
+        // serial stats for Prometheus on /metrics
+        app.add_plugin(MetricsPlugin { addr: addr.clone() });
+    }
+    app.run();
+    std::process::exit(status.code());
+}
 
 fn setup(
     mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
+    scene_window: Res<SceneWindow>,
 ) {
-    let mesh = meshes.add(Mesh::from(shape::Box::new(50.0,10.0,50.0)));
-    let material = materials.add(StandardMaterial {
-        base_color: Color::PINK,
-        ..Default::default()
-    });
-    
-    commands.spawn_bundle(PbrBundle {
-        mesh: mesh.clone(),
-        material: material.clone(),
-        transform: Transform::from_xyz((40.0f32) * 2.0, (20.0f32) * 2.0, 15.0),
-        ..Default::default()
-    }).insert(MyObject());
-
-    // camera
-    commands.spawn_bundle(PerspectiveCameraBundle {
+    // camera, drawing to the window of the 3D view while the UI stays in the main window
+    let mut camera = PerspectiveCameraBundle {
         transform: Transform::from_xyz(80.0, 40.0, 120.0),
         ..Default::default()
-    });
+    };
+    camera.camera.window = scene_window.0;
+    commands.spawn_bundle(camera);
+    commands.spawn_bundle(UiCameraBundle::default());
 }
 
 #[derive(Default)]
 struct SerialStorage {
-    buf: String,
-    latest: Option<Vec3>
+    latest: Option<Vec3>,
+    // angular rate in degrees per second, if the packet format has it
+    gyro: Option<Vec3>,
+    // magnetic field in microtesla, if the packet format has it
+    mag: Option<Vec3>,
+    // rotation fused on the device, if the packet format has it
+    quat: Option<Quat>,
+    // temperature in degrees Celsius, if the packet has it after the acceleration
+    temperature: Option<f32>,
+    // battery voltage in volts, if the packet has it after the temperature
+    battery: Option<f32>
 }
 
 // reading event for serial port
 fn read_serial(
     mut ev_serial: EventReader<SerialReadEvent>, 
-    mut ss: ResMut<SerialStorage>
+    // time in the recording of each chunk replayed
+    mut ev_replay: EventReader<ReplayChunkEvent>,
+    mut ev_sample: EventWriter<AccelSampleEvent>,
+    mut ev_packet: EventWriter<PacketEvent>,
+    mut ss: ResMut<SerialStorage>,
+    mapping: Res<AxisMapping>,
+    frames: Res<Frames>,
+    time: Res<Time>,
+    // time each port was read last, the packets of the next chunk are spread over the time since
+    mut read_times: Local<HashMap<String, f64>>,
+    mut parsers: ResMut<ParserRegistry>,
+    // latency of the serial data is only recorded for the metrics
+    mut latency: Option<ResMut<SerialLatency>>,
+    // samples are held back while the views are frozen, except in the headless mode
+    mut freeze: Option<ResMut<Freeze>>
 ) {
     // you can get label of the port and received data buffer from `SerialReadEvent`
+    let now = time.seconds_since_startup();
+    let mut replay_times = ev_replay.iter();
+    // packets of each port by the time it was read
+    let mut chunks: Vec<(&String, f64, Vec<(Packet, Vec<u8>)>)> = Vec::new();
     for SerialReadEvent(label, buffer) in ev_serial.iter() {
-        let s = match String::from_utf8(buffer.clone()) { 
-            Ok(s) => s, 
-            Err(_) => continue 
+        // chunks replayed are read at their time in the recording, whatever the replay clock
+        let replayed = if label == REPLAY_LABEL { replay_times.next() } else { None };
+        let read_time = replayed.map_or(now, |ReplayChunkEvent(time)| *time);
+        let parsed = match &mut freeze {
+            Some(freeze) if freeze.frozen => freeze.parse(&mut parsers, buffer),
+            _ => {
+                let mut parsed = Vec::new();
+                parsers.parse(buffer, &mut parsed);
+                parsed.into_iter().map(|packet| (packet, Vec::new())).collect()
+            }
         };
-        println!("received packet from {}: {}", label, s);
-        ss.buf.push_str(&s);
-    }
-
-    let buf = ss.buf.clone();
-    let mut iter = buf.rsplit('\n');
-    if let Some(last) = iter.next() {
-       if let Some(second) = iter.next() {
-            ss.buf = last.to_string();
-
-            let parts: Vec<&str> = second.split(' ').skip(3).collect();
-            if parts.len() == 3 {
-                let res = (
-                    parts[0].parse(),
-                    parts[1].parse(),
-                    parts[2].parse(),
-                );
-                if let (Ok(x),Ok(y), Ok(z)) = res {
-                    ss.latest = Some(Vec3::new(x,y,z));
-                    dbg!(&ss.latest);
-                }
+        match chunks.iter_mut().rev().find(|(l, _, _)| *l == label) {
+            Some((_, time, packets)) if *time == read_time => packets.extend(parsed),
+            _ => chunks.push((label, read_time, parsed)),
+        }
+        if let Some(latency) = &mut latency {
+            latency.consumed(label);
+        }
+    }
+
+    let mut packets = Vec::new();
+    for (label, read_time, parsed) in chunks {
+        // the packets read at once are spread over the time since the port was read before,
+        // so they are a sample period apart
+        let previous = read_times.insert(label.clone(), read_time);
+        let times = packet_times(previous, read_time, parsed.len());
+        // the quaternions are in the frame of the device they were read from, turned into NWU before the packets
+        // of several ports are mixed
+        let frame = frames.device(Some(label));
+        for ((mut packet, raw), time) in parsed.into_iter().zip(times) {
+            packet.quat = packet.quat.map(|quat| frame.to_nwu(quat));
+            match &mut freeze {
+                Some(freeze) if freeze.frozen => freeze.push(packet, time, raw),
+                _ => packets.push((packet, time)),
             }
-       } 
+        }
+    }
+    if let Some(freeze) = &mut freeze {
+        // checked first so the resource is only changed while frozen
+        if freeze.frozen {
+            packets.extend(freeze.take_steps());
+        }
+    }
+
+    for (packet, time) in packets {
+        let packet = Packet {
+            accel: mapping.apply(packet.accel),
+            gyro: packet.gyro.map(|gyro| mapping.apply(gyro)),
+            mag: packet.mag.map(|mag| mapping.apply(mag)),
+            quat: packet.quat.map(|quat| mapping.apply_rotation(quat)),
+            temperature: packet.temperature,
+            battery: packet.battery,
+        };
+        ss.latest = Some(packet.accel);
+        ss.gyro = packet.gyro;
+        ss.mag = packet.mag;
+        ss.quat = packet.quat;
+        ss.temperature = packet.temperature;
+        ss.battery = packet.battery;
+        ev_sample.send(AccelSampleEvent(packet.accel));
+        ev_packet.send(PacketEvent(packet, time));
     }
 }
 
 fn box_movement_system(
     ss: Res<SerialStorage>,
+    smoothed: Res<SmoothedRotation>,
+    mut tare: ResMut<Tare>,
     mut query: Query<(&MyObject, &mut Transform)>,
 ) {
-    let v = match ss.latest {
-        Some(v) => v,
-        None => return
-    };
+    if ss.latest.is_none() {
+        return;
+    }
 
     let (_, mut transform) = query.single_mut();
 
-    let x = v.x;
-    let mut z = v.y;
-
-    //z += 0.5;
-
-    // let mut zz = ((z + 1.0) * 1000.0) as i32;
-    // zz = (zz + 500) % 1000;
-    // z = zz as f32;
-    // z = ((z / 1000.0) - 1.0);
-    
-
-    transform.rotation = Quat::from_rotation_x(x) * Quat::from_rotation_z(z);
-    // dbg!(&transform.rotation);
+    // the rotation of the device sent, fused or from the tilt is in NWU whatever the frame setting,
+    // and turned into the y up world of the scene in one place, then smoothed
+    transform.rotation = tare.apply(smoothed.rotation);
 }
 
 // // writing event for serial port
@@ -139,4 +542,94 @@ fn box_movement_system(
 //         let buffer = b"Hello, bevy!";
 //         ev_serial.send(SerialWriteEvent("COM5".to_string(), buffer.to_vec()));
 //     }
-// }
\ No newline at end of file
+// }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::asset::AssetPlugin;
+    use bevy::input::InputPlugin;
+    use bevy::window::WindowPlugin;
+    use calibration::MagCalibration;
+    use orientation::{Orientation, OrientationSource};
+    use recording::{RecordedChunk, Recording};
+
+    /// Samples per second of the recordings
+    const RATE: f64 = 100.0;
+    /// Angular rate about z up of the device lying flat, in degrees per second
+    const TURN_RATE: f32 = 1.5;
+
+    /// Recording of `space` lines of a device lying flat and turning about z up at `TURN_RATE`, one line per chunk
+    fn turning_recording(seconds: f64) -> Recording {
+        let samples = (seconds * RATE) as usize;
+        let mut recording = Recording::default();
+        recording.header.format = Some("space".to_string());
+        recording.chunks = (0..samples)
+            .map(|i| RecordedChunk {
+                time: i as f64 / RATE,
+                bytes: format!("0 0 {} 0 0 1\n", TURN_RATE).into_bytes(),
+            })
+            .collect();
+        recording
+    }
+
+    /// Replay the recording `step` seconds per update through the parser and the filter of the viewer,
+    /// without a window, and return the orientation at the end
+    fn replay(recording: Recording, step: f64) -> Orientation {
+        let updates = (recording.duration() / step).ceil() as usize;
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            // for the timeline and the keys of the replay, which aren't shown or pressed
+            .add_plugin(AssetPlugin)
+            .add_plugin(InputPlugin)
+            .add_plugin(WindowPlugin::default())
+            .add_event::<SerialReadEvent>()
+            .add_event::<ReplayChunkEvent>()
+            .add_event::<AccelSampleEvent>()
+            .init_resource::<Locale>()
+            .init_resource::<SensorUnits>()
+            .init_resource::<AxisMapping>()
+            .init_resource::<Frames>()
+            .init_resource::<MagCalibration>()
+            .init_resource::<SerialStorage>()
+            .insert_resource(ReplayClock::Fixed(step))
+            .add_plugin(ReplayPlugin { recording })
+            .add_plugin(ParserPlugin { format: Some("space".to_string()) })
+            .add_system(read_serial)
+            .add_plugin(OrientationPlugin {
+                source: OrientationSource::Host,
+                devices: Default::default(),
+            });
+        // and a few more for the events of the last chunks to reach the filter
+        for _ in 0..updates + 3 {
+            app.update();
+        }
+        *app.world.get_resource::<Orientation>().unwrap()
+    }
+
+    #[test]
+    fn fast_forwarded_replay_integrates_the_recorded_time() {
+        // 10 minutes in 10 updates
+        let recording = turning_recording(600.0);
+        let turned = TURN_RATE * (recording.duration() as f32);
+        let orientation = replay(recording, 60.0);
+
+        assert!(orientation.fused);
+        let expected = Quat::from_rotation_z(turned.to_radians());
+        let error = 2.0 * orientation.rotation.dot(expected).abs().min(1.0).acos();
+        assert!(
+            error < 1f32.to_radians(),
+            "turned {:?} instead of {} degrees about z",
+            orientation.rotation,
+            turned
+        );
+    }
+
+    #[test]
+    fn replay_step_does_not_change_the_fused_orientation() {
+        let recording = turning_recording(60.0);
+        let fast = replay(recording.clone(), 30.0);
+        let slow = replay(recording, 0.25);
+        assert_eq!(fast.rotation, slow.rotation);
+        assert_eq!((fast.roll, fast.pitch), (slow.roll, slow.pitch));
+    }
+}
@@ -0,0 +1,31 @@
+use bevy::prelude::*;
+
+/// Event to drop a named marker into the recording, e.g. "started shaking"
+pub struct MarkerEvent(pub String);
+
+/// Marker in a recording, with the time it was dropped
+#[derive(Debug, Clone)]
+pub struct Marker {
+    pub time: f64,
+    pub name: String,
+}
+
+/// Drop a numbered marker with `M`, or send `MarkerEvent` to drop a named one
+pub struct MarkerPlugin;
+
+impl Plugin for MarkerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<MarkerEvent>().add_system(marker_hotkey);
+    }
+}
+
+fn marker_hotkey(
+    keys: Res<Input<KeyCode>>,
+    mut count: Local<usize>,
+    mut ev_marker: EventWriter<MarkerEvent>,
+) {
+    if keys.just_pressed(KeyCode::M) {
+        *count += 1;
+        ev_marker.send(MarkerEvent(format!("marker {}", *count)));
+    }
+}
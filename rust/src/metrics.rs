@@ -0,0 +1,252 @@
+use bevy::prelude::*;
+use bevy_serial::{
+    SerialErrorEvent, SerialLatency, SerialLatencyPlugin, SerialPortInfo, SerialReadEvent,
+};
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::plot::AccelSampleEvent;
+
+/// Seconds between updates of the metrics served
+const UPDATE_SECONDS: f32 = 1.0;
+/// Quantiles of the latency summaries
+const QUANTILES: [f64; 3] = [0.5, 0.9, 0.99];
+/// Clients which send no request line for this long are dropped
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Serve the serial stats in the Prometheus text format on `http://<addr>/metrics`, e.g. `0.0.0.0:9184`.
+/// Requests are answered on a background thread with the stats of the last update.
+pub struct MetricsPlugin {
+    pub addr: String,
+}
+
+/// Counters since the app started, by port label
+#[derive(Default)]
+struct CaptureMetrics {
+    bytes_read: HashMap<String, u64>,
+    reads: HashMap<String, u64>,
+    reconnects: HashMap<String, u64>,
+    /// Errors by kind
+    errors: HashMap<&'static str, u64>,
+    /// Packets parsed from all ports
+    packets: u64,
+    /// Whether each port was open at the last update, to count reconnects
+    open: HashMap<String, bool>,
+    timer: Timer,
+    /// Text served on `/metrics`
+    page: Arc<Mutex<String>>,
+}
+
+impl Plugin for MetricsPlugin {
+    fn build(&self, app: &mut App) {
+        let page = Arc::new(Mutex::new(String::new()));
+        match TcpListener::bind(&self.addr) {
+            Ok(listener) => {
+                info!("serving metrics on http://{}/metrics", self.addr);
+                let page = page.clone();
+                thread::spawn(move || serve(listener, page));
+            }
+            Err(e) => error!("failed to serve metrics on {}: {}", self.addr, e),
+        }
+        app.add_plugin(SerialLatencyPlugin)
+            .insert_resource(CaptureMetrics {
+                timer: Timer::from_seconds(UPDATE_SECONDS, true),
+                page,
+                ..Default::default()
+            })
+            .add_system(count_metrics);
+    }
+}
+
+fn count_metrics(
+    mut ev_serial: EventReader<SerialReadEvent>,
+    mut ev_sample: EventReader<AccelSampleEvent>,
+    mut ev_error: EventReader<SerialErrorEvent>,
+    mut metrics: ResMut<CaptureMetrics>,
+    info: Res<SerialPortInfo>,
+    latency: Res<SerialLatency>,
+    time: Res<Time>,
+) {
+    for SerialReadEvent(label, buffer) in ev_serial.iter() {
+        *metrics.bytes_read.entry(label.clone()).or_default() += buffer.len() as u64;
+        *metrics.reads.entry(label.clone()).or_default() += 1;
+    }
+    metrics.packets += ev_sample.iter().count() as u64;
+    for e in ev_error.iter() {
+        let kind = match e {
+            SerialErrorEvent::UnknownLabel(_) => "unknown_label",
+            SerialErrorEvent::Open { .. } => "open",
+            SerialErrorEvent::Poll(_) => "poll",
+            SerialErrorEvent::Read { .. } => "read",
+            SerialErrorEvent::Write { .. } => "write",
+            SerialErrorEvent::Decode { .. } => "decode",
+            SerialErrorEvent::Disconnected(_) => "disconnected",
+        };
+        *metrics.errors.entry(kind).or_default() += 1;
+    }
+
+    if !metrics.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    // a port opened again after it was closed (or removed and added again) is a reconnect
+    let metrics = &mut *metrics;
+    for (label, was_open) in metrics.open.iter_mut() {
+        let open = info.get(label).map_or(false, |state| state.open);
+        if open && !*was_open {
+            *metrics.reconnects.entry(label.clone()).or_default() += 1;
+        }
+        *was_open = open;
+    }
+    for (label, state) in info.iter() {
+        metrics.open.entry(label.clone()).or_insert(state.open);
+    }
+
+    let page = render(metrics, &info, &latency);
+    if let Ok(mut served) = metrics.page.lock() {
+        *served = page;
+    }
+}
+
+/// Metrics in the Prometheus text exposition format
+fn render(metrics: &CaptureMetrics, info: &SerialPortInfo, latency: &SerialLatency) -> String {
+    let mut page = String::new();
+    let mut family = |name: &str, kind: &str, help: &str, samples: Vec<(String, String)>| {
+        let _ = writeln!(page, "# HELP {} {}\n# TYPE {} {}", name, help, name, kind);
+        for (labels, value) in samples {
+            let _ = writeln!(page, "{}{} {}", name, labels, value);
+        }
+    };
+    let by_port = |counts: &HashMap<String, u64>| -> Vec<(String, String)> {
+        counts
+            .iter()
+            .map(|(label, n)| (format!("{{port=\"{}\"}}", escape(label)), n.to_string()))
+            .collect()
+    };
+
+    family(
+        "accel_serial_up",
+        "gauge",
+        "Whether the serial port is open",
+        info.iter()
+            .map(|(label, state)| {
+                (format!("{{port=\"{}\"}}", escape(label)), (state.open as u8).to_string())
+            })
+            .collect(),
+    );
+    family(
+        "accel_serial_read_bytes_total",
+        "counter",
+        "Bytes read from the serial port",
+        by_port(&metrics.bytes_read),
+    );
+    family(
+        "accel_serial_reads_total",
+        "counter",
+        "Chunks of bytes read from the serial port",
+        by_port(&metrics.reads),
+    );
+    family(
+        "accel_serial_reconnects_total",
+        "counter",
+        "Times the serial port was opened again after it was closed",
+        by_port(&metrics.reconnects),
+    );
+    family(
+        "accel_serial_overruns",
+        "gauge",
+        "Overruns counted by the driver since the port was opened",
+        info.iter()
+            .filter_map(|(label, state)| {
+                state.overruns.map(|n| (format!("{{port=\"{}\"}}", escape(label)), n.to_string()))
+            })
+            .collect(),
+    );
+    family(
+        "accel_serial_errors_total",
+        "counter",
+        "Errors of the serial ports by kind",
+        metrics
+            .errors
+            .iter()
+            .map(|(kind, n)| (format!("{{kind=\"{}\"}}", kind), n.to_string()))
+            .collect(),
+    );
+    family(
+        "accel_packets_total",
+        "counter",
+        "Packets parsed from the serial data",
+        vec![(String::new(), metrics.packets.to_string())],
+    );
+
+    let mut samples = Vec::new();
+    for (label, port) in latency.iter() {
+        let stages = [
+            ("poll_to_send", &port.poll_to_send),
+            ("send_to_consume", &port.send_to_consume),
+        ];
+        for (stage, histogram) in stages {
+            let labels = format!("port=\"{}\",stage=\"{}\"", escape(label), stage);
+            for q in QUANTILES {
+                samples.push((
+                    format!("{{{},quantile=\"{}\"}}", labels, q),
+                    histogram.percentile(q).as_secs_f64().to_string(),
+                ));
+            }
+            samples.push((
+                format!("_sum{{{}}}", labels),
+                (histogram.mean().as_secs_f64() * histogram.count() as f64).to_string(),
+            ));
+            samples.push((format!("_count{{{}}}", labels), histogram.count().to_string()));
+        }
+    }
+    family(
+        "accel_serial_latency_seconds",
+        "summary",
+        "Latency of the serial data from the poll to the event, and from the event to the parser",
+        samples,
+    );
+    page
+}
+
+/// Escape a label value
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn serve(listener: TcpListener, page: Arc<Mutex<String>>) {
+    for stream in listener.incoming() {
+        let result = stream.and_then(|stream| respond(stream, &page));
+        if let Err(e) = result {
+            warn!("failed to serve metrics: {}", e);
+        }
+    }
+}
+
+/// Answer a request with the metrics on `/metrics` and 404 on other paths
+fn respond(mut stream: TcpStream, page: &Mutex<String>) -> io::Result<()> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    let mut request = String::new();
+    BufReader::new(&stream).read_line(&mut request)?;
+    let path = request.split_whitespace().nth(1).unwrap_or_default();
+    let (status, body) = if path == "/metrics" {
+        let body = page.lock().map(|page| page.clone()).unwrap_or_default();
+        ("200 OK", body)
+    } else {
+        ("404 Not Found", "not found, try /metrics\n".to_string())
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )
+}
@@ -0,0 +1,245 @@
+use bevy::prelude::*;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::frame::Frames;
+use crate::locale::Locale;
+use crate::orientation::Orientation;
+
+/// Frames per second of the BVH motion, which the poses captured each frame are resampled to
+const BVH_FPS: f64 = 60.0;
+/// Length of the bone of the device in the BVH, along its z axis
+const BONE_LENGTH: f32 = 10.0;
+
+/// Capture the orientation as a motion track for animation: `B` starts capturing, `B` again stops and writes
+/// `mocap-<unix time>.bvh`, one bone turning like the device in the y up world of the scene (what Blender imports),
+/// and `.csv`, the poses with their time as a quaternion in the frame of `--frame` and as roll, pitch and heading
+pub struct MocapPlugin;
+
+impl Plugin for MocapPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MocapCapture>()
+            .add_startup_system(setup_capture_text)
+            .add_system(control_capture)
+            .add_system(capture_poses)
+            .add_system(show_capture);
+    }
+}
+
+/// Orientation at a time in seconds since the capture started
+#[derive(Debug, Clone, Copy)]
+struct Pose {
+    time: f64,
+    orientation: Orientation,
+}
+
+#[derive(Default)]
+struct MocapCapture {
+    capturing: bool,
+    start: f64,
+    poses: Vec<Pose>,
+}
+
+#[derive(Component)]
+struct CaptureText;
+
+fn setup_capture_text(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(8.0),
+                    top: Val::Px(40.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraMono-Medium.ttf"),
+                    font_size: 16.0,
+                    color: Color::WHITE,
+                },
+                Default::default(),
+            ),
+            visibility: Visibility { is_visible: false },
+            ..Default::default()
+        })
+        .insert(CaptureText);
+}
+
+fn control_capture(
+    keys: Res<Input<KeyCode>>,
+    time: Res<Time>,
+    frames: Res<Frames>,
+    mut capture: ResMut<MocapCapture>,
+) {
+    if !keys.just_pressed(KeyCode::B) {
+        return;
+    }
+    if !capture.capturing {
+        info!("motion capture started");
+        *capture = MocapCapture {
+            capturing: true,
+            start: time.seconds_since_startup(),
+            poses: Vec::new(),
+        };
+        return;
+    }
+    capture.capturing = false;
+    if capture.poses.len() < 2 {
+        warn!("no motion captured, the device sent no data");
+        return;
+    }
+
+    let stamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let bvh = format!("mocap-{}.bvh", stamp);
+    let csv = format!("mocap-{}.csv", stamp);
+    match write_bvh(&bvh, &capture.poses) {
+        Ok(()) => info!("exported motion to {}", bvh),
+        Err(e) => error!("failed to export {}: {}", bvh, e),
+    }
+    match write_csv(&csv, &capture.poses, &frames) {
+        Ok(()) => info!("exported {} poses to {}", capture.poses.len(), csv),
+        Err(e) => error!("failed to export {}: {}", csv, e),
+    }
+}
+
+fn capture_poses(
+    orientation: Res<Orientation>,
+    time: Res<Time>,
+    mut capture: ResMut<MocapCapture>,
+) {
+    // only new orientations, so a device that stops sending doesn't add frozen poses
+    if !capture.capturing || !orientation.is_changed() {
+        return;
+    }
+    let time = time.seconds_since_startup() - capture.start;
+    capture.poses.push(Pose {
+        time,
+        orientation: *orientation,
+    });
+}
+
+fn show_capture(
+    capture: Res<MocapCapture>,
+    locale: Res<Locale>,
+    mut text: Query<(&mut Text, &mut Visibility), With<CaptureText>>,
+) {
+    if !capture.is_changed() {
+        return;
+    }
+    let seconds = capture.poses.last().map(|pose| pose.time).unwrap_or(0.0);
+    for (mut text, mut visibility) in text.iter_mut() {
+        visibility.is_visible = capture.capturing;
+        text.sections[0].value =
+            locale.fill("mocap", &[&format!("{:.1}", seconds), &capture.poses.len()]);
+    }
+}
+
+/// Write one root bone with the rotations of the poses resampled at `BVH_FPS`
+fn write_bvh(path: &str, poses: &[Pose]) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    let rotations = resample(poses, 1.0 / BVH_FPS);
+    writeln!(writer, "HIERARCHY")?;
+    writeln!(writer, "ROOT device")?;
+    writeln!(writer, "{{")?;
+    writeln!(writer, "\tOFFSET 0.00 0.00 0.00")?;
+    writeln!(
+        writer,
+        "\tCHANNELS 6 Xposition Yposition Zposition Zrotation Xrotation Yrotation"
+    )?;
+    writeln!(writer, "\tEnd Site")?;
+    writeln!(writer, "\t{{")?;
+    // z up of the device is y up in the world while it lies flat
+    writeln!(writer, "\t\tOFFSET 0.00 {:.2} 0.00", BONE_LENGTH)?;
+    writeln!(writer, "\t}}")?;
+    writeln!(writer, "}}")?;
+    writeln!(writer, "MOTION")?;
+    writeln!(writer, "Frames: {}", rotations.len())?;
+    writeln!(writer, "Frame Time: {:.6}", 1.0 / BVH_FPS)?;
+    for rotation in rotations {
+        let [z, x, y] = euler_zxy(rotation).map(f32::to_degrees);
+        writeln!(writer, "0.00 0.00 0.00 {:.4} {:.4} {:.4}", z, x, y)?;
+    }
+    writer.flush()
+}
+
+/// Write the poses with the quaternion in the frame of the outputs and the angles in degrees,
+/// leaving the heading empty without the magnetic field
+fn write_csv(path: &str, poses: &[Pose], frames: &Frames) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(
+        writer,
+        "time,qw,qx,qy,qz,roll (deg),pitch (deg),heading (deg)"
+    )?;
+    for pose in poses {
+        let o = &pose.orientation;
+        let q = frames.frame.from_nwu(o.rotation);
+        let heading = o
+            .heading
+            .map(|heading| heading.to_degrees().to_string())
+            .unwrap_or_default();
+        writeln!(
+            writer,
+            "{:.6},{},{},{},{},{},{},{}",
+            pose.time,
+            q.w,
+            q.x,
+            q.y,
+            q.z,
+            o.roll.to_degrees(),
+            o.pitch.to_degrees(),
+            heading
+        )?;
+    }
+    writer.flush()
+}
+
+/// Rotations in the world of the scene every `step` seconds from the first pose to the last,
+/// interpolated between the poses around each time
+fn resample(poses: &[Pose], step: f64) -> Vec<Quat> {
+    let (first, last) = match (poses.first(), poses.last()) {
+        (Some(first), Some(last)) => (first.time, last.time),
+        _ => return Vec::new(),
+    };
+    let frames = ((last - first) / step).floor() as usize + 1;
+    let mut index = 0;
+    (0..frames)
+        .map(|frame| {
+            let time = first + frame as f64 * step;
+            while index + 2 < poses.len() && poses[index + 1].time < time {
+                index += 1;
+            }
+            let (a, b) = match poses.get(index + 1) {
+                Some(b) => (&poses[index], b),
+                None => return poses[index].orientation.world_rotation(),
+            };
+            let share = if b.time > a.time {
+                ((time - a.time) / (b.time - a.time)).clamp(0.0, 1.0) as f32
+            } else {
+                0.0
+            };
+            let from = a.orientation.world_rotation();
+            let to = b.orientation.world_rotation();
+            // the short way between q and -q
+            let to = if from.dot(to) < 0.0 { -to } else { to };
+            from.slerp(to, share).normalize()
+        })
+        .collect()
+}
+
+/// Angles about z, x and y in radians, in the order of the channels, of `Rz * Rx * Ry`
+fn euler_zxy(rotation: Quat) -> [f32; 3] {
+    let (x, y, z, w) = (rotation.x, rotation.y, rotation.z, rotation.w);
+    let about_x = (2.0 * (y * z + w * x)).clamp(-1.0, 1.0).asin();
+    let about_y = (-2.0 * (x * z - w * y)).atan2(1.0 - 2.0 * (x * x + y * y));
+    let about_z = (-2.0 * (x * y - w * z)).atan2(1.0 - 2.0 * (x * x + z * z));
+    [about_z, about_x, about_y]
+}
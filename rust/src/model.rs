@@ -0,0 +1,150 @@
+use bevy::prelude::*;
+use std::str::FromStr;
+
+use crate::theme::Theme;
+use crate::MyObject;
+
+/// Position of the model in the scene
+pub const MODEL_ORIGIN: [f32; 3] = [80.0, 40.0, 15.0];
+const GIZMO_LENGTH: f32 = 40.0;
+const GIZMO_WIDTH: f32 = 1.0;
+
+/// Model representing the device
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum Model {
+    /// Flat box like a breakout board
+    #[default]
+    Board,
+    Cube,
+    Phone,
+    /// glTF file, loaded by the asset server (relative to `assets`, or absolute)
+    Gltf(String),
+}
+
+impl FromStr for Model {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "board" => Ok(Model::Board),
+            "cube" => Ok(Model::Cube),
+            "phone" => Ok(Model::Phone),
+            _ if s.ends_with(".gltf") || s.ends_with(".glb") => Ok(Model::Gltf(s.to_string())),
+            _ => Err(format!("unknown model {}", s)),
+        }
+    }
+}
+
+/// Spawn the model of the device with x, y and z gizmos attached
+pub struct ModelPlugin {
+    pub model: Model,
+    /// Scale of a glTF model, e.g. 100 for models in meters
+    pub scale: f32,
+}
+
+struct ModelSettings {
+    model: Model,
+    scale: f32,
+}
+
+/// Gizmo of the x, y or z axis
+#[derive(Component)]
+struct Gizmo(usize);
+
+impl Plugin for ModelPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ModelSettings {
+            model: self.model.clone(),
+            scale: self.scale,
+        })
+        .add_startup_system(spawn_model)
+        .add_system(color_gizmos);
+    }
+}
+
+fn spawn_model(
+    mut commands: Commands,
+    settings: Res<ModelSettings>,
+    asset_server: Res<AssetServer>,
+    theme: Res<Theme>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let transform = Transform::from_translation(Vec3::from(MODEL_ORIGIN));
+    let model = match &settings.model {
+        Model::Gltf(path) => {
+            let scene = asset_server.load(format!("{}#Scene0", path).as_str());
+            commands
+                .spawn_bundle((transform, GlobalTransform::identity()))
+                .with_children(|parent| {
+                    parent
+                        .spawn_bundle((
+                            Transform::from_scale(Vec3::splat(settings.scale)),
+                            GlobalTransform::identity(),
+                        ))
+                        .with_children(|scene_parent| {
+                            scene_parent.spawn_scene(scene);
+                        });
+                })
+                .id()
+        }
+        builtin => {
+            let size = match builtin {
+                Model::Cube => Vec3::new(30.0, 30.0, 30.0),
+                Model::Phone => Vec3::new(35.0, 7.0, 70.0),
+                _ => Vec3::new(50.0, 10.0, 50.0),
+            };
+            commands
+                .spawn_bundle(PbrBundle {
+                    mesh: meshes.add(Mesh::from(shape::Box::new(size.x, size.y, size.z))),
+                    material: materials.add(StandardMaterial {
+                        // recolored by `StalePlugin`
+                        base_color: theme.model,
+                        ..Default::default()
+                    }),
+                    transform,
+                    ..Default::default()
+                })
+                .id()
+        }
+    };
+
+    commands
+        .entity(model)
+        .insert(MyObject())
+        .with_children(|parent| {
+            for (axis, color) in theme.axes.iter().enumerate() {
+                let mut size = Vec3::splat(GIZMO_WIDTH);
+                size[axis] = GIZMO_LENGTH;
+                let mut offset = Vec3::ZERO;
+                offset[axis] = GIZMO_LENGTH / 2.0;
+                parent
+                    .spawn_bundle(PbrBundle {
+                        mesh: meshes.add(Mesh::from(shape::Box::new(size.x, size.y, size.z))),
+                        material: materials.add(StandardMaterial {
+                            base_color: *color,
+                            unlit: true,
+                            ..Default::default()
+                        }),
+                        transform: Transform::from_translation(offset),
+                        ..Default::default()
+                    })
+                    .insert(Gizmo(axis));
+            }
+        });
+}
+
+fn color_gizmos(
+    theme: Res<Theme>,
+    gizmos: Query<(&Gizmo, &Handle<StandardMaterial>)>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    if !theme.is_changed() {
+        return;
+    }
+    for (gizmo, handle) in gizmos.iter() {
+        if let Some(material) = materials.get_mut(handle) {
+            material.base_color = theme.axes[gizmo.0];
+        }
+    }
+}
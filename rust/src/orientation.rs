@@ -0,0 +1,255 @@
+use bevy::prelude::*;
+use bevy_serial::SerialReadEvent;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::f32::consts::TAU;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::calibration::MagCalibration;
+use crate::fusion::Madgwick;
+use crate::parser::PacketEvent;
+use crate::settings::SettingsFile;
+
+/// Seconds between samples over which the angular rate is integrated at most. After a longer gap, e.g. the port
+/// reopened or the replay seeking, the filter isn't turned by the gyro until the next sample.
+const MAX_SAMPLE_GAP: f64 = 0.5;
+
+/// Roll and pitch of the device in radians, and the heading if the packets have the magnetic field.
+/// They come from the quaternion fused on the device if the packets have it and its source is `Device`,
+/// from the Madgwick filter with the angular rate, which follows fast rotations,
+/// or otherwise from the direction of gravity (and the tilt-compensated magnetic field).
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Orientation {
+    pub roll: f32,
+    pub pitch: f32,
+    /// Clockwise from magnetic north, from 0 to 2π
+    pub heading: Option<f32>,
+    /// Rotation of the device with z up while it lies flat, including the heading
+    pub rotation: Quat,
+    /// Whether the rotation comes from a filter, with the gyro, rather than from the tilt alone
+    pub fused: bool,
+}
+
+/// What the rotation follows when the packets have the quaternion fused on the device
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrientationSource {
+    /// The quaternion of the device, bypassing the filter of the viewer
+    #[default]
+    Device,
+    /// The filter of the viewer, ignoring the quaternion, e.g. to compare both
+    Host,
+}
+
+impl fmt::Display for OrientationSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            OrientationSource::Device => "device",
+            OrientationSource::Host => "host",
+        })
+    }
+}
+
+/// Parsed from `device` or `host`
+impl FromStr for OrientationSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "device" => Ok(OrientationSource::Device),
+            "host" => Ok(OrientationSource::Host),
+            _ => Err(format!(
+                "unknown orientation source {}, choose from device or host",
+                s
+            )),
+        }
+    }
+}
+
+/// Orientation source of each device by the label of its port, and of the others
+pub struct OrientationSources {
+    pub default: OrientationSource,
+    pub devices: BTreeMap<String, OrientationSource>,
+    /// Label of the port the last data was read from
+    pub device: Option<String>,
+}
+
+impl OrientationSources {
+    /// Source of the device streamed
+    pub fn current(&self) -> OrientationSource {
+        self.device
+            .as_ref()
+            .and_then(|device| self.devices.get(device))
+            .copied()
+            .unwrap_or(self.default)
+    }
+}
+
+impl Orientation {
+    /// Tilt of the device from the acceleration in g, while it is not accelerating
+    pub fn from_accel(accel: Vec3) -> Self {
+        let roll = accel.y.atan2(accel.z);
+        let pitch = (-accel.x).atan2((accel.y * accel.y + accel.z * accel.z).sqrt());
+        Orientation {
+            roll,
+            pitch,
+            heading: None,
+            rotation: Quat::from_euler(EulerRot::ZYX, 0.0, pitch, roll),
+            fused: false,
+        }
+    }
+
+    /// Roll, pitch and heading of the rotation of the filter
+    fn from_rotation(rotation: Quat, heading: bool) -> Self {
+        // inverse of `Quat::from_euler(EulerRot::ZYX, yaw, pitch, roll)`
+        let (x, y, z, w) = (rotation.x, rotation.y, rotation.z, rotation.w);
+        let roll = (2.0 * (w * x + y * z)).atan2(1.0 - 2.0 * (x * x + y * y));
+        let pitch = (2.0 * (w * y - z * x)).clamp(-1.0, 1.0).asin();
+        let yaw = (2.0 * (w * z + x * y)).atan2(1.0 - 2.0 * (y * y + z * z));
+        Orientation {
+            roll,
+            pitch,
+            // the yaw is counterclockwise about z up
+            heading: heading.then(|| (-yaw).rem_euclid(TAU)),
+            rotation,
+            fused: true,
+        }
+    }
+
+    /// Rotation of the roll and pitch, with a yaw of 0
+    pub fn quat(&self) -> Quat {
+        Quat::from_euler(EulerRot::ZYX, 0.0, self.pitch, self.roll)
+    }
+
+    /// `rotation` in the world of the scene, which is y up: up (z of NWU and of the device) is the world y axis,
+    /// north (x) the world x axis and west (y) the world -z axis
+    pub fn world_rotation(&self) -> Quat {
+        let r = self.rotation;
+        Quat::from_xyzw(r.x, r.z, -r.y, r.w)
+    }
+
+    /// Heading of the calibrated magnetic field with the tilt compensated (NXP AN4248, with z up)
+    pub fn magnetic_heading(&self, mag: Vec3) -> f32 {
+        let (sin_roll, cos_roll) = self.roll.sin_cos();
+        let (sin_pitch, cos_pitch) = self.pitch.sin_cos();
+        let x = mag.x * cos_pitch + (mag.y * sin_roll + mag.z * cos_roll) * sin_pitch;
+        let y = mag.y * cos_roll - mag.z * sin_roll;
+        y.atan2(x).rem_euclid(TAU)
+    }
+}
+
+/// Estimate the orientation from the packets. `O` switches the device streamed between its own quaternion and
+/// the filter of the viewer, which is saved to the settings file for the device.
+pub struct OrientationPlugin {
+    /// Source of the devices without one of their own
+    pub source: OrientationSource,
+    /// Source of each device by the label of its port
+    pub devices: BTreeMap<String, OrientationSource>,
+}
+
+impl Plugin for OrientationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Orientation>()
+            .init_resource::<Madgwick>()
+            .register_type::<Madgwick>()
+            .insert_resource(OrientationSources {
+                default: self.source,
+                devices: self.devices.clone(),
+                device: None,
+            })
+            .add_system(track_device)
+            .add_system(switch_source)
+            .add_system(update_orientation);
+    }
+}
+
+/// Keep the label of the port streamed, which changes when another port is opened
+fn track_device(
+    mut ev_serial: EventReader<SerialReadEvent>,
+    mut sources: ResMut<OrientationSources>,
+) {
+    if let Some(SerialReadEvent(label, _)) = ev_serial.iter().last() {
+        if sources.device.as_ref() != Some(label) {
+            sources.device = Some(label.clone());
+        }
+    }
+}
+
+fn switch_source(
+    keys: Res<Input<KeyCode>>,
+    mut sources: ResMut<OrientationSources>,
+    settings: Option<ResMut<SettingsFile>>,
+) {
+    if !keys.just_pressed(KeyCode::O) {
+        return;
+    }
+    let source = match sources.current() {
+        OrientationSource::Device => OrientationSource::Host,
+        OrientationSource::Host => OrientationSource::Device,
+    };
+    let device = match sources.device.clone() {
+        Some(device) => device,
+        None => {
+            info!("orientation source {}", source);
+            sources.default = source;
+            return;
+        }
+    };
+    info!("orientation source of {} {}", device, source);
+    sources.devices.insert(device.clone(), source);
+
+    if let Some(mut settings) = settings {
+        let devices = &mut settings.settings.devices;
+        devices.entry(device).or_default().orientation = source;
+        settings.save();
+    }
+}
+
+fn update_orientation(
+    mut ev_packet: EventReader<PacketEvent>,
+    sources: Res<OrientationSources>,
+    calibration: Res<MagCalibration>,
+    mut filter: ResMut<Madgwick>,
+    mut orientation: ResMut<Orientation>,
+    // time of the previous sample
+    mut previous: Local<Option<f64>>,
+) {
+    for &PacketEvent(packet, time) in ev_packet.iter() {
+        // the angular rate is integrated over the time between the samples, not the frame time
+        let dt = match previous.replace(time) {
+            Some(previous) if time >= previous && time - previous <= MAX_SAMPLE_GAP => {
+                (time - previous) as f32
+            }
+            _ => 0.0,
+        };
+        let mag = packet.mag.map(|mag| calibration.apply(mag));
+        let mut tilt = Orientation::from_accel(packet.accel);
+        tilt.heading = mag.map(|mag| tilt.magnetic_heading(mag));
+        *orientation = match (packet.quat, packet.gyro) {
+            (Some(quat), _) if sources.current() == OrientationSource::Device => {
+                // so the filter carries on from the device when switched to the host
+                filter.rotation = quat;
+                Orientation::from_rotation(quat, true)
+            }
+            (_, Some(gyro)) => {
+                // start from the tilt and heading, as the filter takes seconds to turn over
+                if !orientation.fused {
+                    let yaw = -tilt.heading.unwrap_or(0.0);
+                    filter.rotation = Quat::from_euler(EulerRot::ZYX, yaw, tilt.pitch, tilt.roll);
+                }
+                filter.update(gyro, packet.accel, mag, dt);
+                Orientation::from_rotation(filter.rotation, mag.is_some())
+            }
+            _ => Orientation {
+                rotation: Quat::from_euler(
+                    EulerRot::ZYX,
+                    -tilt.heading.unwrap_or(0.0),
+                    tilt.pitch,
+                    tilt.roll,
+                ),
+                ..tilt
+            },
+        };
+    }
+}
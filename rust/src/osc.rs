@@ -0,0 +1,228 @@
+use bevy::prelude::*;
+use std::fmt;
+use std::net::UdpSocket;
+use std::str::FromStr;
+
+use crate::orientation::Orientation;
+use crate::parser::{Packet, PacketEvent};
+use crate::units::SensorUnits;
+
+/// Value of the packets sent as an OSC message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OscChannel {
+    /// x, y and z of the acceleration in the unit of the sensor
+    Accel,
+    /// x, y and z of the angular rate in degrees per second, if the packet format has it
+    Gyro,
+    /// Temperature in degrees Celsius, if the packet has it
+    Temperature,
+    /// Battery voltage in volts, if the packet has it
+    Battery,
+    /// Roll in degrees, estimated from gravity
+    Roll,
+    /// Pitch in degrees, estimated from gravity
+    Pitch,
+    /// Magnitude of the acceleration in the unit of the sensor
+    Magnitude,
+}
+
+impl OscChannel {
+    /// Arguments of the message, or `None` if the packet has no such value
+    fn values(&self, packet: &Packet, units: &SensorUnits) -> Option<Vec<f32>> {
+        let orientation = Orientation::from_accel(packet.accel);
+        let accel = packet.accel * units.unit.in_unit(1.0);
+        match self {
+            OscChannel::Accel => Some(accel.to_array().to_vec()),
+            OscChannel::Gyro => packet.gyro.map(|gyro| gyro.to_array().to_vec()),
+            OscChannel::Temperature => packet.temperature.map(|t| vec![t]),
+            OscChannel::Battery => packet.battery.map(|v| vec![v]),
+            OscChannel::Roll => Some(vec![orientation.roll.to_degrees()]),
+            OscChannel::Pitch => Some(vec![orientation.pitch.to_degrees()]),
+            OscChannel::Magnitude => Some(vec![accel.length()]),
+        }
+    }
+}
+
+const CHANNELS: [OscChannel; 7] = [
+    OscChannel::Accel,
+    OscChannel::Gyro,
+    OscChannel::Temperature,
+    OscChannel::Battery,
+    OscChannel::Roll,
+    OscChannel::Pitch,
+    OscChannel::Magnitude,
+];
+
+impl fmt::Display for OscChannel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            OscChannel::Accel => "accel",
+            OscChannel::Gyro => "gyro",
+            OscChannel::Temperature => "temperature",
+            OscChannel::Battery => "battery",
+            OscChannel::Roll => "roll",
+            OscChannel::Pitch => "pitch",
+            OscChannel::Magnitude => "magnitude",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for OscChannel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match CHANNELS.iter().find(|channel| channel.to_string() == s) {
+            Some(channel) => Ok(*channel),
+            None => Err(format!(
+                "unknown channel {}, choose from accel, gyro, temperature, battery, roll, pitch or magnitude",
+                s
+            )),
+        }
+    }
+}
+
+/// OSC address of each channel sent
+#[derive(Debug, Clone, PartialEq)]
+pub struct OscMapping(pub Vec<(OscChannel, String)>);
+
+impl Default for OscMapping {
+    fn default() -> Self {
+        OscMapping(vec![
+            (OscChannel::Accel, "/accel".to_string()),
+            (OscChannel::Gyro, "/gyro".to_string()),
+            (OscChannel::Temperature, "/temperature".to_string()),
+            (OscChannel::Battery, "/battery".to_string()),
+            (OscChannel::Roll, "/roll".to_string()),
+            (OscChannel::Pitch, "/pitch".to_string()),
+        ])
+    }
+}
+
+/// Written like `accel=/accel,roll=/tilt/roll`
+impl fmt::Display for OscMapping {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let pairs: Vec<String> = self
+            .0
+            .iter()
+            .map(|(channel, address)| format!("{}={}", channel, address))
+            .collect();
+        write!(f, "{}", pairs.join(","))
+    }
+}
+
+/// Parsed from `channel=address` pairs separated by commas, e.g. `accel=/accel,roll=/tilt/roll`
+impl FromStr for OscMapping {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .map(|pair| {
+                let (channel, address) = pair
+                    .split_once('=')
+                    .ok_or_else(|| format!("expected channel=address, got {}", pair))?;
+                let address = address.trim();
+                if !address.starts_with('/') {
+                    return Err(format!("OSC address {} must start with /", address));
+                }
+                Ok((channel.trim().parse()?, address.to_string()))
+            })
+            .collect::<Result<_, _>>()
+            .map(OscMapping)
+    }
+}
+
+/// Settings of the OSC output
+#[derive(Debug, Clone)]
+pub struct OscSettings {
+    /// Host and UDP port of the receiver, e.g. `127.0.0.1:9000`
+    pub target: String,
+    /// Messages per second of each channel
+    pub rate: f32,
+    pub mapping: OscMapping,
+}
+
+impl Default for OscSettings {
+    fn default() -> Self {
+        OscSettings {
+            target: "127.0.0.1:9000".to_string(),
+            rate: 30.0,
+            mapping: OscMapping::default(),
+        }
+    }
+}
+
+/// Send the latest packet to an Open Sound Control receiver (TouchDesigner, Max, Pure Data...) over UDP
+/// at the rate of the settings, one message of float arguments per channel of the mapping
+pub struct OscPlugin {
+    pub settings: OscSettings,
+}
+
+struct OscSender {
+    socket: Option<UdpSocket>,
+    settings: OscSettings,
+    timer: Timer,
+    latest: Option<Packet>,
+}
+
+impl Plugin for OscPlugin {
+    fn build(&self, app: &mut App) {
+        let socket = UdpSocket::bind("0.0.0.0:0")
+            .map_err(|e| error!("failed to open the OSC socket: {}", e))
+            .ok();
+        info!("sending OSC to {}: {}", self.settings.target, self.settings.mapping);
+        app.insert_resource(OscSender {
+            socket,
+            settings: self.settings.clone(),
+            timer: Timer::from_seconds(1.0 / self.settings.rate.max(0.1), true),
+            latest: None,
+        })
+        .add_system(send_osc);
+    }
+}
+
+fn send_osc(
+    mut ev_packet: EventReader<PacketEvent>,
+    mut sender: ResMut<OscSender>,
+    units: Res<SensorUnits>,
+    time: Res<Time>,
+) {
+    if let Some(PacketEvent(packet, _)) = ev_packet.iter().last() {
+        sender.latest = Some(*packet);
+    }
+    if !sender.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    let sender = &mut *sender;
+    let (socket, packet) = match (&sender.socket, sender.latest.take()) {
+        (Some(socket), Some(packet)) => (socket, packet),
+        // only new packets are sent
+        _ => return,
+    };
+    for (channel, address) in sender.settings.mapping.0.iter() {
+        if let Some(values) = channel.values(&packet, &units) {
+            let message = encode_message(address, &values);
+            if let Err(e) = socket.send_to(&message, &sender.settings.target) {
+                warn!("failed to send OSC to {}: {}", sender.settings.target, e);
+                return;
+            }
+        }
+    }
+}
+
+/// OSC message with float arguments: the address and the type tags padded to 4 bytes, then big endian floats
+fn encode_message(address: &str, values: &[f32]) -> Vec<u8> {
+    let mut message = Vec::new();
+    push_padded(&mut message, address);
+    push_padded(&mut message, &format!(",{}", "f".repeat(values.len())));
+    for value in values {
+        message.extend_from_slice(&value.to_be_bytes());
+    }
+    message
+}
+
+/// Push an OSC string, with at least one null terminator, padded to 4 bytes
+fn push_padded(message: &mut Vec<u8>, s: &str) {
+    message.extend_from_slice(s.as_bytes());
+    message.resize((message.len() / 4 + 1) * 4, 0);
+}
@@ -0,0 +1,675 @@
+use bevy::prelude::*;
+use bevy_serial::{FieldType, MessageSchema};
+
+use crate::units::SensorUnits;
+
+/// Values of one packet from the device
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Packet {
+    /// Acceleration in g
+    pub accel: Vec3,
+    /// Angular rate in degrees per second, if the format has it
+    pub gyro: Option<Vec3>,
+    /// Magnetic field in microtesla, if the format has it
+    pub mag: Option<Vec3>,
+    /// Rotation fused on the device (e.g. by a BNO055), if the format has it
+    pub quat: Option<Quat>,
+    /// Temperature in degrees Celsius, if the packet has it
+    pub temperature: Option<f32>,
+    /// Battery voltage in volts, if the packet has it
+    pub battery: Option<f32>,
+}
+
+/// A packet parsed by `ParserRegistry::trace`, or a malformed one, with the bytes read since the previous record
+#[derive(Debug, Clone)]
+pub struct ParseRecord {
+    pub raw: Vec<u8>,
+    /// `None` if the bytes were rejected
+    pub packet: Option<Packet>,
+}
+
+/// Sent for each packet parsed from the serial data, with the axes mapped to world axes and the quaternion in NWU,
+/// and the time of the sample in seconds. Only the time between the samples of a device is meaningful,
+/// e.g. to integrate the angular rate.
+#[derive(Debug, Clone, Copy)]
+pub struct PacketEvent(pub Packet, pub f64);
+
+/// Times of the `count` packets completed by data read at `now`, spread evenly over the time since the data read
+/// before at `previous`, so the packets of a chunk are a sample period apart. All are at `now` without earlier data.
+pub fn packet_times(previous: Option<f64>, now: f64, count: usize) -> impl Iterator<Item = f64> {
+    let start = previous.filter(|previous| *previous <= now).unwrap_or(now);
+    (1..=count).map(move |i| start + (now - start) * i as f64 / count as f64)
+}
+
+/// Parser of one packet format, which keeps the bytes of incomplete packets until the next call
+pub trait PacketParser: Send + Sync {
+    /// Short name shown in the format selector
+    fn name(&self) -> &'static str;
+
+    /// Parse the bytes read, pushing complete packets
+    fn parse(&mut self, bytes: &[u8], packets: &mut Vec<Packet>);
+
+    /// Forget the bytes kept, e.g. when another format is selected
+    fn reset(&mut self);
+
+    /// How likely the bytes are in this format, from 0 to 1, used to detect the format
+    fn score(&self, bytes: &[u8]) -> f32;
+
+    /// Set the range and unit to convert the acceleration of the device to g
+    fn set_units(&mut self, units: SensorUnits);
+
+    /// Malformed packets dropped since the parser was created
+    fn rejected(&self) -> u64;
+}
+
+/// Longest line kept without a newline, so binary data doesn't grow the buffer forever
+const MAX_LINE_LEN: usize = 256;
+
+/// Text lines of numbers, split by `separator`:
+/// `ax ay az`, `gx gy gz ax ay az`, `gx gy gz ax ay az mx my mz`,
+/// each of the last two optionally followed by `temperature` or `temperature battery`
+pub struct TextParser {
+    name: &'static str,
+    separator: char,
+    units: SensorUnits,
+    line: Vec<u8>,
+    /// The current line exceeded `MAX_LINE_LEN` and is discarded up to the next newline
+    overflowed: bool,
+    rejected: u64,
+}
+
+impl TextParser {
+    /// Space separated lines printed by the M5Stick firmware
+    pub fn space() -> Self {
+        Self {
+            name: "space",
+            separator: ' ',
+            units: SensorUnits::default(),
+            line: Vec::new(),
+            overflowed: false,
+            rejected: 0,
+        }
+    }
+
+    pub fn csv() -> Self {
+        Self {
+            name: "csv",
+            separator: ',',
+            units: SensorUnits::default(),
+            line: Vec::new(),
+            overflowed: false,
+            rejected: 0,
+        }
+    }
+
+    fn parse_line(&self, line: &str) -> Option<Packet> {
+        let values: Vec<f32> = line
+            .split(self.separator)
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .map(str::parse)
+            .collect::<Result<_, _>>()
+            .ok()?;
+        // "inf" and "NaN" parse as floats but would break the views
+        if !values.iter().all(|v| v.is_finite()) {
+            return None;
+        }
+        let to_g = self.units.unit.to_g(1.0);
+        match values[..] {
+            [ax, ay, az] => Some(Packet {
+                accel: Vec3::new(ax, ay, az) * to_g,
+                gyro: None,
+                mag: None,
+                quat: None,
+                temperature: None,
+                battery: None,
+            }),
+            [gx, gy, gz, ax, ay, az, ref rest @ ..] if rest.len() <= 5 => {
+                // up to two status values, or the magnetometer followed by up to two
+                let (mag, status) = match rest {
+                    [mx, my, mz, status @ ..] => (Some(Vec3::new(*mx, *my, *mz)), status),
+                    _ => (None, rest),
+                };
+                Some(Packet {
+                    accel: Vec3::new(ax, ay, az) * to_g,
+                    gyro: Some(Vec3::new(gx, gy, gz)),
+                    mag,
+                    quat: None,
+                    temperature: status.first().copied(),
+                    battery: status.get(1).copied(),
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+impl PacketParser for TextParser {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn parse(&mut self, bytes: &[u8], packets: &mut Vec<Packet>) {
+        for &byte in bytes {
+            if byte == b'\n' {
+                let line = std::mem::take(&mut self.line);
+                // the tail of a discarded line would parse as a packet of its own
+                if std::mem::take(&mut self.overflowed) {
+                    self.rejected += 1;
+                    continue;
+                }
+                let line = String::from_utf8_lossy(&line);
+                match self.parse_line(&line) {
+                    Some(packet) => packets.push(packet),
+                    // blank lines are not packets
+                    None if !line.trim().is_empty() => self.rejected += 1,
+                    None => {}
+                }
+            } else if self.line.len() < MAX_LINE_LEN {
+                self.line.push(byte);
+            } else {
+                self.line.clear();
+                self.overflowed = true;
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        self.line.clear();
+        self.overflowed = false;
+    }
+
+    fn set_units(&mut self, units: SensorUnits) {
+        self.units = units;
+    }
+
+    fn rejected(&self) -> u64 {
+        self.rejected
+    }
+
+    /// Ratio of printable bytes times the ratio of complete lines parsed with the separator
+    fn score(&self, bytes: &[u8]) -> f32 {
+        if bytes.is_empty() {
+            return 0.0;
+        }
+        let printable = bytes
+            .iter()
+            .filter(|b| b.is_ascii_graphic() || b.is_ascii_whitespace())
+            .count() as f32
+            / bytes.len() as f32;
+        let text = String::from_utf8_lossy(bytes);
+        let lines: Vec<&str> = text.split('\n').collect();
+        // the first and last lines may be partial
+        if lines.len() < 3 {
+            return 0.0;
+        }
+        let complete = &lines[1..lines.len() - 1];
+        let parsed = complete
+            .iter()
+            .filter(|line| self.parse_line(line).is_some())
+            .count();
+        printable * parsed as f32 / complete.len() as f32
+    }
+}
+
+/// Type of the values of a binary packet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryValues {
+    /// `f32` little endian in degrees per second, the unit of the sensor, degrees Celsius and volts
+    F32,
+    /// `i16` little endian raw counts of the range of the sensor and ±250 °/s (MPU-6050 gyro default),
+    /// hundredths of a degree Celsius and millivolts
+    I16,
+}
+
+/// Binary packets of `sync`, gyro x/y/z (or the quaternion w/x/y/z) and accel x/y/z, optionally mag x/y/z,
+/// optionally the temperature and the battery voltage, and the XOR of the value bytes
+pub struct BinaryParser {
+    name: &'static str,
+    sync: [u8; 2],
+    values: BinaryValues,
+    /// Whether the quaternion fused on the device is sent instead of the angular rate
+    quat: bool,
+    /// Whether the magnetic field follows the acceleration
+    mag: bool,
+    /// Whether the temperature and the battery voltage follow the acceleration (and the magnetic field)
+    status: bool,
+    units: SensorUnits,
+    buffer: Vec<u8>,
+    rejected: u64,
+}
+
+/// Counts per degree per second of `BinaryValues::I16`
+const GYRO_COUNTS: f32 = 131.0;
+/// Counts per unit of the quaternion of `BinaryValues::I16`, like the BNO055
+const QUAT_COUNTS: f32 = 16384.0;
+/// Counts per microtesla of `BinaryValues::I16`
+const MAG_COUNTS: f32 = 10.0;
+/// Counts per degree Celsius of `BinaryValues::I16`
+const TEMPERATURE_COUNTS: f32 = 100.0;
+/// Counts per volt of `BinaryValues::I16`
+const BATTERY_COUNTS: f32 = 1000.0;
+
+impl BinaryParser {
+    /// `AA 55`, six `f32`, checksum
+    pub fn f32() -> Self {
+        Self {
+            name: "binary f32",
+            sync: [0xAA, 0x55],
+            values: BinaryValues::F32,
+            quat: false,
+            mag: false,
+            status: false,
+            units: SensorUnits::default(),
+            buffer: Vec::new(),
+            rejected: 0,
+        }
+    }
+
+    /// `AA 56`, six `i16`, checksum
+    pub fn i16() -> Self {
+        Self {
+            name: "binary i16",
+            sync: [0xAA, 0x56],
+            values: BinaryValues::I16,
+            quat: false,
+            mag: false,
+            status: false,
+            units: SensorUnits::default(),
+            buffer: Vec::new(),
+            rejected: 0,
+        }
+    }
+
+    /// `AA 57`, six `f32`, temperature and battery voltage `f32`, checksum
+    pub fn f32_status() -> Self {
+        Self {
+            name: "binary f32 status",
+            sync: [0xAA, 0x57],
+            status: true,
+            ..Self::f32()
+        }
+    }
+
+    /// `AA 58`, six `i16`, temperature and battery voltage `i16`, checksum
+    pub fn i16_status() -> Self {
+        Self {
+            name: "binary i16 status",
+            sync: [0xAA, 0x58],
+            status: true,
+            ..Self::i16()
+        }
+    }
+
+    /// `AA 59`, six `f32`, magnetic field `f32`, checksum
+    pub fn f32_mag() -> Self {
+        Self {
+            name: "binary f32 mag",
+            sync: [0xAA, 0x59],
+            mag: true,
+            ..Self::f32()
+        }
+    }
+
+    /// `AA 5A`, six `i16`, magnetic field `i16`, checksum
+    pub fn i16_mag() -> Self {
+        Self {
+            name: "binary i16 mag",
+            sync: [0xAA, 0x5A],
+            mag: true,
+            ..Self::i16()
+        }
+    }
+
+    /// `AA 5B`, quaternion w/x/y/z and acceleration `f32`, checksum
+    pub fn f32_quat() -> Self {
+        Self {
+            name: "binary f32 quat",
+            sync: [0xAA, 0x5B],
+            quat: true,
+            ..Self::f32()
+        }
+    }
+
+    /// `AA 5C`, quaternion w/x/y/z and acceleration `i16`, checksum
+    pub fn i16_quat() -> Self {
+        Self {
+            name: "binary i16 quat",
+            sync: [0xAA, 0x5C],
+            quat: true,
+            ..Self::i16()
+        }
+    }
+
+    fn value_len(&self) -> usize {
+        match self.values {
+            BinaryValues::F32 => 4,
+            BinaryValues::I16 => 2,
+        }
+    }
+
+    /// Names of the values in the order they are sent
+    fn fields(&self) -> Vec<&'static str> {
+        let mut fields = if self.quat {
+            vec!["qw", "qx", "qy", "qz"]
+        } else {
+            vec!["gx", "gy", "gz"]
+        };
+        fields.extend(["ax", "ay", "az"]);
+        if self.mag {
+            fields.extend(["mx", "my", "mz"]);
+        }
+        if self.status {
+            fields.extend(["temperature", "battery"]);
+        }
+        fields
+    }
+
+    /// Length of a packet with the sync bytes and checksum
+    fn packet_len(&self) -> usize {
+        2 + self.fields().len() * self.value_len() + 1
+    }
+
+    /// Schema of the packets, to generate the struct and framing of the firmware sending them
+    pub fn schema(&self) -> MessageSchema {
+        let (ty, type_name) = match self.values {
+            BinaryValues::F32 => (FieldType::F32, "F32"),
+            BinaryValues::I16 => (FieldType::I16, "I16"),
+        };
+        let name = format!(
+            "Accel{}{}{}{}",
+            type_name,
+            if self.quat { "Quat" } else { "" },
+            if self.mag { "Mag" } else { "" },
+            if self.status { "Status" } else { "" }
+        );
+        self.fields()
+            .iter()
+            .fold(MessageSchema::new(&name, &self.sync), |schema, field| {
+                schema.field(field, ty)
+            })
+    }
+
+    /// Value of the field in the unit of `Packet`
+    fn value(&self, field: &str, bytes: &[u8]) -> f32 {
+        match self.values {
+            BinaryValues::F32 => {
+                let value = f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                // only the acceleration is in the unit of the sensor
+                match field {
+                    "ax" | "ay" | "az" => self.units.unit.to_g(value),
+                    _ => value,
+                }
+            }
+            BinaryValues::I16 => {
+                let counts = match field {
+                    "gx" | "gy" | "gz" => GYRO_COUNTS,
+                    "qw" | "qx" | "qy" | "qz" => QUAT_COUNTS,
+                    "ax" | "ay" | "az" => self.units.range.counts_per_g(),
+                    "mx" | "my" | "mz" => MAG_COUNTS,
+                    "temperature" => TEMPERATURE_COUNTS,
+                    _ => BATTERY_COUNTS,
+                };
+                i16::from_le_bytes([bytes[0], bytes[1]]) as f32 / counts
+            }
+        }
+    }
+
+    /// Packet of the values, or `None` if a value is not finite (e.g. a NaN from the wire) or the quaternion is zero
+    fn decode(&self, payload: &[u8]) -> Option<Packet> {
+        let fields = self.fields();
+        let values: Vec<f32> = fields
+            .iter()
+            .zip(payload.chunks_exact(self.value_len()))
+            .map(|(field, bytes)| self.value(field, bytes))
+            .collect();
+        if !values.iter().all(|v| v.is_finite()) {
+            return None;
+        }
+        let get = |field: &str| {
+            let index = fields.iter().position(|f| *f == field)?;
+            values.get(index).copied()
+        };
+        let vec3 = |x, y, z| Some(Vec3::new(get(x)?, get(y)?, get(z)?));
+        let quat = match (get("qx"), get("qy"), get("qz"), get("qw")) {
+            (Some(x), Some(y), Some(z), Some(w)) => {
+                let quat = Quat::from_xyzw(x, y, z, w);
+                if quat.length_squared() == 0.0 {
+                    return None;
+                }
+                Some(quat.normalize())
+            }
+            _ => None,
+        };
+        Some(Packet {
+            accel: vec3("ax", "ay", "az")?,
+            gyro: vec3("gx", "gy", "gz"),
+            mag: vec3("mx", "my", "mz"),
+            quat,
+            temperature: get("temperature"),
+            battery: get("battery"),
+        })
+    }
+}
+
+impl PacketParser for BinaryParser {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn parse(&mut self, bytes: &[u8], packets: &mut Vec<Packet>) {
+        self.buffer.extend_from_slice(bytes);
+        let len = self.packet_len();
+        loop {
+            // skip to the sync bytes
+            match self.buffer.windows(2).position(|w| w == self.sync) {
+                Some(start) => {
+                    self.buffer.drain(..start);
+                }
+                None => {
+                    // keep the last byte, which may be the first sync byte
+                    let keep = self.buffer.len().min(1);
+                    self.buffer.drain(..self.buffer.len() - keep);
+                    return;
+                }
+            }
+            if self.buffer.len() < len {
+                return;
+            }
+            let payload = &self.buffer[2..len - 1];
+            let checksum = payload.iter().fold(0, |acc, b| acc ^ b);
+            if checksum == self.buffer[len - 1] {
+                match self.decode(payload) {
+                    Some(packet) => packets.push(packet),
+                    None => self.rejected += 1,
+                }
+                self.buffer.drain(..len);
+            } else {
+                // not a packet, look for the next sync bytes
+                self.rejected += 1;
+                self.buffer.drain(..1);
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        self.buffer.clear();
+    }
+
+    fn set_units(&mut self, units: SensorUnits) {
+        self.units = units;
+    }
+
+    fn rejected(&self) -> u64 {
+        self.rejected
+    }
+
+    /// Ratio of the bytes in packets with the sync bytes and a valid checksum
+    fn score(&self, bytes: &[u8]) -> f32 {
+        if bytes.is_empty() {
+            return 0.0;
+        }
+        let len = self.packet_len();
+        let mut valid = 0;
+        let mut i = 0;
+        while i + len <= bytes.len() {
+            let packet = &bytes[i..i + len];
+            let checksum = packet[2..len - 1].iter().fold(0, |acc, b| acc ^ b);
+            if packet[..2] == self.sync && checksum == packet[len - 1] {
+                valid += 1;
+                i += len;
+            } else {
+                i += 1;
+            }
+        }
+        (valid * len) as f32 / bytes.len() as f32
+    }
+}
+
+/// C header for Arduino firmware sending packets of the binary format with the name, or `None` for other formats
+pub fn arduino_header(format: &str) -> Option<String> {
+    let parser = [
+        BinaryParser::f32(),
+        BinaryParser::i16(),
+        BinaryParser::f32_status(),
+        BinaryParser::i16_status(),
+        BinaryParser::f32_mag(),
+        BinaryParser::i16_mag(),
+        BinaryParser::f32_quat(),
+        BinaryParser::i16_quat(),
+    ]
+    .into_iter()
+    .find(|parser| parser.name == format)?;
+    Some(parser.schema().to_arduino())
+}
+
+/// Packet parsers which can be chosen at runtime, with the one used for the serial data
+pub struct ParserRegistry {
+    parsers: Vec<Box<dyn PacketParser>>,
+    selected: usize,
+}
+
+impl Default for ParserRegistry {
+    fn default() -> Self {
+        let mut registry = ParserRegistry {
+            parsers: Vec::new(),
+            selected: 0,
+        };
+        registry.register(TextParser::space());
+        registry.register(TextParser::csv());
+        registry.register(BinaryParser::f32());
+        registry.register(BinaryParser::i16());
+        registry.register(BinaryParser::f32_status());
+        registry.register(BinaryParser::i16_status());
+        registry.register(BinaryParser::f32_mag());
+        registry.register(BinaryParser::i16_mag());
+        registry.register(BinaryParser::f32_quat());
+        registry.register(BinaryParser::i16_quat());
+        registry
+    }
+}
+
+impl ParserRegistry {
+    /// Add a format which can be selected by its name
+    pub fn register(&mut self, parser: impl PacketParser + 'static) {
+        self.parsers.push(Box::new(parser));
+    }
+
+    /// Names of the registered formats
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.parsers.iter().map(|p| p.name())
+    }
+
+    /// Name of the selected format
+    pub fn selected(&self) -> &'static str {
+        self.parsers[self.selected].name()
+    }
+
+    /// Select the format with the name, returning false if there is none
+    pub fn select(&mut self, name: &str) -> bool {
+        match self.parsers.iter().position(|p| p.name() == name) {
+            Some(index) => {
+                self.selected = index;
+                self.parsers[index].reset();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Forget the bytes of an incomplete packet, e.g. after seeking a replay
+    pub fn reset(&mut self) {
+        self.parsers[self.selected].reset();
+    }
+
+    /// The format with the highest score for the bytes, with the score
+    pub fn detect(&self, bytes: &[u8]) -> Option<(&'static str, f32)> {
+        self.parsers
+            .iter()
+            .map(|p| (p.name(), p.score(bytes)))
+            .filter(|(_, score)| *score > 0.0)
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+    }
+
+    /// Set the range and unit of the sensor for all formats
+    pub fn set_units(&mut self, units: SensorUnits) {
+        for parser in self.parsers.iter_mut() {
+            parser.set_units(units);
+        }
+    }
+
+    /// Malformed packets dropped by the selected format since it was created
+    pub fn rejected(&self) -> u64 {
+        self.parsers[self.selected].rejected()
+    }
+
+    /// Parse the bytes with the selected format
+    pub fn parse(&mut self, bytes: &[u8], packets: &mut Vec<Packet>) {
+        self.parsers[self.selected].parse(bytes, packets);
+    }
+
+    /// Parse the bytes one at a time with the selected format, recording each packet and each malformed packet
+    /// with the bytes it was parsed from. `raw` keeps the bytes of the next record between calls.
+    pub fn trace(&mut self, bytes: &[u8], raw: &mut Vec<u8>, records: &mut Vec<ParseRecord>) {
+        let parser = &mut self.parsers[self.selected];
+        let mut packets = Vec::new();
+        for &byte in bytes {
+            raw.push(byte);
+            let rejected = parser.rejected();
+            parser.parse(&[byte], &mut packets);
+            if parser.rejected() > rejected {
+                records.push(ParseRecord {
+                    raw: std::mem::take(raw),
+                    packet: None,
+                });
+            }
+            for packet in packets.drain(..) {
+                records.push(ParseRecord {
+                    raw: std::mem::take(raw),
+                    packet: Some(packet),
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn discards_overlong_line_up_to_newline() {
+        let mut parser = TextParser::space();
+        let mut line = vec![b'9'; MAX_LINE_LEN + 1];
+        line.extend_from_slice(b"1 2 3\n4 5 6\n");
+        let mut packets = vec![];
+        parser.parse(&line, &mut packets);
+
+        assert_eq!(packets.len(), 1);
+        assert_eq!(
+            packets[0].accel,
+            Vec3::new(4.0, 5.0, 6.0) * parser.units.unit.to_g(1.0)
+        );
+        assert_eq!(parser.rejected(), 1);
+    }
+}
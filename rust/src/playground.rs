@@ -0,0 +1,176 @@
+use bevy::{app::Events, prelude::*};
+
+use crate::haptics::{DeviceCommand, DeviceCommandEvent};
+use crate::model::MODEL_ORIGIN;
+use crate::plot::AccelSampleEvent;
+use crate::MyObject;
+
+const GRAVITY: f32 = 9.81;
+/// Units of the scene per meter
+const SCALE: f32 = 100.0;
+/// Half size of the room in meters
+const ROOM_HALF_SIZE: f32 = 1.5;
+/// Time constant of the low-pass filter estimating gravity in seconds
+const GRAVITY_TIME_CONSTANT: f32 = 1.0;
+/// The device is still while the magnitude is within this of 1 g
+const STILL_THRESHOLD: f32 = 0.05;
+/// Number of still samples in a row to reset the velocity (zero velocity update)
+const STILL_SAMPLES: usize = 10;
+/// Part of the velocity kept when bouncing off a wall
+const RESTITUTION: f32 = 0.5;
+/// Slower hits in m/s don't vibrate the device
+const MIN_IMPACT_SPEED: f32 = 0.1;
+/// Hits at this speed in m/s vibrate the device at full strength
+const FULL_IMPACT_SPEED: f32 = 2.0;
+
+/// Position of the device integrated from the acceleration, in meters from the origin
+#[derive(Debug, Default)]
+pub struct DeadReckoning {
+    pub position: Vec3,
+    pub velocity: Vec3,
+    /// Gravity in g in the device frame, estimated by a low-pass filter
+    pub gravity: Option<Vec3>,
+    still: usize,
+}
+
+/// Scene where the model is moved through a room by the integrated position.
+/// The gravity is removed with a low-pass filter, and the velocity is reset while the device is still
+/// (zero velocity update), which keeps the drift bounded. Backspace brings the model back to the center.
+/// The model bounces off the walls, and each hit vibrates the device if `HapticsPlugin` is added.
+pub struct PlaygroundPlugin;
+
+impl Plugin for PlaygroundPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DeadReckoning>()
+            .add_startup_system(setup_room)
+            .add_system(integrate)
+            .add_system(move_model);
+    }
+}
+
+fn setup_room(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let origin = Vec3::from(MODEL_ORIGIN);
+    let size = ROOM_HALF_SIZE * 2.0 * SCALE;
+    let material = materials.add(StandardMaterial {
+        base_color: Color::rgb(0.3, 0.3, 0.35),
+        ..Default::default()
+    });
+    // floor and back wall
+    commands.spawn_bundle(PbrBundle {
+        mesh: meshes.add(Mesh::from(shape::Plane { size })),
+        material: material.clone(),
+        transform: Transform::from_translation(origin - Vec3::Y * size / 2.0),
+        ..Default::default()
+    });
+    commands.spawn_bundle(PbrBundle {
+        mesh: meshes.add(Mesh::from(shape::Plane { size })),
+        material,
+        transform: Transform::from_translation(origin - Vec3::Z * size / 2.0)
+            .with_rotation(Quat::from_rotation_x(std::f32::consts::FRAC_PI_2)),
+        ..Default::default()
+    });
+    commands.spawn_bundle(PointLightBundle {
+        transform: Transform::from_translation(origin + Vec3::new(0.0, size / 2.0, size / 2.0)),
+        point_light: PointLight {
+            intensity: 100000.0,
+            range: size * 2.0,
+            ..Default::default()
+        },
+        ..Default::default()
+    });
+}
+
+fn integrate(
+    mut ev_sample: EventReader<AccelSampleEvent>,
+    mut dr: ResMut<DeadReckoning>,
+    mut device_commands: Option<ResMut<Events<DeviceCommandEvent>>>,
+    keys: Res<Input<KeyCode>>,
+    time: Res<Time>,
+) {
+    if keys.just_pressed(KeyCode::Back) {
+        dr.position = Vec3::ZERO;
+        dr.velocity = Vec3::ZERO;
+    }
+
+    // samples of one frame are spread evenly over the frame
+    let samples: Vec<Vec3> = ev_sample.iter().map(|AccelSampleEvent(a)| *a).collect();
+    if samples.is_empty() {
+        return;
+    }
+    let dt = time.delta_seconds() / samples.len() as f32;
+    let alpha = dt / (GRAVITY_TIME_CONSTANT + dt);
+
+    for accel in samples {
+        let gravity = match dr.gravity {
+            Some(gravity) => gravity + (accel - gravity) * alpha,
+            None => accel,
+        };
+        dr.gravity = Some(gravity);
+
+        if (accel.length() - 1.0).abs() < STILL_THRESHOLD {
+            dr.still += 1;
+        } else {
+            dr.still = 0;
+        }
+        if dr.still >= STILL_SAMPLES {
+            dr.velocity = Vec3::ZERO;
+            continue;
+        }
+
+        let linear = (accel - gravity) * GRAVITY;
+        dr.velocity += linear * dt;
+        let velocity = dr.velocity;
+        dr.position += velocity * dt;
+        if let Some(impact) = bounce(&mut dr) {
+            if let Some(events) = &mut device_commands {
+                events.send(DeviceCommandEvent(vibration(impact)));
+            }
+        }
+    }
+}
+
+/// Keep the position in the room, reflecting the velocity on the walls it hits.
+/// Returns the speed of the hit toward the walls in m/s.
+fn bounce(dr: &mut DeadReckoning) -> Option<f32> {
+    let mut impact: f32 = 0.0;
+    for axis in 0..3 {
+        let side = if dr.position[axis] > ROOM_HALF_SIZE {
+            1.0
+        } else if dr.position[axis] < -ROOM_HALF_SIZE {
+            -1.0
+        } else {
+            continue;
+        };
+        dr.position[axis] = side * ROOM_HALF_SIZE;
+        let speed = dr.velocity[axis] * side;
+        if speed > 0.0 {
+            dr.velocity[axis] = -dr.velocity[axis] * RESTITUTION;
+            impact += speed * speed;
+        }
+    }
+    let impact = impact.sqrt();
+    if impact >= MIN_IMPACT_SPEED {
+        Some(impact)
+    } else {
+        None
+    }
+}
+
+/// Harder hits vibrate stronger and longer
+fn vibration(impact: f32) -> DeviceCommand {
+    let level = (impact / FULL_IMPACT_SPEED).min(1.0);
+    DeviceCommand::Vibrate {
+        duration_ms: 40 + (level * 160.0) as u32,
+        strength: (64.0 + level * 191.0) as u8,
+    }
+}
+
+fn move_model(dr: Res<DeadReckoning>, mut query: Query<&mut Transform, With<MyObject>>) {
+    for mut transform in query.iter_mut() {
+        transform.translation = Vec3::from(MODEL_ORIGIN) + dr.position * SCALE;
+    }
+}
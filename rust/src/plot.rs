@@ -0,0 +1,395 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+
+use crate::orientation::Orientation;
+use crate::parser::PacketEvent;
+use crate::settings::SettingsFile;
+use crate::theme::Theme;
+use crate::units::SensorUnits;
+
+/// Acceleration in g parsed from one packet
+pub struct AccelSampleEvent(pub Vec3);
+
+/// Acceleration with the time it arrived
+#[derive(Debug, Clone, Copy)]
+pub struct Sample {
+    pub time: f64,
+    pub accel: Vec3,
+    /// Acceleration through a low-pass filter, without the noise and vibrations
+    pub filtered: Vec3,
+    /// Angular rate in degrees per second, if the packet has it
+    pub gyro: Option<Vec3>,
+    /// Temperature in degrees Celsius and battery voltage, if the packet has them
+    pub temperature: Option<f32>,
+    pub battery: Option<f32>,
+}
+
+/// Samples of the last `window` seconds shown on the plot
+pub struct SampleHistory {
+    pub samples: VecDeque<Sample>,
+    pub window: f64,
+}
+
+impl Default for SampleHistory {
+    fn default() -> Self {
+        SampleHistory {
+            samples: VecDeque::new(),
+            window: 10.0,
+        }
+    }
+}
+
+impl SampleHistory {
+    /// Samples in the visible time window
+    pub fn visible(&self) -> impl Iterator<Item = &Sample> {
+        let start = self
+            .samples
+            .back()
+            .map(|s| s.time - self.window)
+            .unwrap_or(0.0);
+        self.samples.iter().filter(move |s| s.time >= start)
+    }
+}
+
+/// Low-pass filter of `Sample::filtered`, a first-order filter
+#[derive(Debug, Clone, Copy, Reflect)]
+pub struct LowPass {
+    /// Cutoff frequency in Hz, lower removes more of the noise and vibrations but lags more
+    pub cutoff: f32,
+}
+
+impl Default for LowPass {
+    fn default() -> Self {
+        // a time constant of 0.2 s
+        LowPass { cutoff: 0.8 }
+    }
+}
+
+impl LowPass {
+    /// Time constant in seconds
+    fn time_constant(&self) -> f64 {
+        1.0 / (2.0 * std::f64::consts::PI * self.cutoff.max(f32::EPSILON) as f64)
+    }
+}
+
+/// Size of the plot panel in pixels
+pub const PLOT_WIDTH: f32 = 320.0;
+pub const PLOT_HEIGHT: f32 = 160.0;
+
+const PLOT_POINTS: usize = 160;
+const POINT_SIZE: f32 = 2.0;
+/// Space between pinned plots in pixels
+const PLOT_MARGIN: f32 = 8.0;
+/// Bottom and top of the plots of the angular rate in degrees per second, the default range of the MPU-6050
+const GYRO_SPAN: (f32, f32) = (-250.0, 250.0);
+/// Bottom and top of the plots of the temperature in degrees Celsius
+const TEMPERATURE_SPAN: (f32, f32) = (0.0, 60.0);
+/// Bottom and top of the plots of the battery voltage, from an empty to a full LiPo cell
+const BATTERY_SPAN: (f32, f32) = (3.0, 4.3);
+
+/// Value drawn on a plot
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlotChannel {
+    X,
+    Y,
+    Z,
+    FilteredX,
+    FilteredY,
+    FilteredZ,
+    Magnitude,
+    Roll,
+    Pitch,
+    GyroX,
+    GyroY,
+    GyroZ,
+    Temperature,
+    Battery,
+}
+
+impl PlotChannel {
+    fn color(self, theme: &Theme) -> Color {
+        match self {
+            PlotChannel::X => theme.axes[0],
+            PlotChannel::Y => theme.axes[1],
+            PlotChannel::Z => theme.axes[2],
+            PlotChannel::FilteredX => theme.filtered[0],
+            PlotChannel::FilteredY => theme.filtered[1],
+            PlotChannel::FilteredZ => theme.filtered[2],
+            PlotChannel::Magnitude => theme.magnitude,
+            PlotChannel::Roll => theme.roll,
+            PlotChannel::Pitch => theme.pitch,
+            PlotChannel::GyroX => theme.axes[0],
+            PlotChannel::GyroY => theme.axes[1],
+            PlotChannel::GyroZ => theme.axes[2],
+            PlotChannel::Temperature => theme.temperature,
+            PlotChannel::Battery => theme.battery,
+        }
+    }
+
+    /// Value of the sample, and the values at the bottom and the top of the plot,
+    /// or `None` if the packet of the sample doesn't have the value
+    fn value(self, sample: &Sample, range: f32) -> Option<(f32, (f32, f32))> {
+        let accel = (-range, range);
+        let angle = (-PI, PI);
+        match self {
+            PlotChannel::X => Some((sample.accel.x, accel)),
+            PlotChannel::Y => Some((sample.accel.y, accel)),
+            PlotChannel::Z => Some((sample.accel.z, accel)),
+            PlotChannel::FilteredX => Some((sample.filtered.x, accel)),
+            PlotChannel::FilteredY => Some((sample.filtered.y, accel)),
+            PlotChannel::FilteredZ => Some((sample.filtered.z, accel)),
+            PlotChannel::Magnitude => Some((sample.accel.length(), accel)),
+            PlotChannel::Roll => Some((Orientation::from_accel(sample.accel).roll, angle)),
+            PlotChannel::Pitch => Some((Orientation::from_accel(sample.accel).pitch, angle)),
+            PlotChannel::GyroX => sample.gyro.map(|g| (g.x, GYRO_SPAN)),
+            PlotChannel::GyroY => sample.gyro.map(|g| (g.y, GYRO_SPAN)),
+            PlotChannel::GyroZ => sample.gyro.map(|g| (g.z, GYRO_SPAN)),
+            PlotChannel::Temperature => sample.temperature.map(|t| (t, TEMPERATURE_SPAN)),
+            PlotChannel::Battery => sample.battery.map(|v| (v, BATTERY_SPAN)),
+        }
+    }
+}
+
+/// Channels of one pinned plot, kept in the settings file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlotLayout {
+    pub channels: Vec<PlotChannel>,
+}
+
+impl Default for PlotLayout {
+    fn default() -> Self {
+        Self {
+            channels: PRESETS[0].to_vec(),
+        }
+    }
+}
+
+/// Channels chosen by `C` in turn. Other combinations can be written in the settings file.
+const PRESETS: [&[PlotChannel]; 7] = [
+    &[PlotChannel::X, PlotChannel::Y, PlotChannel::Z],
+    &[
+        PlotChannel::FilteredX,
+        PlotChannel::FilteredY,
+        PlotChannel::FilteredZ,
+    ],
+    &[PlotChannel::Magnitude],
+    &[PlotChannel::Roll, PlotChannel::Pitch],
+    &[
+        PlotChannel::X,
+        PlotChannel::Y,
+        PlotChannel::Z,
+        PlotChannel::Magnitude,
+    ],
+    &[PlotChannel::GyroX, PlotChannel::GyroY, PlotChannel::GyroZ],
+    &[PlotChannel::Temperature, PlotChannel::Battery],
+];
+
+/// Plots pinned from right to left, and the one the keys change
+struct PlotLayouts {
+    plots: Vec<PlotLayout>,
+    selected: usize,
+}
+
+/// Plots of the acceleration over the last seconds in the top right corner.
+/// `[`/`]` select a plot, `C` changes its channels, `=` pins another plot, `-` removes the plot,
+/// and `Shift` + `[`/`]` moves it. The plots are saved to the settings file.
+pub struct PlotPlugin {
+    /// Plots at startup, at least one
+    pub plots: Vec<PlotLayout>,
+}
+
+impl Plugin for PlotPlugin {
+    fn build(&self, app: &mut App) {
+        let plots = if self.plots.is_empty() {
+            vec![PlotLayout::default()]
+        } else {
+            self.plots.clone()
+        };
+        app.add_event::<AccelSampleEvent>()
+            .init_resource::<SampleHistory>()
+            .init_resource::<LowPass>()
+            .register_type::<LowPass>()
+            .insert_resource(PlotLayouts { plots, selected: 0 })
+            .add_system(update_history)
+            .add_system(arrange_plots)
+            .add_system(spawn_plots)
+            .add_system(update_plot);
+    }
+}
+
+fn update_history(
+    mut ev_packet: EventReader<PacketEvent>,
+    mut history: ResMut<SampleHistory>,
+    low_pass: Res<LowPass>,
+    time: Res<Time>,
+) {
+    let now = time.seconds_since_startup();
+    let time_constant = low_pass.time_constant();
+    for PacketEvent(packet, _) in ev_packet.iter() {
+        let accel = packet.accel;
+        let filtered = match history.samples.back() {
+            Some(last) => {
+                let dt = now - last.time;
+                let alpha = (dt / (time_constant + dt)) as f32;
+                last.filtered + (accel - last.filtered) * alpha
+            }
+            None => accel,
+        };
+        history.samples.push_back(Sample {
+            time: now,
+            accel,
+            filtered,
+            gyro: packet.gyro,
+            temperature: packet.temperature,
+            battery: packet.battery,
+        });
+    }
+    let start = now - history.window;
+    while history.samples.front().map_or(false, |s| s.time < start) {
+        history.samples.pop_front();
+    }
+}
+
+#[derive(Component)]
+struct PlotPanel;
+
+#[derive(Component)]
+struct PlotPoint {
+    channel: PlotChannel,
+    index: usize,
+}
+
+/// Select, change, pin, remove and move plots, saving the plots to the settings file
+fn arrange_plots(
+    keys: Res<Input<KeyCode>>,
+    mut layouts: ResMut<PlotLayouts>,
+    settings: Option<ResMut<SettingsFile>>,
+) {
+    let shift = keys.pressed(KeyCode::LShift) || keys.pressed(KeyCode::RShift);
+    let selected = layouts.selected;
+    let len = layouts.plots.len();
+    // plots are pinned from right to left, so `[` goes to the next one
+    if keys.just_pressed(KeyCode::LBracket) && selected + 1 < len {
+        if shift {
+            layouts.plots.swap(selected, selected + 1);
+        }
+        layouts.selected += 1;
+    } else if keys.just_pressed(KeyCode::RBracket) && selected > 0 {
+        if shift {
+            layouts.plots.swap(selected, selected - 1);
+        }
+        layouts.selected -= 1;
+    } else if keys.just_pressed(KeyCode::C) {
+        let channels = &mut layouts.plots[selected].channels;
+        let next = PRESETS
+            .iter()
+            .position(|preset| **preset == channels[..])
+            .map_or(0, |i| (i + 1) % PRESETS.len());
+        *channels = PRESETS[next].to_vec();
+    } else if keys.just_pressed(KeyCode::Equals) {
+        layouts.plots.push(PlotLayout::default());
+        layouts.selected = len;
+    } else if keys.just_pressed(KeyCode::Minus) && len > 1 {
+        layouts.plots.remove(selected);
+        layouts.selected = selected.min(len - 2);
+    } else {
+        return;
+    }
+
+    if let Some(mut settings) = settings {
+        if settings.settings.plots != layouts.plots {
+            settings.settings.plots = layouts.plots.clone();
+            settings.save();
+        }
+    }
+}
+
+/// Spawn the plots again when they or the theme are changed
+fn spawn_plots(
+    mut commands: Commands,
+    layouts: Res<PlotLayouts>,
+    theme: Res<Theme>,
+    panels: Query<Entity, With<PlotPanel>>,
+) {
+    if !layouts.is_changed() && !theme.is_changed() {
+        return;
+    }
+    for entity in panels.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    for (i, layout) in layouts.plots.iter().enumerate() {
+        let color = if i == layouts.selected && layouts.plots.len() > 1 {
+            theme.selected_panel
+        } else {
+            theme.panel
+        };
+        commands
+            .spawn_bundle(NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Px(PLOT_WIDTH), Val::Px(PLOT_HEIGHT)),
+                    position_type: PositionType::Absolute,
+                    position: Rect {
+                        right: Val::Px(PLOT_MARGIN + i as f32 * (PLOT_WIDTH + PLOT_MARGIN)),
+                        top: Val::Px(PLOT_MARGIN),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                color: color.into(),
+                ..Default::default()
+            })
+            .insert(PlotPanel)
+            .with_children(|parent| {
+                for &channel in layout.channels.iter() {
+                    for index in 0..PLOT_POINTS {
+                        parent
+                            .spawn_bundle(NodeBundle {
+                                style: Style {
+                                    size: Size::new(Val::Px(POINT_SIZE), Val::Px(POINT_SIZE)),
+                                    position_type: PositionType::Absolute,
+                                    ..Default::default()
+                                },
+                                color: channel.color(&theme).into(),
+                                visibility: Visibility { is_visible: false },
+                                ..Default::default()
+                            })
+                            .insert(PlotPoint { channel, index });
+                    }
+                }
+            });
+    }
+}
+
+/// Place the points at the last samples in the window, scaled to the range of the sensor (or ±180° for angles,
+/// and `GYRO_SPAN`, `TEMPERATURE_SPAN` and `BATTERY_SPAN`)
+fn update_plot(
+    history: Res<SampleHistory>,
+    units: Res<SensorUnits>,
+    mut points: Query<(&PlotPoint, &mut Style, &mut Visibility)>,
+) {
+    let samples: Vec<&Sample> = history.visible().collect();
+    let (start, end) = match (samples.first(), samples.last()) {
+        (Some(first), Some(last)) => (last.time - history.window, last.time.max(first.time)),
+        _ => (0.0, 0.0),
+    };
+    // thin out samples if there are more than points
+    let stride = (samples.len() + PLOT_POINTS - 1) / PLOT_POINTS;
+    let offset = samples.len().saturating_sub(stride.max(1) * PLOT_POINTS);
+    let range = units.range.full_scale();
+
+    for (point, mut style, mut visibility) in points.iter_mut() {
+        let sample = samples.get(offset + point.index * stride.max(1));
+        let value = sample.and_then(|sample| Some((sample, point.channel.value(sample, range)?)));
+        visibility.is_visible = value.is_some() && end > start;
+        if let Some((sample, (value, (bottom, top)))) = value {
+            let value = value.clamp(bottom, top);
+            let x = ((sample.time - start) / (end - start)) as f32 * (PLOT_WIDTH - POINT_SIZE);
+            let y = (value - bottom) / (top - bottom) * (PLOT_HEIGHT - POINT_SIZE);
+            style.position.left = Val::Px(x);
+            style.position.bottom = Val::Px(y);
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use bevy::prelude::*;
+use std::io::{self, Write};
+use std::net::UdpSocket;
+
+use crate::frame::{Frame, Frames};
+use crate::parser::{Packet, PacketEvent};
+use crate::units::SensorUnits;
+
+/// UDP port Teleplot listens on by default
+pub const TELEPLOT_PORT: u16 = 47269;
+
+/// Text output of the packets for other plotting tools, to cross-check the plots of the viewer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlotterOutput {
+    /// Teleplot lines like `>ax:0.01`, one datagram per packet to the host and UDP port
+    Teleplot(String),
+    /// Arduino Serial Plotter lines like `ax:0.01 ay:0.02 az:0.98` on stdout
+    SerialPlotter,
+}
+
+/// Re-emit each parsed packet to the outputs: the acceleration in the unit of the sensor,
+/// the angular rate in degrees per second, the quaternion fused on the device, the magnetic field in microtesla,
+/// the temperature in degrees Celsius and the battery voltage in volts if the packet has them
+pub struct PlotterPlugin {
+    pub outputs: Vec<PlotterOutput>,
+}
+
+struct PlotterSender {
+    outputs: Vec<PlotterOutput>,
+    socket: Option<UdpSocket>,
+}
+
+impl Plugin for PlotterPlugin {
+    fn build(&self, app: &mut App) {
+        let teleplot = self
+            .outputs
+            .iter()
+            .any(|output| matches!(output, PlotterOutput::Teleplot(_)));
+        let socket = if teleplot {
+            UdpSocket::bind("0.0.0.0:0")
+                .map_err(|e| error!("failed to open the Teleplot socket: {}", e))
+                .ok()
+        } else {
+            None
+        };
+        for output in self.outputs.iter() {
+            info!("plotting to {:?}", output);
+        }
+        app.insert_resource(PlotterSender {
+            outputs: self.outputs.clone(),
+            socket,
+        })
+        .add_system(send_plotter);
+    }
+}
+
+fn send_plotter(
+    mut ev_packet: EventReader<PacketEvent>,
+    mut sender: ResMut<PlotterSender>,
+    units: Res<SensorUnits>,
+    frames: Res<Frames>,
+) {
+    let sender = &mut *sender;
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    for PacketEvent(packet, _) in ev_packet.iter() {
+        let channels = channels(packet, &units, frames.frame);
+        for output in sender.outputs.iter() {
+            match output {
+                PlotterOutput::Teleplot(target) => {
+                    let socket = match &sender.socket {
+                        Some(socket) => socket,
+                        None => continue,
+                    };
+                    let message: String = channels
+                        .iter()
+                        .map(|(name, value)| format!(">{}:{}\n", name, value))
+                        .collect();
+                    if let Err(e) = socket.send_to(message.as_bytes(), target) {
+                        warn!("failed to send to Teleplot at {}: {}", target, e);
+                    }
+                }
+                PlotterOutput::SerialPlotter => {
+                    let line: Vec<String> = channels
+                        .iter()
+                        .map(|(name, value)| format!("{}:{}", name, value))
+                        .collect();
+                    // a closed stdout (e.g. the plotter piped to has exited) is ignored
+                    let _ = writeln!(stdout, "{}", line.join(" "));
+                }
+            }
+        }
+    }
+}
+
+/// Names and values of the channels of the packet, with the quaternion in `frame`
+fn channels(packet: &Packet, units: &SensorUnits, frame: Frame) -> Vec<(&'static str, f32)> {
+    let accel = packet.accel * units.unit.in_unit(1.0);
+    let mut channels = vec![("ax", accel.x), ("ay", accel.y), ("az", accel.z)];
+    if let Some(gyro) = packet.gyro {
+        channels.extend([("gx", gyro.x), ("gy", gyro.y), ("gz", gyro.z)]);
+    }
+    if let Some(quat) = packet.quat.map(|quat| frame.from_nwu(quat)) {
+        channels.extend([
+            ("qw", quat.w),
+            ("qx", quat.x),
+            ("qy", quat.y),
+            ("qz", quat.z),
+        ]);
+    }
+    if let Some(mag) = packet.mag {
+        channels.extend([("mx", mag.x), ("my", mag.y), ("mz", mag.z)]);
+    }
+    if let Some(temperature) = packet.temperature {
+        channels.push(("temperature", temperature));
+    }
+    if let Some(battery) = packet.battery {
+        channels.push(("battery", battery));
+    }
+    channels
+}
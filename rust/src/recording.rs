@@ -0,0 +1,538 @@
+use bevy::{app::AppExit, prelude::*};
+use bevy_serial::{decode_hex, encode_hex, SerialReadEvent};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::markers::{Marker, MarkerEvent};
+use crate::units::{AccelRange, AccelUnit, SensorUnits};
+
+/// Chunk of bytes read from the serial port, with the time since the recording started
+#[derive(Debug, Clone)]
+pub struct RecordedChunk {
+    pub time: f64,
+    pub bytes: Vec<u8>,
+}
+
+/// Version written in the header of new recordings. Files without a header are version 1.
+pub const RECORDING_VERSION: u32 = 2;
+
+/// Start of the first line of a recording with a header, followed by the version
+pub const RECORDING_MAGIC: &str = "# accelviewer recording";
+
+/// Header at the top of a recording, one `# key value` line per field:
+///
+/// ```text
+/// # accelviewer recording 2
+/// # codec hex
+/// # started 1760611054
+/// # range 8g
+/// # unit g
+/// # format csv
+/// ```
+///
+/// Unknown keys are ignored, so fields can be added without a new version.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordingHeader {
+    pub version: u32,
+    /// Encoding of the bytes of a chunk, only `hex` for now
+    pub codec: String,
+    /// Unix time when the file was started
+    pub started: Option<u64>,
+    pub range: Option<AccelRange>,
+    pub unit: Option<AccelUnit>,
+    /// Packet format of the bytes, if it was known when recording
+    pub format: Option<String>,
+}
+
+impl Default for RecordingHeader {
+    /// Header of a version 1 recording, which has none
+    fn default() -> Self {
+        RecordingHeader {
+            version: 1,
+            codec: "hex".to_string(),
+            started: None,
+            range: None,
+            unit: None,
+            format: None,
+        }
+    }
+}
+
+impl RecordingHeader {
+    /// Header of a new recording made with the units and format
+    pub fn new(units: SensorUnits, format: Option<String>) -> Self {
+        RecordingHeader {
+            version: RECORDING_VERSION,
+            range: Some(units.range),
+            unit: Some(units.unit),
+            format,
+            ..Default::default()
+        }
+    }
+
+    /// Header lines of a file started at the unix time
+    fn to_lines(&self, started: u64) -> String {
+        let mut lines = format!(
+            "{} {}\n# codec {}\n# started {}\n",
+            RECORDING_MAGIC, self.version, self.codec, started
+        );
+        if let Some(range) = self.range {
+            lines += &format!("# range {}g\n", range.full_scale());
+        }
+        if let Some(unit) = self.unit {
+            lines += &format!("# unit {}\n", unit.symbol());
+        }
+        if let Some(format) = &self.format {
+            lines += &format!("# format {}\n", format);
+        }
+        lines
+    }
+
+    /// Read a `# key value` line
+    fn parse_line(&mut self, line: &str) -> Result<(), String> {
+        if let Some(version) = line.strip_prefix(RECORDING_MAGIC) {
+            let version = version
+                .trim()
+                .parse()
+                .map_err(|_| format!("invalid version {}", version.trim()))?;
+            if version > RECORDING_VERSION {
+                return Err(format!(
+                    "recording version {} is newer than the supported version {}",
+                    version, RECORDING_VERSION
+                ));
+            }
+            self.version = version;
+            return Ok(());
+        }
+        let (key, value) = match line.trim_start_matches('#').trim().split_once(' ') {
+            Some(parts) => parts,
+            None => return Ok(()),
+        };
+        match key {
+            "codec" if value != "hex" => return Err(format!("unknown codec {}", value)),
+            "codec" => self.codec = value.to_string(),
+            "started" => self.started = value.parse().ok(),
+            "range" => self.range = Some(value.parse()?),
+            "unit" => self.unit = Some(value.parse()?),
+            "format" => self.format = Some(value.to_string()),
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// Recording loaded in memory, indexed by time.
+///
+/// A recording file starts with a `RecordingHeader`, then has one chunk per line,
+/// as the time in seconds and the bytes in hex:
+/// `12.345678 31 2E 30 0A`
+///
+/// Markers are lines with `MARK` and the name instead of bytes:
+/// `12.345678 MARK started shaking`
+#[derive(Debug, Default, Clone)]
+pub struct Recording {
+    pub header: RecordingHeader,
+    pub chunks: Vec<RecordedChunk>,
+    pub markers: Vec<Marker>,
+}
+
+/// Open a text file, decompressing it if the extension is `.gz`
+pub fn open_text(path: &Path) -> io::Result<Box<dyn BufRead>> {
+    let file = File::open(path)?;
+    let reader: Box<dyn Read> = if path.extension().map_or(false, |e| e == "gz") {
+        Box::new(GzDecoder::new(file))
+    } else {
+        Box::new(file)
+    };
+    Ok(Box::new(BufReader::new(reader)))
+}
+
+impl Recording {
+    /// Read the lines of a recording file, `source` naming it in warnings
+    pub fn read(lines: impl Iterator<Item = io::Result<String>>, source: &str) -> io::Result<Self> {
+        let mut recording = Recording::default();
+        for line in lines {
+            let line = line?;
+            if line.starts_with('#') {
+                recording
+                    .header
+                    .parse_line(&line)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                continue;
+            }
+            let (time, rest) = match line.split_once(' ') {
+                Some(parts) => parts,
+                None => continue,
+            };
+            let time = match time.parse() {
+                Ok(time) => time,
+                Err(_) => {
+                    warn!("skipping malformed line in {}: {}", source, line);
+                    continue;
+                }
+            };
+            if let Some(name) = rest.strip_prefix("MARK ") {
+                recording.markers.push(Marker {
+                    time,
+                    name: name.to_string(),
+                });
+                continue;
+            }
+            match decode_hex(rest) {
+                Ok(bytes) => recording.chunks.push(RecordedChunk { time, bytes }),
+                Err(_) => warn!("skipping malformed line in {}: {}", source, line),
+            }
+        }
+        Ok(recording)
+    }
+
+    /// Time of the last chunk or marker
+    pub fn duration(&self) -> f64 {
+        let chunk = self.chunks.last().map(|c| c.time).unwrap_or(0.0);
+        let marker = self.markers.last().map(|m| m.time).unwrap_or(0.0);
+        chunk.max(marker)
+    }
+
+    /// Index of the first chunk at or after `time`
+    pub fn index_at(&self, time: f64) -> usize {
+        self.chunks.partition_point(|c| c.time < time)
+    }
+}
+
+/// Settings of the recorder
+#[derive(Debug, Clone)]
+pub struct RecorderSettings {
+    pub path: String,
+    /// Start a new file when the current one exceeds this size in bytes
+    pub max_bytes: Option<u64>,
+    /// Start a new file after this many seconds
+    pub max_seconds: Option<f64>,
+    /// Compress rotated files with gzip
+    pub gzip: bool,
+    /// Sync the file to disk at this interval in seconds, so a crash or power loss loses little data
+    pub fsync_interval: f64,
+}
+
+impl Default for RecorderSettings {
+    fn default() -> Self {
+        RecorderSettings {
+            path: "recording.txt".to_string(),
+            max_bytes: None,
+            max_seconds: None,
+            gzip: false,
+            fsync_interval: 1.0,
+        }
+    }
+}
+
+/// Write every chunk read from serial ports to a recording file.
+///
+/// When the file exceeds the size or age limit, it is renamed to `<path>.<unix seconds>.<milliseconds>` (and compressed
+/// to that name with `.gz` on a background thread if enabled), and a new file is started at `path`.
+/// A counter is appended if that name is already taken, e.g. by rotations within a millisecond.
+/// Times in each file start from when the file was created, and each file starts with the header.
+pub struct Recorder {
+    settings: RecorderSettings,
+    header: RecordingHeader,
+    writer: BufWriter<File>,
+    bytes: u64,
+    file_start: f64,
+    last_sync: f64,
+}
+
+impl Recorder {
+    pub fn create(settings: RecorderSettings, header: RecordingHeader, now: f64) -> io::Result<Self> {
+        let file = File::create(&settings.path)?;
+        let mut recorder = Recorder {
+            settings,
+            header,
+            writer: BufWriter::new(file),
+            bytes: 0,
+            file_start: now,
+            last_sync: now,
+        };
+        recorder.write_header()?;
+        Ok(recorder)
+    }
+
+    fn write_header(&mut self) -> io::Result<()> {
+        let lines = self.header.to_lines(unix_time());
+        self.writer.write_all(lines.as_bytes())?;
+        self.bytes += lines.len() as u64;
+        Ok(())
+    }
+
+    /// Path of the file being written
+    pub fn path(&self) -> &str {
+        &self.settings.path
+    }
+
+    /// Flush the written lines and sync them to disk, e.g. before the recording is stopped
+    fn finish(&mut self) -> io::Result<()> {
+        self.writer.flush()?;
+        self.writer.get_ref().sync_all()
+    }
+
+    fn write_line(&mut self, now: f64, line: &str) -> io::Result<()> {
+        let line = format!("{:.6} {}\n", now - self.file_start, line);
+        self.writer.write_all(line.as_bytes())?;
+        self.bytes += line.len() as u64;
+        Ok(())
+    }
+
+    /// Flush written lines, sync them to disk at the interval, and rotate the file at the limits
+    fn flush(&mut self, now: f64) -> io::Result<()> {
+        self.writer.flush()?;
+        if now - self.last_sync >= self.settings.fsync_interval {
+            self.writer.get_ref().sync_data()?;
+            self.last_sync = now;
+        }
+
+        let too_big = self
+            .settings
+            .max_bytes
+            .map_or(false, |max| self.bytes >= max);
+        let too_old = self
+            .settings
+            .max_seconds
+            .map_or(false, |max| now - self.file_start >= max);
+        if too_big || too_old {
+            self.rotate(now)?;
+        }
+        Ok(())
+    }
+
+    fn rotate(&mut self, now: f64) -> io::Result<()> {
+        self.writer.get_ref().sync_all()?;
+        let rotated = rotated_path(&self.settings.path);
+        fs::rename(&self.settings.path, &rotated)?;
+        self.writer = BufWriter::new(File::create(&self.settings.path)?);
+        self.bytes = 0;
+        self.file_start = now;
+        self.write_header()?;
+        info!("rotated recording to {}", rotated);
+
+        if self.settings.gzip {
+            thread::spawn(move || {
+                if let Err(e) = compress(&rotated) {
+                    error!("failed to compress {}: {}", rotated, e);
+                }
+            });
+        }
+        Ok(())
+    }
+}
+
+/// `<path>.<unix time>.<milliseconds>`, with `.<n>` appended until neither it nor its `.gz` exists
+fn rotated_path(path: &str) -> String {
+    let since_epoch = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let stamp = format!(
+        "{}.{}.{:03}",
+        path,
+        since_epoch.as_secs(),
+        since_epoch.subsec_millis()
+    );
+    let taken = |name: &str| Path::new(name).exists() || Path::new(&format!("{}.gz", name)).exists();
+    let mut rotated = stamp.clone();
+    let mut n = 1;
+    while taken(&rotated) {
+        rotated = format!("{}.{}", stamp, n);
+        n += 1;
+    }
+    rotated
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Compress the file to `<path>.gz` and remove it.
+/// The compressed file is written under a temporary name first, so a crash never leaves a truncated `.gz`.
+fn compress(path: &str) -> io::Result<()> {
+    let gz = format!("{}.gz", path);
+    let tmp = format!("{}.tmp", gz);
+    let mut encoder = GzEncoder::new(BufWriter::new(File::create(&tmp)?), Compression::default());
+    io::copy(&mut File::open(path)?, &mut encoder)?;
+    encoder.finish()?.into_inner()?.sync_all()?;
+    fs::rename(&tmp, &gz)?;
+    fs::remove_file(path)
+}
+
+/// Start or stop recording, sent by the HTTP API
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecordCommand {
+    /// Start recording to the path, relative to the directory of the path of the settings (or to that path),
+    /// ending the current recording. Paths which would leave the directory are refused, see `is_confined`.
+    Start(Option<String>),
+    Stop,
+}
+
+/// Whether the path of a `RecordCommand::Start` stays in the directory of the recordings next to `settings_path`,
+/// so remote clients can't overwrite other files: a relative path without `..`, which still resolves into the
+/// directory once symlinks are followed. A new file is resolved by its parent directory, which must exist anyway.
+pub fn is_confined(settings_path: &str, path: &str) -> bool {
+    let relative = !path.is_empty()
+        && Path::new(path)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+    if !relative {
+        return false;
+    }
+    let joined = start_path(settings_path, path);
+    // a dangling link is resolved too, and refused, as creating the file would create its target
+    let existing = if joined.symlink_metadata().is_ok() {
+        joined.as_path()
+    } else {
+        directory_of(&joined)
+    };
+    match (directory_of(Path::new(settings_path)).canonicalize(), existing.canonicalize()) {
+        (Ok(directory), Ok(resolved)) => resolved.starts_with(directory),
+        _ => false,
+    }
+}
+
+/// Path of a recording started with `RecordCommand::Start(Some(path))`, next to the path of the settings
+fn start_path(settings_path: &str, path: &str) -> PathBuf {
+    Path::new(settings_path).with_file_name(path)
+}
+
+/// Directory of a file, `.` for a bare file name
+fn directory_of(path: &Path) -> &Path {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    }
+}
+
+/// Record the serial data with the settings, from startup if `start` is set or when `RecordCommand::Start` is sent
+pub struct RecorderPlugin {
+    pub settings: RecorderSettings,
+    pub header: RecordingHeader,
+    pub start: bool,
+}
+
+/// Settings and header of the recordings started with `RecordCommand`
+pub(crate) struct RecorderConfig {
+    pub(crate) settings: RecorderSettings,
+    header: RecordingHeader,
+}
+
+impl Plugin for RecorderPlugin {
+    fn build(&self, app: &mut App) {
+        if self.start {
+            let recorder = Recorder::create(self.settings.clone(), self.header.clone(), 0.0)
+                .unwrap_or_else(|e| panic!("Failed to create recording {}: {}", self.settings.path, e));
+            app.insert_resource(recorder);
+        }
+        app.insert_resource(RecorderConfig {
+            settings: self.settings.clone(),
+            header: self.header.clone(),
+        })
+        .add_event::<RecordCommand>()
+        .add_system(apply_record_commands)
+        .add_system(record_serial)
+        .add_system_to_stage(CoreStage::Last, sync_on_exit);
+    }
+}
+
+fn apply_record_commands(
+    mut commands: Commands,
+    mut ev_command: EventReader<RecordCommand>,
+    config: Res<RecorderConfig>,
+    mut recorder: Option<ResMut<Recorder>>,
+    time: Res<Time>,
+) {
+    for command in ev_command.iter() {
+        if let RecordCommand::Start(Some(path)) = command {
+            if !is_confined(&config.settings.path, path) {
+                error!("refusing to record to {}, outside the directory of the recordings", path);
+                continue;
+            }
+        }
+        if let Some(recorder) = &mut recorder {
+            if let Err(e) = recorder.finish() {
+                error!("failed to sync recording: {}", e);
+            }
+            info!("stopped recording to {}", recorder.path());
+        }
+        match command {
+            RecordCommand::Start(path) => {
+                let settings = RecorderSettings {
+                    path: match path {
+                        Some(path) => start_path(&config.settings.path, path)
+                            .to_string_lossy()
+                            .into_owned(),
+                        None => config.settings.path.clone(),
+                    },
+                    ..config.settings.clone()
+                };
+                let now = time.seconds_since_startup();
+                match Recorder::create(settings, config.header.clone(), now) {
+                    Ok(new) => {
+                        info!("recording to {}", new.path());
+                        commands.insert_resource(new);
+                    }
+                    Err(e) => error!("failed to create recording: {}", e),
+                }
+            }
+            RecordCommand::Stop => commands.remove_resource::<Recorder>(),
+        }
+    }
+}
+
+fn record_serial(
+    mut ev_serial: EventReader<SerialReadEvent>,
+    mut ev_marker: EventReader<MarkerEvent>,
+    recorder: Option<ResMut<Recorder>>,
+    time: Res<Time>,
+) {
+    let mut recorder = match recorder {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    let now = time.seconds_since_startup();
+    let mut wrote = false;
+    for SerialReadEvent(_, buffer) in ev_serial.iter() {
+        if let Err(e) = recorder.write_line(now, &encode_hex(buffer)) {
+            error!("failed to write recording: {}", e);
+        }
+        wrote = true;
+    }
+    for MarkerEvent(name) in ev_marker.iter() {
+        // a name can't span lines in the recording
+        let name = name.replace('\n', " ");
+        info!("marker at {:.3}s: {}", now, name);
+        if let Err(e) = recorder.write_line(now, &format!("MARK {}", name)) {
+            error!("failed to write recording: {}", e);
+        }
+        wrote = true;
+    }
+    // the app may exit without dropping resources, so don't keep chunks in the buffer
+    if wrote {
+        if let Err(e) = recorder.flush(now) {
+            error!("failed to flush recording: {}", e);
+        }
+    }
+}
+
+/// Sync the recording to disk when the app exits, e.g. when the headless mode is terminated
+fn sync_on_exit(mut ev_exit: EventReader<AppExit>, recorder: Option<ResMut<Recorder>>) {
+    let mut recorder = match (ev_exit.iter().last(), recorder) {
+        (Some(_), Some(recorder)) => recorder,
+        _ => return,
+    };
+    if let Err(e) = recorder.finish() {
+        error!("failed to sync recording: {}", e);
+    }
+}
@@ -0,0 +1,226 @@
+use bevy::prelude::*;
+use std::fs;
+use std::time::SystemTime;
+
+use crate::alarms::Alarms;
+use crate::axes::AxisMapping;
+use crate::calibration::MagCalibration;
+use crate::connect::{PortCommand, BAUD_RATE};
+use crate::frame::Frames;
+use crate::fusion::Madgwick;
+use crate::locale::Locale;
+use crate::orientation::OrientationSources;
+use crate::plot::LowPass;
+use crate::settings::{Settings, SettingsFile};
+use crate::smoothing::Smoothing;
+use crate::toast::{ToastEvent, ToastLevel};
+use crate::tuning::Tuning;
+use crate::units::SensorUnits;
+
+/// Seconds between checks of the modification time of the settings file
+const POLL_SECONDS: f32 = 1.0;
+
+/// Sent when the settings file has been changed outside the viewer, with the settings before and after
+#[derive(Debug, Clone)]
+pub struct SettingsReloadEvent {
+    pub previous: Settings,
+    pub settings: Settings,
+}
+
+/// Watch the settings file and apply its changes while the viewer runs: the filters, smoothing, magnetometer
+/// calibration, axis mapping, alarms, orientation sources and frames at once, the baud rate by reopening the port
+/// with a `PortCommand`, and a toast to restart for the language, theme and sensor.
+/// Changes made by the viewer itself, which saves to the same file, aren't reloaded.
+pub struct ReloadPlugin;
+
+impl Plugin for ReloadPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SettingsReloadEvent>()
+            .insert_resource(SettingsWatch {
+                modified: None,
+                timer: Timer::from_seconds(POLL_SECONDS, true),
+            })
+            .add_system(watch_settings)
+            .add_system(reload_filters)
+            .add_system(reload_mapping)
+            .add_system(reload_orientation)
+            .add_system(reload_port)
+            .add_system(report_restart);
+    }
+}
+
+struct SettingsWatch {
+    /// Modification time of the file when it was last read
+    modified: Option<SystemTime>,
+    timer: Timer,
+}
+
+fn watch_settings(
+    time: Res<Time>,
+    mut watch: ResMut<SettingsWatch>,
+    settings_file: Option<ResMut<SettingsFile>>,
+    mut ev_reload: EventWriter<SettingsReloadEvent>,
+) {
+    // the file isn't watched if it couldn't be read at startup
+    let mut settings_file = match settings_file {
+        Some(settings_file) => settings_file,
+        None => return,
+    };
+    if !watch.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    let modified = match fs::metadata(&settings_file.path).and_then(|m| m.modified()) {
+        Ok(modified) => modified,
+        // e.g. replaced by an editor, the next poll reads the new file
+        Err(_) => return,
+    };
+    let previous = watch.modified.replace(modified);
+    if previous.is_none() || previous == Some(modified) {
+        return;
+    }
+    let settings = match Settings::load(&settings_file.path) {
+        Ok(settings) => settings,
+        Err(e) => {
+            // probably saved halfway through an edit, so the current settings are kept
+            warn!(
+                "failed to reload settings {}: {}",
+                settings_file.path.display(),
+                e
+            );
+            return;
+        }
+    };
+    if settings == settings_file.settings {
+        return;
+    }
+    info!("settings {} reloaded", settings_file.path.display());
+    let previous = std::mem::replace(&mut settings_file.settings, settings.clone());
+    ev_reload.send(SettingsReloadEvent { previous, settings });
+}
+
+fn reload_filters(
+    mut ev_reload: EventReader<SettingsReloadEvent>,
+    mut low_pass: ResMut<LowPass>,
+    mut filter: ResMut<Madgwick>,
+    mut smoothing: ResMut<Smoothing>,
+    mut calibration: ResMut<MagCalibration>,
+    mut tuning: Option<ResMut<Tuning>>,
+) {
+    for SettingsReloadEvent { previous, settings } in ev_reload.iter() {
+        if settings.filter != previous.filter {
+            info!(
+                "low-pass cutoff {} Hz, Madgwick beta {}",
+                settings.filter.cutoff, settings.filter.beta
+            );
+            low_pass.cutoff = settings.filter.cutoff;
+            filter.beta = settings.filter.beta;
+            if let Some(tuning) = &mut tuning {
+                tuning.cutoff = settings.filter.cutoff;
+                tuning.beta = settings.filter.beta;
+            }
+        }
+        if settings.smoothing != previous.smoothing {
+            info!(
+                "smoothing {} over {} s",
+                settings.smoothing.mode, settings.smoothing.time_constant
+            );
+            *smoothing = settings.smoothing;
+        }
+        if settings.magnetometer != previous.magnetometer {
+            info!("magnetometer calibration reloaded");
+            *calibration = settings.magnetometer;
+        }
+    }
+}
+
+fn reload_mapping(
+    mut ev_reload: EventReader<SettingsReloadEvent>,
+    units: Res<SensorUnits>,
+    mut mapping: ResMut<AxisMapping>,
+    mut alarms: ResMut<Alarms>,
+    mut tuning: Option<ResMut<Tuning>>,
+) {
+    for SettingsReloadEvent { previous, settings } in ev_reload.iter() {
+        if settings.axes != previous.axes {
+            *mapping = settings.axes.unwrap_or_default();
+            info!("axis mapping {}", *mapping);
+        }
+        if settings.alarms != previous.alarms {
+            *alarms = Alarms::new(&settings.alarms, units.unit);
+            for alarm in alarms.0.iter() {
+                info!("alarm {}", alarm);
+            }
+            // the thresholds of the inspector follow the new alarms
+            if let Some(tuning) = &mut tuning {
+                tuning.alarm_thresholds = alarms.0.iter().map(|alarm| alarm.threshold).collect();
+            }
+        }
+    }
+}
+
+fn reload_orientation(
+    mut ev_reload: EventReader<SettingsReloadEvent>,
+    mut sources: ResMut<OrientationSources>,
+    mut frames: ResMut<Frames>,
+) {
+    for SettingsReloadEvent { previous, settings } in ev_reload.iter() {
+        if settings.orientation != previous.orientation || settings.devices != previous.devices {
+            sources.default = settings.orientation;
+            sources.devices = settings
+                .devices
+                .iter()
+                .map(|(device, settings)| (device.clone(), settings.orientation))
+                .collect();
+            info!("orientation source {}", sources.current());
+        }
+        if settings.frame != previous.frame || settings.devices != previous.devices {
+            frames.frame = settings.frame;
+            frames.devices = settings
+                .devices
+                .iter()
+                .filter_map(|(device, settings)| Some((device.clone(), settings.frame?)))
+                .collect();
+            info!("frame {}", frames.device(sources.device.as_deref()));
+        }
+    }
+}
+
+/// Reopen the port streamed with the new baud rate
+fn reload_port(
+    mut ev_reload: EventReader<SettingsReloadEvent>,
+    // only the port selection of the viewer handles the commands, not the replay
+    mut commands: Option<ResMut<Events<PortCommand>>>,
+) {
+    for SettingsReloadEvent { previous, settings } in ev_reload.iter() {
+        if settings.baud_rate == previous.baud_rate {
+            continue;
+        }
+        let baud_rate = settings.baud_rate.unwrap_or(BAUD_RATE);
+        if let Some(commands) = &mut commands {
+            info!("reopening the port at {} baud", baud_rate);
+            commands.send(PortCommand::SetBaudRate(baud_rate));
+        }
+    }
+}
+
+/// Settings read only at startup
+fn report_restart(
+    mut ev_reload: EventReader<SettingsReloadEvent>,
+    locale: Res<Locale>,
+    mut ev_toast: EventWriter<ToastEvent>,
+) {
+    for SettingsReloadEvent { previous, settings } in ev_reload.iter() {
+        let changed = [
+            ("language", settings.language != previous.language),
+            ("theme", settings.theme != previous.theme),
+            ("sensor", settings.sensor != previous.sensor),
+        ];
+        for (name, _) in changed.iter().filter(|(_, changed)| *changed) {
+            warn!("{} changed in the settings file, restart to apply it", name);
+            ev_toast.send(ToastEvent::new(
+                ToastLevel::Warning,
+                locale.fill("settings_restart", &[name]),
+            ));
+        }
+    }
+}
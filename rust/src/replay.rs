@@ -0,0 +1,308 @@
+use bevy::prelude::*;
+use bevy_serial::SerialReadEvent;
+
+use crate::locale::Locale;
+use crate::parser::ParserRegistry;
+use crate::recording::Recording;
+
+/// Label of `SerialReadEvent`s sent by the replay
+pub const REPLAY_LABEL: &str = "replay";
+
+const SPEEDS: [f64; 6] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0];
+const SEEK_STEP: f64 = 5.0;
+
+/// Playback state of a recording
+pub struct Replay {
+    pub recording: Recording,
+    pub time: f64,
+    pub speed: f64,
+    pub paused: bool,
+    next: usize,
+}
+
+impl Replay {
+    pub fn new(recording: Recording) -> Self {
+        Replay {
+            recording,
+            time: 0.0,
+            speed: 1.0,
+            paused: false,
+            next: 0,
+        }
+    }
+
+    /// Jump to the time, skipping the chunks in between
+    pub fn seek(&mut self, time: f64) {
+        self.time = time.clamp(0.0, self.recording.duration());
+        self.next = self.recording.index_at(self.time);
+    }
+
+    fn change_speed(&mut self, faster: bool) {
+        let i = SPEEDS.iter().position(|&s| s >= self.speed).unwrap_or(2);
+        let i = if faster {
+            (i + 1).min(SPEEDS.len() - 1)
+        } else {
+            i.saturating_sub(1)
+        };
+        self.speed = SPEEDS[i];
+    }
+}
+
+/// Time in the recording of the chunk of each `SerialReadEvent` sent by the replay, sent along with it in the same
+/// order, so the samples are timed by the recording rather than by the updates that replay them
+#[derive(Debug, Clone, Copy)]
+pub struct ReplayChunkEvent(pub f64);
+
+/// Clock advancing the time of the replay in each update
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReplayClock {
+    /// The frame time of `Time` times the replay speed
+    Wall,
+    /// A fixed step in seconds of the recording per update, regardless of the frame time and the speed.
+    /// Tests insert it before `ReplayPlugin` to run a long capture in a few updates,
+    /// with the same chunks in each update on every run. The samples keep the times of the recording,
+    /// so the fused orientation doesn't depend on the step.
+    Fixed(f64),
+}
+
+impl Default for ReplayClock {
+    fn default() -> Self {
+        ReplayClock::Wall
+    }
+}
+
+impl ReplayClock {
+    /// Seconds of the recording to replay in this update
+    fn delta(&self, time: &Time, speed: f64) -> f64 {
+        match self {
+            ReplayClock::Wall => time.delta_seconds_f64() * speed,
+            ReplayClock::Fixed(step) => *step,
+        }
+    }
+}
+
+/// Replay a recording as `SerialReadEvent`s instead of reading the serial port, each with a `ReplayChunkEvent`,
+/// which is added with the systems reading the serial data.
+/// The replay follows the `ReplayClock` resource, the frame time unless another clock was inserted.
+///
+/// Space pauses, Up/Down changes the speed from 0.25x to 8x, Left/Right seeks by 5 seconds,
+/// and clicking the timeline jumps to that time.
+pub struct ReplayPlugin {
+    pub recording: Recording,
+}
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Replay::new(self.recording.clone()))
+            .init_resource::<ReplayClock>()
+            .add_startup_system(setup_timeline)
+            .add_system(replay_controls)
+            .add_system(scrub_timeline)
+            .add_system(replay_serial)
+            .add_system(update_timeline);
+    }
+}
+
+fn replay_serial(
+    mut ev_serial: EventWriter<SerialReadEvent>,
+    mut ev_chunk: EventWriter<ReplayChunkEvent>,
+    mut replay: ResMut<Replay>,
+    clock: Res<ReplayClock>,
+    time: Res<Time>,
+) {
+    if replay.paused {
+        return;
+    }
+    let duration = replay.recording.duration();
+    let delta = clock.delta(&time, replay.speed);
+    replay.time = (replay.time + delta).min(duration);
+
+    let replay = &mut *replay;
+    while let Some(chunk) = replay.recording.chunks.get(replay.next) {
+        if chunk.time > replay.time {
+            break;
+        }
+        ev_serial.send(SerialReadEvent(
+            REPLAY_LABEL.to_string(),
+            chunk.bytes.clone(),
+        ));
+        ev_chunk.send(ReplayChunkEvent(chunk.time));
+        replay.next += 1;
+    }
+}
+
+fn replay_controls(
+    keys: Res<Input<KeyCode>>,
+    mut replay: ResMut<Replay>,
+    mut parsers: ResMut<ParserRegistry>,
+) {
+    if keys.just_pressed(KeyCode::Space) {
+        replay.paused = !replay.paused;
+    }
+    if keys.just_pressed(KeyCode::Up) {
+        replay.change_speed(true);
+    }
+    if keys.just_pressed(KeyCode::Down) {
+        replay.change_speed(false);
+    }
+    let seek = if keys.just_pressed(KeyCode::Right) {
+        SEEK_STEP
+    } else if keys.just_pressed(KeyCode::Left) {
+        -SEEK_STEP
+    } else {
+        return;
+    };
+    let time = replay.time + seek;
+    replay.seek(time);
+    // don't join a partial line from before the seek with the next chunk
+    parsers.reset();
+}
+
+#[derive(Component)]
+struct Timeline;
+
+#[derive(Component)]
+struct TimelineProgress;
+
+#[derive(Component)]
+struct TimelineText;
+
+/// Width of a marker tick on the timeline
+const MARKER_WIDTH: f32 = 2.0;
+
+fn setup_timeline(mut commands: Commands, asset_server: Res<AssetServer>, replay: Res<Replay>) {
+    let duration = replay.recording.duration();
+    commands
+        .spawn_bundle(ButtonBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Px(24.0)),
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(0.0),
+                    bottom: Val::Px(0.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            color: Color::rgba(0.1, 0.1, 0.1, 0.8).into(),
+            ..Default::default()
+        })
+        .insert(Timeline)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Percent(0.0), Val::Percent(100.0)),
+                        ..Default::default()
+                    },
+                    color: Color::rgba(0.3, 0.5, 0.9, 0.8).into(),
+                    ..Default::default()
+                })
+                .insert(TimelineProgress);
+
+            // a tick for each marker, positioned by its time
+            for marker in replay.recording.markers.iter() {
+                let ratio = if duration > 0.0 {
+                    marker.time / duration
+                } else {
+                    0.0
+                };
+                parent.spawn_bundle(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(MARKER_WIDTH), Val::Percent(100.0)),
+                        position_type: PositionType::Absolute,
+                        position: Rect {
+                            left: Val::Percent(ratio as f32 * 100.0),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    },
+                    color: Color::YELLOW.into(),
+                    ..Default::default()
+                });
+            }
+        });
+
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(8.0),
+                    bottom: Val::Px(28.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraMono-Medium.ttf"),
+                    font_size: 18.0,
+                    color: Color::WHITE,
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(TimelineText);
+}
+
+/// Jump to the clicked position of the timeline, which spans the width of the window
+fn scrub_timeline(
+    windows: Res<Windows>,
+    query: Query<&Interaction, With<Timeline>>,
+    mut replay: ResMut<Replay>,
+    mut parsers: ResMut<ParserRegistry>,
+) {
+    let clicked = query.iter().any(|i| *i == Interaction::Clicked);
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    if let (true, Some(cursor)) = (clicked, window.cursor_position()) {
+        let time = replay.recording.duration() * (cursor.x / window.width()) as f64;
+        replay.seek(time);
+        parsers.reset();
+    }
+}
+
+fn update_timeline(
+    replay: Res<Replay>,
+    locale: Res<Locale>,
+    mut progress: Query<&mut Style, With<TimelineProgress>>,
+    mut text: Query<&mut Text, With<TimelineText>>,
+) {
+    let duration = replay.recording.duration();
+    let ratio = if duration > 0.0 {
+        replay.time / duration
+    } else {
+        0.0
+    };
+    for mut style in progress.iter_mut() {
+        style.size.width = Val::Percent(ratio as f32 * 100.0);
+    }
+    // show the last marker passed
+    let marker = replay
+        .recording
+        .markers
+        .iter()
+        .take_while(|m| m.time <= replay.time)
+        .last()
+        .map(|m| format!("  [{:.1}s {}]", m.time, m.name))
+        .unwrap_or_default();
+    for mut text in text.iter_mut() {
+        text.sections[0].value = format!(
+            "{:6.1}s / {:.1}s  x{}{}{}",
+            replay.time,
+            duration,
+            replay.speed,
+            if replay.paused {
+                format!("  {}", locale.text("paused"))
+            } else {
+                String::new()
+            },
+            marker
+        );
+    }
+}
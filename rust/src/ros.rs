@@ -0,0 +1,158 @@
+use bevy::prelude::*;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tungstenite::Message;
+
+use crate::frame::{Frame, Frames};
+use crate::orientation::Orientation;
+use crate::parser::{Packet, PacketEvent};
+use crate::units::STANDARD_GRAVITY;
+
+/// Seconds between attempts to connect to rosbridge
+const RETRY_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Settings of the rosbridge connection
+#[derive(Debug, Clone)]
+pub struct RosSettings {
+    /// Websocket of rosbridge, e.g. `ws://localhost:9090`
+    pub url: String,
+    pub topic: String,
+    /// `frame_id` of the messages
+    pub frame_id: String,
+}
+
+impl Default for RosSettings {
+    fn default() -> Self {
+        RosSettings {
+            url: "ws://localhost:9090".to_string(),
+            topic: "/imu".to_string(),
+            frame_id: "imu_link".to_string(),
+        }
+    }
+}
+
+/// Publish the packets parsed from the serial data as ROS 2 `sensor_msgs/msg/Imu` through the rosbridge websocket.
+/// The orientation is the quaternion fused on the device if the packets have it, or else the roll and pitch
+/// estimated from gravity (the yaw is always 0), in the frame of the settings (ROS expects `enu`),
+/// and the angular velocity is marked as unknown if the packet format has no gyro.
+/// The messages are sent by a background thread, which reconnects while rosbridge is unreachable.
+pub struct RosBridgePlugin {
+    pub settings: RosSettings,
+}
+
+/// JSON messages for the rosbridge thread
+struct RosBridge(Mutex<Sender<String>>);
+
+impl Plugin for RosBridgePlugin {
+    fn build(&self, app: &mut App) {
+        let (sender, receiver) = mpsc::channel();
+        let settings = self.settings.clone();
+        thread::spawn(move || publish(settings, receiver));
+        app.insert_resource(RosBridge(Mutex::new(sender)))
+            .insert_resource(self.settings.clone())
+            .add_system(publish_packets);
+    }
+}
+
+fn publish_packets(
+    mut ev_packet: EventReader<PacketEvent>,
+    bridge: Res<RosBridge>,
+    settings: Res<RosSettings>,
+    frames: Res<Frames>,
+) {
+    let stamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let sender = match bridge.0.lock() {
+        Ok(sender) => sender,
+        Err(_) => return,
+    };
+    for PacketEvent(packet, _) in ev_packet.iter() {
+        let message = format!(
+            r#"{{"op":"publish","topic":"{}","msg":{}}}"#,
+            settings.topic,
+            imu_message(packet, frames.frame, stamp, &settings.frame_id)
+        );
+        if sender.send(message).is_err() {
+            return;
+        }
+    }
+}
+
+/// `sensor_msgs/msg/Imu` in JSON
+fn imu_message(packet: &Packet, frame: Frame, stamp: Duration, frame_id: &str) -> String {
+    let rotation = packet
+        .quat
+        .unwrap_or_else(|| Orientation::from_accel(packet.accel).quat());
+    let q = frame.from_nwu(rotation);
+    let accel = packet.accel * STANDARD_GRAVITY;
+    // a covariance starting with -1 marks the value as unknown, all zeros as unknown covariance
+    let (gyro, gyro_covariance) = match packet.gyro {
+        Some(gyro) => (gyro * std::f32::consts::PI / 180.0, 0.0),
+        None => (Vec3::ZERO, -1.0),
+    };
+    format!(
+        concat!(
+            r#"{{"header":{{"stamp":{{"sec":{},"nanosec":{}}},"frame_id":"{}"}},"#,
+            r#""orientation":{{"x":{},"y":{},"z":{},"w":{}}},"#,
+            r#""orientation_covariance":[0,0,0,0,0,0,0,0,0],"#,
+            r#""angular_velocity":{{"x":{},"y":{},"z":{}}},"#,
+            r#""angular_velocity_covariance":[{},0,0,0,0,0,0,0,0],"#,
+            r#""linear_acceleration":{{"x":{},"y":{},"z":{}}},"#,
+            r#""linear_acceleration_covariance":[0,0,0,0,0,0,0,0,0]}}"#
+        ),
+        stamp.as_secs(),
+        stamp.subsec_nanos(),
+        frame_id,
+        q.x,
+        q.y,
+        q.z,
+        q.w,
+        gyro.x,
+        gyro.y,
+        gyro.z,
+        gyro_covariance,
+        accel.x,
+        accel.y,
+        accel.z,
+    )
+}
+
+/// Connect to rosbridge, advertise the topic and send the messages, reconnecting when the connection fails.
+/// Messages are dropped while disconnected.
+fn publish(settings: RosSettings, receiver: Receiver<String>) {
+    let advertise = format!(
+        r#"{{"op":"advertise","topic":"{}","type":"sensor_msgs/msg/Imu"}}"#,
+        settings.topic
+    );
+    loop {
+        let mut socket = match tungstenite::connect(settings.url.as_str()) {
+            Ok((socket, _)) => socket,
+            Err(e) => {
+                warn!("failed to connect to rosbridge {}: {}", settings.url, e);
+                thread::sleep(RETRY_INTERVAL);
+                // drop the messages of the wait
+                receiver.try_iter().for_each(drop);
+                continue;
+            }
+        };
+        info!("publishing {} to rosbridge {}", settings.topic, settings.url);
+
+        let mut result = socket.write_message(Message::Text(advertise.clone()));
+        while result.is_ok() {
+            match receiver.recv() {
+                Ok(message) => result = socket.write_message(Message::Text(message)),
+                // the app has stopped
+                Err(_) => {
+                    let _ = socket.close(None);
+                    return;
+                }
+            }
+        }
+        if let Err(e) = result {
+            warn!("disconnected from rosbridge {}: {}", settings.url, e);
+        }
+    }
+}
@@ -0,0 +1,400 @@
+use bevy::core_pipeline::node::MAIN_PASS_DRIVER;
+use bevy::prelude::*;
+use bevy::render::camera::ExtractedCamera;
+use bevy::render::render_graph::{Node, NodeRunError, RenderGraph, RenderGraphContext};
+use bevy::render::render_resource::{Buffer, Texture, TextureView};
+use bevy::render::renderer::{RenderContext, RenderDevice};
+use bevy::render::texture::BevyDefault;
+use bevy::render::view::{ExtractedWindows, ViewTarget};
+use bevy::render::{RenderApp, RenderStage, RenderWorld};
+use bevy::ui::node::UI_PASS_DRIVER;
+use bevy::window::WindowId;
+use gif::{Encoder, Frame, Repeat};
+use image::RgbaImage;
+use std::fs::File;
+use std::io::BufWriter;
+use std::num::NonZeroU32;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Mutex, PoisonError};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+use wgpu::{
+    BufferDescriptor, BufferUsages, Extent3d, ImageCopyBuffer, ImageCopyTexture, ImageDataLayout,
+    Maintain, MapMode, Origin3d, TextureAspect, TextureDescriptor, TextureDimension, TextureFormat,
+    TextureUsages, TextureViewDescriptor,
+};
+
+/// Render graph node copying the frame of the primary window to a buffer
+const SCREENSHOT_NODE: &str = "screenshot";
+/// Bytes per pixel of `TextureFormat::bevy_default()`
+const PIXEL_BYTES: u32 = 4;
+/// Seconds between the frames of a clip (10 frames per second)
+const CLIP_FRAME_INTERVAL: f64 = 0.1;
+/// Speed of the color quantization of the clip frames, from 1 (best colors) to 30 (fastest)
+const CLIP_QUANTIZE_SPEED: i32 = 20;
+
+/// Save the frame of the primary window with `F12` as `screenshot-<unix time>.png` in the current directory,
+/// and record `clip-<unix time>.gif` at 10 frames per second while `F11` is held.
+/// Bevy 0.6 can't read back the window surface, so the cameras of the window render the frame to a texture instead,
+/// and the window skips that frame. The inspector of `--tune` draws to the window directly and is not captured.
+pub struct ScreenshotPlugin;
+
+impl Plugin for ScreenshotPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ScreenshotRequest>()
+            .init_resource::<ClipRequest>()
+            .add_system(request_screenshot)
+            .add_system(request_clip_frames);
+
+        let render_app = app.sub_app_mut(RenderApp);
+        render_app
+            .init_resource::<Capture>()
+            .add_system_to_stage(RenderStage::Extract, extract_request)
+            // after the view targets of the window are prepared
+            .add_system_to_stage(RenderStage::Queue, redirect_views)
+            // after the frame is submitted
+            .add_system_to_stage(RenderStage::Cleanup, save_screenshot);
+
+        let mut graph = render_app.world.get_resource_mut::<RenderGraph>().unwrap();
+        graph.add_node(SCREENSHOT_NODE, ScreenshotNode);
+        graph
+            .add_node_edge(MAIN_PASS_DRIVER, SCREENSHOT_NODE)
+            .unwrap();
+        graph
+            .add_node_edge(UI_PASS_DRIVER, SCREENSHOT_NODE)
+            .unwrap();
+    }
+}
+
+/// Path of the screenshot requested in the app, taken in the next frame
+#[derive(Default)]
+struct ScreenshotRequest(Option<String>);
+
+/// Clip recorded in the app while the key is held
+#[derive(Default)]
+struct ClipRequest {
+    /// Path of the clip to start in the next frame
+    start: Option<String>,
+    /// Time of the frame to capture in the next frame
+    frame: Option<f64>,
+    /// The key was released, finish the clip after the frames captured
+    finish: bool,
+    /// Time of the last frame requested, while recording
+    last_frame: Option<f64>,
+}
+
+/// Screenshot and clip frame being taken in the render world
+#[derive(Default)]
+struct Capture {
+    /// Path of the screenshot requested, until the frame is redirected
+    path: Option<String>,
+    /// Time of the clip frame requested, until the frame is redirected
+    clip_time: Option<f64>,
+    frame: Option<CaptureFrame>,
+    /// Frames for the encoder thread of the clip being recorded
+    clip: Option<Mutex<Sender<ClipFrame>>>,
+    /// Finish the clip once the frame of this frame is read back
+    finish_clip: bool,
+}
+
+/// Frame of a clip read back, with its time in seconds
+struct ClipFrame {
+    image: RgbaImage,
+    time: f64,
+}
+
+/// Texture the cameras of the primary window render to in this frame, and the buffer it is copied to
+struct CaptureFrame {
+    /// Path of the screenshot, if one was requested
+    path: Option<String>,
+    /// Time of the clip frame, if one was requested
+    clip_time: Option<f64>,
+    texture: Texture,
+    buffer: Buffer,
+    width: u32,
+    height: u32,
+    /// Bytes of a row in the buffer, padded to `COPY_BYTES_PER_ROW_ALIGNMENT`
+    row_bytes: u32,
+}
+
+fn request_screenshot(keys: Res<Input<KeyCode>>, mut request: ResMut<ScreenshotRequest>) {
+    if keys.just_pressed(KeyCode::F12) {
+        let stamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        request.0 = Some(format!("screenshot-{}.png", stamp));
+    }
+}
+
+fn request_clip_frames(
+    keys: Res<Input<KeyCode>>,
+    time: Res<Time>,
+    mut request: ResMut<ClipRequest>,
+) {
+    let now = time.seconds_since_startup();
+    if keys.just_pressed(KeyCode::F11) {
+        let stamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        request.start = Some(format!("clip-{}.gif", stamp));
+        request.last_frame = None;
+    }
+    if keys.pressed(KeyCode::F11) {
+        let due = request
+            .last_frame
+            .map_or(true, |last| now - last >= CLIP_FRAME_INTERVAL);
+        if due {
+            request.frame = Some(now);
+            request.last_frame = Some(now);
+        }
+    }
+    if keys.just_released(KeyCode::F11) {
+        request.finish = true;
+    }
+}
+
+fn extract_request(
+    mut render_world: ResMut<RenderWorld>,
+    mut request: ResMut<ScreenshotRequest>,
+    mut clip_request: ResMut<ClipRequest>,
+) {
+    let mut capture = render_world.get_resource_mut::<Capture>().unwrap();
+    if let Some(path) = request.0.take() {
+        capture.path = Some(path);
+    }
+    if let Some(path) = clip_request.start.take() {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || encode_clip(path, receiver));
+        // a clip still recording is finished by dropping its sender
+        capture.clip = Some(Mutex::new(sender));
+    }
+    if let Some(time) = clip_request.frame.take() {
+        capture.clip_time = Some(time);
+    }
+    if clip_request.finish {
+        clip_request.finish = false;
+        capture.finish_clip = true;
+    }
+}
+
+/// Render the views of the primary window to a texture which can be copied, instead of the window
+fn redirect_views(
+    mut capture: ResMut<Capture>,
+    windows: Res<ExtractedWindows>,
+    render_device: Res<RenderDevice>,
+    mut views: Query<(&ExtractedCamera, &mut ViewTarget)>,
+) {
+    let path = capture.path.take();
+    let clip_time = capture.clip_time.take();
+    if path.is_none() && clip_time.is_none() {
+        return;
+    }
+    let window = match windows.get(&WindowId::primary()) {
+        Some(window) => window,
+        None => {
+            warn!("no window to capture");
+            return;
+        }
+    };
+    let (width, height) = (window.physical_width, window.physical_height);
+    let texture = render_device.create_texture(&TextureDescriptor {
+        label: Some("screenshot_texture"),
+        size: Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format: TextureFormat::bevy_default(),
+        usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+    });
+    let view = TextureView::from(texture.create_view(&TextureViewDescriptor::default()));
+    for (camera, mut target) in views.iter_mut() {
+        if camera.window_id == WindowId::primary() {
+            target.view = view.clone();
+        }
+    }
+
+    let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    let row_bytes = (width * PIXEL_BYTES + align - 1) / align * align;
+    let buffer = render_device.create_buffer(&BufferDescriptor {
+        label: Some("screenshot_buffer"),
+        size: (row_bytes * height) as u64,
+        usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    capture.frame = Some(CaptureFrame {
+        path,
+        clip_time,
+        texture,
+        buffer,
+        width,
+        height,
+        row_bytes,
+    });
+}
+
+struct ScreenshotNode;
+
+impl Node for ScreenshotNode {
+    fn run(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let frame = match &world.get_resource::<Capture>().unwrap().frame {
+            Some(frame) => frame,
+            None => return Ok(()),
+        };
+        render_context.command_encoder.copy_texture_to_buffer(
+            ImageCopyTexture {
+                texture: &frame.texture,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+                aspect: TextureAspect::All,
+            },
+            ImageCopyBuffer {
+                buffer: &frame.buffer,
+                layout: ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(frame.row_bytes),
+                    rows_per_image: None,
+                },
+            },
+            Extent3d {
+                width: frame.width,
+                height: frame.height,
+                depth_or_array_layers: 1,
+            },
+        );
+        Ok(())
+    }
+}
+
+/// Read the frame back once it is rendered, save it as PNG and pass it to the clip being recorded
+fn save_screenshot(mut capture: ResMut<Capture>, render_device: Res<RenderDevice>) {
+    if let Some(frame) = capture.frame.take() {
+        if let Some(image) = read_back(&frame, &render_device) {
+            if let Some(path) = &frame.path {
+                match image.save(path) {
+                    Ok(()) => info!("saved screenshot to {}", path),
+                    Err(e) => error!("failed to save {}: {}", path, e),
+                }
+            }
+            if let (Some(time), Some(clip)) = (frame.clip_time, &mut capture.clip) {
+                let sender = clip.get_mut().unwrap_or_else(PoisonError::into_inner);
+                if sender.send(ClipFrame { image, time }).is_err() {
+                    // the encoder thread failed and logged why
+                    capture.clip = None;
+                }
+            }
+        }
+    }
+    if capture.finish_clip {
+        capture.finish_clip = false;
+        // the encoder thread writes the last frame and the end of the GIF once the sender is dropped
+        capture.clip = None;
+    }
+}
+
+fn read_back(frame: &CaptureFrame, render_device: &RenderDevice) -> Option<RgbaImage> {
+    let slice = frame.buffer.slice(..);
+    let mapping = slice.map_async(MapMode::Read);
+    render_device.wgpu_device().poll(Maintain::Wait);
+    if let Err(e) = futures_lite::future::block_on(mapping) {
+        error!("failed to read back the frame: {:?}", e);
+        return None;
+    }
+    let mut pixels = Vec::with_capacity((frame.width * frame.height * PIXEL_BYTES) as usize);
+    for row in slice.get_mapped_range().chunks(frame.row_bytes as usize) {
+        pixels.extend_from_slice(&row[..(frame.width * PIXEL_BYTES) as usize]);
+    }
+    frame.buffer.unmap();
+    // the window is BGRA on most platforms
+    if matches!(
+        TextureFormat::bevy_default(),
+        TextureFormat::Bgra8UnormSrgb | TextureFormat::Bgra8Unorm
+    ) {
+        for pixel in pixels.chunks_mut(PIXEL_BYTES as usize) {
+            pixel.swap(0, 2);
+        }
+    }
+
+    let image = RgbaImage::from_raw(frame.width, frame.height, pixels)
+        .expect("the frame has a pixel for each of its width and height");
+    Some(image)
+}
+
+/// Encode the frames of a clip as a looping GIF until the sender is dropped.
+/// Each frame is shown until the time of the next one, and frames of another size than the first
+/// (the window was resized) are skipped.
+fn encode_clip(path: String, receiver: Receiver<ClipFrame>) {
+    let mut frames = receiver.iter();
+    let mut previous = match frames.next() {
+        Some(frame) => frame,
+        None => return,
+    };
+    let (width, height) = previous.image.dimensions();
+    let size = match (u16::try_from(width), u16::try_from(height)) {
+        (Ok(width), Ok(height)) => (width, height),
+        _ => {
+            error!("the window is too large for {}", path);
+            return;
+        }
+    };
+    let mut encoder = match File::create(&path)
+        .map_err(gif::EncodingError::from)
+        .and_then(|file| Encoder::new(BufWriter::new(file), size.0, size.1, &[]))
+    {
+        Ok(encoder) => encoder,
+        Err(e) => {
+            error!("failed to create {}: {}", path, e);
+            return;
+        }
+    };
+
+    let mut result = encoder.set_repeat(Repeat::Infinite);
+    let mut count = 0;
+    for next in frames {
+        if result.is_err() {
+            break;
+        }
+        if next.image.dimensions() != (width, height) {
+            continue;
+        }
+        result = write_clip_frame(
+            &mut encoder,
+            size,
+            previous.image,
+            next.time - previous.time,
+        );
+        count += 1;
+        previous = next;
+    }
+    if result.is_ok() {
+        result = write_clip_frame(&mut encoder, size, previous.image, CLIP_FRAME_INTERVAL);
+        count += 1;
+    }
+    match result {
+        Ok(()) => info!("saved clip of {} frames to {}", count, path),
+        Err(e) => error!("failed to write {}: {}", path, e),
+    }
+}
+
+fn write_clip_frame(
+    encoder: &mut Encoder<BufWriter<File>>,
+    (width, height): (u16, u16),
+    image: RgbaImage,
+    seconds: f64,
+) -> Result<(), gif::EncodingError> {
+    let mut pixels = image.into_raw();
+    let mut frame = Frame::from_rgba_speed(width, height, &mut pixels, CLIP_QUANTIZE_SPEED);
+    // in hundredths of a second
+    frame.delay = (seconds * 100.0).round().clamp(1.0, u16::MAX as f64) as u16;
+    encoder.write_frame(&frame)
+}
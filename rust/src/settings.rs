@@ -0,0 +1,175 @@
+use bevy::log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::alarms::Alarm;
+use crate::axes::AxisMapping;
+use crate::calibration::MagCalibration;
+use crate::frame::Frame;
+use crate::fusion::DEFAULT_BETA;
+use crate::locale::Language;
+use crate::orientation::OrientationSource;
+use crate::plot::{LowPass, PlotLayout};
+use crate::smoothing::Smoothing;
+use crate::theme::ThemeName;
+use crate::units::SensorUnits;
+use crate::windows::WindowLayout;
+
+/// File read at startup if no other file is given with `--settings`
+pub const DEFAULT_SETTINGS_PATH: &str = "settings.toml";
+
+/// Settings kept between runs in a TOML file like
+///
+/// ```toml
+/// language = "es"
+/// theme = "color_blind"
+/// orientation = "device"
+/// frame = "enu"
+/// baud_rate = 230400
+/// axes = "y,-x,z"
+/// alarms = ["accel:2.5", "tilt:30:5"]
+///
+/// [sensor]
+/// range = "8g"
+/// unit = "m/s2"
+///
+/// [magnetometer]
+/// offset = [12.5, -3.0, 40.2]
+/// scale = [1.02, 0.97, 1.01]
+///
+/// [filter]
+/// cutoff = 2.0
+/// beta = 0.05
+///
+/// [smoothing]
+/// mode = "damped"
+/// time_constant = 0.15
+///
+/// [windows]
+/// split = true
+///
+/// [windows.main]
+/// position = [0, 0]
+/// size = [1280.0, 720.0]
+///
+/// [windows.scene]
+/// position = [1920, 0]
+/// size = [1920.0, 1080.0]
+///
+/// [devices."/dev/ttyUSB0"]
+/// orientation = "host"
+/// frame = "ned"
+///
+/// [[plots]]
+/// channels = ["x", "y", "z"]
+///
+/// [[plots]]
+/// channels = ["roll", "pitch", "magnitude"]
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Language of the viewer, before the tables as TOML requires
+    pub language: Language,
+    pub theme: ThemeName,
+    /// Whether the quaternion of devices sending one is used, unless the device has its own setting
+    pub orientation: OrientationSource,
+    /// Frame of the rotations written out, and of the quaternions of devices without their own frame
+    pub frame: Frame,
+    /// Baud rate of the serial port, 115200 if unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub baud_rate: Option<u32>,
+    /// Axis mapping, unless given with `--axes`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub axes: Option<AxisMapping>,
+    /// Alarms like `--alarm`, unless some are given with it, or the default alarms if empty
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub alarms: Vec<Alarm>,
+    pub sensor: SensorUnits,
+    /// Hard and soft-iron calibration, saved by the calibration wizard (N)
+    pub magnetometer: MagCalibration,
+    pub filter: FilterSettings,
+    /// Smoothing of the rotation of the model
+    pub smoothing: Smoothing,
+    /// Positions and sizes of the windows, and whether the 3D view has its own
+    pub windows: WindowLayout,
+    /// Settings of each device by the label of its port, changed in the viewer
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub devices: BTreeMap<String, DeviceSettings>,
+    /// Plots pinned from right to left, or one plot of x, y and z if empty
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub plots: Vec<PlotLayout>,
+}
+
+/// Settings of one device
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeviceSettings {
+    /// Whether its quaternion or the filter of the viewer is used (O)
+    pub orientation: OrientationSource,
+    /// Frame of its quaternion, if it isn't the frame of the viewer
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frame: Option<Frame>,
+}
+
+/// Parameters of the filters, which `--tune` changes live
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FilterSettings {
+    /// Cutoff of the low-pass filter of the filtered plots in Hz
+    pub cutoff: f32,
+    /// Gain of the correction of the Madgwick filter in rad/s
+    pub beta: f32,
+}
+
+impl Default for FilterSettings {
+    fn default() -> Self {
+        FilterSettings {
+            cutoff: LowPass::default().cutoff,
+            beta: DEFAULT_BETA,
+        }
+    }
+}
+
+impl Settings {
+    pub fn load(path: &Path) -> io::Result<Settings> {
+        let text = fs::read_to_string(path)?;
+        toml::from_str(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Read the settings, writing the defaults if the file doesn't exist yet so that it can be edited
+    pub fn load_or_create(path: &Path) -> io::Result<Settings> {
+        match Settings::load(path) {
+            Ok(settings) => Ok(settings),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                let settings = Settings::default();
+                settings.save(path)?;
+                Ok(settings)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let text =
+            toml::to_string(self).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        fs::write(path, text)
+    }
+}
+
+/// Resource of the settings file, to save the settings changed in the viewer
+pub struct SettingsFile {
+    pub path: PathBuf,
+    pub settings: Settings,
+}
+
+impl SettingsFile {
+    pub fn save(&self) {
+        if let Err(e) = self.settings.save(&self.path) {
+            warn!("failed to save settings {}: {}", self.path.display(), e);
+        }
+    }
+}
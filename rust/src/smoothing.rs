@@ -0,0 +1,163 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::orientation::Orientation;
+
+/// How the rotation of the model follows the orientation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SmoothingMode {
+    /// The model has the rotation of the orientation
+    #[default]
+    Off,
+    /// The model turns towards the orientation by a share of the angle left each frame,
+    /// so it lags by about the time constant
+    Slerp,
+    /// The model turns like a critically damped spring, which starts and stops turning gently, filters out more
+    /// of the noise than `Slerp` for the same lag, and never overshoots
+    Damped,
+}
+
+impl fmt::Display for SmoothingMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            SmoothingMode::Off => "off",
+            SmoothingMode::Slerp => "slerp",
+            SmoothingMode::Damped => "damped",
+        })
+    }
+}
+
+/// Parsed from `off`, `slerp` or `damped`
+impl FromStr for SmoothingMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(SmoothingMode::Off),
+            "slerp" => Ok(SmoothingMode::Slerp),
+            "damped" => Ok(SmoothingMode::Damped),
+            _ => Err(format!(
+                "unknown smoothing {}, choose from off, slerp or damped",
+                s
+            )),
+        }
+    }
+}
+
+/// Smoothing between the orientation and the model, for noisy or slow sensors
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Smoothing {
+    pub mode: SmoothingMode,
+    /// Seconds for the model to catch up with the orientation, longer is smoother but lags more
+    pub time_constant: f32,
+}
+
+impl Default for Smoothing {
+    fn default() -> Self {
+        Smoothing {
+            mode: SmoothingMode::Off,
+            time_constant: 0.1,
+        }
+    }
+}
+
+/// Rotation of the model in the world of the scene, following the orientation with the smoothing
+#[derive(Debug, Clone, Copy)]
+pub struct SmoothedRotation {
+    pub rotation: Quat,
+    /// Angular velocity of the damped spring in rad/s, about the world axes
+    velocity: Vec3,
+}
+
+impl Default for SmoothedRotation {
+    fn default() -> Self {
+        SmoothedRotation {
+            rotation: Quat::IDENTITY,
+            velocity: Vec3::ZERO,
+        }
+    }
+}
+
+impl SmoothedRotation {
+    /// Follow `target` for `dt` seconds
+    pub fn update(&mut self, target: Quat, smoothing: &Smoothing, dt: f32) {
+        let tau = smoothing.time_constant;
+        let mode = if tau > 0.0 {
+            smoothing.mode
+        } else {
+            SmoothingMode::Off
+        };
+        // q and -q are the same rotation, the one closer to the current rotation takes the short way
+        let target = if self.rotation.dot(target) < 0.0 {
+            -target
+        } else {
+            target
+        };
+        match mode {
+            SmoothingMode::Off => {
+                self.rotation = target;
+                self.velocity = Vec3::ZERO;
+            }
+            SmoothingMode::Slerp => {
+                // exponential decay of the angle left, the same whatever the frame rate
+                let share = 1.0 - (-dt / tau).exp();
+                self.rotation = self.rotation.slerp(target, share).normalize();
+                self.velocity = Vec3::ZERO;
+            }
+            SmoothingMode::Damped => {
+                // critically damped spring on the angle from the target (Game Programming Gems 4, 1.10),
+                // stable for any step
+                let omega = 2.0 / tau;
+                let x = omega * dt;
+                let decay = 1.0 / (1.0 + x + 0.48 * x * x + 0.235 * x * x * x);
+                let offset = scaled_axis(self.rotation * target.inverse());
+                let change = (self.velocity + omega * offset) * dt;
+                self.velocity = (self.velocity - omega * change) * decay;
+                self.rotation =
+                    (Quat::from_scaled_axis((offset + change) * decay) * target).normalize();
+            }
+        }
+    }
+}
+
+/// Axis of the rotation scaled by its angle in radians, which is at most π
+fn scaled_axis(rotation: Quat) -> Vec3 {
+    let rotation = if rotation.w < 0.0 {
+        -rotation
+    } else {
+        rotation
+    };
+    let (axis, angle) = rotation.to_axis_angle();
+    axis * angle
+}
+
+/// Smooth the rotation of the model with the mode and time constant of the settings
+pub struct SmoothingPlugin {
+    pub smoothing: Smoothing,
+}
+
+impl Plugin for SmoothingPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.smoothing)
+            .init_resource::<SmoothedRotation>()
+            .add_system(smooth_rotation);
+    }
+}
+
+/// Runs every frame rather than with the model, so the rotation is as smooth as the frame rate allows
+fn smooth_rotation(
+    orientation: Res<Orientation>,
+    smoothing: Res<Smoothing>,
+    time: Res<Time>,
+    mut smoothed: ResMut<SmoothedRotation>,
+) {
+    smoothed.update(
+        orientation.world_rotation(),
+        &smoothing,
+        time.delta_seconds(),
+    );
+}
@@ -0,0 +1,282 @@
+use bevy::prelude::*;
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+
+use crate::locale::Locale;
+use crate::plot::AccelSampleEvent;
+use crate::theme::Theme;
+
+/// Number of samples of one FFT, a power of two
+const FFT_SIZE: usize = 256;
+/// Size of the spectrum panel in pixels, below the plot, the export button and the format selector
+const PANEL_WIDTH: f32 = 320.0;
+const PANEL_HEIGHT: f32 = 120.0;
+const BAR_COUNT: usize = 64;
+
+/// Spectrum of the acceleration magnitude over the last `FFT_SIZE` samples, for vibration diagnostics
+#[derive(Debug, Default)]
+pub struct Spectrum {
+    /// Amplitude of each frequency bin in g, from `bin_hz` up to the Nyquist frequency
+    pub amplitudes: Vec<f32>,
+    /// Width of one bin in Hz
+    pub bin_hz: f32,
+    /// Frequency of the largest amplitude in Hz
+    pub peak_hz: Option<f32>,
+}
+
+/// Whether the spectrum is computed and shown (toggled with F)
+#[derive(Default)]
+pub struct VibrationMode {
+    pub enabled: bool,
+}
+
+/// Magnitudes of the acceleration with the time they arrived
+#[derive(Default)]
+struct MagnitudeHistory(VecDeque<(f64, f32)>);
+
+/// Rolling FFT of the acceleration magnitude, shown as bars below the plot in vibration mode
+pub struct SpectrumPlugin;
+
+impl Plugin for SpectrumPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Spectrum>()
+            .init_resource::<VibrationMode>()
+            .init_resource::<MagnitudeHistory>()
+            .add_startup_system(setup_spectrum)
+            .add_system(toggle_mode)
+            .add_system(update_spectrum)
+            .add_system(show_spectrum)
+            .add_system(color_spectrum);
+    }
+}
+
+#[derive(Component)]
+struct SpectrumPanel;
+
+#[derive(Component)]
+struct SpectrumBar(usize);
+
+#[derive(Component)]
+struct PeakText;
+
+fn setup_spectrum(mut commands: Commands, asset_server: Res<AssetServer>, theme: Res<Theme>) {
+    let bar_width = PANEL_WIDTH / BAR_COUNT as f32;
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Px(PANEL_WIDTH), Val::Px(PANEL_HEIGHT)),
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    right: Val::Px(8.0),
+                    top: Val::Px(240.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            color: theme.panel.into(),
+            visibility: Visibility { is_visible: false },
+            ..Default::default()
+        })
+        .insert(SpectrumPanel)
+        .with_children(|parent| {
+            for i in 0..BAR_COUNT {
+                parent
+                    .spawn_bundle(NodeBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(bar_width - 1.0), Val::Px(0.0)),
+                            position_type: PositionType::Absolute,
+                            position: Rect {
+                                left: Val::Px(i as f32 * bar_width),
+                                bottom: Val::Px(0.0),
+                                ..Default::default()
+                            },
+                            ..Default::default()
+                        },
+                        color: theme.spectrum.into(),
+                        visibility: Visibility { is_visible: false },
+                        ..Default::default()
+                    })
+                    .insert(SpectrumBar(i));
+            }
+            parent
+                .spawn_bundle(TextBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        position: Rect {
+                            left: Val::Px(4.0),
+                            top: Val::Px(4.0),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    },
+                    text: Text::with_section(
+                        "",
+                        TextStyle {
+                            font: asset_server.load("fonts/FiraMono-Medium.ttf"),
+                            font_size: 14.0,
+                            color: Color::WHITE,
+                        },
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                })
+                .insert(PeakText);
+        });
+}
+
+fn toggle_mode(keys: Res<Input<KeyCode>>, mut mode: ResMut<VibrationMode>) {
+    if keys.just_pressed(KeyCode::F) {
+        mode.enabled = !mode.enabled;
+        info!("vibration mode {}", if mode.enabled { "on" } else { "off" });
+    }
+}
+
+fn update_spectrum(
+    mut ev_sample: EventReader<AccelSampleEvent>,
+    mut history: ResMut<MagnitudeHistory>,
+    mut spectrum: ResMut<Spectrum>,
+    mode: Res<VibrationMode>,
+    time: Res<Time>,
+) {
+    let now = time.seconds_since_startup();
+    for AccelSampleEvent(accel) in ev_sample.iter() {
+        history.0.push_back((now, accel.length()));
+    }
+    while history.0.len() > FFT_SIZE {
+        history.0.pop_front();
+    }
+    if !mode.enabled || history.0.len() < FFT_SIZE {
+        return;
+    }
+
+    // samples arrive in batches per frame, so the rate is averaged over the window
+    let duration = history.0.back().unwrap().0 - history.0.front().unwrap().0;
+    if duration <= 0.0 {
+        return;
+    }
+    let sample_rate = (FFT_SIZE - 1) as f32 / duration as f32;
+
+    // remove the gravity (DC) and apply the Hann window
+    let mean = history.0.iter().map(|(_, m)| m).sum::<f32>() / FFT_SIZE as f32;
+    let mut re: Vec<f32> = history
+        .0
+        .iter()
+        .enumerate()
+        .map(|(i, (_, m))| {
+            let window = 0.5 - 0.5 * (2.0 * PI * i as f32 / (FFT_SIZE - 1) as f32).cos();
+            (m - mean) * window
+        })
+        .collect();
+    let mut im = vec![0.0; FFT_SIZE];
+    fft(&mut re, &mut im);
+
+    // amplitude of a sine, compensating the window gain of 0.5
+    spectrum.amplitudes = (1..FFT_SIZE / 2)
+        .map(|k| 4.0 * (re[k] * re[k] + im[k] * im[k]).sqrt() / FFT_SIZE as f32)
+        .collect();
+    spectrum.bin_hz = sample_rate / FFT_SIZE as f32;
+    spectrum.peak_hz = spectrum
+        .amplitudes
+        .iter()
+        .enumerate()
+        .max_by(|a, b| a.1.total_cmp(b.1))
+        .map(|(i, _)| (i + 1) as f32 * spectrum.bin_hz);
+}
+
+/// In-place radix-2 FFT, the length must be a power of two
+fn fft(re: &mut [f32], im: &mut [f32]) {
+    let n = re.len();
+    // bit reversal permutation
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * PI / len as f32;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (w_im, w_re) = (angle * k as f32).sin_cos();
+                let (a, b) = (start + k, start + k + len / 2);
+                let t_re = re[b] * w_re - im[b] * w_im;
+                let t_im = re[b] * w_im + im[b] * w_re;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+fn show_spectrum(
+    mode: Res<VibrationMode>,
+    spectrum: Res<Spectrum>,
+    locale: Res<Locale>,
+    mut panel: Query<&mut Visibility, With<SpectrumPanel>>,
+    mut bars: Query<(&SpectrumBar, &mut Style, &mut Visibility), Without<SpectrumPanel>>,
+    mut text: Query<&mut Text, With<PeakText>>,
+) {
+    // children are hidden separately because visibility is not inherited
+    for mut visibility in panel.iter_mut() {
+        visibility.is_visible = mode.enabled;
+    }
+
+    // bins are grouped into bars by their maximum
+    let per_bar = spectrum.amplitudes.len().div_ceil(BAR_COUNT);
+    let max = spectrum.amplitudes.iter().copied().fold(0.0, f32::max);
+    for (bar, mut style, mut visibility) in bars.iter_mut() {
+        visibility.is_visible = mode.enabled && max > 0.0;
+        let amplitude = spectrum
+            .amplitudes
+            .iter()
+            .skip(bar.0 * per_bar.max(1))
+            .take(per_bar.max(1))
+            .copied()
+            .fold(0.0, f32::max);
+        style.size.height = Val::Px(if max > 0.0 {
+            amplitude / max * (PANEL_HEIGHT - 24.0)
+        } else {
+            0.0
+        });
+    }
+    for mut text in text.iter_mut() {
+        text.sections[0].value = match spectrum.peak_hz {
+            _ if !mode.enabled => String::new(),
+            Some(peak) => locale.fill(
+                "peak",
+                &[
+                    &format!("{:.1}", peak),
+                    &format!("{:.0}", spectrum.bin_hz * FFT_SIZE as f32 / 2.0),
+                ],
+            ),
+            None => locale.fill("collecting", &[&FFT_SIZE]),
+        };
+    }
+}
+
+fn color_spectrum(
+    theme: Res<Theme>,
+    mut panel: Query<&mut UiColor, With<SpectrumPanel>>,
+    mut bars: Query<&mut UiColor, (With<SpectrumBar>, Without<SpectrumPanel>)>,
+) {
+    if !theme.is_changed() {
+        return;
+    }
+    for mut color in panel.iter_mut() {
+        *color = theme.panel.into();
+    }
+    for mut color in bars.iter_mut() {
+        *color = theme.spectrum.into();
+    }
+}
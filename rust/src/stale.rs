@@ -0,0 +1,98 @@
+use bevy::prelude::*;
+use bevy_serial::{SerialReadEvent, SerialStaleEvent};
+
+use crate::locale::Locale;
+use crate::theme::Theme;
+use crate::MyObject;
+
+/// Whether the sensor has stopped sending data
+#[derive(Default)]
+pub struct SensorStatus {
+    pub stale: bool,
+}
+
+/// Grey out the model and show "sensor not responding" while the port is stale
+pub struct StalePlugin;
+
+impl Plugin for StalePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SensorStatus>()
+            .add_startup_system(setup_stale_text)
+            .add_system(update_status)
+            .add_system(show_status);
+    }
+}
+
+#[derive(Component)]
+struct StaleText;
+
+fn setup_stale_text(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    locale: Res<Locale>,
+    theme: Res<Theme>,
+) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(8.0),
+                    top: Val::Px(8.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                locale.text("not_responding"),
+                TextStyle {
+                    font: asset_server.load("fonts/FiraMono-Medium.ttf"),
+                    font_size: 24.0,
+                    color: theme.error,
+                },
+                Default::default(),
+            ),
+            visibility: Visibility { is_visible: false },
+            ..Default::default()
+        })
+        .insert(StaleText);
+}
+
+fn update_status(
+    mut ev_stale: EventReader<SerialStaleEvent>,
+    mut ev_serial: EventReader<SerialReadEvent>,
+    mut status: ResMut<SensorStatus>,
+) {
+    for SerialStaleEvent(label, since) in ev_stale.iter() {
+        warn!("{} not responding for {:.1?}", label, since.elapsed());
+        status.stale = true;
+    }
+    if ev_serial.iter().any(|SerialReadEvent(_, buffer)| !buffer.is_empty()) {
+        status.stale = false;
+    }
+}
+
+fn show_status(
+    status: Res<SensorStatus>,
+    theme: Res<Theme>,
+    objects: Query<&Handle<StandardMaterial>, With<MyObject>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut text: Query<(&mut Text, &mut Visibility), With<StaleText>>,
+) {
+    if !status.is_changed() && !theme.is_changed() {
+        return;
+    }
+    for handle in objects.iter() {
+        if let Some(material) = materials.get_mut(handle) {
+            material.base_color = if status.stale {
+                theme.stale
+            } else {
+                theme.model
+            };
+        }
+    }
+    for (mut text, mut visibility) in text.iter_mut() {
+        text.sections[0].style.color = theme.error;
+        visibility.is_visible = status.stale;
+    }
+}
@@ -0,0 +1,170 @@
+use bevy::prelude::*;
+use std::io::{self, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use crate::frame::{Frame, Frames};
+use crate::parser::{Packet, PacketEvent};
+
+/// How often the server thread accepts clients while no samples arrive
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(100);
+/// Batches of messages queued for the server thread, about a second of frames;
+/// the batches of the frames after are dropped until the thread catches up
+const MAX_QUEUED_BATCHES: usize = 64;
+/// How long a write to a client may block before the client is dropped
+const WRITE_TIMEOUT: Duration = Duration::from_millis(250);
+
+/// Stream the packets parsed from the serial data to TCP clients of `addr`, as length-prefixed protobuf
+/// `Sample` messages defined in `proto/accel.proto`.
+/// The messages of each frame are sent to a background thread which writes them to the clients,
+/// so slow clients don't stall the app: a client which doesn't read for `WRITE_TIMEOUT` is dropped,
+/// and the batches are dropped while the thread is behind.
+pub struct StreamPlugin {
+    pub addr: String,
+}
+
+/// Messages of each frame for the server thread
+struct SampleStream(Mutex<SyncSender<Vec<u8>>>);
+
+impl Plugin for StreamPlugin {
+    fn build(&self, app: &mut App) {
+        let (sender, receiver) = mpsc::sync_channel(MAX_QUEUED_BATCHES);
+        let listener = TcpListener::bind(&self.addr).and_then(|listener| {
+            listener.set_nonblocking(true)?;
+            Ok(listener)
+        });
+        match listener {
+            Ok(listener) => {
+                info!("streaming samples on tcp://{}", self.addr);
+                thread::spawn(move || serve(listener, receiver));
+            }
+            Err(e) => error!("failed to stream samples on {}: {}", self.addr, e),
+        }
+        app.insert_resource(SampleStream(Mutex::new(sender)))
+            .add_system(stream_packets);
+    }
+}
+
+fn stream_packets(
+    mut ev_packet: EventReader<PacketEvent>,
+    stream: Res<SampleStream>,
+    time: Res<Time>,
+    frames: Res<Frames>,
+    mut dropped: Local<u64>,
+) {
+    let now = time.seconds_since_startup();
+    let mut batch = Vec::new();
+    for PacketEvent(packet, _) in ev_packet.iter() {
+        let message = encode_sample(now, packet, frames.frame);
+        write_varint(&mut batch, message.len() as u64);
+        batch.extend_from_slice(&message);
+    }
+    if batch.is_empty() {
+        return;
+    }
+    if let Ok(sender) = stream.0.lock() {
+        match sender.try_send(batch) {
+            Ok(()) => *dropped = 0,
+            Err(TrySendError::Full(_)) => {
+                if *dropped == 0 {
+                    warn!("the sample stream is behind, dropping samples");
+                }
+                *dropped += 1;
+            }
+            // the thread has stopped if the port couldn't be bound
+            Err(TrySendError::Disconnected(_)) => {}
+        }
+    }
+}
+
+/// Encode the `Sample` message, with the quaternion in `frame`
+fn encode_sample(time: f64, packet: &Packet, frame: Frame) -> Vec<u8> {
+    let mut message = Vec::with_capacity(48);
+    // wire type 1 is 64 bit, 5 is 32 bit
+    write_varint(&mut message, (1 << 3) | 1);
+    message.extend_from_slice(&time.to_le_bytes());
+    let mut float = |field: u64, value: f32| {
+        write_varint(&mut message, (field << 3) | 5);
+        message.extend_from_slice(&value.to_le_bytes());
+    };
+    float(2, packet.accel.x);
+    float(3, packet.accel.y);
+    float(4, packet.accel.z);
+    if let Some(gyro) = packet.gyro {
+        float(5, gyro.x);
+        float(6, gyro.y);
+        float(7, gyro.z);
+    }
+    if let Some(mag) = packet.mag {
+        float(10, mag.x);
+        float(11, mag.y);
+        float(12, mag.z);
+    }
+    if let Some(quat) = packet.quat.map(|quat| frame.from_nwu(quat)) {
+        float(13, quat.w);
+        float(14, quat.x);
+        float(15, quat.y);
+        float(16, quat.z);
+    }
+    if let Some(temperature) = packet.temperature {
+        float(8, temperature);
+    }
+    if let Some(battery) = packet.battery {
+        float(9, battery);
+    }
+    message
+}
+
+fn write_varint(buffer: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buffer.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buffer.push(value as u8);
+}
+
+/// Accept clients and write each batch of messages to all of them, dropping the ones which fail
+fn serve(listener: TcpListener, receiver: Receiver<Vec<u8>>) {
+    let mut clients: Vec<TcpStream> = Vec::new();
+    loop {
+        let batch = match receiver.recv_timeout(ACCEPT_INTERVAL) {
+            Ok(batch) => Some(batch),
+            Err(RecvTimeoutError::Timeout) => None,
+            Err(RecvTimeoutError::Disconnected) => return,
+        };
+
+        loop {
+            match listener.accept() {
+                Ok((client, addr)) => match client
+                    .set_nonblocking(false)
+                    .and_then(|()| client.set_write_timeout(Some(WRITE_TIMEOUT)))
+                {
+                    Ok(()) => {
+                        info!("streaming samples to {}", addr);
+                        clients.push(client);
+                    }
+                    Err(e) => warn!("failed to stream samples to {}: {}", addr, e),
+                },
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    warn!("failed to accept a client: {}", e);
+                    break;
+                }
+            }
+        }
+
+        if let Some(batch) = batch {
+            // a client which timed out may have received part of a message, so it is dropped too
+            clients.retain_mut(|client| match client.write_all(&batch) {
+                Ok(()) => true,
+                Err(e) => {
+                    info!("stopped streaming samples to {:?}: {}", client.peer_addr(), e);
+                    false
+                }
+            });
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use bevy::prelude::*;
+
+/// Reference orientation treated as zero, so the model can be aligned with how the device is held
+#[derive(Debug, Clone, Copy)]
+pub struct Tare {
+    pub reference: Quat,
+    /// Take the next orientation as the reference
+    pending: bool,
+}
+
+impl Default for Tare {
+    fn default() -> Self {
+        Tare {
+            reference: Quat::IDENTITY,
+            pending: false,
+        }
+    }
+}
+
+impl Tare {
+    /// Orientation relative to the reference, taking this one as the reference if requested
+    pub fn apply(&mut self, rotation: Quat) -> Quat {
+        if self.pending {
+            self.reference = rotation;
+            self.pending = false;
+        }
+        self.reference.inverse() * rotation
+    }
+}
+
+/// Z sets the current orientation as zero, Shift + Z clears it
+pub struct TarePlugin;
+
+impl Plugin for TarePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Tare>().add_system(set_tare);
+    }
+}
+
+fn set_tare(keys: Res<Input<KeyCode>>, mut tare: ResMut<Tare>) {
+    if !keys.just_pressed(KeyCode::Z) {
+        return;
+    }
+    if keys.pressed(KeyCode::LShift) || keys.pressed(KeyCode::RShift) {
+        info!("orientation reference cleared");
+        *tare = Tare::default();
+    } else {
+        info!("current orientation set as zero");
+        tare.pending = true;
+    }
+}
@@ -0,0 +1,204 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::settings::SettingsFile;
+
+/// Color theme of the viewer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ThemeName {
+    #[default]
+    Default,
+    /// Opaque black panels and bright colors, e.g. for projectors
+    HighContrast,
+    /// Okabe-Ito colors, which stay apart with red-green and blue-yellow color blindness
+    ColorBlind,
+}
+
+const THEMES: [ThemeName; 3] = [
+    ThemeName::Default,
+    ThemeName::HighContrast,
+    ThemeName::ColorBlind,
+];
+
+impl fmt::Display for ThemeName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            ThemeName::Default => "default",
+            ThemeName::HighContrast => "high_contrast",
+            ThemeName::ColorBlind => "color_blind",
+        })
+    }
+}
+
+/// Parsed from `default`, `high_contrast` or `color_blind`
+impl FromStr for ThemeName {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        THEMES
+            .iter()
+            .find(|theme| theme.to_string() == s)
+            .copied()
+            .ok_or_else(|| {
+                format!(
+                    "unknown theme {}, choose from default, high_contrast or color_blind",
+                    s
+                )
+            })
+    }
+}
+
+/// Okabe-Ito colors
+const VERMILLION: Color = Color::rgb(0.835, 0.369, 0.0);
+const ORANGE: Color = Color::rgb(0.902, 0.624, 0.0);
+const SKY_BLUE: Color = Color::rgb(0.337, 0.706, 0.914);
+const BLUISH_GREEN: Color = Color::rgb(0.0, 0.620, 0.451);
+const YELLOW: Color = Color::rgb(0.941, 0.894, 0.259);
+const BLUE: Color = Color::rgb(0.0, 0.447, 0.698);
+const REDDISH_PURPLE: Color = Color::rgb(0.8, 0.475, 0.655);
+
+/// Colors of the plots, gizmos, alarms and panels, changed with `T`.
+/// Systems drawing with them check `is_changed` to recolor what they spawned.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+    pub name: ThemeName,
+    /// x, y and z on the plots, the gizmos and the exported image
+    pub axes: [Color; 3],
+    /// Low-pass filtered x, y and z on the plots
+    pub filtered: [Color; 3],
+    pub magnitude: Color,
+    pub roll: Color,
+    pub pitch: Color,
+    pub temperature: Color,
+    pub battery: Color,
+    /// Background of the plots and the spectrum
+    pub panel: Color,
+    /// Background of the plot selected with `[`/`]`
+    pub selected_panel: Color,
+    /// Built-in models, and the models while the sensor is not responding
+    pub model: Color,
+    pub stale: Color,
+    pub alarm_active: Color,
+    pub alarm_inactive: Color,
+    pub alarm_disabled: Color,
+    pub spectrum: Color,
+    /// Toasts and text of warnings and errors
+    pub warning: Color,
+    pub error: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        ThemeName::Default.into()
+    }
+}
+
+impl From<ThemeName> for Theme {
+    fn from(name: ThemeName) -> Self {
+        match name {
+            ThemeName::Default => Theme {
+                name,
+                axes: [Color::RED, Color::GREEN, Color::BLUE],
+                filtered: [Color::PINK, Color::LIME_GREEN, Color::CYAN],
+                magnitude: Color::WHITE,
+                roll: Color::ORANGE,
+                pitch: Color::PURPLE,
+                temperature: Color::TOMATO,
+                battery: Color::YELLOW_GREEN,
+                panel: Color::rgba(0.1, 0.1, 0.1, 0.8),
+                selected_panel: Color::rgba(0.2, 0.2, 0.3, 0.8),
+                model: Color::PINK,
+                stale: Color::GRAY,
+                alarm_active: Color::RED,
+                alarm_inactive: Color::WHITE,
+                alarm_disabled: Color::GRAY,
+                spectrum: Color::ORANGE,
+                warning: Color::ORANGE,
+                error: Color::ORANGE_RED,
+            },
+            ThemeName::HighContrast => Theme {
+                name,
+                axes: [
+                    Color::rgb(1.0, 0.2, 0.2),
+                    Color::rgb(0.2, 1.0, 0.2),
+                    Color::rgb(0.3, 0.6, 1.0),
+                ],
+                filtered: [Color::FUCHSIA, Color::YELLOW, Color::CYAN],
+                magnitude: Color::WHITE,
+                roll: Color::ORANGE,
+                pitch: Color::VIOLET,
+                temperature: Color::SALMON,
+                battery: Color::LIME_GREEN,
+                panel: Color::BLACK,
+                selected_panel: Color::rgb(0.15, 0.15, 0.45),
+                model: Color::YELLOW,
+                stale: Color::DARK_GRAY,
+                alarm_active: Color::rgb(1.0, 0.2, 0.2),
+                alarm_inactive: Color::WHITE,
+                alarm_disabled: Color::SILVER,
+                spectrum: Color::YELLOW,
+                warning: Color::YELLOW,
+                error: Color::rgb(1.0, 0.3, 0.3),
+            },
+            ThemeName::ColorBlind => Theme {
+                name,
+                axes: [VERMILLION, SKY_BLUE, YELLOW],
+                filtered: [ORANGE, BLUE, Color::SILVER],
+                magnitude: Color::WHITE,
+                roll: BLUISH_GREEN,
+                pitch: REDDISH_PURPLE,
+                temperature: ORANGE,
+                battery: BLUISH_GREEN,
+                panel: Color::rgba(0.1, 0.1, 0.1, 0.8),
+                selected_panel: Color::rgba(0.2, 0.2, 0.3, 0.8),
+                model: SKY_BLUE,
+                stale: Color::GRAY,
+                // active alarms are told apart by their lightness too
+                alarm_active: VERMILLION,
+                alarm_inactive: Color::WHITE,
+                alarm_disabled: Color::GRAY,
+                spectrum: ORANGE,
+                warning: YELLOW,
+                error: VERMILLION,
+            },
+        }
+    }
+}
+
+/// `T` changes the color theme, which is saved to the settings file
+pub struct ThemePlugin {
+    /// Theme at startup
+    pub theme: ThemeName,
+}
+
+impl Plugin for ThemePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Theme::from(self.theme))
+            .add_system(cycle_theme);
+    }
+}
+
+fn cycle_theme(
+    keys: Res<Input<KeyCode>>,
+    mut theme: ResMut<Theme>,
+    settings: Option<ResMut<SettingsFile>>,
+) {
+    if !keys.just_pressed(KeyCode::T) {
+        return;
+    }
+    let index = THEMES
+        .iter()
+        .position(|name| *name == theme.name)
+        .unwrap_or(0);
+    let name = THEMES[(index + 1) % THEMES.len()];
+    info!("theme {}", name);
+    *theme = name.into();
+
+    if let Some(mut settings) = settings {
+        settings.settings.theme = name;
+        settings.save();
+    }
+}
@@ -0,0 +1,223 @@
+use bevy::prelude::*;
+use bevy_serial::{SerialErrorEvent, SerialOpenedEvent};
+use std::collections::{HashSet, VecDeque};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::locale::Locale;
+use crate::parser::ParserRegistry;
+use crate::theme::Theme;
+
+/// Seconds a toast stays on screen
+const TOAST_SECS: f64 = 6.0;
+/// Toasts shown at once, the oldest are dropped first
+const MAX_TOASTS: usize = 5;
+/// Interval in seconds to sum up the malformed packets into one toast
+const REJECTED_INTERVAL: f64 = 2.0;
+
+/// Severity of a toast, which sets its color
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToastLevel {
+    Info,
+    Warning,
+    Error,
+}
+
+impl ToastLevel {
+    fn color(self, theme: &Theme) -> Color {
+        match self {
+            ToastLevel::Info => Color::rgb(0.6, 0.9, 0.6),
+            ToastLevel::Warning => theme.warning,
+            ToastLevel::Error => theme.error,
+        }
+    }
+}
+
+/// Send to show a message in the top left corner for a few seconds
+#[derive(Debug, Clone)]
+pub struct ToastEvent {
+    pub level: ToastLevel,
+    pub message: String,
+}
+
+impl ToastEvent {
+    pub fn new(level: ToastLevel, message: impl Into<String>) -> Self {
+        Self {
+            level,
+            message: message.into(),
+        }
+    }
+}
+
+/// Toasts for serial errors, reconnects and malformed packets with the UTC time they happened,
+/// so they don't only go to the terminal
+pub struct ToastPlugin;
+
+impl Plugin for ToastPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ToastEvent>()
+            .init_resource::<Toasts>()
+            .add_startup_system(setup_toasts)
+            .add_system(toast_serial_errors)
+            .add_system(toast_connections)
+            .add_system(toast_rejected_packets)
+            .add_system(show_toasts);
+    }
+}
+
+struct Toast {
+    level: ToastLevel,
+    text: String,
+    /// Seconds since startup when the toast is hidden
+    until: f64,
+}
+
+#[derive(Default)]
+struct Toasts(VecDeque<Toast>);
+
+/// Malformed packets counted since the last toast
+#[derive(Default)]
+struct RejectedPackets {
+    /// Format selected in the last update, and the packets it had rejected
+    selected: Option<(&'static str, u64)>,
+    count: u64,
+    since: f64,
+}
+
+#[derive(Component)]
+struct ToastText;
+
+fn setup_toasts(mut commands: Commands) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                // below "sensor not responding"
+                position: Rect {
+                    left: Val::Px(8.0),
+                    top: Val::Px(40.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::default(),
+            ..Default::default()
+        })
+        .insert(ToastText);
+}
+
+fn toast_serial_errors(
+    mut ev_error: EventReader<SerialErrorEvent>,
+    mut ev_toast: EventWriter<ToastEvent>,
+) {
+    for e in ev_error.iter() {
+        ev_toast.send(ToastEvent::new(ToastLevel::Error, e.to_string()));
+    }
+}
+
+/// Ports opened again after they were opened once, e.g. after a cable was replugged
+fn toast_connections(
+    mut ev_opened: EventReader<SerialOpenedEvent>,
+    mut ev_toast: EventWriter<ToastEvent>,
+    mut opened: Local<HashSet<String>>,
+    locale: Res<Locale>,
+) {
+    for SerialOpenedEvent { label, .. } in ev_opened.iter() {
+        if !opened.insert(label.clone()) {
+            ev_toast.send(ToastEvent::new(
+                ToastLevel::Info,
+                locale.fill("reconnected", &[label]),
+            ));
+        }
+    }
+}
+
+fn toast_rejected_packets(
+    parsers: Res<ParserRegistry>,
+    mut ev_toast: EventWriter<ToastEvent>,
+    mut rejected: Local<RejectedPackets>,
+    locale: Res<Locale>,
+    time: Res<Time>,
+) {
+    let format = parsers.selected();
+    let total = parsers.rejected();
+    // each format has its own count
+    if let Some((last_format, last_total)) = rejected.selected {
+        if last_format == format {
+            rejected.count += total.saturating_sub(last_total);
+        }
+    }
+    rejected.selected = Some((format, total));
+
+    let now = time.seconds_since_startup();
+    if now - rejected.since < REJECTED_INTERVAL {
+        return;
+    }
+    // a single one is usually the partial packet when the port is opened
+    if rejected.count > 1 {
+        ev_toast.send(ToastEvent::new(
+            ToastLevel::Warning,
+            locale.fill("rejected", &[&rejected.count, &format]),
+        ));
+    }
+    rejected.count = 0;
+    rejected.since = now;
+}
+
+fn show_toasts(
+    mut ev_toast: EventReader<ToastEvent>,
+    mut toasts: ResMut<Toasts>,
+    mut text: Query<&mut Text, With<ToastText>>,
+    asset_server: Res<AssetServer>,
+    theme: Res<Theme>,
+    time: Res<Time>,
+) {
+    let now = time.seconds_since_startup();
+    let mut changed = theme.is_changed();
+    for ToastEvent { level, message } in ev_toast.iter() {
+        toasts.0.push_back(Toast {
+            level: *level,
+            text: format!("{} {}", utc_time(), message),
+            until: now + TOAST_SECS,
+        });
+        if toasts.0.len() > MAX_TOASTS {
+            toasts.0.pop_front();
+        }
+        changed = true;
+    }
+    let len = toasts.0.len();
+    toasts.0.retain(|toast| toast.until > now);
+    changed |= toasts.0.len() != len;
+    if !changed {
+        return;
+    }
+
+    let font = asset_server.load("fonts/FiraMono-Medium.ttf");
+    for mut text in text.iter_mut() {
+        text.sections = toasts
+            .0
+            .iter()
+            .map(|toast| TextSection {
+                value: format!("{}\n", toast.text),
+                style: TextStyle {
+                    font: font.clone(),
+                    font_size: 16.0,
+                    color: toast.level.color(&theme),
+                },
+            })
+            .collect();
+    }
+}
+
+/// `HH:MM:SS` in UTC
+fn utc_time() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    format!(
+        "{:02}:{:02}:{:02}",
+        secs / 3600 % 24,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
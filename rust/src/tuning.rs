@@ -0,0 +1,71 @@
+use bevy::prelude::*;
+use bevy_inspector_egui::{Inspectable, InspectorPlugin};
+
+use crate::alarms::Alarms;
+use crate::fusion::Madgwick;
+use crate::plot::LowPass;
+
+/// Parameters tuned live in the inspector window, starting from the values of the viewer
+#[derive(Debug, Clone, Reflect, Inspectable)]
+pub struct Tuning {
+    /// Cutoff of the low-pass filter of the filtered plots in Hz
+    #[inspectable(min = 0.05, max = 50.0, speed = 0.05)]
+    pub cutoff: f32,
+    /// Gain of the correction of the Madgwick filter in rad/s, higher trusts the accelerometer and magnetometer more
+    #[inspectable(min = 0.0, max = 2.0, speed = 0.005)]
+    pub beta: f32,
+    /// Threshold of each alarm in the order of `--alarm`, in the unit shown at the top
+    pub alarm_thresholds: Vec<f32>,
+}
+
+/// The values in use when the inspector opens
+impl FromWorld for Tuning {
+    fn from_world(world: &mut World) -> Self {
+        Tuning {
+            cutoff: world
+                .get_resource::<LowPass>()
+                .map(|low_pass| low_pass.cutoff)
+                .unwrap_or_default(),
+            beta: world
+                .get_resource::<Madgwick>()
+                .map(|filter| filter.beta)
+                .unwrap_or_default(),
+            alarm_thresholds: world
+                .get_resource::<Alarms>()
+                .map(|alarms| alarms.0.iter().map(|alarm| alarm.threshold).collect())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Inspector window to tune the cutoff of the low-pass filter, the beta of the Madgwick filter and the thresholds of
+/// the alarms while data streams. Added after the plugins of the parameters, so it starts from their values.
+pub struct TuningPlugin;
+
+impl Plugin for TuningPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Tuning>()
+            .add_plugin(InspectorPlugin::<Tuning>::new())
+            .add_system(apply_tuning);
+    }
+}
+
+fn apply_tuning(
+    tuning: Res<Tuning>,
+    mut low_pass: ResMut<LowPass>,
+    mut filter: ResMut<Madgwick>,
+    mut alarms: ResMut<Alarms>,
+) {
+    if !tuning.is_changed() {
+        return;
+    }
+    low_pass.cutoff = tuning.cutoff;
+    filter.beta = tuning.beta;
+    // the number of alarms is set on the command line, not in the inspector
+    for (alarm, threshold) in alarms.0.iter_mut().zip(tuning.alarm_thresholds.iter()) {
+        if alarm.threshold != *threshold {
+            info!("alarm {} tuned to {}", alarm, threshold);
+            alarm.threshold = *threshold;
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Standard gravity in m/s²
+pub const STANDARD_GRAVITY: f32 = 9.80665;
+
+/// Full scale range of the accelerometer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum AccelRange {
+    #[default]
+    #[serde(rename = "2g")]
+    G2,
+    #[serde(rename = "4g")]
+    G4,
+    #[serde(rename = "8g")]
+    G8,
+    #[serde(rename = "16g")]
+    G16,
+}
+
+impl AccelRange {
+    /// Largest value in g
+    pub fn full_scale(&self) -> f32 {
+        match self {
+            AccelRange::G2 => 2.0,
+            AccelRange::G4 => 4.0,
+            AccelRange::G8 => 8.0,
+            AccelRange::G16 => 16.0,
+        }
+    }
+
+    /// Counts per g of 16 bit raw values
+    pub fn counts_per_g(&self) -> f32 {
+        32768.0 / self.full_scale()
+    }
+}
+
+/// Written like `±8 g`
+impl fmt::Display for AccelRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "±{} g", self.full_scale())
+    }
+}
+
+/// Parsed from `2g`, `4g`, `8g` or `16g` (or without the `g`)
+impl FromStr for AccelRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim_end_matches('g') {
+            "2" => Ok(AccelRange::G2),
+            "4" => Ok(AccelRange::G4),
+            "8" => Ok(AccelRange::G8),
+            "16" => Ok(AccelRange::G16),
+            _ => Err(format!(
+                "unknown range {}, choose from 2g, 4g, 8g or 16g",
+                s
+            )),
+        }
+    }
+}
+
+/// Unit of the acceleration sent by the device and shown by the viewer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum AccelUnit {
+    #[default]
+    #[serde(rename = "g")]
+    G,
+    #[serde(rename = "m/s2")]
+    MetersPerSecondSquared,
+}
+
+impl AccelUnit {
+    /// Convert a value in this unit to g
+    pub fn to_g(self, value: f32) -> f32 {
+        match self {
+            AccelUnit::G => value,
+            AccelUnit::MetersPerSecondSquared => value / STANDARD_GRAVITY,
+        }
+    }
+
+    /// Convert a value in g to this unit
+    pub fn in_unit(self, g: f32) -> f32 {
+        match self {
+            AccelUnit::G => g,
+            AccelUnit::MetersPerSecondSquared => g * STANDARD_GRAVITY,
+        }
+    }
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            AccelUnit::G => "g",
+            AccelUnit::MetersPerSecondSquared => "m/s²",
+        }
+    }
+}
+
+/// Parsed from `g` or `m/s2` (`m/s²` too)
+impl FromStr for AccelUnit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "g" => Ok(AccelUnit::G),
+            "m/s2" | "m/s²" => Ok(AccelUnit::MetersPerSecondSquared),
+            _ => Err(format!("unknown unit {}, choose from g or m/s2", s)),
+        }
+    }
+}
+
+/// Range and unit of the sensor.
+/// Parsers convert the values of the device to g with these, so the samples passed between systems are always in g,
+/// and the plot, HUD and alarms convert them back to the unit for display.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SensorUnits {
+    pub range: AccelRange,
+    pub unit: AccelUnit,
+}
@@ -0,0 +1,159 @@
+use bevy::prelude::*;
+use bevy::window::{CreateWindow, WindowCreated, WindowId, WindowMoved, WindowResized};
+use serde::{Deserialize, Serialize};
+
+use crate::settings::SettingsFile;
+
+/// Seconds after the last move or resize to save the layout, so dragging a window doesn't write the file each frame
+const SAVE_DELAY: f64 = 1.0;
+
+/// Position and size of a window
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowPlacement {
+    /// Top left corner on the desktop in physical pixels, or placed by the system if unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub position: Option<[i32; 2]>,
+    /// Width and height in logical pixels
+    pub size: [f32; 2],
+}
+
+impl Default for WindowPlacement {
+    fn default() -> Self {
+        WindowPlacement {
+            position: None,
+            size: [1280.0, 720.0],
+        }
+    }
+}
+
+/// Windows of the viewer, saved to the settings file when they are moved or resized
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowLayout {
+    /// Whether the 3D view has a window of its own, leaving the plots, console, HUD and panels in the main window
+    pub split: bool,
+    pub main: WindowPlacement,
+    /// Window of the 3D view when split
+    pub scene: WindowPlacement,
+}
+
+impl WindowLayout {
+    /// Descriptor of the main window, inserted before the window is created
+    pub fn main_descriptor(&self) -> WindowDescriptor {
+        WindowDescriptor {
+            width: self.main.size[0],
+            height: self.main.size[1],
+            ..Default::default()
+        }
+    }
+
+    fn placement_mut(&mut self, id: WindowId, scene: WindowId) -> Option<&mut WindowPlacement> {
+        if id == WindowId::primary() {
+            Some(&mut self.main)
+        } else if id == scene {
+            Some(&mut self.scene)
+        } else {
+            None
+        }
+    }
+}
+
+/// Window the 3D camera draws to, the main window unless the layout is split
+#[derive(Debug, Clone, Copy)]
+pub struct SceneWindow(pub WindowId);
+
+/// Open the 3D view in a second window if the layout is split, e.g. to keep it clean on one monitor with the
+/// diagnostics on another, and restore and save the positions and sizes of the windows.
+/// Bevy draws the UI in the main window only, so the plots, console and panels stay there.
+pub struct WindowLayoutPlugin {
+    pub layout: WindowLayout,
+}
+
+impl Plugin for WindowLayoutPlugin {
+    fn build(&self, app: &mut App) {
+        let scene = if self.layout.split {
+            WindowId::new()
+        } else {
+            WindowId::primary()
+        };
+        app.insert_resource(self.layout)
+            .insert_resource(SceneWindow(scene))
+            .add_startup_system(create_scene_window)
+            .add_system(place_windows)
+            .add_system(save_layout);
+    }
+}
+
+fn create_scene_window(
+    layout: Res<WindowLayout>,
+    scene: Res<SceneWindow>,
+    mut ev_create: EventWriter<CreateWindow>,
+) {
+    if !layout.split {
+        return;
+    }
+    ev_create.send(CreateWindow {
+        id: scene.0,
+        descriptor: WindowDescriptor {
+            width: layout.scene.size[0],
+            height: layout.scene.size[1],
+            title: "bevy-accel 3D view".to_string(),
+            ..Default::default()
+        },
+    });
+}
+
+/// Move the windows to their saved positions once they are open
+fn place_windows(
+    mut ev_created: EventReader<WindowCreated>,
+    layout: Res<WindowLayout>,
+    scene: Res<SceneWindow>,
+    mut windows: ResMut<Windows>,
+) {
+    for WindowCreated { id } in ev_created.iter() {
+        let placement = if *id == WindowId::primary() {
+            layout.main
+        } else if *id == scene.0 {
+            layout.scene
+        } else {
+            continue;
+        };
+        if let (Some([x, y]), Some(window)) = (placement.position, windows.get_mut(*id)) {
+            window.set_position(IVec2::new(x, y));
+        }
+    }
+}
+
+fn save_layout(
+    mut ev_moved: EventReader<WindowMoved>,
+    mut ev_resized: EventReader<WindowResized>,
+    scene: Res<SceneWindow>,
+    time: Res<Time>,
+    mut layout: ResMut<WindowLayout>,
+    settings: Option<ResMut<SettingsFile>>,
+    // time of the last change not saved yet
+    mut changed: Local<Option<f64>>,
+) {
+    let now = time.seconds_since_startup();
+    for WindowMoved { id, position } in ev_moved.iter() {
+        if let Some(placement) = layout.placement_mut(*id, scene.0) {
+            placement.position = Some([position.x, position.y]);
+            *changed = Some(now);
+        }
+    }
+    for WindowResized { id, width, height } in ev_resized.iter() {
+        if let Some(placement) = layout.placement_mut(*id, scene.0) {
+            placement.size = [*width, *height];
+            *changed = Some(now);
+        }
+    }
+    match *changed {
+        Some(time) if now - time >= SAVE_DELAY => *changed = None,
+        _ => return,
+    }
+    if let Some(mut settings) = settings {
+        settings.settings.windows = *layout;
+        settings.save();
+    }
+}